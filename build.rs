@@ -1,6 +1,7 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=../src/main/proto/plugin_service.proto");
     
+    #[cfg(feature = "grpc")]
     tonic_build::compile_protos("./proto/plugin_service.proto")?;
     
     Ok(())
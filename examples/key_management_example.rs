@@ -4,13 +4,13 @@ use password_manager::{
     persistence::DbPersistence,
     plugin_config::PluginConfig,
     plugin_sdk::PluginSDK,
+    ShutdownSignals,
 };
 use std::collections::HashMap;
 use std::env;
 use std::error::Error;
 use std::fs;
 use std::sync::Arc;
-use tokio::signal;
 use tonic::transport::Endpoint;
 
 #[tokio::main]
@@ -77,13 +77,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
     config.set_plugin_id(format!("key_management_{}", uuid::Uuid::new_v4())); // 添加唯一ID
     
     // 初始化插件
-    if !plugin.initialize(config.clone()).await {
-        eprintln!("插件初始化失败");
+    if let Err(e) = plugin.try_initialize(config.clone()).await {
+        eprintln!("插件初始化失败: {}", e);
         return Ok(());
     }
     
     // 手动设置插件信息，类似示例插件
-    let mut info = plugin.get_info();
+    let info = plugin.info_mut();
     info.set_description("密钥管理服务".to_string());
     
     // 添加支持的命令
@@ -207,12 +207,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
         println!("错误信息: {}", result.get_error_message());
     }
     
-    // 等待中断信号
+    // 等待中断信号，容器停止时发送的 SIGTERM 同样处理
     println!("按Ctrl+C退出");
-    match signal::ctrl_c().await {
-        Ok(()) => {
-            println!("接收到中断信号，正在清理资源...");
-            // 不调用 stop 方法，因为它可能会尝试与 gRPC 服务器通信
+    match ShutdownSignals::default().wait().await {
+        Ok(signal) => {
+            println!("接收到{}信号，正在清理资源...", signal);
+            // 通知服务器插件已停止，并写完剩余的审计日志
+            plugin.stop().await;
         }
         Err(e) => {
             eprintln!("无法监听中断信号: {}", e);
@@ -1,51 +1,222 @@
 use async_trait::async_trait;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+#[cfg(feature = "grpc")]
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+#[cfg(feature = "grpc")]
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
 // 使用tokio的Duration而不是std的Duration
 use tokio::time::Duration;
-use tokio::sync::mpsc;
+#[cfg(feature = "grpc")]
+use tokio::time::Instant;
+#[cfg(feature = "grpc")]
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch};
 use tokio::task::JoinHandle;
-use tonic::transport::{Channel, Endpoint};
+#[cfg(feature = "grpc")]
+use tonic::transport::server::TcpIncoming;
+#[cfg(feature = "grpc")]
+use tonic::transport::{Channel, Endpoint, Server};
+#[cfg(feature = "grpc")]
 use tonic::Request; // 添加这一行导入
 
 use crate::command_result::CommandResult;
-use crate::plugin_config::PluginConfig;
+use crate::plugin_config::{PluginConfig, RuntimeSettings};
+use crate::plugin_identity::PluginIdentity;
+#[cfg(feature = "grpc")]
+use crate::plugin_identity::RegistrationOutcome;
+use crate::plugin_metrics::PluginMetrics;
 use crate::plugin_info::PluginInfo;
 use crate::plugin_sdk::PluginSDK;
+#[cfg(feature = "grpc")]
+use crate::plugin_server::PluginServer;
+use crate::plugin_status::{PluginHealth, PluginState};
+#[cfg(feature = "grpc")]
+use crate::retry_jitter::JitterStrategy;
+use crate::server_features::ServerFeatures;
 
 // 导入生成的protobuf代码
+#[cfg(feature = "grpc")]
 pub mod plugin {
     // 移除不正确的 cfg 条件
     tonic::include_proto!("plugin");
 }
 
+#[cfg(feature = "grpc")]
 use plugin::plugin_service_client::PluginServiceClient;
+#[cfg(feature = "grpc")]
+use plugin::plugin_service_server::PluginServiceServer;
+#[cfg(feature = "grpc")]
 use plugin::{
-    HeartbeatRequest, PluginRegistration, StopRequest,
+    FindPluginRequest, GetPluginByNameRequest, HeartbeatRequest, PluginRegistration, StopRequest,
+    UpdatePluginRequest,
 };
 
+/// 服务器失联时的回调，用于清理内存中的敏感数据
+pub type OfflineHook = Arc<dyn Fn() + Send + Sync>;
+
+/// 心跳附带指标时调用，返回的字段合并到心跳的 `metrics` 中
+pub type TelemetryHook = Arc<dyn Fn() -> serde_json::Map<String, serde_json::Value> + Send + Sync>;
+
+/// 服务器长时间不可达时自动停止插件的策略（dead man's switch）
+#[cfg(feature = "grpc")]
+struct OfflinePolicy {
+    max_failures: Option<u32>,
+    max_offline: Option<Duration>,
+    hook: Option<OfflineHook>,
+    server_shutdown: watch::Sender<bool>,
+}
+
+#[cfg(feature = "grpc")]
+impl OfflinePolicy {
+    fn exceeded(&self, consecutive_failures: u32, offline_for: Duration) -> bool {
+        let too_many_failures = self.max_failures.is_some_and(|max| consecutive_failures >= max);
+        let offline_too_long = self.max_offline.is_some_and(|max| offline_for >= max);
+        too_many_failures || offline_too_long
+    }
+
+    // 停止插件：清除运行标志、关闭入站服务并执行清理回调
+    fn trigger(&self, health: &PluginHealth) {
+        {
+            let mut guard = health.running.lock().unwrap();
+            *guard = false;
+        }
+        health.mark_stopped();
+        self.server_shutdown.send_replace(true);
+
+        if let Some(hook) = &self.hook {
+            hook();
+        }
+    }
+}
+
+// 心跳附带的运行指标，命令数按上次成功心跳以来的增量计算
+#[cfg(feature = "grpc")]
+#[derive(Clone)]
+struct HeartbeatTelemetry {
+    health: PluginHealth,
+    metrics: PluginMetrics,
+    hook: Option<TelemetryHook>,
+    reported_commands: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "grpc")]
+impl HeartbeatTelemetry {
+    // 返回指标和当前命令总数，心跳成功后用后者调用 mark_reported
+    fn collect(&self) -> (serde_json::Value, u64) {
+        let total = self.metrics.total_commands();
+        let reported = self.reported_commands.load(Ordering::SeqCst);
+        let mut metrics = serde_json::Map::new();
+        if let Some(hook) = &self.hook {
+            metrics.extend(hook());
+        }
+        metrics.insert("commands_since_last_heartbeat".to_string(), total.saturating_sub(reported).into());
+        metrics.insert("uptime_secs".to_string(), self.health.uptime().map_or(0, |uptime| uptime.as_secs()).into());
+        (serde_json::Value::Object(metrics), total)
+    }
+
+    fn mark_reported(&self, total: u64) {
+        self.reported_commands.store(total, Ordering::SeqCst);
+    }
+}
+
+// 心跳中上报的状态，服务器支持时附带插件支持的命令和运行指标
+#[cfg(feature = "grpc")]
+struct HeartbeatStatus {
+    status: String,
+    commands: Vec<String>,
+    features: Arc<Mutex<ServerFeatures>>,
+    telemetry: Option<HeartbeatTelemetry>,
+}
+
+#[cfg(feature = "grpc")]
+impl HeartbeatStatus {
+    // 返回 status_info 和本次上报的命令总数
+    fn status_info(&self) -> (String, Option<u64>) {
+        let features = self.features.lock().unwrap();
+        match &self.telemetry {
+            Some(telemetry) => {
+                let (metrics, total) = telemetry.collect();
+                (features.status_info_with_metrics(&self.status, &self.commands, Some(&metrics)), Some(total))
+            }
+            None => (features.status_info(&self.status, &self.commands), None),
+        }
+    }
+
+    fn mark_reported(&self, total: Option<u64>) {
+        if let (Some(telemetry), Some(total)) = (&self.telemetry, total) {
+            telemetry.mark_reported(total);
+        }
+    }
+}
+
+// 命令执行流程处理的通用参数，对所有命令都有效，不属于命令本身
+const GENERIC_PARAMS: [&str; 5] = ["user", "tenant", "security_module", "timeout_ms", "operation_id"];
+
+/// 从 reconfigure 命令的参数中去掉通用参数，剩下的都是要修改的配置项
+pub(crate) fn setting_changes(params: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut changes = params.clone();
+    changes.retain(|name, _| !GENERIC_PARAMS.contains(&name.as_str()));
+    changes
+}
+
 /// 基础插件实现
+///
+/// 启用 `grpc` 特性（默认）时启动后向服务器注册并发送心跳，可以通过 `serve` 提供入站gRPC服务；
+/// 关闭该特性时只在本地运行，初始化、启动、停止和命令执行不依赖网络
 pub struct BasePlugin {
     config: Option<PluginConfig>,
     info: PluginInfo,
+    identity: PluginIdentity, // 插件ID以这里为准，心跳线程重新注册后也会更新
+    server_features: Arc<Mutex<ServerFeatures>>, // 每次注册成功后根据响应重新协商
     running: Arc<Mutex<bool>>,
+    health: PluginHealth,
+    metrics: PluginMetrics,
+    settings: Arc<RwLock<RuntimeSettings>>,
     heartbeat_handle: Option<JoinHandle<()>>,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    server_shutdown: watch::Sender<bool>,
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))] // 没有心跳时不会触发自停策略
+    offline_hook: Option<OfflineHook>,
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))] // 只在心跳中上报
+    telemetry_hook: Option<TelemetryHook>,
+    #[cfg(feature = "grpc")]
+    reported_commands: Arc<AtomicU64>,
+    #[cfg(feature = "grpc")]
+    listener: Mutex<Option<TcpListener>>, // plugin_grpc_port=0 时启动阶段预先绑定、等待 serve 使用的监听
+    #[cfg(feature = "grpc")]
+    bound_port: AtomicI32, // 入站gRPC服务实际绑定的端口，未绑定时为0
 }
 
 // 在 BasePlugin 结构体中添加心跳和重试注册的方法
 
 impl BasePlugin {
     pub fn new() -> Self {
+        let running = Arc::new(Mutex::new(false));
         Self {
             config: None,
             info: PluginInfo::new(),
-            running: Arc::new(Mutex::new(false)),
+            identity: PluginIdentity::new(),
+            server_features: Arc::new(Mutex::new(ServerFeatures::default())),
+            health: PluginHealth::new(Arc::clone(&running)),
+            metrics: PluginMetrics::new(),
+            settings: Arc::new(RwLock::new(RuntimeSettings::new())),
+            running,
             heartbeat_handle: None,
             shutdown_tx: None,
+            server_shutdown: watch::Sender::new(false),
+            offline_hook: None,
+            telemetry_hook: None,
+            #[cfg(feature = "grpc")]
+            reported_commands: Arc::new(AtomicU64::new(0)),
+            #[cfg(feature = "grpc")]
+            listener: Mutex::new(None),
+            #[cfg(feature = "grpc")]
+            bound_port: AtomicI32::new(0),
         }
     }
 
+    #[cfg(feature = "grpc")]
     async fn create_client(&self) -> Result<PluginServiceClient<Channel>, Box<dyn std::error::Error + Send + Sync>> {
         let config = self.config.as_ref().ok_or("Plugin not initialized")?;
         let endpoint = format!("http://{}:{}", config.get_server_host(), config.get_server_port());
@@ -53,9 +224,10 @@ impl BasePlugin {
         
         // 修改连接方式，使用connect()而不是connect_lazy()
         println!("使用connect()方法建立连接");
+        let settings = self.settings();
         let channel = Endpoint::from_shared(endpoint)?
-            .timeout(std::time::Duration::from_secs(30)) // 增加超时时间
-            .connect_timeout(std::time::Duration::from_secs(15)) // 增加连接超时
+            .timeout(std::time::Duration::from_secs(settings.get_request_timeout())) // 增加超时时间
+            .connect_timeout(std::time::Duration::from_secs(settings.get_connect_timeout())) // 增加连接超时
             .tcp_keepalive(Some(std::time::Duration::from_secs(60))) // 增加TCP保活时间
             .connect()
             .await?;
@@ -65,14 +237,18 @@ impl BasePlugin {
     }
 
     // 1. 修复 heartbeat_loop 函数，添加缺失的变量定义
+    #[cfg(feature = "grpc")]
     async fn heartbeat_loop(
-        plugin_id: String,
-        status: String,
-        running: Arc<Mutex<bool>>,
+        identity: PluginIdentity,
+        status: HeartbeatStatus,
+        health: PluginHealth,
+        settings: Arc<RwLock<RuntimeSettings>>,
+        offline_policy: OfflinePolicy,
         mut shutdown_rx: mpsc::Receiver<()>,
         server_host: String,
         server_port: i32,
-        retry_registration: bool, // 添加重试注册标志
+        mut retry_registration: bool, // 添加重试注册标志
+        jitter: JitterStrategy,
         plugin_name: String,      // 添加插件信息
         plugin_version: String,
         plugin_type: String,
@@ -87,20 +263,31 @@ impl BasePlugin {
         let mut _registration_retried = false; // 添加下划线前缀表示有意不使用
         let mut retry_count = 0;
         let max_retries = 3; // 最大重试次数
+        // 上次心跳响应中的服务器时间，用于检测服务器重启
+        let mut last_server_time: Option<i64> = None;
+        let mut server_restarted = false;
+        // 连续心跳失败次数和最近一次心跳成功的时间，用于自停策略
+        let mut consecutive_failures: u32 = 0;
+        let mut last_success = Instant::now();
         
         loop {
+            // 每轮重新读取心跳间隔，运行时修改后立即生效
+            let heartbeat_interval = settings.read().unwrap().get_heartbeat_interval();
+            // 心跳失败后的重连加抖动，避免多个插件同时重连服务器
+            let mut delay = Duration::from_secs(heartbeat_interval);
+            if consecutive_failures > 0 {
+                delay = jitter.apply(delay, &mut rand::thread_rng());
+            }
+            
             tokio::select! {
-                _ = tokio::time::sleep(Duration::from_secs(5)) => {
-                    let is_running = {
-                        let guard = running.lock().unwrap();
-                        *guard
-                    };
-        
-                    if !is_running {
+                _ = tokio::time::sleep(delay) => {
+                    if !health.is_running() {
                         println!("插件已停止，心跳线程退出");
                         break;
                     }
         
+                    let mut heartbeat_ok = false;
+
                     // 使用connect()而不是connect_lazy()
                     match Endpoint::from_shared(endpoint.clone()) {
                         Ok(endpoint) => {
@@ -114,17 +301,32 @@ impl BasePlugin {
                                     let mut client = PluginServiceClient::new(channel);
                                     
                                     // 发送心跳
+                                    let (status_info, reported) = status.status_info();
                                     let request = tonic::Request::new(HeartbeatRequest {
-                                        plugin_id: plugin_id.clone(),
-                                        status_info: status.clone(),
+                                        plugin_id: identity.id(),
+                                        status_info,
                                     });
         
                                     match client.heartbeat(request).await {
-                                        Ok(_) => {
-                                            println!("心跳发送成功");
+                                        Ok(response) => {
+                                            let response = response.into_inner();
+                                            if settings.read().unwrap().log_enabled("info") {
+                                                println!("心跳发送成功");
+                                            }
+                                            health.set_server_connected(true);
+                                            heartbeat_ok = true;
+                                            status.mark_reported(reported);
+                                            
+                                            // 服务器时间回退说明服务器已重启并丢失了注册信息，需要重新注册
+                                            if Self::detect_server_restart(&mut last_server_time, response.server_time) {
+                                                println!("检测到服务器重启 (server_time 回退)，将重新注册插件");
+                                                server_restarted = true;
+                                                retry_registration = true;
+                                                retry_count = 0;
+                                            }
                                             
-                                            // 如果需要重试注册且尚未达到最大重试次数
-                                            if retry_registration && retry_count < max_retries && plugin_id.contains("-") {
+                                            // 还没有服务器分配的ID或服务器重启后重新注册，已注册成功后不再重复注册
+                                            if retry_registration && retry_count < max_retries && (server_restarted || !identity.is_server_assigned()) {
                                                 println!("心跳成功，尝试重新注册插件 (尝试 {}/{})", retry_count + 1, max_retries);
                                                 retry_count += 1;
                                                 
@@ -148,9 +350,14 @@ impl BasePlugin {
                                                         
                                                         if response.success {
                                                             println!("插件重新注册成功: {}", response.message);
-                                                            println!("新插件ID: {}", response.plugin_id);
+                                                            if identity.accept_registration(&response.plugin_id) != RegistrationOutcome::Ignored {
+                                                                println!("新插件ID: {}", response.plugin_id);
+                                                            }
+                                                            // 服务器可能已升级或回退，重新协商协议特性
+                                                            *status.features.lock().unwrap() = ServerFeatures::negotiate(&response.message);
                                                             _registration_retried = true; // 使用修改后的变量名
                                                             retry_count = max_retries; // 不再重试
+                                                            server_restarted = false;
                                                         } else {
                                                             eprintln!("插件重新注册失败: {}", response.message);
                                                         }
@@ -163,11 +370,13 @@ impl BasePlugin {
                                         }
                                         Err(e) => {
                                             eprintln!("心跳发送失败: {}", e);
+                                            health.set_server_connected(false);
                                         }
                                     }
                                 }
                                 Err(e) => {
                                     eprintln!("心跳连接失败: {}", e);
+                                    health.set_server_connected(false);
                                 }
                             }
                         }
@@ -175,6 +384,20 @@ impl BasePlugin {
                             eprintln!("创建心跳Endpoint失败: {}", e);
                         }
                     }
+        
+                    if heartbeat_ok {
+                        health.record_heartbeat();
+                        consecutive_failures = 0;
+                        last_success = Instant::now();
+                    } else {
+                        consecutive_failures += 1;
+                        if offline_policy.exceeded(consecutive_failures, last_success.elapsed()) {
+                            eprintln!("与服务器失联 (连续失败 {} 次，已 {} 秒)，触发自停策略",
+                                      consecutive_failures, last_success.elapsed().as_secs());
+                            offline_policy.trigger(&health);
+                            break;
+                        }
+                    }
                 }
                 _ = shutdown_rx.recv() => {
                     println!("收到关闭信号，心跳线程退出");
@@ -184,7 +407,22 @@ impl BasePlugin {
         }
     }
     
+    /// 根据心跳响应中的 server_time 判断服务器是否重启
+    ///
+    /// server_time 比上次看到的值小时认为服务器已重启；为 0 表示服务器未提供时间，忽略
+    #[cfg(feature = "grpc")]
+    fn detect_server_restart(last_server_time: &mut Option<i64>, server_time: i64) -> bool {
+        if server_time <= 0 {
+            return false;
+        }
+
+        let restarted = matches!(*last_server_time, Some(last) if server_time < last);
+        *last_server_time = Some(server_time);
+        restarted
+    }
+    
     // 将 register_with_server 方法移到 impl 块内部
+    #[cfg(feature = "grpc")]
     async fn register_with_server(&mut self) -> bool {
         // 首先检查配置是否存在
         if self.config.is_none() {
@@ -200,7 +438,6 @@ impl BasePlugin {
         let plugin_description;
         let plugin_type;
         let host_address;
-        let plugin_grpc_port;
         
         // 使用作用域来限制不可变借用
         {
@@ -217,11 +454,14 @@ impl BasePlugin {
                 .map(|s| s.to_string())
                 .unwrap_or_else(|| "localhost".to_string());
                 
-            // 获取插件的gRPC端口
-            plugin_grpc_port = config.get_config("plugin_grpc_port")
-                .and_then(|s| s.parse::<i32>().ok())
-                .unwrap_or(19091); // 默认使用19091作为插件自身的gRPC端口
         }
+
+        // 获取插件的gRPC端口，配置为0时使用实际绑定的端口
+        let plugin_grpc_port = self.advertised_port();
+        
+        // 记录注册时使用的地址和端口
+        self.info.set_host(host_address.clone());
+        self.info.set_port(plugin_grpc_port);
         
         // 创建连接字符串
         let conn_str = format!("http://{}:{}", server_host, server_port);
@@ -251,60 +491,78 @@ impl BasePlugin {
                         let response = response.into_inner();
                         if response.success {
                             println!("插件注册成功: {}", response.message);
+                            self.health.set_server_connected(true);
                             
-                            // 更新配置中的注册状态
-                            if let Some(config) = &mut self.config {
-                                config.set_plugin_id(response.plugin_id.clone());
-                            }
-                            self.info.set_id(response.plugin_id.clone());
+                            // 更新注册状态，重复的注册响应不会改变已分配的ID
+                            self.identity.accept_registration(&response.plugin_id);
+                            self.sync_plugin_id();
+                            self.negotiate_features(&response.message);
                             
                             return true;
                         } else {
                             eprintln!("插件注册失败: {}", response.message);
-                            // 生成本地ID
-                            use uuid::Uuid;
-                            let local_id = Uuid::new_v4().to_string();
-                            self.info.set_id(local_id.clone());
-                            if let Some(config) = &mut self.config {
-                                config.set_plugin_id(local_id.clone()); // 添加 clone() 以避免移动
-                            }
-                            println!("生成本地插件ID: {}", self.info.get_id());
+                            self.ensure_plugin_id();
                             return true;
                         }
                     },
                     Err(e) => {
                         eprintln!("插件注册失败: {}", e);
                         eprintln!("错误详情: {:?}", e);
-                        // 生成本地ID
-                        use uuid::Uuid;
-                        let local_id = Uuid::new_v4().to_string();
-                        self.info.set_id(local_id.clone());
-                        if let Some(config) = &mut self.config {
-                            config.set_plugin_id(local_id.clone()); // 添加 clone()
-                        }
-                        println!("生成本地插件ID: {}", self.info.get_id());
+                        self.ensure_plugin_id();
                         return true;
                     }
                 }
             }
             Err(e) => {
                 eprintln!("创建gRPC客户端失败: {}", e);
-                // 生成本地ID
-                use uuid::Uuid;
-                let local_id = Uuid::new_v4().to_string();
-                self.info.set_id(local_id.clone());
-                if let Some(config) = &mut self.config {
-                    config.set_plugin_id(local_id.clone()); // 添加 clone() 以避免移动
-                }
-                println!("生成本地插件ID: {}", local_id);
+                self.ensure_plugin_id();
                 return true;
             }
         }
     }
+
+    // 根据注册响应协商服务器支持的协议特性
+    #[cfg(feature = "grpc")]
+    fn negotiate_features(&self, message: &str) {
+        let features = ServerFeatures::negotiate(message);
+        match features.server_version() {
+            Some((major, minor, patch)) => println!(
+                "服务器版本: {}.{}.{}，心跳附带插件能力: {}",
+                major, minor, patch, features.supports_inline_capabilities()
+            ),
+            None => println!("注册响应中没有服务器版本，按旧版服务器处理"),
+        }
+        *self.server_features.lock().unwrap() = features;
+    }
+
+    /// 与服务器协商得到的协议特性，注册成功前所有可选特性关闭
+    pub fn server_features(&self) -> ServerFeatures {
+        *self.server_features.lock().unwrap()
+    }
+
+    // 还没有插件ID时生成本地ID；已有的ID（服务器分配的或之前使用的本地ID）保持不变
+    fn ensure_plugin_id(&mut self) {
+        if self.identity.id().is_empty() {
+            let local_id = uuid::Uuid::new_v4().to_string();
+            self.identity.set_local_id(local_id.clone());
+            println!("生成本地插件ID: {}", local_id);
+        }
+        self.sync_plugin_id();
+    }
+
+    // 将共享的插件ID同步到插件信息和配置
+    fn sync_plugin_id(&mut self) {
+        let id = self.identity.id();
+        self.info.set_id(id.clone());
+        if let Some(config) = &mut self.config {
+            config.set_plugin_id(id);
+        }
+    }
     
     // 添加心跳方法
+    #[cfg(feature = "grpc")]
     pub async fn send_heartbeat(&self) -> Result<bool, String> {
-        if self.info.get_id().is_empty() {
+        if self.identity.id().is_empty() {
             return Err("插件未注册，无法发送心跳".to_string());
         }
         
@@ -317,13 +575,22 @@ impl BasePlugin {
                     if *guard { "RUNNING" } else { "STOPPED" }
                 };
                 
+                let heartbeat_status = HeartbeatStatus {
+                    status: status.to_string(), // 使用实际运行状态
+                    commands: self.info.get_supported_commands().clone(),
+                    features: Arc::clone(&self.server_features),
+                    telemetry: self.heartbeat_telemetry(),
+                };
+                let (status_info, reported) = heartbeat_status.status_info();
                 let request = tonic::Request::new(HeartbeatRequest {
-                    plugin_id: self.info.get_id().to_string(),
-                    status_info: status.to_string(), // 使用实际运行状态
+                    plugin_id: self.identity.id(),
+                    status_info,
                 });
         
                 match client.heartbeat(request).await {
                     Ok(_) => {
+                        self.health.record_heartbeat();
+                        heartbeat_status.mark_reported(reported);
                         println!("心跳发送成功，状态: {}", status);
                         Ok(true)
                     },
@@ -335,6 +602,7 @@ impl BasePlugin {
     }
     
     // 添加重试注册方法
+    #[cfg(feature = "grpc")]
     pub async fn retry_register(&mut self) -> Result<(), String> {
         // 增加默认重试次数到5次
         let max_retries = match &self.config {
@@ -352,21 +620,24 @@ impl BasePlugin {
             None => 3, // 默认值
         };
         
-        println!("开始注册插件，最大尝试次数: {}，重试间隔: {}秒", max_retries, retry_interval);
+        let jitter = self.config.as_ref().map(|config| config.get_retry_jitter()).unwrap_or_default();
+        
+        println!("开始注册插件，最大尝试次数: {}，重试间隔: {}秒，抖动: {:?}", max_retries, retry_interval, jitter);
             
         for i in 0..max_retries {
             println!("尝试注册插件 (尝试 {}/{})", i+1, max_retries);
             
             // 调用已有的注册方法
             if self.register_with_server().await {
-                println!("注册成功，插件ID: {}", self.info.get_id());
+                println!("注册成功，插件ID: {}", self.identity.id());
                 return Ok(());
             }
             
             // 最后一次尝试后不需要等待
             if i < max_retries - 1 {
-                println!("注册失败，{}秒后重试...", retry_interval);
-                tokio::time::sleep(tokio::time::Duration::from_secs(retry_interval)).await;
+                let delay = jitter.apply(Duration::from_secs(retry_interval), &mut rand::thread_rng());
+                println!("注册失败，{}毫秒后重试...", delay.as_millis());
+                tokio::time::sleep(delay).await;
             }
         }
         
@@ -374,82 +645,457 @@ impl BasePlugin {
         println!("注册失败，已达到最大重试次数 {}，将以本地模式运行", max_retries);
         
         // 确保我们有一个有效的本地ID
-        if self.info.get_id().is_empty() {
-            use uuid::Uuid;
-            let local_id = Uuid::new_v4().to_string();
-            self.info.set_id(local_id.clone());
-            if let Some(config) = &mut self.config {
-                config.set_plugin_id(local_id.clone());
-            }
-            println!("生成本地插件ID: {}", local_id);
-        }
+        self.ensure_plugin_id();
         
         // 返回Ok而不是Err，因为我们可以以本地模式运行
         Ok(())
     }
-}
 
-#[async_trait]
-impl PluginSDK for BasePlugin {
-    async fn initialize(&mut self, config: PluginConfig) -> bool {
-        self.config = Some(config.clone());
-        
-        // 设置插件基本信息
-        self.info.set_id(config.get_plugin_id().to_string());
-        self.info.set_name(config.get_plugin_name().to_string());
-        self.info.set_version(config.get_plugin_version().to_string());
-        self.info.set_type(config.get_plugin_type().to_string());
-        
-        true
+    /// 按名称和类型查找已注册的插件
+    ///
+    /// 服务器未找到时返回 `Ok(None)`
+    #[cfg(feature = "grpc")]
+    pub async fn find_plugin(&self, name: &str, plugin_type: &str) -> Result<Option<PluginInfo>, String> {
+        let mut client = self.create_client().await
+            .map_err(|e| format!("创建gRPC客户端失败: {}", e))?;
+
+        let request = tonic::Request::new(FindPluginRequest {
+            name: name.to_string(),
+            r#type: plugin_type.to_string(),
+        });
+
+        let response = client.find_plugin(request).await
+            .map_err(|e| format!("查找插件失败: {}", e))?
+            .into_inner();
+
+        if !response.found {
+            return Ok(None);
+        }
+
+        Ok(response.plugin.map(PluginInfo::from))
     }
 
-    async fn start(&mut self) -> bool {
+    /// 按名称获取已注册的插件
+    ///
+    /// 服务器未找到时返回 `Ok(None)`
+    #[cfg(feature = "grpc")]
+    pub async fn get_plugin_by_name(&self, name: &str) -> Result<Option<PluginInfo>, String> {
+        let mut client = self.create_client().await
+            .map_err(|e| format!("创建gRPC客户端失败: {}", e))?;
+
+        let request = tonic::Request::new(GetPluginByNameRequest {
+            name: name.to_string(),
+        });
+
+        let response = client.get_plugin_by_name(request).await
+            .map_err(|e| format!("获取插件失败: {}", e))?
+            .into_inner();
+
+        if !response.found {
+            return Ok(None);
+        }
+
+        Ok(response.plugin.map(PluginInfo::from))
+    }
+
+    // 获取向服务器注册的主机地址
+    #[cfg(feature = "grpc")]
+    fn advertised_host(&self) -> String {
+        self.config.as_ref()
+            .and_then(|config| config.get_config("host_address"))
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "localhost".to_string())
+    }
+
+    // 配置的插件gRPC端口，默认19091，0表示由系统分配
+    #[cfg(feature = "grpc")]
+    fn configured_port(&self) -> i32 {
+        self.config.as_ref()
+            .and_then(|config| config.get_config("plugin_grpc_port"))
+            .and_then(|s| s.parse::<i32>().ok())
+            .unwrap_or(19091)
+    }
+
+    // 获取向服务器注册的插件gRPC端口，配置为0时使用实际绑定的端口
+    #[cfg(feature = "grpc")]
+    fn advertised_port(&self) -> i32 {
+        match self.configured_port() {
+            0 => self.bound_port.load(Ordering::SeqCst),
+            port => port,
+        }
+    }
+
+    /// 入站gRPC服务实际绑定的端口，尚未绑定时为 None
+    #[cfg(feature = "grpc")]
+    pub fn bound_port(&self) -> Option<u16> {
+        match self.bound_port.load(Ordering::SeqCst) {
+            0 => None,
+            port => u16::try_from(port).ok(),
+        }
+    }
+
+    // 绑定入站gRPC端口并记录实际端口，已预先绑定时直接返回
+    #[cfg(feature = "grpc")]
+    async fn bind_listener(&self) -> Result<TcpListener, String> {
+        if let Some(listener) = self.listener.lock().unwrap().take() {
+            return Ok(listener);
+        }
+
+        let addr: SocketAddr = format!("0.0.0.0:{}", self.configured_port())
+            .parse()
+            .map_err(|e| format!("无效的插件gRPC地址: {}", e))?;
+        let listener = TcpListener::bind(addr).await
+            .map_err(|e| format!("绑定插件gRPC端口失败: {}", e))?;
+        let port = listener.local_addr()
+            .map_err(|e| format!("获取插件gRPC端口失败: {}", e))?
+            .port();
+        self.bound_port.store(i32::from(port), Ordering::SeqCst);
+        Ok(listener)
+    }
+
+    /// 将当前状态、主机地址和gRPC端口同步到服务器
+    #[cfg(feature = "grpc")]
+    pub async fn update_registration(&self) -> Result<(), String> {
+        if self.identity.id().is_empty() {
+            return Err("插件未注册，无法更新注册信息".to_string());
+        }
+
+        let status = if self.info.get_status().is_empty() {
+            let guard = self.running.lock().unwrap();
+            if *guard { "RUNNING" } else { "STOPPED" }.to_string()
+        } else {
+            self.info.get_status().to_string()
+        };
+
+        let mut client = self.create_client().await
+            .map_err(|e| format!("创建gRPC客户端失败: {}", e))?;
+
+        let request = tonic::Request::new(UpdatePluginRequest {
+            plugin_id: self.identity.id(),
+            status,
+            host: self.advertised_host(),
+            port: self.advertised_port(),
+        });
+
+        let response = client.update_plugin(request).await
+            .map_err(|e| format!("更新注册信息失败: {}", e))?
+            .into_inner();
+
+        if response.success {
+            println!("注册信息已更新: {}", response.message);
+            Ok(())
+        } else {
+            Err(format!("服务器拒绝更新注册信息: {}", response.message))
+        }
+    }
+
+    /// 修改向服务器注册的主机地址和gRPC端口
+    ///
+    /// 插件运行中且地址发生变化时自动调用 `update_registration` 通知服务器
+    #[cfg(feature = "grpc")]
+    pub async fn set_advertised_address(&mut self, host: String, port: i32) -> Result<(), String> {
+        let config = self.config.as_mut().ok_or("插件配置未初始化")?;
+        config.add_config("host_address".to_string(), host.clone());
+        config.add_config("plugin_grpc_port".to_string(), port.to_string());
+
+        let changed = self.info.get_host() != host || self.info.get_port() != port;
+        self.info.set_host(host);
+        self.info.set_port(port);
+
         let is_running = {
-            let mut guard = self.running.lock().unwrap();
-            if *guard {
-                return true;
+            let guard = self.running.lock().unwrap();
+            *guard
+        };
+
+        if changed && is_running {
+            self.update_registration().await?;
+        }
+
+        Ok(())
+    }
+
+    /// 在配置的 plugin_grpc_port 上启动插件自身的gRPC服务
+    ///
+    /// 主应用的 ExecuteCommand/GetStatus/StopPlugin 调用转发给 `plugin`，
+    /// 插件停止时服务随之关闭。返回服务任务的句柄。
+    /// `plugin_grpc_port=0` 时由系统分配端口，`start` 阶段已预先绑定并注册实际端口；
+    /// 未经 `start` 绑定时在这里绑定，已注册则通过 `update_registration` 上报实际端口
+    #[cfg(feature = "grpc")]
+    pub async fn serve<P>(&self, plugin: Arc<P>) -> Result<JoinHandle<()>, String>
+    where
+        P: PluginSDK + Send + Sync + 'static,
+    {
+        let prebound = self.listener.lock().unwrap().is_some();
+        let listener = self.bind_listener().await?;
+        println!("插件gRPC服务监听: 0.0.0.0:{}", self.bound_port.load(Ordering::SeqCst));
+
+        if !prebound && self.configured_port() == 0 && !self.identity.id().is_empty() && self.health.is_running()
+            && let Err(e) = self.update_registration().await
+        {
+            eprintln!("上报插件gRPC端口失败: {}", e);
+        }
+
+        self.server_shutdown.send_replace(false);
+        let mut shutdown_rx = self.server_shutdown.subscribe();
+        let service = PluginServer::new(plugin, self.health.clone(), self.server_shutdown.clone());
+
+        let handle = tokio::spawn(async move {
+            let result = Server::builder()
+                .add_service(PluginServiceServer::new(service))
+                .serve_with_incoming_shutdown(TcpIncoming::from(listener), async move {
+                    let _ = shutdown_rx.wait_for(|stopped| *stopped).await;
+                })
+                .await;
+
+            if let Err(e) = result {
+                eprintln!("插件gRPC服务异常退出: {}", e);
             }
-            *guard = true;
-            true
+            println!("插件gRPC服务已关闭");
+        });
+
+        Ok(handle)
+    }
+
+    /// 获取插件当前状态
+    pub fn state(&self) -> PluginState {
+        self.health.state()
+    }
+
+    /// 获取共享的健康状态，可交给后台任务更新
+    pub fn health(&self) -> PluginHealth {
+        self.health.clone()
+    }
+
+    /// 最近一次心跳成功的时间，本次启动后还没有成功的心跳时为 None
+    pub fn last_heartbeat_at(&self) -> Option<std::time::Instant> {
+        self.health.last_heartbeat_at()
+    }
+
+    /// 自启动以来的运行时长，未运行时为 None
+    pub fn uptime(&self) -> Option<Duration> {
+        self.health.uptime()
+    }
+
+    /// 获取共享的运行指标
+    pub fn metrics(&self) -> PluginMetrics {
+        self.metrics.clone()
+    }
+
+    /// 记录命令执行耗时，超过 `slow_command_threshold_ms` 时输出警告
+    ///
+    /// 返回该命令是否为慢命令
+    pub fn record_command(&self, command: &str, elapsed: Duration) -> bool {
+        let threshold = self.settings().get_slow_command_threshold_ms();
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let slow = threshold > 0 && elapsed_ms > threshold;
+
+        if slow && self.settings().log_enabled("warn") {
+            eprintln!("警告: 命令 {} 执行耗时 {}ms，超过阈值 {}ms", command, elapsed_ms, threshold);
+        }
+
+        self.metrics.record_command(command, elapsed, slow);
+        slow
+    }
+
+    /// 标记插件本地数据是否已加载完成
+    ///
+    /// 插件在 `start` 之后完成自身初始化（如加载持久化数据）时调用，之前状态为 `STARTING`
+    pub fn set_ready(&self, ready: bool) {
+        self.health.set_ready(ready);
+    }
+
+    /// 修改插件信息，注册、更新注册和心跳都使用这里的名称、描述、状态和支持的命令
+    ///
+    /// 插件ID和端口以注册结果和实际绑定的端口为准，在这里设置的值会被 `get_info` 覆盖
+    pub fn info_mut(&mut self) -> &mut PluginInfo {
+        &mut self.info
+    }
+
+    /// 设置触发自停策略时执行的回调
+    ///
+    /// 在 `max_heartbeat_failures` 或 `max_offline_secs` 超限、插件自动停止后调用
+    pub fn set_offline_hook(&mut self, hook: OfflineHook) {
+        self.offline_hook = Some(hook);
+    }
+
+    /// 设置心跳附带的自定义指标
+    ///
+    /// 只在 `heartbeat_metrics=true` 且服务器支持结构化 status_info 时上报
+    pub fn set_telemetry_hook(&mut self, hook: TelemetryHook) {
+        self.telemetry_hook = Some(hook);
+    }
+
+    // 配置开启心跳指标时返回采集器，手动心跳和心跳线程共用已上报的命令数
+    #[cfg(feature = "grpc")]
+    fn heartbeat_telemetry(&self) -> Option<HeartbeatTelemetry> {
+        let enabled = self.config.as_ref().is_some_and(|config| config.get_heartbeat_metrics());
+        enabled.then(|| HeartbeatTelemetry {
+            health: self.health.clone(),
+            metrics: self.metrics.clone(),
+            hook: self.telemetry_hook.clone(),
+            reported_commands: Arc::clone(&self.reported_commands),
+        })
+    }
+
+    /// 获取当前运行时配置
+    pub fn settings(&self) -> RuntimeSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// 在运行时修改可热更新的配置项，不会断开gRPC连接或丢失内存状态
+    ///
+    /// 所有配置项校验通过后才会生效，返回实际发生变化的配置项
+    pub fn apply_settings(&self, changes: &HashMap<String, String>) -> Result<Vec<String>, String> {
+        let mut updated = self.settings();
+        let mut changed = Vec::new();
+        for (key, value) in changes {
+            // 每个配置项对应单独的字段，与应用前比较即可知道该项是否变化
+            let previous = updated.clone();
+            updated.apply(key, value)?;
+            if updated != previous {
+                changed.push(key.clone());
+            }
+        }
+        changed.sort();
+
+        *self.settings.write().unwrap() = updated;
+        Ok(changed)
+    }
+
+    /// 使用新的插件配置重新配置插件
+    ///
+    /// 只允许修改 `HOT_RELOADABLE_KEYS` 中的配置项，修改插件类型、服务器地址等需要重启插件
+    pub fn reconfigure(&mut self, config: PluginConfig) -> Result<Vec<String>, String> {
+        let current = self.config.as_ref().ok_or("插件配置未初始化")?;
+
+        if config.get_server_host() != current.get_server_host() {
+            return Err("配置项 server_host 不支持运行时修改，请重启插件".to_string());
+        }
+        if config.get_server_port() != current.get_server_port() {
+            return Err("配置项 server_port 不支持运行时修改，请重启插件".to_string());
+        }
+        if config.get_plugin_type() != current.get_plugin_type() {
+            return Err("配置项 plugin_type 不支持运行时修改，请重启插件".to_string());
+        }
+        if config.get_plugin_name() != current.get_plugin_name() {
+            return Err("配置项 plugin_name 不支持运行时修改，请重启插件".to_string());
+        }
+
+        // 只收集发生变化的额外配置项
+        let changes: HashMap<String, String> = config.get_additional_config()
+            .iter()
+            .filter(|(key, value)| current.get_config(key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        let changed = self.apply_settings(&changes)?;
+
+        if let Some(current) = &mut self.config {
+            for (key, value) in changes {
+                current.add_config(key, value);
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// 停止插件并返回服务器是否确认了停止请求
+    ///
+    /// 插件未运行时不会通知服务器，返回 `Ok(false)`
+    pub async fn stop_with_result(&mut self) -> Result<bool, String> {
+        self.health.set_stopping(true);
+        let result = self.shutdown_and_notify().await;
+
+        self.health.set_ready(false);
+        self.health.set_server_connected(false);
+        self.health.set_stopping(false);
+        self.health.mark_stopped();
+        result
+    }
+
+    async fn shutdown_and_notify(&mut self) -> Result<bool, String> {
+        let was_running = {
+            let mut guard = self.running.lock().unwrap();
+            let was_running = *guard;
+            *guard = false;
+            was_running
         };
-    
-        if !is_running {
-            return false;
+
+        // 关闭入站gRPC服务，释放尚未使用的预绑定端口
+        self.server_shutdown.send_replace(true);
+        #[cfg(feature = "grpc")]
+        self.listener.lock().unwrap().take();
+
+        if !was_running {
+            return Ok(false);
         }
-    
+
+        // 停止心跳线程
+        if let Some(tx) = &self.shutdown_tx {
+            let _ = tx.send(()).await;
+        }
+
+        if let Some(handle) = self.heartbeat_handle.take() {
+            let _ = handle.await;
+        }
+
+        self.notify_server_stopped().await
+    }
+
+    // 通知服务器停止插件，返回服务器是否确认
+    #[cfg(feature = "grpc")]
+    async fn notify_server_stopped(&self) -> Result<bool, String> {
+        let mut client = self.create_client().await
+            .map_err(|e| format!("创建gRPC客户端失败: {}", e))?;
+
+        let request = tonic::Request::new(StopRequest {
+            plugin_id: self.identity.id(),
+        });
+
+        match client.stop_plugin(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                if response.success {
+                    println!("服务器已确认停止插件: {}", response.message);
+                } else {
+                    eprintln!("服务器拒绝停止插件: {}", response.message);
+                }
+                Ok(response.success)
+            }
+            Err(e) => Err(format!("发送停止请求失败: {}", e)),
+        }
+    }
+
+    // 启动心跳线程
+    #[cfg(feature = "grpc")]
+    fn start_heartbeat(&mut self, retry_registration: bool) {
         // 创建一个配置的副本，避免后面的借用冲突
         let config_clone = match &self.config {
             Some(config) => config.clone(),
-            None => return false,
+            None => return,
         };
     
-        // 尝试注册插件
-        println!("尝试注册插件...");
-        let registration_success = self.register_with_server().await;
-        
-        // 如果注册失败，我们将在心跳中重试
-        let retry_registration = !registration_success || self.info.get_id().contains("-");
-    
-        // 如果插件ID为空，生成一个本地ID
-        if self.info.get_id().is_empty() {
-            use uuid::Uuid;
-            let local_id = Uuid::new_v4().to_string();
-            self.info.set_id(local_id.clone());
-            if let Some(config) = &mut self.config {
-                config.set_plugin_id(local_id.clone());
-            }
-            println!("生成本地插件ID: {}", local_id);
-        }
-    
-        // 启动心跳线程
         let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
         self.shutdown_tx = Some(shutdown_tx);
     
-        let plugin_id = self.info.get_id().to_string();
-        let status = self.info.get_status().to_string();
-        let running = Arc::clone(&self.running);
+        let identity = self.identity.clone();
+        let status = HeartbeatStatus {
+            status: self.info.get_status().to_string(),
+            commands: self.info.get_supported_commands().clone(),
+            features: Arc::clone(&self.server_features),
+            telemetry: self.heartbeat_telemetry(),
+        };
+        let health = self.health.clone();
+        let settings = Arc::clone(&self.settings);
+        let offline_policy = OfflinePolicy {
+            max_failures: config_clone.get_max_heartbeat_failures(),
+            max_offline: config_clone.get_max_offline_secs().map(Duration::from_secs),
+            hook: self.offline_hook.clone(),
+            server_shutdown: self.server_shutdown.clone(),
+        };
         let server_host = config_clone.get_server_host().to_string();
         let server_port = config_clone.get_server_port();
+        let jitter = config_clone.get_retry_jitter();
     
         // 添加插件信息用于重新注册
         let plugin_name = self.info.get_name().to_string();
@@ -462,19 +1108,20 @@ impl PluginSDK for BasePlugin {
             .map(|s| s.to_string())
             .unwrap_or_else(|| "localhost".to_string());
             
-        let plugin_grpc_port = config_clone.get_config("plugin_grpc_port")
-            .and_then(|s| s.parse::<i32>().ok())
-            .unwrap_or(19091);
+        let plugin_grpc_port = self.advertised_port();
     
         let handle = tokio::spawn(async move {
             Self::heartbeat_loop(
-                plugin_id,
+                identity,
                 status,
-                running,
+                health,
+                settings,
+                offline_policy,
                 shutdown_rx,
                 server_host,
                 server_port,
                 retry_registration,
+                jitter,
                 plugin_name,
                 plugin_version,
                 plugin_type,
@@ -486,56 +1133,110 @@ impl PluginSDK for BasePlugin {
         });
     
         self.heartbeat_handle = Some(handle);
-        println!("插件已启动，ID: {}", self.info.get_id());
-    
+    }
+
+    // 未启用 grpc 特性时没有需要通知的服务器
+    #[cfg(not(feature = "grpc"))]
+    async fn notify_server_stopped(&self) -> Result<bool, String> {
+        Ok(false)
+    }
+}
+
+#[async_trait]
+impl PluginSDK for BasePlugin {
+    async fn initialize(&mut self, config: PluginConfig) -> bool {
+        self.config = Some(config.clone());
+        *self.settings.write().unwrap() = RuntimeSettings::from_config(&config);
+        
+        // 设置插件基本信息
+        self.identity.set_local_id(config.get_plugin_id().to_string());
+        self.info.set_id(self.identity.id());
+        self.info.set_name(config.get_plugin_name().to_string());
+        self.info.set_version(config.get_plugin_version().to_string());
+        self.info.set_type(config.get_plugin_type().to_string());
+        
         true
     }
 
-    async fn stop(&mut self) -> bool {
-        let was_running = {
+    async fn start(&mut self) -> bool {
+        let is_running = {
             let mut guard = self.running.lock().unwrap();
-            let was_running = *guard;
-            *guard = false;
-            was_running
+            if *guard {
+                return true;
+            }
+            *guard = true;
+            true
         };
-
-        if !was_running {
-            return true;
+    
+        if !is_running {
+            return false;
         }
-
-        // 停止心跳线程
-        if let Some(tx) = &self.shutdown_tx {
-            let _ = tx.send(()).await;
+    
+        if self.config.is_none() {
+            return false;
         }
+        self.health.mark_started();
+    
+        // 尝试注册插件，端口由系统分配时先绑定以便注册实际端口
+        #[cfg(feature = "grpc")]
+        let retry_registration = {
+            if self.configured_port() == 0 {
+                match self.bind_listener().await {
+                    Ok(listener) => *self.listener.lock().unwrap() = Some(listener),
+                    Err(e) => eprintln!("预先绑定插件gRPC端口失败: {}", e),
+                }
+            }
 
-        if let Some(handle) = self.heartbeat_handle.take() {
-            let _ = handle.await;
-        }
+            println!("尝试注册插件...");
+            let registration_success = self.register_with_server().await;
 
-        // 通知服务器停止插件
-        if let Some(client_result) = self.create_client().await.ok() {
-            let mut client = client_result;
-            let request = tonic::Request::new(StopRequest {
-                plugin_id: self.info.get_id().to_string(),
-            });
+            // 没有拿到服务器分配的ID时在心跳中重试
+            !registration_success || !self.identity.is_server_assigned()
+        };
+    
+        // 如果插件ID为空，生成一个本地ID
+        self.ensure_plugin_id();
+    
+        #[cfg(feature = "grpc")]
+        self.start_heartbeat(retry_registration);
+        println!("插件已启动，ID: {}", self.identity.id());
+    
+        true
+    }
 
-            let _ = client.stop_plugin(request).await;
+    async fn stop(&mut self) -> bool {
+        // 服务器是否确认不影响本地停止结果
+        if let Err(e) = self.stop_with_result().await {
+            eprintln!("通知服务器停止插件失败: {}", e);
         }
 
         true
     }
 
     fn get_info(&self) -> PluginInfo {
-        self.info.clone()
+        // 心跳线程重新注册后插件ID可能已经变化
+        let mut info = self.info.clone();
+        info.set_id(self.identity.id());
+        // 端口由系统分配时以实际绑定的端口为准
+        #[cfg(feature = "grpc")]
+        if self.config.is_some() {
+            info.set_port(self.advertised_port());
+        }
+        info
     }
 
-    async fn execute_command(&self, command: &str, _params: &HashMap<String, String>) -> CommandResult {
-        // 添加下划线前缀表示有意不使用该变量
-        CommandResult::new(
-            false,
-            String::new(),
-            format!("不支持的命令: {}", command),
-        )
+    async fn execute_command(&self, command: &str, params: &HashMap<String, String>) -> CommandResult {
+        match command {
+            "reconfigure" => match self.apply_settings(&setting_changes(params)) {
+                Ok(changed) => CommandResult::json(&changed),
+                Err(e) => CommandResult::new(false, String::new(), e),
+            },
+            _ => CommandResult::new(
+                false,
+                String::new(),
+                format!("不支持的命令: {}", command),
+            ),
+        }
     }
 
     async fn handle_message(&self, message: &str) -> String {
@@ -544,3 +1245,28 @@ impl PluginSDK for BasePlugin {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reconfigure_command_ignores_generic_params() {
+        let mut plugin = BasePlugin::new();
+        assert!(plugin.initialize(PluginConfig::new()).await);
+
+        let params = HashMap::from([
+            ("log_level".to_string(), "warn".to_string()),
+            ("user".to_string(), "admin".to_string()),
+            ("operation_id".to_string(), "reconfigure-1".to_string()),
+        ]);
+        let result = plugin.execute_command("reconfigure", &params).await;
+        assert!(result.is_success(), "{}", result.get_error_message());
+        assert_eq!(result.get_result(), r#"["log_level"]"#);
+        assert_eq!(plugin.settings().get_log_level(), "warn");
+
+        // 通用参数之外的未知配置项仍然报错
+        let params = HashMap::from([("server_host".to_string(), "10.0.0.1".to_string())]);
+        assert!(!plugin.execute_command("reconfigure", &params).await.is_success());
+    }
+}
@@ -0,0 +1,40 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// 规范化的 JSON 序列化，用于签名和验证
+///
+/// 对象的键按字典序排列（包括嵌套对象），没有多余空白。`HashMap` 字段（如密钥标签）
+/// 的遍历顺序不固定，相同内容直接序列化可能得到不同的字节，签名前需要先规范化
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, String> {
+    let value = serde_json::to_value(value).map_err(|e| format!("序列化失败: {}", e))?;
+    serde_json::to_vec(&canonicalize(value)).map_err(|e| format!("序列化失败: {}", e))
+}
+
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, String> {
+    let bytes = to_vec(value)?;
+    String::from_utf8(bytes).map_err(|e| format!("序列化失败: {}", e))
+}
+
+/// 按键排序重建所有对象
+pub fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().map(|(key, value)| (key, canonicalize(value))).collect::<Map<_, _>>())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonicalize).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_object_keys_are_sorted() {
+        let value = serde_json::json!({"b": 1, "a": {"d": [{"z": 1, "y": 2}], "c": null}});
+        assert_eq!(to_string(&value).unwrap(), r#"{"a":{"c":null,"d":[{"y":2,"z":1}]},"b":1}"#);
+    }
+}
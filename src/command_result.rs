@@ -1,20 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "grpc")]
+use crate::base_plugin::plugin::CommandResponse;
+use crate::canonical_json;
+
+/// 命令失败时的错误码，字符串形式稳定，供调用方按错误类别处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    KeyNotFound,
+    InvalidStatus,     // 密钥状态不允许该操作
+    ApprovalRequired,
+    Unauthorized,
+    InvalidParams,     // 缺少参数或参数无效
+    UnknownCommand,
+    Timeout,
+    Cancelled,
+    Draining,          // 插件正在排空，不再接受新命令
+    Internal,          // 其他错误，如安全模块或持久化存储失败
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::KeyNotFound => "KEY_NOT_FOUND",
+            ErrorCode::InvalidStatus => "INVALID_STATUS",
+            ErrorCode::ApprovalRequired => "APPROVAL_REQUIRED",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::InvalidParams => "INVALID_PARAMS",
+            ErrorCode::UnknownCommand => "UNKNOWN_COMMAND",
+            ErrorCode::Timeout => "TIMEOUT",
+            ErrorCode::Cancelled => "CANCELLED",
+            ErrorCode::Draining => "DRAINING",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+
+    /// 根据错误信息推断错误码，用于没有显式设置错误码的失败结果
+    pub fn from_message(message: &str) -> Self {
+        if message.starts_with("Key not found") || message.starts_with("Deleted key not found") {
+            ErrorCode::KeyNotFound
+        } else if message.starts_with("Key is not active") || message.starts_with("Key is not suspended") {
+            ErrorCode::InvalidStatus
+        } else if message.contains("requires approval") {
+            ErrorCode::ApprovalRequired
+        } else if message.starts_with("Missing parameter") || message.starts_with("Invalid ") {
+            ErrorCode::InvalidParams
+        } else {
+            ErrorCode::Internal
+        }
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "KEY_NOT_FOUND" => Ok(ErrorCode::KeyNotFound),
+            "INVALID_STATUS" => Ok(ErrorCode::InvalidStatus),
+            "APPROVAL_REQUIRED" => Ok(ErrorCode::ApprovalRequired),
+            "UNAUTHORIZED" => Ok(ErrorCode::Unauthorized),
+            "INVALID_PARAMS" => Ok(ErrorCode::InvalidParams),
+            "UNKNOWN_COMMAND" => Ok(ErrorCode::UnknownCommand),
+            "TIMEOUT" => Ok(ErrorCode::Timeout),
+            "CANCELLED" => Ok(ErrorCode::Cancelled),
+            "DRAINING" => Ok(ErrorCode::Draining),
+            "INTERNAL" => Ok(ErrorCode::Internal),
+            _ => Err(format!("Invalid error code: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// 命令执行结果结构体
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommandResult {
     success: bool,
     result: String,
     error_message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_code: Option<ErrorCode>, // 失败时的错误码，error_message 供人阅读
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<u64>, // 命令执行耗时（毫秒），由插件在分发命令后填写
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<Vec<u8>>, // 开启响应签名时对 signing_payload 的签名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signing_key_id: Option<String>, // 签名使用的密钥ID，轮换签名密钥后用于选择验证密钥
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    progress: Option<f32>,      // 流式执行时的进度，0.0 到 1.0，最终结果为 1.0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stage: Option<String>,      // 流式执行时当前所处的阶段
+}
+
+/// gRPC 响应 extensions 字段的内容：CommandResponse 中没有对应字段的结果字段
+///
+/// 增加字段时只能新增可选字段，旧版本解析时忽略不认识的字段
+#[cfg(feature = "grpc")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ResponseExtensions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signing_key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    progress: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stage: Option<String>,
+}
+
+#[cfg(feature = "grpc")]
+impl From<CommandResult> for CommandResponse {
+    fn from(result: CommandResult) -> Self {
+        let extensions = ResponseExtensions {
+            elapsed_ms: result.elapsed_ms,
+            signing_key_id: result.signing_key_id,
+            progress: result.progress,
+            stage: result.stage,
+        };
+        let extensions = serde_json::to_string(&extensions)
+            .ok()
+            .filter(|extensions| extensions != "{}")
+            .unwrap_or_default();
+
+        CommandResponse {
+            success: result.success,
+            result: result.result,
+            error_message: result.error_message,
+            signature: result.signature.unwrap_or_default(),
+            error_code: result.error_code.map(|code| code.as_str().to_string()).unwrap_or_default(),
+            extensions,
+        }
+    }
+}
+
+/// 调用方解析插件的响应；不认识的错误码按 INTERNAL 处理，无法解析的 extensions 忽略
+#[cfg(feature = "grpc")]
+impl From<CommandResponse> for CommandResult {
+    fn from(response: CommandResponse) -> Self {
+        let extensions: ResponseExtensions = if response.extensions.is_empty() {
+            ResponseExtensions::default()
+        } else {
+            serde_json::from_str(&response.extensions).unwrap_or_default()
+        };
+
+        Self {
+            success: response.success,
+            result: response.result,
+            error_message: response.error_message,
+            error_code: (!response.error_code.is_empty())
+                .then(|| ErrorCode::from_str(&response.error_code).unwrap_or(ErrorCode::Internal)),
+            elapsed_ms: extensions.elapsed_ms,
+            signature: (!response.signature.is_empty()).then_some(response.signature),
+            signing_key_id: extensions.signing_key_id,
+            progress: extensions.progress,
+            stage: extensions.stage,
+        }
+    }
+}
+
+/// 参数校验错误，field 为出错的参数名
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self { field: field.to_string(), message: message.into() }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
 }
 
 impl CommandResult {
+    /// 成功结果，result 为 value 的 JSON；序列化失败时返回失败结果，不返回空的成功结果
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(json) => Self::new(true, json, String::new()),
+            Err(e) => Self::new(false, String::new(), format!("Failed to serialize result: {}", e))
+                .with_error_code(ErrorCode::Internal),
+        }
+    }
+
+    /// 参数校验失败的结果，error_message 为全部校验错误的 JSON 数组（`[{"field": ..., "message": ...}]`）
+    pub fn validation_failed(errors: &[ValidationError]) -> Self {
+        let message = serde_json::to_string(errors)
+            .unwrap_or_else(|_| errors.iter().map(ValidationError::to_string).collect::<Vec<_>>().join("; "));
+        Self::new(false, String::new(), message).with_error_code(ErrorCode::InvalidParams)
+    }
+
     pub fn new(success: bool, result: String, error_message: String) -> Self {
         Self {
             success,
             result,
             error_message,
+            error_code: None,
+            elapsed_ms: None,
+            signature: None,
+            signing_key_id: None,
+            progress: None,
+            stage: None,
         }
     }
 
+    /// 流式执行中的中间结果，只携带进度和阶段
+    pub fn progress(progress: f32, stage: &str) -> Self {
+        let mut result = Self::new(true, String::new(), String::new());
+        result.set_progress(Some(progress));
+        result.set_stage(Some(stage.to_string()));
+        result
+    }
+
     pub fn is_success(&self) -> bool {
         self.success
     }
@@ -38,4 +247,139 @@ impl CommandResult {
     pub fn set_error_message(&mut self, error_message: String) {
         self.error_message = error_message;
     }
-}
\ No newline at end of file
+
+    pub fn get_error_code(&self) -> Option<ErrorCode> {
+        self.error_code
+    }
+
+    pub fn set_error_code(&mut self, error_code: Option<ErrorCode>) {
+        self.error_code = error_code;
+    }
+
+    /// 设置错误码
+    pub fn with_error_code(mut self, error_code: ErrorCode) -> Self {
+        self.error_code = Some(error_code);
+        self
+    }
+
+    pub fn get_elapsed_ms(&self) -> Option<u64> {
+        self.elapsed_ms
+    }
+
+    pub fn set_elapsed_ms(&mut self, elapsed_ms: Option<u64>) {
+        self.elapsed_ms = elapsed_ms;
+    }
+
+    pub fn get_signature(&self) -> Option<&[u8]> {
+        self.signature.as_deref()
+    }
+
+    pub fn set_signature(&mut self, signature: Option<Vec<u8>>) {
+        self.signature = signature;
+    }
+
+    pub fn get_signing_key_id(&self) -> Option<&str> {
+        self.signing_key_id.as_deref()
+    }
+
+    pub fn set_signing_key_id(&mut self, signing_key_id: Option<String>) {
+        self.signing_key_id = signing_key_id;
+    }
+
+    pub fn get_progress(&self) -> Option<f32> {
+        self.progress
+    }
+
+    pub fn set_progress(&mut self, progress: Option<f32>) {
+        self.progress = progress;
+    }
+
+    pub fn get_stage(&self) -> Option<&str> {
+        self.stage.as_deref()
+    }
+
+    pub fn set_stage(&mut self, stage: Option<String>) {
+        self.stage = stage;
+    }
+
+    /// 结果是 JSON 时改写为规范形式（键按字典序排列），签名前调用，
+    /// 使内容相同的结果得到相同的签名内容
+    pub fn canonicalize_result(&mut self) {
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(&self.result)
+            && let Ok(result) = canonical_json::to_string(&value)
+        {
+            self.result = result;
+        }
+    }
+
+    /// 签名覆盖的内容：`[success, result, error_message]` 组成的 JSON 数组，不包含耗时
+    pub fn signing_payload(&self) -> Vec<u8> {
+        canonical_json::to_vec(&(self.success, &self.result, &self.error_message)).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialization_failure_is_reported_as_failed_result() {
+        // JSON 对象的键必须是字符串
+        let unserializable = std::collections::HashMap::from([((1, 2), "value")]);
+        let result = CommandResult::json(&unserializable);
+        assert!(!result.is_success());
+        assert!(result.get_result().is_empty());
+        assert!(result.get_error_message().starts_with("Failed to serialize result"), "{}", result.get_error_message());
+        assert_eq!(result.get_error_code(), Some(ErrorCode::Internal));
+
+        let result = CommandResult::json(&["a", "b"]);
+        assert!(result.is_success());
+        assert_eq!(result.get_result(), r#"["a","b"]"#);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn rich_result_round_trips_through_command_response() {
+        let mut result = CommandResult::new(false, r#"{"id":"k1"}"#.to_string(), "Key not found".to_string())
+            .with_error_code(ErrorCode::KeyNotFound);
+        result.set_elapsed_ms(Some(12));
+        result.set_signature(Some(vec![1, 2, 3]));
+        result.set_signing_key_id(Some("audit-key".to_string()));
+        result.set_progress(Some(0.5));
+        result.set_stage(Some("rewrap".to_string()));
+
+        let response = CommandResponse::from(result.clone());
+        assert_eq!(response.error_code, "KEY_NOT_FOUND");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&response.extensions).unwrap(),
+            serde_json::json!({"elapsed_ms": 12, "signing_key_id": "audit-key", "progress": 0.5, "stage": "rewrap"})
+        );
+        assert_eq!(CommandResult::from(response), result);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn plain_result_has_no_extensions() {
+        let result = CommandResult::new(true, "ok".to_string(), String::new());
+        let response = CommandResponse::from(result.clone());
+        assert!(response.extensions.is_empty());
+        assert!(response.error_code.is_empty());
+        assert_eq!(CommandResult::from(response), result);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn unknown_error_code_and_malformed_extensions_are_tolerated() {
+        let response = CommandResponse {
+            success: false,
+            result: String::new(),
+            error_message: "boom".to_string(),
+            signature: Vec::new(),
+            error_code: "SOMETHING_NEW".to_string(),
+            extensions: "not json".to_string(),
+        };
+        let result = CommandResult::from(response);
+        assert_eq!(result.get_error_code(), Some(ErrorCode::Internal));
+        assert_eq!(result.get_elapsed_ms(), None);
+    }
+}
@@ -25,7 +25,7 @@ impl PluginSDK for ExamplePlugin {
         let result = self.base.initialize(config).await;
         
         // 设置插件详细信息，确保与后端SysPlugin.java匹配
-        let mut info = self.base.get_info();
+        let info = self.base.info_mut();
         info.set_name("密码管理示例插件".to_string());
         info.set_version("1.0.0".to_string());
         info.set_type("PASSWORD_MANAGER".to_string()); // 确保类型与后端期望的一致
@@ -43,6 +43,7 @@ impl PluginSDK for ExamplePlugin {
         info.add_supported_event("password_changed".to_string());
         
         // 尝试注册插件
+        #[cfg(feature = "grpc")]
         match self.base.retry_register().await {
             Ok(_) => println!("插件注册成功"),
             Err(e) => eprintln!("插件注册失败: {}", e),
@@ -55,18 +56,21 @@ impl PluginSDK for ExamplePlugin {
         println!("启动密码管理示例插件...");
         
         // 更新插件状态为运行中
-        let mut info = self.base.get_info();
-        info.set_status("RUNNING".to_string());
+        self.base.info_mut().set_status("RUNNING".to_string());
         
-        self.base.start().await
+        if !self.base.start().await {
+            return false;
+        }
+
+        self.base.set_ready(true);
+        true
     }
 
     async fn stop(&mut self) -> bool {
         println!("停止密码管理示例插件...");
         
         // 更新插件状态为已停止
-        let mut info = self.base.get_info();
-        info.set_status("STOPPED".to_string());
+        self.base.info_mut().set_status("STOPPED".to_string());
         
         self.base.stop().await
     }
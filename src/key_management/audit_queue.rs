@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::key_management::dead_letter::{DeadLetterStore, DeadLetterWrite};
+use crate::key_management::models::key_models::AuditLogEntry;
+use crate::persistence::PersistenceInterface;
+
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 审计日志写入队列
+///
+/// 条目由后台任务按顺序写入持久化存储。写入失败的条目留在内存中，按指数退避重试，
+/// 持久化存储恢复后依次补写；等待写入的条目超过 `queue_size` 时丢弃最早的条目。
+/// 配置了死信存储时，被丢弃或关闭时仍未写入的条目放入死信存储
+pub struct AuditQueue {
+    sender: mpsc::Sender<AuditLogEntry>,
+    handle: JoinHandle<()>,
+    dead_letters: Option<Arc<DeadLetterStore>>,
+}
+
+impl AuditQueue {
+    /// 启动后台写入任务，必须在 tokio 运行时中调用
+    pub fn spawn(persistence: Arc<dyn PersistenceInterface + Send + Sync>, queue_size: usize) -> Self {
+        Self::spawn_inner(persistence, queue_size, None)
+    }
+
+    /// 启动后台写入任务，无法写入的条目放入死信存储
+    pub fn spawn_with_dead_letters(
+        persistence: Arc<dyn PersistenceInterface + Send + Sync>,
+        queue_size: usize,
+        dead_letters: Arc<DeadLetterStore>,
+    ) -> Self {
+        Self::spawn_inner(persistence, queue_size, Some(dead_letters))
+    }
+
+    fn spawn_inner(
+        persistence: Arc<dyn PersistenceInterface + Send + Sync>,
+        queue_size: usize,
+        dead_letters: Option<Arc<DeadLetterStore>>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(queue_size);
+        let handle = tokio::spawn(write_loop(persistence, queue_size, receiver, dead_letters.clone()));
+
+        Self { sender, handle, dead_letters }
+    }
+
+    /// 将条目放入写入队列，队列已满时丢弃并输出错误
+    pub fn send(&self, entry: &AuditLogEntry) {
+        match self.sender.try_send(entry.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(entry)) => {
+                eprintln!("审计日志写入队列已满，丢弃审计日志: {}", entry.id);
+                dead_letter(self.dead_letters.as_deref(), entry, "审计日志写入队列已满");
+            }
+            Err(TrySendError::Closed(entry)) => {
+                eprintln!("审计日志写入队列已关闭，丢弃审计日志: {}", entry.id);
+                dead_letter(self.dead_letters.as_deref(), entry, "审计日志写入队列已关闭");
+            }
+        }
+    }
+
+    /// 关闭队列，等待后台任务写完剩余的条目；之前失败的条目不再等待退避，最后尝试写入一次
+    pub async fn close(self) {
+        let Self { sender, mut handle, .. } = self;
+        drop(sender);
+
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut handle).await.is_err() {
+            eprintln!("审计日志写入队列关闭超时，未写入的审计日志已丢弃");
+            handle.abort();
+        }
+    }
+}
+
+async fn write_loop(
+    persistence: Arc<dyn PersistenceInterface + Send + Sync>,
+    queue_size: usize,
+    mut receiver: mpsc::Receiver<AuditLogEntry>,
+    dead_letters: Option<Arc<DeadLetterStore>>,
+) {
+    let mut pending: VecDeque<AuditLogEntry> = VecDeque::new();
+    let mut failures: u32 = 0;
+    let mut retry_at: Option<Instant> = None;
+    let mut last_error = String::new();
+
+    loop {
+        // 退避期间只接收新条目，不访问持久化存储
+        if retry_at.is_none_or(|at| Instant::now() >= at) {
+            match flush(persistence.as_ref(), &mut pending).await {
+                Ok(()) => {
+                    if failures > 0 {
+                        println!("持久化存储已恢复，补写审计日志完成");
+                    }
+                    failures = 0;
+                    retry_at = None;
+                }
+                Err(e) => {
+                    last_error = e.clone();
+                    failures += 1;
+                    let delay = retry_delay(failures);
+                    eprintln!(
+                        "保存审计日志失败 (第 {} 次): {}，{} 条等待重试，{}ms 后重试",
+                        failures, e, pending.len(), delay.as_millis()
+                    );
+                    retry_at = Some(Instant::now() + delay);
+                }
+            }
+        }
+
+        let next = match retry_at {
+            Some(at) => match tokio::time::timeout_at(at, receiver.recv()).await {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            },
+            None => receiver.recv().await,
+        };
+        let Some(entry) = next else { break };
+
+        if pending.len() >= queue_size
+            && let Some(dropped) = pending.pop_front()
+        {
+            eprintln!("审计日志等待写入的条目过多，丢弃审计日志: {}", dropped.id);
+            dead_letter(dead_letters.as_deref(), dropped, &last_error);
+        }
+        pending.push_back(entry);
+    }
+
+    if let Err(e) = flush(persistence.as_ref(), &mut pending).await {
+        eprintln!("审计日志写入队列已关闭，{} 条审计日志未能写入: {}", pending.len(), e);
+        for entry in pending {
+            dead_letter(dead_letters.as_deref(), entry, &e);
+        }
+    }
+}
+
+fn dead_letter(dead_letters: Option<&DeadLetterStore>, entry: AuditLogEntry, error: &str) {
+    if let Some(dead_letters) = dead_letters {
+        dead_letters.push(DeadLetterWrite::AuditLog(entry), error);
+    }
+}
+
+// 按顺序写入等待中的条目，遇到失败时停止，未写入的条目保留在队列中
+async fn flush(persistence: &(dyn PersistenceInterface + Send + Sync), pending: &mut VecDeque<AuditLogEntry>) -> Result<(), String> {
+    while let Some(entry) = pending.front() {
+        persistence.save_audit_log(entry).await?;
+        pending.pop_front();
+    }
+    Ok(())
+}
+
+fn retry_delay(failures: u32) -> Duration {
+    (RETRY_BASE_DELAY * 2u32.pow(failures.saturating_sub(1).min(8))).min(MAX_RETRY_DELAY)
+}
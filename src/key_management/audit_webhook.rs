@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::key_management::models::key_models::AuditLogEntry;
+use crate::plugin_config::PluginConfig;
+
+const DEFAULT_QUEUE_SIZE: usize = 1024;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_BATCH_MS: u64 = 1000;
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 审计日志 webhook 配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditWebhookConfig {
+    pub url: String,
+    pub queue_size: usize,   // 待发送队列长度，队列满时丢弃新条目
+    pub max_retries: u32,    // 单个条目失败后的重试次数
+    pub buffer_failed: bool, // 重试仍失败的条目是否缓存，下次投递成功后补发
+    pub batch_size: usize,   // 批量模式下单次最多发送的条目数
+    pub batch_ms: u64,       // 批量模式下条目最多等待的毫秒数，0 表示不启用批量模式
+}
+
+impl AuditWebhookConfig {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            max_retries: DEFAULT_MAX_RETRIES,
+            buffer_failed: false,
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch_ms: 0,
+        }
+    }
+
+    /// 是否启用批量模式：条目累积到 `batch_size` 条或等待 `batch_ms` 毫秒后以 JSON 数组发送
+    pub fn batching(&self) -> bool {
+        self.batch_ms > 0
+    }
+
+    /// 从插件配置读取，未配置 `audit_webhook_url` 时返回 None
+    pub fn from_config(config: &PluginConfig) -> Result<Option<Self>, String> {
+        let url = match config.get_config("audit_webhook_url") {
+            Some(url) if !url.is_empty() => url.clone(),
+            _ => return Ok(None),
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("Invalid audit_webhook_url: {}", url));
+        }
+
+        let mut webhook = Self::new(url);
+        if let Some(value) = config.get_config("audit_webhook_queue_size") {
+            webhook.queue_size = match value.parse::<usize>() {
+                Ok(size) if size > 0 => size,
+                _ => return Err(format!("Invalid audit_webhook_queue_size: {}", value)),
+            };
+        }
+        if let Some(value) = config.get_config("audit_webhook_max_retries") {
+            webhook.max_retries = value.parse::<u32>()
+                .map_err(|_| format!("Invalid audit_webhook_max_retries: {}", value))?;
+        }
+        if let Some(value) = config.get_config("audit_webhook_buffer_failed") {
+            webhook.buffer_failed = value.to_lowercase() == "true";
+        }
+
+        // 批量参数只配置其中一个时，另一个使用默认值
+        let batch_size = config.get_config("audit_webhook_batch_size");
+        let batch_ms = config.get_config("audit_webhook_batch_ms");
+        if let Some(value) = batch_size {
+            webhook.batch_size = match value.parse::<usize>() {
+                Ok(size) if size > 0 => size,
+                _ => return Err(format!("Invalid audit_webhook_batch_size: {}", value)),
+            };
+        }
+        match batch_ms {
+            Some(value) => {
+                webhook.batch_ms = match value.parse::<u64>() {
+                    Ok(ms) if ms > 0 => ms,
+                    _ => return Err(format!("Invalid audit_webhook_batch_ms: {}", value)),
+                };
+            }
+            None if batch_size.is_some() => webhook.batch_ms = DEFAULT_BATCH_MS,
+            None => {}
+        }
+
+        Ok(Some(webhook))
+    }
+}
+
+/// 审计日志 webhook 发送端
+///
+/// 条目先进入有界队列，由后台任务以 JSON POST 到配置的地址，接收方响应慢不会阻塞密钥操作。
+/// 默认每个条目单独发送一个 JSON 对象，批量模式下发送 JSON 数组
+pub struct AuditWebhook {
+    sender: mpsc::Sender<AuditLogEntry>,
+    handle: JoinHandle<()>,
+}
+
+impl AuditWebhook {
+    /// 启动后台发送任务，必须在 tokio 运行时中调用
+    pub fn spawn(config: AuditWebhookConfig) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("创建webhook客户端失败: {}", e))?;
+
+        let (sender, receiver) = mpsc::channel(config.queue_size);
+        let handle = tokio::spawn(delivery_loop(client, config, receiver));
+
+        Ok(Self { sender, handle })
+    }
+
+    /// 将条目放入发送队列，队列已满时丢弃并输出错误
+    pub fn send(&self, entry: &AuditLogEntry) {
+        match self.sender.try_send(entry.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(entry)) => {
+                eprintln!("审计webhook队列已满，丢弃审计日志: {}", entry.id);
+            }
+            Err(TrySendError::Closed(entry)) => {
+                eprintln!("审计webhook已关闭，丢弃审计日志: {}", entry.id);
+            }
+        }
+    }
+
+    /// 关闭发送队列，等待后台任务发送完剩余的条目，超时后放弃
+    pub async fn close(self) {
+        let Self { sender, mut handle } = self;
+        drop(sender);
+
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut handle).await.is_err() {
+            eprintln!("审计webhook关闭超时，未发送的审计日志已丢弃");
+            handle.abort();
+        }
+    }
+}
+
+async fn delivery_loop(client: reqwest::Client, config: AuditWebhookConfig, mut receiver: mpsc::Receiver<AuditLogEntry>) {
+    let mut failed: VecDeque<AuditLogEntry> = VecDeque::new();
+
+    while let Some(first) = receiver.recv().await {
+        let mut batch = vec![first];
+
+        // 批量模式下从第一个条目开始计时，数量或时间先到者触发发送；队列关闭时立即发送
+        if config.batching() {
+            let deadline = Instant::now() + Duration::from_millis(config.batch_ms);
+            while batch.len() < config.batch_size {
+                match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Ok(Some(entry)) => batch.push(entry),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+        }
+
+        if deliver(&client, &config, &batch).await {
+            // 接收方恢复后补发之前失败的条目
+            while !failed.is_empty() {
+                let count = if config.batching() { failed.len().min(config.batch_size) } else { 1 };
+                let pending: Vec<AuditLogEntry> = failed.iter().take(count).cloned().collect();
+                if !deliver(&client, &config, &pending).await {
+                    break;
+                }
+                failed.drain(..count);
+            }
+        } else if config.buffer_failed {
+            for entry in batch {
+                if failed.len() >= config.queue_size
+                    && let Some(dropped) = failed.pop_front()
+                {
+                    eprintln!("审计webhook失败缓存已满，丢弃审计日志: {}", dropped.id);
+                }
+                failed.push_back(entry);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        eprintln!("审计webhook已关闭，{} 条发送失败的审计日志未能补发", failed.len());
+    }
+}
+
+// 发送一批条目，失败时按指数退避重试，最终失败返回 false
+//
+// 非批量模式下 entries 只有一个条目，以 JSON 对象发送
+async fn deliver(client: &reqwest::Client, config: &AuditWebhookConfig, entries: &[AuditLogEntry]) -> bool {
+    for attempt in 0..=config.max_retries {
+        let request = client.post(&config.url);
+        let request = if config.batching() { request.json(entries) } else { request.json(&entries[0]) };
+        let result = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        match result {
+            Ok(_) => return true,
+            Err(e) => {
+                eprintln!("审计webhook发送失败 (第 {} 次): {}", attempt + 1, e);
+                if attempt < config.max_retries {
+                    tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt.min(6))).await;
+                }
+            }
+        }
+    }
+
+    false
+}
@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::key_management::models::key_models::{AuditLogEntry, KeyMetadata};
+use crate::timestamp;
+
+/// 重试用尽后仍未写入持久化存储的数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum DeadLetterWrite {
+    KeyMetadata(KeyMetadata),
+    AuditLog(AuditLogEntry),
+}
+
+impl DeadLetterWrite {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DeadLetterWrite::KeyMetadata(_) => "key_metadata",
+            DeadLetterWrite::AuditLog(_) => "audit_log",
+        }
+    }
+
+    /// 写入对应的密钥ID或审计日志ID
+    pub fn target_id(&self) -> &str {
+        match self {
+            DeadLetterWrite::KeyMetadata(metadata) => &metadata.id,
+            DeadLetterWrite::AuditLog(entry) => &entry.id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: String,
+    #[serde(with = "crate::timestamp")]
+    pub failed_at: DateTime<Utc>,
+    pub error: String,
+    pub write: DeadLetterWrite,
+}
+
+/// 死信存储，保存重试用尽的持久化写入，供 `replay_dead_letters` 命令手动重放
+///
+/// 配置了文件路径时每次变化后整体重写该文件（每行一条JSON），插件重启后仍然保留；
+/// 未配置时只保存在内存中
+#[derive(Debug)]
+pub struct DeadLetterStore {
+    path: Option<PathBuf>,
+    letters: Mutex<Vec<DeadLetter>>,
+}
+
+impl DeadLetterStore {
+    pub fn in_memory() -> Self {
+        Self { path: None, letters: Mutex::new(Vec::new()) }
+    }
+
+    /// 打开文件存储，文件已存在时加载其中的死信
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let letters = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).map_err(|e| format!("解析死信失败: {}", e)))
+                .collect::<Result<Vec<DeadLetter>, String>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("读取死信文件失败: {}", e)),
+        };
+
+        Ok(Self { path: Some(path), letters: Mutex::new(letters) })
+    }
+
+    /// 记录一次重试用尽的写入；写入文件失败时仍保留在内存中
+    pub fn push(&self, write: DeadLetterWrite, error: &str) {
+        let letter = DeadLetter {
+            id: uuid::Uuid::new_v4().to_string(),
+            failed_at: timestamp::now(),
+            error: error.to_string(),
+            write,
+        };
+        eprintln!("写入已放入死信存储: {} {} ({})", letter.write.kind(), letter.write.target_id(), letter.id);
+
+        let mut letters = self.letters.lock().unwrap();
+        letters.push(letter);
+        self.save(&letters);
+    }
+
+    /// 当前所有死信，按记录顺序
+    pub fn list(&self) -> Vec<DeadLetter> {
+        self.letters.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.letters.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 移除指定ID的死信，返回实际移除的数量
+    pub fn remove(&self, ids: &[String]) -> usize {
+        self.remove_where(|letter| ids.contains(&letter.id))
+    }
+
+    /// 移除指定密钥的元数据死信，元数据已通过其他途径补写时调用
+    pub fn remove_key_metadata(&self, key_id: &str) -> usize {
+        self.remove_where(|letter| matches!(&letter.write, DeadLetterWrite::KeyMetadata(metadata) if metadata.id == key_id))
+    }
+
+    fn remove_where(&self, predicate: impl Fn(&DeadLetter) -> bool) -> usize {
+        let mut letters = self.letters.lock().unwrap();
+        let before = letters.len();
+        letters.retain(|letter| !predicate(letter));
+        let removed = before - letters.len();
+        if removed > 0 {
+            self.save(&letters);
+        }
+        removed
+    }
+
+    // 先写临时文件再替换，避免写入中途失败损坏已有的死信
+    fn save(&self, letters: &[DeadLetter]) {
+        let Some(path) = &self.path else { return };
+        if let Err(e) = write_letters(path, letters) {
+            eprintln!("保存死信文件失败: {}", e);
+        }
+    }
+}
+
+fn write_letters(path: &Path, letters: &[DeadLetter]) -> Result<(), String> {
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut file = fs::File::create(&temp_path).map_err(|e| format!("创建临时死信文件失败: {}", e))?;
+        for letter in letters {
+            let line = serde_json::to_string(letter).map_err(|e| format!("序列化死信失败: {}", e))?;
+            writeln!(file, "{}", line).map_err(|e| format!("写入临时死信文件失败: {}", e))?;
+        }
+        file.sync_all().map_err(|e| format!("写入临时死信文件失败: {}", e))?;
+    }
+    fs::rename(&temp_path, path).map_err(|e| format!("替换死信文件失败: {}", e))
+}
@@ -1,7 +1,15 @@
 pub mod models;
 pub mod security;
 pub mod plugin;
+pub mod audit_webhook;
+pub mod audit_queue;
+pub mod dead_letter;
 
 pub use models::key_models::{KeyMetadata, KeyStatus, KeyType, KeyAlgorithm, AuditLogEntry};
-pub use security::security_module::{SecurityModuleInterface, MockHSM};
-pub use plugin::KeyManagementPlugin;
\ No newline at end of file
+pub use security::security_module::{SecurityModuleInterface, MockHSM, IntegrityError, KdfParams, PublicKeyFormat};
+pub use security::software_module::{SoftwareSecurityModule, SharedKeyStore};
+pub use security::x509::SubjectName;
+pub use plugin::{KeyManagementPlugin, DrainedHook, ExpiryHook};
+pub use audit_webhook::{AuditWebhook, AuditWebhookConfig};
+pub use audit_queue::AuditQueue;
+pub use dead_letter::{DeadLetter, DeadLetterStore, DeadLetterWrite};
\ No newline at end of file
@@ -1,8 +1,28 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
 use uuid::Uuid;
 
+use crate::timestamp;
+
+/// 未指定租户时使用的默认租户
+pub const DEFAULT_TENANT: &str = "default";
+
+/// 未指定安全模块时使用的默认安全模块ID，对应插件创建时传入的安全模块
+pub const DEFAULT_SECURITY_MODULE_ID: &str = "default";
+
+/// 访问控制列表中表示允许全部操作的条目
+pub const ACL_ALL_OPERATIONS: &str = "*";
+
+fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+fn default_security_module_id() -> String {
+    DEFAULT_SECURITY_MODULE_ID.to_string()
+}
+
 /// 密钥状态枚举
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum KeyStatus {
@@ -27,6 +47,48 @@ impl ToString for KeyStatus {
     }
 }
 
+impl FromStr for KeyStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ACTIVE" => Ok(KeyStatus::Active),
+            "SUSPENDED" => Ok(KeyStatus::Suspended),
+            "EXPIRED" => Ok(KeyStatus::Expired),
+            "COMPROMISED" => Ok(KeyStatus::Compromised),
+            "DESTROYED" => Ok(KeyStatus::Destroyed),
+            "PENDING_DESTRUCTION" => Ok(KeyStatus::PendingDestruction),
+            _ => Err(format!("Invalid key status: {}", s)),
+        }
+    }
+}
+
+impl KeyStatus {
+    /// 稳定的数字编码，已分配的编码不能修改，新增状态使用新的编码
+    pub fn as_code(&self) -> u8 {
+        match self {
+            KeyStatus::Active => 1,
+            KeyStatus::Suspended => 2,
+            KeyStatus::Expired => 3,
+            KeyStatus::Compromised => 4,
+            KeyStatus::Destroyed => 5,
+            KeyStatus::PendingDestruction => 6,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Result<Self, String> {
+        match code {
+            1 => Ok(KeyStatus::Active),
+            2 => Ok(KeyStatus::Suspended),
+            3 => Ok(KeyStatus::Expired),
+            4 => Ok(KeyStatus::Compromised),
+            5 => Ok(KeyStatus::Destroyed),
+            6 => Ok(KeyStatus::PendingDestruction),
+            _ => Err(format!("Invalid key status code: {}", code)),
+        }
+    }
+}
+
 /// 密钥类型枚举
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum KeyType {
@@ -49,6 +111,58 @@ impl ToString for KeyType {
     }
 }
 
+impl FromStr for KeyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SYMMETRIC" => Ok(KeyType::Symmetric),
+            "ASYMMETRIC_PRIVATE" => Ok(KeyType::AsymmetricPrivate),
+            "ASYMMETRIC_PUBLIC" => Ok(KeyType::AsymmetricPublic),
+            "HMAC" => Ok(KeyType::HMAC),
+            "PASSWORD" => Ok(KeyType::Password),
+            _ => Err(format!("Invalid key type: {}", s)),
+        }
+    }
+}
+
+impl KeyType {
+    /// 稳定的数字编码，已分配的编码不能修改，新增类型使用新的编码
+    pub fn as_code(&self) -> u8 {
+        match self {
+            KeyType::Symmetric => 1,
+            KeyType::AsymmetricPrivate => 2,
+            KeyType::AsymmetricPublic => 3,
+            KeyType::HMAC => 4,
+            KeyType::Password => 5,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Result<Self, String> {
+        match code {
+            1 => Ok(KeyType::Symmetric),
+            2 => Ok(KeyType::AsymmetricPrivate),
+            3 => Ok(KeyType::AsymmetricPublic),
+            4 => Ok(KeyType::HMAC),
+            5 => Ok(KeyType::Password),
+            _ => Err(format!("Invalid key type code: {}", code)),
+        }
+    }
+
+    pub fn is_asymmetric(&self) -> bool {
+        matches!(self, KeyType::AsymmetricPrivate | KeyType::AsymmetricPublic)
+    }
+
+    /// 密钥对中另一半的类型，非对称类型以外为 None
+    pub fn paired_type(&self) -> Option<KeyType> {
+        match self {
+            KeyType::AsymmetricPrivate => Some(KeyType::AsymmetricPublic),
+            KeyType::AsymmetricPublic => Some(KeyType::AsymmetricPrivate),
+            _ => None,
+        }
+    }
+}
+
 /// 密钥算法枚举
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum KeyAlgorithm {
@@ -71,22 +185,90 @@ impl ToString for KeyAlgorithm {
     }
 }
 
+impl FromStr for KeyAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "AES-256" => Ok(KeyAlgorithm::AES256),
+            "RSA-2048" => Ok(KeyAlgorithm::RSA2048),
+            "RSA-4096" => Ok(KeyAlgorithm::RSA4096),
+            "ECDSA" => Ok(KeyAlgorithm::ECDSA),
+            "ED25519" => Ok(KeyAlgorithm::ED25519),
+            _ => Err(format!("Invalid algorithm: {}", s)),
+        }
+    }
+}
+
+impl KeyAlgorithm {
+    /// 稳定的数字编码，已分配的编码不能修改，新增算法使用新的编码
+    pub fn as_code(&self) -> u8 {
+        match self {
+            KeyAlgorithm::AES256 => 1,
+            KeyAlgorithm::RSA2048 => 2,
+            KeyAlgorithm::RSA4096 => 3,
+            KeyAlgorithm::ECDSA => 4,
+            KeyAlgorithm::ED25519 => 5,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Result<Self, String> {
+        match code {
+            1 => Ok(KeyAlgorithm::AES256),
+            2 => Ok(KeyAlgorithm::RSA2048),
+            3 => Ok(KeyAlgorithm::RSA4096),
+            4 => Ok(KeyAlgorithm::ECDSA),
+            5 => Ok(KeyAlgorithm::ED25519),
+            _ => Err(format!("Invalid algorithm code: {}", code)),
+        }
+    }
+
+    /// 算法与密钥类型的兼容关系：AES-256 只能用于对称类密钥，RSA/ECDSA/ED25519 只能用于非对称密钥
+    pub fn supports_key_type(&self, key_type: &KeyType) -> bool {
+        match self {
+            KeyAlgorithm::AES256 => matches!(
+                key_type,
+                KeyType::Symmetric | KeyType::Password | KeyType::HMAC
+            ),
+            KeyAlgorithm::RSA2048 | KeyAlgorithm::RSA4096 | KeyAlgorithm::ECDSA | KeyAlgorithm::ED25519 => matches!(
+                key_type,
+                KeyType::AsymmetricPrivate | KeyType::AsymmetricPublic
+            ),
+        }
+    }
+}
+
 /// 密钥元数据结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyMetadata {
     pub id: String,
     pub name: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>, // 运维人员的内部备注，与面向用户的 description 分开
     pub key_type: KeyType,
     pub algorithm: KeyAlgorithm,
     pub status: KeyStatus,
     pub owner: String,
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamp")]
     pub updated_at: DateTime<Utc>,
+    #[serde(default, with = "crate::timestamp::option")]
     pub expiration_date: Option<DateTime<Utc>>,
     pub version: u32,
     pub requires_approval: bool,
     pub tags: HashMap<String, String>,
+    #[serde(default, with = "crate::timestamp::option")]
+    pub deleted_at: Option<DateTime<Utc>>, // 软删除时间，恢复期内可以恢复
+    #[serde(default, with = "crate::timestamp::option")]
+    pub last_used_at: Option<DateTime<Utc>>, // 最近一次用于签名、验证或加解密的时间，从未使用时为 None
+    #[serde(default = "default_tenant")]
+    pub tenant: String, // 所属租户，只有同一租户的请求可以访问
+    #[serde(default = "default_security_module_id")]
+    pub security_module_id: String, // 保存密钥材料的安全模块，创建时确定
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acl: Option<HashMap<String, BTreeSet<String>>>, // 用户 -> 允许的操作；None 表示不限制，设置后只有所有者和列表中的用户可以操作
 }
 
 impl KeyMetadata {
@@ -98,11 +280,12 @@ impl KeyMetadata {
         owner: String,
         requires_approval: bool,
     ) -> Self {
-        let now = Utc::now();
+        let now = timestamp::now();
         Self {
             id: Uuid::new_v4().to_string(),
             name,
             description,
+            notes: None,
             key_type,
             algorithm,
             status: KeyStatus::Active,
@@ -113,6 +296,79 @@ impl KeyMetadata {
             version: 1,
             requires_approval,
             tags: HashMap::new(),
+            deleted_at: None,
+            last_used_at: None,
+            tenant: default_tenant(),
+            security_module_id: default_security_module_id(),
+            acl: None,
+        }
+    }
+
+    /// 使用指定时间作为创建和更新时间
+    pub fn with_timestamp(mut self, now: DateTime<Utc>) -> Self {
+        self.created_at = now;
+        self.updated_at = now;
+        self
+    }
+
+    /// 使用指定的密钥ID
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// 设置所属租户
+    pub fn with_tenant(mut self, tenant: String) -> Self {
+        self.tenant = tenant;
+        self
+    }
+
+    /// 设置保存密钥材料的安全模块
+    pub fn with_security_module_id(mut self, security_module_id: String) -> Self {
+        self.security_module_id = security_module_id;
+        self
+    }
+
+    /// 密钥能否用于签名、加解密、签发证书和轮换：状态为 Active 且未到过期时间
+    pub fn is_usable(&self) -> bool {
+        self.ensure_usable().is_ok()
+    }
+
+    /// 同 `is_usable`，不可用时返回原因
+    pub fn ensure_usable(&self) -> Result<(), String> {
+        self.ensure_usable_at(Utc::now())
+    }
+
+    /// 按指定时间判断密钥是否可用，插件使用自己的时钟判断
+    pub fn is_usable_at(&self, now: DateTime<Utc>) -> bool {
+        self.ensure_usable_at(now).is_ok()
+    }
+
+    /// 按指定时间检查密钥是否可用
+    ///
+    /// 过期检查是定期执行的，到期后、标记为 Expired 之前的密钥状态仍是 Active，这里同时检查过期时间
+    pub fn ensure_usable_at(&self, now: DateTime<Utc>) -> Result<(), String> {
+        if self.status != KeyStatus::Active {
+            return Err(format!("Key is not active, current status: {:?}", self.status));
+        }
+        if let Some(expiration_date) = self.expiration_date
+            && expiration_date <= now
+        {
+            return Err(format!("Key is not active, expired at {}", timestamp::format(&expiration_date)));
+        }
+        Ok(())
+    }
+
+    /// 用户能否对密钥执行指定操作：所有者总是可以，未设置访问控制列表时不限制
+    pub fn can_access(&self, user: &str, operation: &str) -> bool {
+        if user == self.owner {
+            return true;
+        }
+        match &self.acl {
+            None => true,
+            Some(acl) => acl.get(user).is_some_and(|operations| {
+                operations.contains(operation) || operations.contains(ACL_ALL_OPERATIONS)
+            }),
         }
     }
 }
@@ -121,6 +377,7 @@ impl KeyMetadata {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditLogEntry {
     pub id: String,
+    #[serde(with = "crate::timestamp")]
     pub timestamp: DateTime<Utc>,
     pub user: String,
     pub action: String,
@@ -128,6 +385,8 @@ pub struct AuditLogEntry {
     pub details: String,
     pub success: bool,
     pub error: Option<String>,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
 }
 
 impl AuditLogEntry {
@@ -140,13 +399,14 @@ impl AuditLogEntry {
     ) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
-            timestamp: Utc::now(),
+            timestamp: timestamp::now(),
             user,
             action,
             key_id,
             details,
             success,
             error: None,
+            tenant: default_tenant(),
         }
     }
 
@@ -159,13 +419,75 @@ impl AuditLogEntry {
     ) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
-            timestamp: Utc::now(),
+            timestamp: timestamp::now(),
             user,
             action,
             key_id,
             details,
             success: false,
             error: Some(error),
+            tenant: default_tenant(),
+        }
+    }
+
+    /// 使用指定时间作为记录时间
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// 使用指定的记录ID
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// 设置记录所属租户
+    pub fn with_tenant(mut self, tenant: String) -> Self {
+        self.tenant = tenant;
+        self
+    }
+}
+
+/// 密钥附件信息，不包含附件内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttachmentInfo {
+    pub name: String,
+    pub size: usize, // 字节数
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_round_trips_through_its_code() {
+        let statuses = [
+            KeyStatus::Active, KeyStatus::Suspended, KeyStatus::Expired,
+            KeyStatus::Compromised, KeyStatus::Destroyed, KeyStatus::PendingDestruction,
+        ];
+        for status in &statuses {
+            assert_eq!(&KeyStatus::from_code(status.as_code()).unwrap(), status);
+        }
+        let key_types = [
+            KeyType::Symmetric, KeyType::AsymmetricPrivate, KeyType::AsymmetricPublic, KeyType::HMAC, KeyType::Password,
+        ];
+        for key_type in &key_types {
+            assert_eq!(&KeyType::from_code(key_type.as_code()).unwrap(), key_type);
         }
+        let algorithms = [
+            KeyAlgorithm::AES256, KeyAlgorithm::RSA2048, KeyAlgorithm::RSA4096, KeyAlgorithm::ECDSA, KeyAlgorithm::ED25519,
+        ];
+        for algorithm in &algorithms {
+            assert_eq!(&KeyAlgorithm::from_code(algorithm.as_code()).unwrap(), algorithm);
+        }
+
+        // 同一枚举内编码互不相同，0 不是有效编码
+        let mut codes: Vec<u8> = statuses.iter().map(KeyStatus::as_code).collect();
+        codes.dedup();
+        assert_eq!(codes.len(), statuses.len());
+        assert!(KeyStatus::from_code(0).is_err());
+        assert!(KeyType::from_code(0).is_err());
+        assert!(KeyAlgorithm::from_code(0).is_err());
     }
-}
\ No newline at end of file
+}
@@ -1,75 +1,469 @@
 // 添加 async_trait 导入
 use async_trait::async_trait;
-use std::collections::HashMap;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use uuid::Uuid;
+use std::time::Instant;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 
-use crate::base_plugin::BasePlugin;
-use crate::command_result::CommandResult;
+use crate::base_plugin::{self, BasePlugin};
+use crate::clock::{Clock, SystemClock};
+use crate::random::RandomSource;
+use crate::timestamp;
+use crate::canonical_json;
+use crate::command_result::{CommandResult, ErrorCode, ValidationError};
+use crate::operation_registry::{OperationGuard, OperationInfo, OperationRegistry};
 use crate::plugin_config::PluginConfig;
+use crate::plugin_metrics::PluginMetrics;
 use crate::plugin_sdk::PluginSDK;
-use crate::persistence::PersistenceInterface;
+use crate::persistence::{factory, key_stats, keystore_diff, KeyDrift, KeyQuery, KeyStats, PersistenceInterface};
 
 use crate::key_management::models::key_models::{
-    KeyMetadata, KeyStatus, KeyType, KeyAlgorithm, AuditLogEntry
+    KeyMetadata, KeyStatus, KeyType, KeyAlgorithm, AuditLogEntry, AttachmentInfo, ACL_ALL_OPERATIONS, DEFAULT_SECURITY_MODULE_ID, DEFAULT_TENANT
 };
-use crate::key_management::security::security_module::{SecurityModuleInterface, MockHSM};
+use crate::key_management::security::constant_time;
+use crate::key_management::security::security_module::{SecurityModuleInterface, MockHSM, KdfParams, PublicKeyFormat};
+use crate::key_management::security::x509::{self, SubjectName};
+use crate::key_management::security::escrow::{self, EscrowPublicKey};
+use crate::key_management::audit_webhook::{AuditWebhook, AuditWebhookConfig};
+use crate::key_management::audit_queue::{self, AuditQueue};
+use crate::key_management::dead_letter::{DeadLetter, DeadLetterStore, DeadLetterWrite};
+
+// 口令派生密钥的盐长度（字节）
+const KDF_SALT_LEN: usize = 16;
+
+// 自签名证书的默认有效期（天）
+const DEFAULT_CERT_VALIDITY_DAYS: i64 = 365;
+
+// 重新包装密钥时每处理多少个密钥记录一次进度
+const REWRAP_PROGRESS_INTERVAL: usize = 100;
+
+// 批量验签时同时进行的验证数
+const VERIFY_BATCH_CONCURRENCY: usize = 16;
+
+// verify_batch 的单条输入，data 和 signature 为 base64
+#[derive(serde::Deserialize)]
+struct VerifyItem {
+    key_id: String,
+    data: String,
+    signature: String,
+}
+
+// 安全模块自检默认覆盖的算法
+const SELFTEST_ALGORITHMS: [KeyAlgorithm; 5] = [
+    KeyAlgorithm::AES256,
+    KeyAlgorithm::RSA2048,
+    KeyAlgorithm::RSA4096,
+    KeyAlgorithm::ECDSA,
+    KeyAlgorithm::ED25519,
+];
+
+// 安全模块自检中一个算法的各步结果，status 为 pass、fail 或 skipped
+#[derive(Default)]
+struct SelftestSteps {
+    steps: Vec<serde_json::Value>,
+    failed: bool,
+}
+
+impl SelftestSteps {
+    // 记录一步的结果，返回是否成功
+    fn record(&mut self, operation: &str, result: Result<(), String>) -> bool {
+        match result {
+            Ok(()) => {
+                self.steps.push(serde_json::json!({ "operation": operation, "status": "pass" }));
+                true
+            }
+            Err(e) => {
+                self.failed = true;
+                self.steps.push(serde_json::json!({ "operation": operation, "status": "fail", "error": e }));
+                false
+            }
+        }
+    }
+
+    fn skip(&mut self, operation: &str, reason: &str) {
+        self.steps.push(serde_json::json!({ "operation": operation, "status": "skipped", "reason": reason }));
+    }
+}
+
+// 流式执行时接收中间结果的通道
+type ProgressSink = UnboundedSender<CommandResult>;
+
+// 从配置或命令参数中读取 Argon2 参数，未设置的项使用 defaults 中的值
+fn kdf_params_from(values: &HashMap<String, String>, defaults: &KdfParams) -> Result<KdfParams, String> {
+    let read = |key: &str, default: u32| -> Result<u32, String> {
+        match values.get(key) {
+            Some(value) => value.parse::<u32>().map_err(|_| format!("Invalid {}: {}", key, value)),
+            None => Ok(default),
+        }
+    };
+
+    KdfParams::new(
+        read("kdf_memory_kib", defaults.memory_kib)?,
+        read("kdf_iterations", defaults.iterations)?,
+        read("kdf_parallelism", defaults.parallelism)?,
+    )
+}
+
+// 流式执行时发送中间进度，普通执行时忽略
+fn report_progress(progress: Option<&ProgressSink>, value: f32, stage: &str) {
+    if let Some(sink) = progress {
+        let _ = sink.unbounded_send(CommandResult::progress(value, stage));
+    }
+}
+
+// 算法迁移时不复制到新密钥的标签：描述原密钥材料的信息和上一次迁移的关联，kdf 开头的派生参数同样不复制
+const MIGRATION_SKIPPED_TAGS: [&str; 5] = ["pair_id", "public_key", "certificate", "migrated_from", "migrated_to"];
+
+fn migrated_tags(tags: &HashMap<String, String>) -> HashMap<String, String> {
+    tags.iter()
+        .filter(|(key, _)| !key.starts_with("kdf") && !MIGRATION_SKIPPED_TAGS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+tokio::task_local! {
+    // 当前命令所属的租户，由 run_command 根据 tenant 参数设置
+    static REQUEST_TENANT: String;
+    // 当前命令创建的密钥使用的安全模块，由 run_command 根据 security_module 参数设置
+    static REQUEST_SECURITY_MODULE: String;
+}
+
+// 当前命令所属的租户，后台任务中为默认租户
+fn current_tenant() -> String {
+    REQUEST_TENANT.try_with(String::clone).unwrap_or_else(|_| DEFAULT_TENANT.to_string())
+}
+
+// 当前命令创建的密钥使用的安全模块，未指定时为默认安全模块
+fn current_security_module_id() -> String {
+    REQUEST_SECURITY_MODULE.try_with(String::clone).unwrap_or_else(|_| DEFAULT_SECURITY_MODULE_ID.to_string())
+}
+
+// 保存密钥元数据，失败时按指数退避重试；重试次数用尽后标记为待补写并放入死信存储
+async fn save_metadata_with_retry(
+    persistence: &(dyn PersistenceInterface + Send + Sync),
+    metadata: KeyMetadata,
+    dirty_keys: &Mutex<HashSet<String>>,
+    dead_letters: &DeadLetterStore,
+) {
+    let mut attempt = 1;
+    loop {
+        match persistence.save_key_metadata(&metadata).await {
+            Ok(()) => return,
+            Err(e) if attempt >= PERSIST_MAX_ATTEMPTS => {
+                eprintln!("保存密钥元数据失败 (共 {} 次)，等待补写: {}: {}", attempt, metadata.id, e);
+                dirty_keys.lock().unwrap().insert(metadata.id.clone());
+                dead_letters.push(DeadLetterWrite::KeyMetadata(metadata), &e);
+                return;
+            }
+            Err(e) => {
+                let delay = PERSIST_RETRY_DELAY * 2u32.pow(attempt - 1);
+                eprintln!("保存密钥元数据失败 (第 {} 次): {}，{}ms 后重试", attempt, e, delay.as_millis());
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// 作用于全部租户数据的管理命令，只允许默认租户执行
+const ADMIN_COMMANDS: [&str; 13] = [
+    "backup", "restore", "compact_audit_log", "diff_keystore", "rewrap_all", "reconfigure", "rotate_audit_key",
+    "selftest_security_module", "reconcile", "escrow_key", "recover_from_escrow", "replay_dead_letters", "drain",
+];
+
+// 受密钥访问控制列表限制的命令，授权时使用命令名作为操作名
+const ACL_OPERATIONS: [&str; 17] = [
+    "sign", "encrypt", "decrypt", "generate_csr", "generate_self_signed_cert", "rotate_key", "suspend_key",
+    "resume_key", "delete_key", "recover_key", "set_alias", "put_attachment", "get_attachment", "update_key",
+    "generate_data_key", "verify_password", "rehash_password",
+];
+
+// generate_data_key 支持的数据密钥长度（字节），默认 32
+const DATA_KEY_LENGTHS: [usize; 3] = [16, 24, 32];
+
+// 后台保存密钥元数据的最大尝试次数和首次重试间隔，之后每次重试间隔加倍
+const PERSIST_MAX_ATTEMPTS: u32 = 5;
+const PERSIST_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+// 密钥使用时间每次使用都更新内存，距上次写入持久化存储超过该间隔（秒）时才写入
+const LAST_USED_PERSIST_INTERVAL_SECS: i64 = 60;
+
+// 签名密钥标签：active 为当前用于签名命令结果的密钥，retired 为已轮换、只用于验证历史签名的密钥
+const AUDIT_SIGNING_TAG: &str = "audit_signing";
+
+// 托管数据保存为密钥附件，该附件名保留，不能通过 put_attachment 写入
+const ESCROW_ATTACHMENT: &str = "escrow.bin";
+
+// create_key 命令的参数
+struct CreateKeyRequest {
+    name: String,
+    description: String,
+    key_type: KeyType,
+    algorithm: KeyAlgorithm,
+    requires_approval: bool,
+    expiration_date: Option<chrono::DateTime<chrono::Utc>>,
+    tags: HashMap<String, String>, // 参数中 tag. 前缀的条目
+}
+
+// 解析并校验 create_key 命令的参数，返回全部校验错误而不是第一个
+fn create_key_request(params: &HashMap<String, String>) -> Result<CreateKeyRequest, Vec<ValidationError>> {
+    let mut errors = Vec::new();
+
+    let name = params.get("name").map(String::as_str).unwrap_or_default();
+    if name.trim().is_empty() {
+        errors.push(ValidationError::new("name", "Missing parameter: name"));
+    }
+
+    let key_type = params.get("key_type").map(String::as_str).unwrap_or("SYMMETRIC");
+    let key_type = KeyType::from_str(key_type)
+        .map_err(|e| errors.push(ValidationError::new("key_type", e)))
+        .ok();
+
+    let algorithm = params.get("algorithm").map(String::as_str).unwrap_or("AES-256");
+    let algorithm = KeyAlgorithm::from_str(algorithm)
+        .map_err(|e| errors.push(ValidationError::new("algorithm", e)))
+        .ok();
+
+    // 两者都有效时才检查是否匹配
+    if let (Some(key_type), Some(algorithm)) = (&key_type, &algorithm)
+        && !algorithm.supports_key_type(key_type)
+    {
+        errors.push(ValidationError::new("algorithm", format!(
+            "Algorithm {} is not compatible with key type {}",
+            algorithm.to_string(),
+            key_type.to_string()
+        )));
+    }
+
+    let expiration_date = match params.get("expiration_date") {
+        Some(value) => timestamp::parse(value)
+            .map_err(|e| errors.push(ValidationError::new("expiration_date", format!("Invalid expiration_date: {}", e))))
+            .ok(),
+        None => None,
+    };
+
+    match (key_type, algorithm) {
+        (Some(key_type), Some(algorithm)) if errors.is_empty() => Ok(CreateKeyRequest {
+            name: name.to_string(),
+            description: params.get("description").cloned().unwrap_or_default(),
+            key_type,
+            algorithm,
+            requires_approval: params.get("requires_approval").is_some_and(|v| v.to_lowercase() == "true"),
+            expiration_date,
+            tags: params.iter()
+                .filter_map(|(key, value)| key.strip_prefix("tag.").map(|tag| (tag.to_string(), value.clone())))
+                .collect(),
+        }),
+        _ => Err(errors),
+    }
+}
+
+// derive_key 命令的参数
+struct DeriveKeyRequest {
+    name: String,
+    description: String,
+    password: String,
+    key_type: KeyType, // SYMMETRIC 或 PASSWORD，默认 SYMMETRIC
+    salt: Option<Vec<u8>>, // 指定盐时可以重新派生出相同的密钥，未指定时随机生成
+    kdf_params: KdfParams,
+    tags: HashMap<String, String>, // 参数中 tag. 前缀的条目
+}
+
+// 解析 derive_key 命令的参数，命令参数中的 Argon2 参数优先于 defaults
+fn derive_key_request(params: &HashMap<String, String>, defaults: &KdfParams) -> Result<DeriveKeyRequest, String> {
+    let name = params.get("name").ok_or_else(|| "Missing parameter: name".to_string())?;
+    let password = params.get("password")
+        .filter(|password| !password.is_empty())
+        .ok_or_else(|| "Missing parameter: password".to_string())?;
+    // PASSWORD 类型的密钥只保存口令的派生结果，用 verify_password 校验口令
+    let key_type = match params.get("key_type") {
+        Some(key_type) => KeyType::from_str(key_type)?,
+        None => KeyType::Symmetric,
+    };
+    let salt = match params.get("salt") {
+        Some(salt) => Some(BASE64.decode(salt).map_err(|e| format!("Invalid salt: {}", e))?),
+        None => None,
+    };
+
+    Ok(DeriveKeyRequest {
+        name: name.clone(),
+        description: params.get("description").cloned().unwrap_or_default(),
+        password: password.clone(),
+        key_type,
+        salt,
+        kdf_params: kdf_params_from(params, defaults)?,
+        tags: params.iter()
+            .filter_map(|(key, value)| key.strip_prefix("tag.").map(|tag| (tag.to_string(), value.clone())))
+            .collect(),
+    })
+}
+
+/// 密钥即将过期时的回调，参数为即将过期的密钥元数据
+pub type ExpiryHook = Arc<dyn Fn(&KeyMetadata) + Send + Sync>;
+
+// (密钥ID, 提前天数) -> 已通知的过期时间
+type ExpiryNotified = HashMap<(String, u32), chrono::DateTime<chrono::Utc>>;
+
+/// 插件排空完成（进入排空状态后正在执行的命令全部结束）时的回调
+pub type DrainedHook = Arc<dyn Fn() + Send + Sync>;
+
+// 正在执行的命令，登记时计数加一，丢弃时减一
+struct InFlightGuard(Arc<watch::Sender<usize>>);
+
+impl InFlightGuard {
+    fn enter(in_flight: &Arc<watch::Sender<usize>>) -> Self {
+        in_flight.send_modify(|count| *count += 1);
+        Self(Arc::clone(in_flight))
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
 
 /// 密钥管理插件
 pub struct KeyManagementPlugin {
     base: BasePlugin,
     keys: Arc<Mutex<HashMap<String, KeyMetadata>>>,
+    deleted_keys: Arc<Mutex<HashMap<String, KeyMetadata>>>, // 软删除、仍在恢复期内的密钥
+    aliases: Arc<Mutex<HashMap<String, String>>>, // 别名 -> 密钥ID
     audit_log: Arc<Mutex<Vec<AuditLogEntry>>>,
-    security_module: Arc<dyn SecurityModuleInterface + Send + Sync>,
+    security_module: Arc<dyn SecurityModuleInterface + Send + Sync>, // 默认安全模块
+    security_modules: HashMap<String, Arc<dyn SecurityModuleInterface + Send + Sync>>, // 其他安全模块，按ID注册
     pending_approvals: Arc<Mutex<HashMap<String, (String, String)>>>, // 操作ID -> (密钥ID, 操作类型)
     persistence: Option<Arc<dyn PersistenceInterface + Send + Sync>>,
+    dirty_keys: Arc<Mutex<HashSet<String>>>, // 元数据未能写入持久化存储、等待补写的密钥ID
+    dead_letters: Arc<DeadLetterStore>, // 重试用尽的持久化写入，由 dead_letter_path 配置为文件存储
+    max_in_memory_keys: Option<usize>, // 内存中最多保留的密钥数，超出时淘汰最久未使用且已持久化的密钥
+    key_access: Arc<Mutex<HashMap<String, u64>>>, // 密钥ID -> 最近一次使用的序号
+    access_counter: Arc<AtomicU64>,
+    persisting: Arc<Mutex<HashMap<String, usize>>>, // 正在后台写入的密钥ID -> 未完成的写入数，写完前不能淘汰
+    unpersisted_usage: Arc<Mutex<HashSet<String>>>, // last_used_at 已更新但尚未写入持久化存储的密钥ID
+    kdf_params: KdfParams,
+    expiry_hooks: Arc<Mutex<Vec<(u32, ExpiryHook)>>>, // (提前天数, 回调)
+    expiry_notified: Arc<Mutex<ExpiryNotified>>,
+    audit_webhook: Option<AuditWebhook>,
+    audit_queue: Option<AuditQueue>, // 插件运行期间审计日志经由该队列写入持久化存储
+    audit_queue_size: usize,
+    escrow_public_key: Option<EscrowPublicKey>, // 托管密钥使用的恢复公钥，由 escrow_public_key 配置
+    response_signing_key: Arc<Mutex<Option<String>>>, // 用于签名命令结果的密钥ID，可通过 rotate_audit_key 轮换
+    clock: Arc<dyn Clock>,
+    random: RandomSource, // 密钥ID、审计日志ID、盐等使用的随机数
+    operations: OperationRegistry, // 正在执行的命令和后台任务
+    draining: Arc<AtomicBool>, // 排空状态下拒绝新命令
+    in_flight: Arc<watch::Sender<usize>>, // 正在执行的命令数，不含 drain 本身
+    drained_hooks: Arc<Mutex<Vec<DrainedHook>>>,
 }
 
 impl KeyManagementPlugin {
     pub fn new() -> Self {
-        Self {
-            base: BasePlugin::new(),
-            keys: Arc::new(Mutex::new(HashMap::new())),
-            audit_log: Arc::new(Mutex::new(Vec::new())),
-            security_module: Arc::new(MockHSM),
-            pending_approvals: Arc::new(Mutex::new(HashMap::new())),
-            persistence: None,
-        }
+        Self::with_security_module(Arc::new(MockHSM))
     }
 
     pub fn with_security_module(security_module: Arc<dyn SecurityModuleInterface + Send + Sync>) -> Self {
+        let keys: Arc<Mutex<HashMap<String, KeyMetadata>>> = Arc::new(Mutex::new(HashMap::new()));
+        let deleted_keys: Arc<Mutex<HashMap<String, KeyMetadata>>> = Arc::new(Mutex::new(HashMap::new()));
+
+        // 触发自停策略时清除内存中的密钥
+        let mut base = BasePlugin::new();
+        let keys_clone = Arc::clone(&keys);
+        let deleted_keys_clone = Arc::clone(&deleted_keys);
+        base.set_offline_hook(Arc::new(move || {
+            keys_clone.lock().unwrap().clear();
+            deleted_keys_clone.lock().unwrap().clear();
+            println!("已清除内存中的密钥");
+        }));
+
+        // 心跳指标中上报活跃密钥数
+        let keys_clone = Arc::clone(&keys);
+        base.set_telemetry_hook(Arc::new(move || {
+            let active_keys = keys_clone.lock().unwrap().values().filter(|key| key.status == KeyStatus::Active).count();
+            let mut metrics = serde_json::Map::new();
+            metrics.insert("active_keys".to_string(), active_keys.into());
+            metrics
+        }));
+
         Self {
-            base: BasePlugin::new(),
-            keys: Arc::new(Mutex::new(HashMap::new())),
+            base,
+            keys,
+            deleted_keys,
+            aliases: Arc::new(Mutex::new(HashMap::new())),
             audit_log: Arc::new(Mutex::new(Vec::new())),
             security_module,
+            security_modules: HashMap::new(),
             pending_approvals: Arc::new(Mutex::new(HashMap::new())),
             persistence: None,
+            dirty_keys: Arc::new(Mutex::new(HashSet::new())),
+            dead_letters: Arc::new(DeadLetterStore::in_memory()),
+            max_in_memory_keys: None,
+            key_access: Arc::new(Mutex::new(HashMap::new())),
+            access_counter: Arc::new(AtomicU64::new(0)),
+            persisting: Arc::new(Mutex::new(HashMap::new())),
+            unpersisted_usage: Arc::new(Mutex::new(HashSet::new())),
+            kdf_params: KdfParams::default(),
+            expiry_hooks: Arc::new(Mutex::new(Vec::new())),
+            expiry_notified: Arc::new(Mutex::new(HashMap::new())),
+            audit_webhook: None,
+            audit_queue: None,
+            audit_queue_size: audit_queue::DEFAULT_QUEUE_SIZE,
+            escrow_public_key: None,
+            response_signing_key: Arc::new(Mutex::new(None)),
+            clock: Arc::new(SystemClock),
+            random: RandomSource::os(),
+            operations: OperationRegistry::new(),
+            draining: Arc::new(AtomicBool::new(false)),
+            in_flight: Arc::new(watch::Sender::new(0)),
+            drained_hooks: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// 注册其他安全模块，创建密钥时通过 security_module 参数选择，之后该密钥的操作都由这个模块执行
+    pub fn with_named_security_module(mut self, id: &str, security_module: Arc<dyn SecurityModuleInterface + Send + Sync>) -> Self {
+        self.security_modules.insert(id.to_string(), security_module);
+        self
+    }
+
     pub fn with_persistence(mut self, persistence: Arc<dyn PersistenceInterface + Send + Sync>) -> Self {
         self.persistence = Some(persistence);
         self
     }
 
-    fn add_audit_log(&self, entry: AuditLogEntry) {
-        let mut log = self.audit_log.lock().unwrap();
-        log.push(entry.clone());
-        
-        // 如果有持久化存储，则保存审计日志
-        if let Some(persistence) = &self.persistence {
-            let persistence_clone = Arc::clone(persistence);
-            let entry_clone = entry.clone();
-            tokio::spawn(async move {
-                if let Err(e) = persistence_clone.save_audit_log(&entry_clone).await {
-                    eprintln!("保存审计日志失败: {}", e);
-                }
-            });
-        }
+    /// 指定死信存储，默认只保存在内存中
+    pub fn with_dead_letter_store(mut self, dead_letters: Arc<DeadLetterStore>) -> Self {
+        self.dead_letters = dead_letters;
+        self
     }
 
-    async fn create_key(
+    /// 修改插件信息（描述、支持的命令等），见 `BasePlugin::info_mut`
+    pub fn info_mut(&mut self) -> &mut crate::plugin_info::PluginInfo {
+        self.base.info_mut()
+    }
+
+    /// 替换时间来源，默认使用系统时间
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// 替换生成ID和盐使用的随机数来源，默认使用系统随机数
+    ///
+    /// 密钥材料由安全模块生成，需要可复现时同时替换安全模块的随机数来源
+    pub fn with_random_source(mut self, random: RandomSource) -> Self {
+        self.random = random;
+        self
+    }
+
+    // 创建密钥元数据，ID 和时间取自插件的随机数和时间来源
+    fn new_metadata(
         &self,
         name: String,
         description: String,
@@ -77,201 +471,3653 @@ impl KeyManagementPlugin {
         algorithm: KeyAlgorithm,
         owner: String,
         requires_approval: bool,
-        tags: Option<HashMap<String, String>>,
-        expiration_date: Option<chrono::DateTime<chrono::Utc>>,
-    ) -> Result<KeyMetadata, String> {
-        // 创建密钥元数据
-        let mut metadata = KeyMetadata::new(
-            name,
-            description,
-            key_type,
-            algorithm.clone(), // 在这里克隆 algorithm
-            owner.clone(),
-            requires_approval,
-        );
-    
-        // 设置标签
-        if let Some(t) = tags {
-            metadata.tags = t;
+    ) -> KeyMetadata {
+        KeyMetadata::new(name, description, key_type, algorithm, owner, requires_approval)
+            .with_id(self.random.uuid())
+            .with_timestamp(self.clock.now())
+            .with_tenant(current_tenant())
+            .with_security_module_id(current_security_module_id())
+    }
+
+    // 按ID查找安全模块，默认ID对应创建插件时传入的安全模块
+    fn security_module_by_id(&self, id: &str) -> Result<Arc<dyn SecurityModuleInterface + Send + Sync>, String> {
+        if id == DEFAULT_SECURITY_MODULE_ID {
+            return Ok(Arc::clone(&self.security_module));
         }
-    
-        // 设置过期日期
-        metadata.expiration_date = expiration_date;
-    
-        // 生成实际密钥
-        let key_data = self.security_module.generate_key(algorithm).await?;
-    
-        // 存储密钥
-        self.security_module.store_key(&metadata.id, &key_data).await?;
-    
-        // 保存元数据
+        self.security_modules
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Security module {} is not registered", id))
+    }
+
+    // 保存密钥材料的安全模块，即密钥创建时绑定的模块；密钥必须已加载到内存（见 load_key）
+    fn key_security_module(&self, key_id: &str) -> Result<Arc<dyn SecurityModuleInterface + Send + Sync>, String> {
+        let module_id = self.keys.lock().unwrap()
+            .get(key_id)
+            .or(self.deleted_keys.lock().unwrap().get(key_id))
+            .map(|metadata| metadata.security_module_id.clone())
+            .ok_or_else(|| format!("Key not found: {}", key_id))?;
+        self.security_module_by_id(&module_id)
+            .map_err(|e| format!("{} (required by key {})", e, key_id))
+    }
+
+    /// 启动插件自身的gRPC服务，主应用的 ExecuteCommand 调用会转发到 `execute_command`
+    #[cfg(feature = "grpc")]
+    pub async fn serve(self: &Arc<Self>) -> Result<JoinHandle<()>, String> {
+        self.base.serve(Arc::clone(self)).await
+    }
+
+    // 从持久化存储加载密钥元数据到内存
+    async fn load_from_persistence(&self) -> Result<usize, String> {
+        let persistence = match &self.persistence {
+            Some(persistence) => Arc::clone(persistence),
+            None => return Ok(0),
+        };
+
+        let loaded = persistence.list_key_metadata(None).await?;
+        let aliases = persistence.load_aliases().await?;
+        let count = loaded.len();
+
         let mut keys = self.keys.lock().unwrap();
-        keys.insert(metadata.id.clone(), metadata.clone());
-        
-        // 如果有持久化存储，则保存密钥元数据
+        let mut deleted_keys = self.deleted_keys.lock().unwrap();
+        keys.clear();
+        deleted_keys.clear();
+        for metadata in loaded {
+            if metadata.deleted_at.is_some() {
+                deleted_keys.insert(metadata.id.clone(), metadata);
+            } else {
+                keys.insert(metadata.id.clone(), metadata);
+            }
+        }
+        *self.aliases.lock().unwrap() = aliases;
+
+        // 轮换后的签名密钥优先于配置中的 response_signing_key_id
+        if let Some(metadata) = keys.values().find(|metadata| {
+            metadata.key_type == KeyType::AsymmetricPrivate
+                && metadata.tags.get(AUDIT_SIGNING_TAG).map(String::as_str) == Some("active")
+        }) {
+            *self.response_signing_key.lock().unwrap() = Some(metadata.id.clone());
+        }
+
+        Ok(count)
+    }
+
+    // 将命令参数中的密钥ID或别名解析为密钥ID，密钥ID优先；都不匹配时原样返回，由调用方报告密钥不存在
+    fn resolve_key_id(&self, key_id_or_alias: &str) -> String {
+        if self.keys.lock().unwrap().contains_key(key_id_or_alias) {
+            return key_id_or_alias.to_string();
+        }
+        self.aliases.lock().unwrap()
+            .get(key_id_or_alias)
+            .cloned()
+            .unwrap_or_else(|| key_id_or_alias.to_string())
+    }
+
+    // 从命令参数读取 key_id（可以是别名）并解析为密钥ID
+    //
+    // 其他租户的密钥按不存在处理，不暴露密钥是否存在
+    fn key_id_param(&self, params: &HashMap<String, String>) -> Result<String, String> {
+        match params.get("key_id") {
+            Some(key_id) if !key_id.is_empty() => self.tenant_key_id(key_id),
+            _ => Err("Missing parameter: key_id".to_string()),
+        }
+    }
+
+    // 将密钥ID或别名解析为当前租户可见的密钥ID
+    fn tenant_key_id(&self, key_id_or_alias: &str) -> Result<String, String> {
+        let key_id = self.resolve_key_id(key_id_or_alias);
+        if let Some(tenant) = self.key_tenant(&key_id)
+            && tenant != current_tenant()
+        {
+            return Err("Key not found".to_string());
+        }
+        Ok(key_id)
+    }
+
+    // 密钥（包括软删除的密钥）所属的租户，密钥不存在时返回 None
+    fn key_tenant(&self, key_id: &str) -> Option<String> {
+        if let Some(metadata) = self.keys.lock().unwrap().get(key_id) {
+            return Some(metadata.tenant.clone());
+        }
+        self.deleted_keys.lock().unwrap().get(key_id).map(|metadata| metadata.tenant.clone())
+    }
+
+    // 将别名指向密钥，别名已存在时改为指向新的密钥
+    async fn set_alias(&self, alias: &str, key_id: &str, user: &str) -> Result<(), String> {
+        if alias.is_empty() {
+            return Err("Alias must not be empty".to_string());
+        }
+
+        {
+            let keys = self.keys.lock().unwrap();
+            // 密钥ID优先于别名解析，与已有密钥ID相同的别名永远不会生效
+            if keys.contains_key(alias) || self.deleted_keys.lock().unwrap().contains_key(alias) {
+                return Err(format!("Alias conflicts with an existing key id: {}", alias));
+            }
+            if !keys.contains_key(key_id) {
+                return Err("Key not found".to_string());
+            }
+        }
+
+        // 别名全局唯一，不能改为指向其他租户的别名
+        let previous_target = self.aliases.lock().unwrap().get(alias).cloned();
+        if let Some(previous_target) = previous_target
+            && self.key_tenant(&previous_target).is_some_and(|tenant| tenant != current_tenant())
+        {
+            return Err(format!("Alias already in use: {}", alias));
+        }
+
         if let Some(persistence) = &self.persistence {
-            let persistence_clone = Arc::clone(persistence);
-            let metadata_clone = metadata.clone();
-            tokio::spawn(async move {
-                if let Err(e) = persistence_clone.save_key_metadata(&metadata_clone).await {
-                    eprintln!("保存密钥元数据失败: {}", e);
-                }
-            });
+            persistence.save_alias(alias, key_id).await?;
         }
-    
-        // 记录审计日志
+        let previous = self.aliases.lock().unwrap().insert(alias.to_string(), key_id.to_string());
+
+        let details = match previous {
+            Some(previous) if previous != key_id => format!("Moved alias {} from key {}", alias, previous),
+            _ => format!("Set alias: {}", alias),
+        };
         self.add_audit_log(AuditLogEntry::new(
-            "CREATE_KEY".to_string(),
-            owner,
-            Some(metadata.id.clone()),
-            format!("Created key: {}", metadata.name),
+            "SET_ALIAS".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            details,
             true,
         ));
-    
+
+        Ok(())
+    }
+
+    // 删除密钥：配置了 soft_delete_window 时只标记删除，恢复期过后由过期检查任务彻底删除
+    async fn delete_key(&self, key_id: &str, user: &str) -> Result<KeyMetadata, String> {
+        if self.base.settings().get_soft_delete_window() == 0 {
+            return self.purge_key(key_id, user, "DELETE_KEY").await;
+        }
+
+        let mut metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        let now = self.clock.now();
+        metadata.deleted_at = Some(now);
+        metadata.updated_at = now;
+
+        if let Some(persistence) = &self.persistence {
+            persistence.save_key_metadata(&metadata).await?;
+        }
+
+        self.keys.lock().unwrap().remove(key_id);
+        self.deleted_keys.lock().unwrap().insert(key_id.to_string(), metadata.clone());
+
+        self.add_audit_log(AuditLogEntry::new(
+            "DELETE_KEY".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Soft-deleted key: {}", metadata.name),
+            true,
+        ));
+
         Ok(metadata)
     }
 
-    async fn rotate_key(&self, key_id: &str, user: &str) -> Result<KeyMetadata, String> {
-        // 检查密钥是否存在
-        let mut keys = self.keys.lock().unwrap();
-        let metadata = keys.get_mut(key_id).ok_or_else(|| "Key not found".to_string())?;
+    /// 恢复软删除的密钥，超过恢复期的密钥不能恢复
+    pub async fn recover_key(&self, key_id: &str, user: &str) -> Result<KeyMetadata, String> {
+        let mut metadata = self.deleted_keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Deleted key not found".to_string())?;
 
-        // 检查密钥状态
-        if metadata.status != KeyStatus::Active {
-            return Err(format!("Key is not active, current status: {:?}", metadata.status));
+        let now = self.clock.now();
+        if self.recovery_expired(&metadata, now) {
+            return Err("Recovery window has expired".to_string());
         }
 
-        // 检查是否需要审批
-        if metadata.requires_approval {
-            let operation_id = Uuid::new_v4().to_string();
-            let mut approvals = self.pending_approvals.lock().unwrap();
-            approvals.insert(operation_id.clone(), (key_id.to_string(), "ROTATE".to_string()));
+        metadata.deleted_at = None;
+        metadata.updated_at = now;
 
-            // 记录审计日志
-            self.add_audit_log(AuditLogEntry::new(
-                "REQUEST_KEY_ROTATION".to_string(),
-                user.to_string(),
-                Some(key_id.to_string()),
-                format!("Requested key rotation, approval ID: {}", operation_id),
-                true,
-            ));
+        if let Some(persistence) = &self.persistence {
+            persistence.save_key_metadata(&metadata).await?;
+        }
 
-            return Err(format!("Key rotation requires approval. Approval ID: {}", operation_id));
+        self.deleted_keys.lock().unwrap().remove(key_id);
+        self.keys.lock().unwrap().insert(key_id.to_string(), metadata.clone());
+
+        self.add_audit_log(AuditLogEntry::new(
+            "RECOVER_KEY".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Recovered key: {}", metadata.name),
+            true,
+        ));
+
+        Ok(metadata)
+    }
+
+    /// 授权用户对密钥执行指定操作，`*` 表示全部操作
+    ///
+    /// 密钥第一次授权后开始受访问控制列表限制，之后只有所有者和列表中的用户可以操作
+    pub async fn grant_access(&self, key_id: &str, grantee: &str, operations: &[String], user: &str) -> Result<KeyMetadata, String> {
+        for operation in operations {
+            if operation != ACL_ALL_OPERATIONS && !ACL_OPERATIONS.contains(&operation.as_str()) {
+                return Err(format!("Invalid operation: {}", operation));
+            }
+        }
+        if operations.is_empty() {
+            return Err("Missing parameter: operations".to_string());
         }
 
-        // 生成新密钥
-        let key_data = self.security_module.generate_key(metadata.algorithm.clone()).await?;
+        let mut metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
 
-        // 存储新密钥
-        self.security_module.store_key(key_id, &key_data).await?;
+        metadata.acl.get_or_insert_with(HashMap::new)
+            .entry(grantee.to_string())
+            .or_default()
+            .extend(operations.iter().cloned());
+        metadata.updated_at = self.clock.now();
 
-        // 更新元数据
-        metadata.updated_at = chrono::Utc::now();
-        metadata.version += 1;
-        
-        // 如果有持久化存储，则更新密钥元数据
         if let Some(persistence) = &self.persistence {
-            let persistence_clone = Arc::clone(persistence);
-            let metadata_clone = metadata.clone();
-            tokio::spawn(async move {
-                if let Err(e) = persistence_clone.save_key_metadata(&metadata_clone).await {
-                    eprintln!("更新密钥元数据失败: {}", e);
-                }
-            });
+            persistence.save_key_metadata(&metadata).await?;
         }
 
-        // 记录审计日志
+        self.keys.lock().unwrap().insert(key_id.to_string(), metadata.clone());
+
         self.add_audit_log(AuditLogEntry::new(
-            "ROTATE_KEY".to_string(),
+            "GRANT_ACCESS".to_string(),
             user.to_string(),
             Some(key_id.to_string()),
-            format!("Rotated key: {}", metadata.name),
+            format!("Granted {} on key {} to {}", operations.join(","), metadata.name, grantee),
             true,
         ));
 
-        Ok(metadata.clone())
+        Ok(metadata)
     }
 
-    // 将 execute_command 方法改为公有
-    pub async fn execute_command(&self, command: &str, params: &HashMap<String, String>) -> CommandResult {
-        let user = params.get("user").cloned().unwrap_or_else(|| "system".to_string());
-        
-        match command {
-            "create_key" => {
-                let name = match params.get("name") {
+    /// 撤销用户对密钥的授权，未指定操作时撤销该用户的全部授权
+    ///
+    /// 撤销全部用户的授权后密钥仍受访问控制列表限制，只有所有者可以操作
+    pub async fn revoke_access(&self, key_id: &str, grantee: &str, operations: Option<&[String]>, user: &str) -> Result<KeyMetadata, String> {
+        let mut metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        let acl = metadata.acl.get_or_insert_with(HashMap::new);
+        match operations {
+            Some(operations) => {
+                if let Some(granted) = acl.get_mut(grantee) {
+                    granted.retain(|operation| !operations.contains(operation));
+                    if granted.is_empty() {
+                        acl.remove(grantee);
+                    }
+                }
+            }
+            None => {
+                acl.remove(grantee);
+            }
+        }
+        metadata.updated_at = self.clock.now();
+
+        if let Some(persistence) = &self.persistence {
+            persistence.save_key_metadata(&metadata).await?;
+        }
+
+        self.keys.lock().unwrap().insert(key_id.to_string(), metadata.clone());
+
+        self.add_audit_log(AuditLogEntry::new(
+            "REVOKE_ACCESS".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!(
+                "Revoked {} on key {} from {}",
+                operations.map(|operations| operations.join(",")).unwrap_or_else(|| ACL_ALL_OPERATIONS.to_string()),
+                metadata.name,
+                grantee
+            ),
+            true,
+        ));
+
+        Ok(metadata)
+    }
+
+    // 检查请求的用户能否对命令指定的密钥执行该命令，只有所有者可以修改访问控制列表
+    //
+    // 缺少参数、密钥不存在等错误留给命令本身报告
+    fn check_key_access(&self, command: &str, params: &HashMap<String, String>) -> Result<(), String> {
+        let owner_only = command == "grant_access" || command == "revoke_access";
+        if !owner_only && !ACL_OPERATIONS.contains(&command) {
+            return Ok(());
+        }
+        let Ok(key_id) = self.key_id_param(params) else { return Ok(()) };
+        let metadata = self.keys.lock().unwrap().get(&key_id).cloned()
+            .or_else(|| self.deleted_keys.lock().unwrap().get(&key_id).cloned());
+        let Some(metadata) = metadata else { return Ok(()) };
+
+        let user = params.get("user").map(String::as_str).unwrap_or("system");
+        let permitted = if owner_only { user == metadata.owner } else { metadata.can_access(user, command) };
+        if permitted {
+            return Ok(());
+        }
+
+        let error = format!("User {} is not permitted to {} key {}", user, command, key_id);
+        self.add_audit_log(AuditLogEntry::with_error(
+            "ACCESS_DENIED".to_string(),
+            user.to_string(),
+            Some(key_id),
+            format!("Command: {}", command),
+            error.clone(),
+        ));
+        Err(error)
+    }
+
+    /// 修改密钥的描述和备注，参数为 None 的字段保持不变，备注为空字符串时清除备注
+    pub async fn update_key(&self, key_id: &str, description: Option<String>, notes: Option<String>, user: &str) -> Result<KeyMetadata, String> {
+        let mut metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        let mut changed = Vec::new();
+        if let Some(description) = description {
+            metadata.description = description;
+            changed.push("description");
+        }
+        if let Some(notes) = notes {
+            metadata.notes = (!notes.is_empty()).then_some(notes);
+            changed.push("notes");
+        }
+        metadata.updated_at = self.clock.now();
+
+        if let Some(persistence) = &self.persistence {
+            persistence.save_key_metadata(&metadata).await?;
+        }
+
+        self.keys.lock().unwrap().insert(key_id.to_string(), metadata.clone());
+
+        // 备注是内部信息，审计日志只记录修改了哪些字段
+        self.add_audit_log(AuditLogEntry::new(
+            "UPDATE_KEY".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Updated {} of key {}", changed.join(", "), metadata.name),
+            true,
+        ));
+
+        Ok(metadata)
+    }
+
+    /// 为当前租户中满足过滤条件的全部密钥设置标签，返回更新的密钥数
+    ///
+    /// 过滤条件与 list_keys 相同（tag.* 除外，参数中的 tag.* 是要设置的标签），用户没有 update_key 权限的密钥跳过。
+    /// 更新的密钥版本加一，在同一批次中写入持久化存储；内存中只更新已加载的密钥，已淘汰的不重新加载
+    pub async fn apply_tags(&self, params: &HashMap<String, String>, user: &str) -> Result<usize, String> {
+        let mut filters = HashMap::new();
+        let mut tags = HashMap::new();
+        for (key, value) in params {
+            match key.strip_prefix("tag.") {
+                Some(tag_key) => {
+                    tags.insert(tag_key.to_string(), value.clone());
+                }
+                None => {
+                    filters.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        if tags.is_empty() {
+            return Err("Missing parameter: tag.*".to_string());
+        }
+        filters.remove("include_deleted");
+
+        let now = self.clock.now();
+        let updated: Vec<KeyMetadata> = self.list_keys(&filters).await?
+            .into_iter()
+            .filter(|metadata| metadata.can_access(user, "update_key"))
+            .map(|mut metadata| {
+                metadata.tags.extend(tags.clone());
+                metadata.version += 1;
+                metadata.updated_at = now;
+                metadata
+            })
+            .collect();
+
+        if let Some(persistence) = &self.persistence {
+            persistence.save_key_metadata_batch(&updated).await?;
+        }
+
+        let mut tag_names: Vec<&str> = tags.keys().map(String::as_str).collect();
+        tag_names.sort();
+        {
+            let mut keys = self.keys.lock().unwrap();
+            for metadata in &updated {
+                if let Some(loaded) = keys.get_mut(&metadata.id) {
+                    *loaded = metadata.clone();
+                }
+            }
+        }
+        for metadata in &updated {
+            self.add_audit_log(AuditLogEntry::new(
+                "APPLY_TAGS".to_string(),
+                user.to_string(),
+                Some(metadata.id.clone()),
+                format!("Applied tags {} to key {}", tag_names.join(", "), metadata.name),
+                true,
+            ));
+        }
+
+        Ok(updated.len())
+    }
+
+    // 暂停使用（Active -> Suspended）或恢复使用（Suspended -> Active）密钥
+    async fn set_key_status(&self, key_id: &str, status: KeyStatus, user: &str) -> Result<KeyMetadata, String> {
+        let (action, required) = match status {
+            KeyStatus::Suspended => ("SUSPEND_KEY", KeyStatus::Active),
+            KeyStatus::Active => ("RESUME_KEY", KeyStatus::Suspended),
+            _ => return Err(format!("Cannot change key status to {:?}", status)),
+        };
+
+        let mut metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        if metadata.status != required {
+            return Err(format!(
+                "Key is not {}, current status: {:?}",
+                required.to_string().to_lowercase(),
+                metadata.status
+            ));
+        }
+
+        metadata.status = status;
+        metadata.updated_at = self.clock.now();
+
+        if let Some(persistence) = &self.persistence {
+            persistence.save_key_metadata(&metadata).await?;
+        }
+
+        self.keys.lock().unwrap().insert(key_id.to_string(), metadata.clone());
+
+        self.add_audit_log(AuditLogEntry::new(
+            action.to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Changed status of key {} to {}", metadata.name, metadata.status.to_string()),
+            true,
+        ));
+
+        Ok(metadata)
+    }
+
+    /// 按时间顺序返回密钥的完整生命周期
+    ///
+    /// 汇总该密钥的全部审计日志（持久化存储中的和尚未写入的），成功的事件附带其后的状态和版本。
+    /// 已软删除的密钥也可以查询
+    pub async fn key_history(&self, key_id: &str) -> Result<serde_json::Value, String> {
+        let metadata = self.keys.lock().unwrap().get(key_id).cloned()
+            .or_else(|| self.deleted_keys.lock().unwrap().get(key_id).cloned());
+
+        let mut entries: Vec<AuditLogEntry> = Vec::new();
+        if let Some(persistence) = &self.persistence {
+            let filters = HashMap::from([("key_id".to_string(), key_id.to_string())]);
+            entries = persistence.load_audit_logs(Some(filters), None).await?;
+        }
+        let mut seen: HashSet<String> = entries.iter().map(|entry| entry.id.clone()).collect();
+        for entry in self.audit_log.lock().unwrap().iter() {
+            if entry.key_id.as_deref() == Some(key_id) && seen.insert(entry.id.clone()) {
+                entries.push(entry.clone());
+            }
+        }
+
+        let tenant = current_tenant();
+        entries.retain(|entry| entry.tenant == tenant);
+        if metadata.is_none() && entries.is_empty() {
+            return Err("Key not found".to_string());
+        }
+        // 时间戳只精确到毫秒，同一毫秒内的事件保持写入顺序
+        entries.sort_by_key(|entry| entry.timestamp);
+
+        // 按事件重放状态和版本
+        let mut status: Option<KeyStatus> = None;
+        let mut version: u32 = 0;
+        let events: Vec<serde_json::Value> = entries.iter().map(|entry| {
+            let mut event = serde_json::json!({
+                "timestamp": timestamp::format(&entry.timestamp),
+                "action": entry.action,
+                "user": entry.user,
+                "success": entry.success,
+                "details": entry.details,
+            });
+            if let Some(error) = &entry.error {
+                event["error"] = serde_json::json!(error);
+            }
+            if entry.success {
+                let transition = match entry.action.as_str() {
+                    "CREATE_KEY" | "CREATE_KEY_PAIR" | "DERIVE_KEY" => {
+                        version = 1;
+                        Some(KeyStatus::Active)
+                    }
+                    "ROTATE_KEY" | "APPLY_TAGS" | "REHASH_PASSWORD" => {
+                        version += 1;
+                        None
+                    }
+                    "SUSPEND_KEY" => Some(KeyStatus::Suspended),
+                    "RESUME_KEY" => Some(KeyStatus::Active),
+                    "KEY_EXPIRED" => Some(KeyStatus::Expired),
+                    "MIGRATE_KEY" => Some(KeyStatus::PendingDestruction),
+                    "PURGE_KEY" => Some(KeyStatus::Destroyed),
+                    _ => None,
+                };
+                if let Some(transition) = transition {
+                    status = Some(transition);
+                }
+                if let Some(status) = &status {
+                    event["status"] = serde_json::json!(status.to_string());
+                    event["version"] = serde_json::json!(version);
+                }
+            }
+            event
+        }).collect();
+
+        Ok(serde_json::json!({
+            "key_id": key_id,
+            "name": metadata.as_ref().map(|metadata| metadata.name.clone()),
+            "status": metadata.as_ref().map(|metadata| metadata.status.to_string()),
+            "version": metadata.as_ref().map(|metadata| metadata.version),
+            "deleted_at": metadata.as_ref().and_then(|metadata| metadata.deleted_at).map(|deleted_at| timestamp::format(&deleted_at)),
+            "events": events,
+        }))
+    }
+
+    /// 彻底删除超过恢复期的软删除密钥，返回删除的数量
+    pub async fn sweep_deleted_keys(&self) -> usize {
+        let now = self.clock.now();
+        let expired: Vec<String> = self.deleted_keys.lock().unwrap()
+            .values()
+            .filter(|metadata| self.recovery_expired(metadata, now))
+            .map(|metadata| metadata.id.clone())
+            .collect();
+
+        let mut purged = 0;
+        for key_id in expired {
+            match self.purge_key(&key_id, "system", "PURGE_KEY").await {
+                Ok(_) => purged += 1,
+                Err(e) => eprintln!("彻底删除密钥 {} 失败: {}", key_id, e),
+            }
+        }
+        purged
+    }
+
+    // 软删除的密钥是否已超过恢复期，恢复期按当前配置计算
+    fn recovery_expired(&self, metadata: &KeyMetadata, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let Some(deleted_at) = metadata.deleted_at else {
+            return false;
+        };
+        let window = i64::try_from(self.base.settings().get_soft_delete_window()).unwrap_or(i64::MAX);
+        let window = chrono::Duration::try_seconds(window).unwrap_or(chrono::Duration::MAX);
+        deleted_at.checked_add_signed(window).is_some_and(|until| until <= now)
+    }
+
+    // 彻底删除密钥：依次删除持久化的元数据和别名、内存中的记录以及安全模块中的材料
+    async fn purge_key(&self, key_id: &str, user: &str, action: &str) -> Result<KeyMetadata, String> {
+        let metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .or_else(|| self.deleted_keys.lock().unwrap().get(key_id).cloned())
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        let aliases: Vec<String> = self.aliases.lock().unwrap()
+            .iter()
+            .filter(|(_, target)| *target == key_id)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+
+        if let Some(persistence) = &self.persistence {
+            persistence.delete_key_metadata(key_id).await?;
+            for alias in &aliases {
+                if let Err(e) = persistence.delete_alias(alias).await {
+                    eprintln!("删除别名失败: {}", e);
+                }
+            }
+        }
+
+        self.keys.lock().unwrap().remove(key_id);
+        self.deleted_keys.lock().unwrap().remove(key_id);
+        self.aliases.lock().unwrap().retain(|_, target| target != key_id);
+        // 重放时不能再写回已删除的密钥
+        self.dead_letters.remove_key_metadata(key_id);
+
+        // 公钥一半在安全模块中没有材料
+        if metadata.key_type != KeyType::AsymmetricPublic
+            && let Err(e) = match self.security_module_by_id(&metadata.security_module_id) {
+                Ok(security_module) => security_module.delete_key(key_id).await,
+                Err(e) => Err(e),
+            }
+        {
+            eprintln!("删除密钥材料失败: {}", e);
+        }
+
+        self.add_audit_log(AuditLogEntry::new(
+            action.to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Deleted key: {}", metadata.name),
+            true,
+        ).with_tenant(metadata.tenant.clone()));
+
+        Ok(metadata)
+    }
+
+    // 附件保存在持久化存储中，读写附件前检查存储已配置且密钥存在
+    fn attachment_store(&self, key_id: &str) -> Result<Arc<dyn PersistenceInterface + Send + Sync>, String> {
+        let persistence = match &self.persistence {
+            Some(persistence) => Arc::clone(persistence),
+            None => return Err("未配置持久化存储".to_string()),
+        };
+        if !self.keys.lock().unwrap().contains_key(key_id) {
+            return Err("Key not found".to_string());
+        }
+        Ok(persistence)
+    }
+
+    // 保存密钥附件，附件内容为 base64，解码后不能超过 max_attachment_size
+    async fn put_attachment(&self, key_id: &str, name: &str, data: &str, user: &str) -> Result<AttachmentInfo, String> {
+        // 附件名在文件存储中直接作为文件名使用
+        if name.is_empty() || name.len() > 255 || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(format!("Invalid attachment name: {}", name));
+        }
+        if name == ESCROW_ATTACHMENT {
+            return Err(format!("Invalid attachment name: {} is reserved", name));
+        }
+        let persistence = self.attachment_store(key_id)?;
+
+        let data = BASE64.decode(data).map_err(|e| format!("Invalid data: {}", e))?;
+        let max_size = self.base.settings().get_max_attachment_size();
+        if data.len() > max_size {
+            return Err(format!("Attachment too large: {} bytes, limit is {} bytes", data.len(), max_size));
+        }
+
+        persistence.put_attachment(key_id, name, &data).await?;
+
+        self.add_audit_log(AuditLogEntry::new(
+            "PUT_ATTACHMENT".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Stored attachment {} ({} bytes)", name, data.len()),
+            true,
+        ));
+
+        Ok(AttachmentInfo { name: name.to_string(), size: data.len() })
+    }
+
+    /// 用配置的恢复公钥托管密钥材料，托管数据保存为密钥附件并返回（base64）
+    pub async fn escrow_key(&self, key_id: &str, user: &str) -> Result<serde_json::Value, String> {
+        let result = self.escrow_key_inner(key_id, user).await;
+        let entry = match &result {
+            Ok(value) => AuditLogEntry::new(
+                "ESCROW_KEY".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                format!("Escrowed key under recovery key {}", value["recovery_key_fingerprint"].as_str().unwrap_or_default()),
+                true,
+            ),
+            Err(e) => AuditLogEntry::with_error(
+                "ESCROW_KEY".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                "Escrow key".to_string(),
+                e.clone(),
+            ),
+        };
+        self.add_audit_log(entry);
+        result
+    }
+
+    async fn escrow_key_inner(&self, key_id: &str, user: &str) -> Result<serde_json::Value, String> {
+        let public_key = self.escrow_public_key.as_ref().ok_or_else(|| "未配置托管恢复公钥".to_string())?;
+        let persistence = self.attachment_store(key_id)?;
+        let mut metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+        if metadata.key_type == KeyType::AsymmetricPublic {
+            return Err("Escrowing public keys is not supported".to_string());
+        }
+
+        let material = self.key_security_module(key_id)?.retrieve_key(key_id).await?;
+        let blob = public_key.seal(key_id, &material)?;
+        persistence.put_attachment(key_id, ESCROW_ATTACHMENT, &blob).await?;
+
+        let escrowed_at = self.clock.now();
+        metadata.tags.insert("escrowed_at".to_string(), timestamp::format(&escrowed_at));
+        metadata.tags.insert("escrowed_by".to_string(), user.to_string());
+        metadata.tags.insert("escrow_key_fingerprint".to_string(), public_key.fingerprint().to_string());
+        metadata.updated_at = escrowed_at;
+        persistence.save_key_metadata(&metadata).await?;
+        self.keys.lock().unwrap().insert(key_id.to_string(), metadata);
+
+        Ok(serde_json::json!({
+            "key_id": key_id,
+            "escrowed_at": timestamp::format(&escrowed_at),
+            "recovery_key_fingerprint": public_key.fingerprint(),
+            "blob": BASE64.encode(&blob),
+        }))
+    }
+
+    /// 用恢复私钥解开托管数据，将密钥材料重新写入安全模块；未提供托管数据时使用保存的附件
+    pub async fn recover_from_escrow(&self, key_id: &str, recovery_private_key: &str, blob: Option<&str>, user: &str) -> Result<(), String> {
+        let result = self.recover_from_escrow_inner(key_id, recovery_private_key, blob).await;
+        let entry = match &result {
+            Ok(()) => AuditLogEntry::new(
+                "RECOVER_FROM_ESCROW".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                format!("Restored key material from escrow ({})", if blob.is_some() { "supplied blob" } else { "stored blob" }),
+                true,
+            ),
+            Err(e) => AuditLogEntry::with_error(
+                "RECOVER_FROM_ESCROW".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                "Recover key from escrow".to_string(),
+                e.clone(),
+            ),
+        };
+        self.add_audit_log(entry);
+        result
+    }
+
+    async fn recover_from_escrow_inner(&self, key_id: &str, recovery_private_key: &str, blob: Option<&str>) -> Result<(), String> {
+        if !self.keys.lock().unwrap().contains_key(key_id) {
+            return Err("Key not found".to_string());
+        }
+        let blob = match blob {
+            Some(blob) => BASE64.decode(blob).map_err(|e| format!("Invalid blob: {}", e))?,
+            None => self.attachment_store(key_id)?.get_attachment(key_id, ESCROW_ATTACHMENT).await?,
+        };
+
+        let material = escrow::open(recovery_private_key, key_id, &blob)?;
+        self.key_security_module(key_id)?.store_key(key_id, &material).await
+    }
+
+    /// 校验候选口令是否与 PASSWORD 类型的密钥匹配，只返回是否匹配，不返回存储的值
+    ///
+    /// 用密钥记录的盐和 Argon2 参数派生候选口令，与存储的派生结果做常量时间比较。
+    /// 匹配与否都记录审计日志，日志中不包含口令
+    pub async fn verify_password(&self, key_id: &str, password: &str, user: &str) -> Result<bool, String> {
+        let result = self.verify_password_inner(key_id, password).await;
+        let entry = match &result {
+            Ok(matched) => AuditLogEntry::new(
+                "VERIFY_PASSWORD".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                if *matched { "Password matched" } else { "Password did not match" }.to_string(),
+                *matched,
+            ),
+            Err(e) => AuditLogEntry::with_error(
+                "VERIFY_PASSWORD".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                "Verify password".to_string(),
+                e.clone(),
+            ),
+        };
+        self.add_audit_log(entry);
+        result
+    }
+
+    async fn verify_password_inner(&self, key_id: &str, password: &str) -> Result<bool, String> {
+        let metadata = self.active_key(key_id)?;
+        if metadata.key_type != KeyType::Password {
+            return Err(format!("Key is not a password key: {}", key_id));
+        }
+        let salt = match metadata.tags.get("kdf_salt") {
+            Some(salt) => BASE64.decode(salt).map_err(|e| format!("Invalid kdf_salt: {}", e))?,
+            None => return Err(format!("Key has no kdf_salt: {}", key_id)),
+        };
+        let params = kdf_params_from(&metadata.tags, &self.kdf_params)?;
+
+        let security_module = self.key_security_module(key_id)?;
+        let candidate = security_module.derive_key(password.as_bytes(), &salt, metadata.algorithm.clone(), &params).await?;
+        let stored = security_module.retrieve_key(key_id).await?;
+
+        self.mark_key_used(key_id);
+        Ok(constant_time::eq(&candidate, &stored))
+    }
+
+    /// 检查 PASSWORD 类型密钥记录的 Argon2 参数是否满足当前配置的参数下限，返回每个密钥的参数和是否需要重新派生
+    ///
+    /// 指定 key_id 时只检查该密钥，否则检查当前租户的全部 PASSWORD 类型密钥
+    pub async fn verify_kdf_params(&self, key_id: Option<&str>) -> Result<Vec<serde_json::Value>, String> {
+        let keys = match key_id {
+            Some(key_id) => {
+                let metadata = self.keys.lock().unwrap()
+                    .get(key_id)
+                    .cloned()
+                    .ok_or_else(|| "Key not found".to_string())?;
+                if metadata.key_type != KeyType::Password {
+                    return Err(format!("Key is not a password key: {}", key_id));
+                }
+                vec![metadata]
+            }
+            None => {
+                let filters = HashMap::from([("type".to_string(), KeyType::Password.to_string())]);
+                self.list_keys(&filters).await?
+            }
+        };
+
+        keys.iter().map(|metadata| {
+            let params = kdf_params_from(&metadata.tags, &self.kdf_params)?;
+            Ok(serde_json::json!({
+                "key_id": metadata.id,
+                "name": metadata.name,
+                "kdf_memory_kib": params.memory_kib,
+                "kdf_iterations": params.iterations,
+                "kdf_parallelism": params.parallelism,
+                "needs_rehash": !params.meets(&self.kdf_params),
+            }))
+        }).collect()
+    }
+
+    /// 用当前配置的 Argon2 参数和新的盐重新派生 PASSWORD 类型密钥，口令必须与原口令匹配
+    ///
+    /// 密钥ID不变，版本加一，参数和盐记录在标签中
+    pub async fn rehash_password(&self, key_id: &str, password: &str, user: &str) -> Result<KeyMetadata, String> {
+        let result = self.rehash_password_inner(key_id, password).await;
+        // 不记录口令
+        let entry = match &result {
+            Ok(metadata) => {
+                let params = self.kdf_params;
+                AuditLogEntry::new(
+                    "REHASH_PASSWORD".to_string(),
+                    user.to_string(),
+                    Some(key_id.to_string()),
+                    format!(
+                        "Rehashed password key {} with memory {} KiB, {} iterations, parallelism {}",
+                        metadata.name, params.memory_kib, params.iterations, params.parallelism
+                    ),
+                    true,
+                )
+            }
+            Err(e) => AuditLogEntry::with_error(
+                "REHASH_PASSWORD".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                "Rehash password".to_string(),
+                e.clone(),
+            ),
+        };
+        self.add_audit_log(entry);
+        result
+    }
+
+    async fn rehash_password_inner(&self, key_id: &str, password: &str) -> Result<KeyMetadata, String> {
+        if !self.verify_password_inner(key_id, password).await? {
+            return Err("Password does not match".to_string());
+        }
+
+        let mut salt = vec![0u8; KDF_SALT_LEN];
+        self.random.fill(&mut salt);
+        let params = self.kdf_params;
+        let security_module = self.key_security_module(key_id)?;
+        let key_data = security_module.derive_key(password.as_bytes(), &salt, KeyAlgorithm::AES256, &params).await?;
+        security_module.store_key(key_id, &key_data).await?;
+
+        let metadata = {
+            let mut keys = self.keys.lock().unwrap();
+            let metadata = keys.get_mut(key_id).ok_or_else(|| "Key not found".to_string())?;
+            metadata.tags.insert("kdf_salt".to_string(), BASE64.encode(&salt));
+            metadata.tags.insert("kdf_memory_kib".to_string(), params.memory_kib.to_string());
+            metadata.tags.insert("kdf_iterations".to_string(), params.iterations.to_string());
+            metadata.tags.insert("kdf_parallelism".to_string(), params.parallelism.to_string());
+            metadata.updated_at = self.clock.now();
+            metadata.version += 1;
+            metadata.clone()
+        };
+        self.persist_metadata_in_background(metadata.clone());
+
+        Ok(metadata)
+    }
+
+    // 按过滤条件列出密钥，按创建时间排序；include_deleted 为 true 时包含恢复期内的软删除密钥
+    async fn list_keys(&self, params: &HashMap<String, String>) -> Result<Vec<KeyMetadata>, String> {
+        let query = KeyQuery::from_filters(Some(params))?.with_tenant(current_tenant());
+        let include_deleted = params.get("include_deleted")
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+
+        let mut list: Vec<KeyMetadata> = self.keys.lock().unwrap()
+            .values()
+            .filter(|metadata| query.matches(metadata))
+            .cloned()
+            .collect();
+        if include_deleted {
+            list.extend(self.deleted_keys.lock().unwrap()
+                .values()
+                .filter(|metadata| query.matches(metadata))
+                .cloned());
+        }
+
+        // 限制了内存中的密钥数时，被淘汰的密钥从持久化存储中补齐
+        if self.max_in_memory_keys.is_some()
+            && let Some(persistence) = &self.persistence
+        {
+            let stored = persistence.query_keys(&query).await?;
+            let keys = self.keys.lock().unwrap();
+            let deleted_keys = self.deleted_keys.lock().unwrap();
+            list.extend(stored.into_iter().filter(|metadata| {
+                (include_deleted || metadata.deleted_at.is_none())
+                    && !keys.contains_key(&metadata.id)
+                    && !deleted_keys.contains_key(&metadata.id)
+            }));
+        }
+
+        list.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(list)
+    }
+
+    // 检查密钥能否用于数据操作：必须存在、处于启用状态且未过期
+    fn active_key(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        let metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        metadata.ensure_usable_at(self.clock.now())?;
+
+        Ok(metadata)
+    }
+
+    // 使用密钥签名或加解密数据，参数 data、aad 和返回值均为 base64，操作结果记录审计日志
+    //
+    // aad 为加解密的附加认证数据，解密时必须与加密时相同
+    async fn data_operation(&self, command: &str, key_id: &str, params: &HashMap<String, String>, user: &str) -> Result<String, String> {
+        let data = match params.get("data") {
+            Some(data) => BASE64.decode(data).map_err(|e| format!("Invalid data: {}", e))?,
+            None => return Err("Missing parameter: data".to_string()),
+        };
+        let aad = match params.get("aad") {
+            Some(aad) => BASE64.decode(aad).map_err(|e| format!("Invalid aad: {}", e))?,
+            None => Vec::new(),
+        };
+
+        let metadata = self.active_key(key_id)?;
+
+        // 加密模式由密钥的 encryption_mode 标签决定，默认随机模式；两种模式的密文解密方式相同
+        let deterministic = command == "encrypt" && match metadata.tags.get("encryption_mode").map(String::as_str) {
+            None | Some("randomized") => false,
+            Some("deterministic") => true,
+            Some(mode) => return Err(format!("Invalid encryption_mode: {}", mode)),
+        };
+
+        let security_module = self.key_security_module(key_id)?;
+        let (action, result) = match command {
+            "sign" => ("SIGN_DATA", security_module.sign_data(key_id, &data).await),
+            "encrypt" if deterministic => ("ENCRYPT_DATA", security_module.encrypt_data_deterministic(key_id, &data, &aad).await),
+            "encrypt" => ("ENCRYPT_DATA", security_module.encrypt_data(key_id, &data, &aad).await),
+            _ => ("DECRYPT_DATA", security_module.decrypt_data(key_id, &data, &aad).await),
+        };
+
+        match result {
+            Ok(output) => {
+                self.mark_key_used(key_id);
+                self.add_audit_log(AuditLogEntry::new(
+                    action.to_string(),
+                    user.to_string(),
+                    Some(key_id.to_string()),
+                    format!("{} bytes", data.len()),
+                    true,
+                ));
+                Ok(BASE64.encode(output))
+            }
+            Err(e) => {
+                self.add_audit_log(AuditLogEntry::with_error(
+                    action.to_string(),
+                    user.to_string(),
+                    Some(key_id.to_string()),
+                    format!("{} bytes", data.len()),
+                    e.clone(),
+                ));
+                Err(e)
+            }
+        }
+    }
+
+    /// 生成随机数据密钥，返回明文和用主密钥加密后的密文（均为 base64），明文不保存
+    ///
+    /// 密文用 decrypt 命令和同一个主密钥解密即可得到数据密钥，aad 必须与生成时相同
+    pub async fn generate_data_key(&self, key_id: &str, length: usize, aad: &[u8], user: &str) -> Result<serde_json::Value, String> {
+        if !DATA_KEY_LENGTHS.contains(&length) {
+            return Err(format!("Invalid length: {} (expected 16, 24 or 32)", length));
+        }
+        let metadata = self.active_key(key_id)?;
+        if metadata.key_type != KeyType::Symmetric {
+            return Err(format!("Key {} is not a symmetric key and cannot wrap data keys", key_id));
+        }
+
+        let mut data_key = vec![0u8; length];
+        self.random.fill(&mut data_key);
+
+        let result = match self.key_security_module(key_id) {
+            Ok(security_module) => security_module.encrypt_data(key_id, &data_key, aad).await,
+            Err(e) => Err(e),
+        };
+        let details = format!("Generated {}-byte data key", length);
+        match result {
+            Ok(wrapped) => {
+                self.mark_key_used(key_id);
+                self.add_audit_log(AuditLogEntry::new(
+                    "GENERATE_DATA_KEY".to_string(),
+                    user.to_string(),
+                    Some(key_id.to_string()),
+                    details,
+                    true,
+                ));
+                Ok(serde_json::json!({
+                    "key_id": key_id,
+                    "key_version": metadata.version,
+                    "plaintext": BASE64.encode(&data_key),
+                    "ciphertext": BASE64.encode(wrapped),
+                }))
+            }
+            Err(e) => {
+                self.add_audit_log(AuditLogEntry::with_error(
+                    "GENERATE_DATA_KEY".to_string(),
+                    user.to_string(),
+                    Some(key_id.to_string()),
+                    details,
+                    e.clone(),
+                ));
+                Err(e)
+            }
+        }
+    }
+
+    // 记录密钥的使用时间，签名、验证、加解密和生成数据密钥成功后调用
+    //
+    // 间隔内的多次使用只更新内存，未写入的使用时间在淘汰密钥或插件停止时补写
+    fn mark_key_used(&self, key_id: &str) {
+        let now = self.clock.now();
+        let changed = {
+            let mut keys = self.keys.lock().unwrap();
+            let Some(metadata) = keys.get_mut(key_id) else { return };
+            let persist = metadata.last_used_at
+                .is_none_or(|last_used| now.signed_duration_since(last_used).num_seconds() >= LAST_USED_PERSIST_INTERVAL_SECS);
+            metadata.last_used_at = Some(now);
+            persist.then(|| metadata.clone())
+        };
+        if self.persistence.is_none() {
+            return;
+        }
+        match changed {
+            Some(metadata) => {
+                self.unpersisted_usage.lock().unwrap().remove(key_id);
+                self.persist_metadata_in_background(metadata);
+            }
+            None => {
+                self.unpersisted_usage.lock().unwrap().insert(key_id.to_string());
+            }
+        }
+    }
+
+    /// 将只更新了内存的密钥使用时间写入持久化存储，返回写入的密钥数
+    pub async fn flush_key_usage(&self) -> usize {
+        let Some(persistence) = &self.persistence else { return 0 };
+        let pending: Vec<KeyMetadata> = {
+            let key_ids: Vec<String> = self.unpersisted_usage.lock().unwrap().drain().collect();
+            let keys = self.keys.lock().unwrap();
+            key_ids.iter().filter_map(|key_id| keys.get(key_id).cloned()).collect()
+        };
+
+        let flushed = pending.len();
+        for metadata in pending {
+            save_metadata_with_retry(persistence.as_ref(), metadata, &self.dirty_keys, &self.dead_letters).await;
+        }
+        flushed
+    }
+
+    /// 列出当前租户中从未使用或最近 `since_days` 天内未使用的密钥（不含已删除的密钥）
+    pub async fn list_unused_keys(&self, since_days: u32) -> Result<Vec<KeyMetadata>, String> {
+        let cutoff = self.clock.now() - chrono::Duration::days(i64::from(since_days));
+        let keys = self.list_keys(&HashMap::new()).await?;
+        Ok(keys.into_iter()
+            .filter(|metadata| metadata.last_used_at.is_none_or(|last_used| last_used < cutoff))
+            .collect())
+    }
+
+    // 批量验证签名，按输入顺序返回每条的结果：Ok(是否有效) 或无法验证的原因
+    //
+    // 各条验证相互独立，并发执行
+    async fn verify_batch(&self, items: Vec<VerifyItem>) -> Vec<Result<bool, String>> {
+        for item in &items {
+            if let Ok(key_id) = self.tenant_key_id(&item.key_id) {
+                self.load_key(&key_id).await;
+            }
+        }
+
+        stream::iter(items)
+            .map(|item| {
+                let prepared = self.tenant_key_id(&item.key_id).and_then(|key_id| {
+                    if !self.keys.lock().unwrap().contains_key(&key_id) {
+                        return Err("Key not found".to_string());
+                    }
+                    let data = BASE64.decode(&item.data).map_err(|e| format!("Invalid data: {}", e))?;
+                    let signature = BASE64.decode(&item.signature).map_err(|e| format!("Invalid signature: {}", e))?;
+                    let security_module = self.key_security_module(&key_id)?;
+                    Ok((security_module, key_id, data, signature))
+                });
+                async move {
+                    let (security_module, key_id, data, signature) = prepared?;
+                    let verify_key_id = key_id.clone();
+                    let verified = tokio::spawn(async move { security_module.verify_signature(&verify_key_id, &data, &signature).await })
+                        .await
+                        .map_err(|e| format!("验证任务失败: {}", e))?;
+                    if verified.is_ok() {
+                        self.mark_key_used(&key_id);
+                    }
+                    verified
+                }
+            })
+            .buffered(VERIFY_BATCH_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// 注册密钥即将过期的回调
+    ///
+    /// 过期检查发现启用中的密钥距离过期不足 `days_before` 天时调用，每个密钥在同一个过期时间下
+    /// 对每个阈值只通知一次；可以注册多个阈值（如 30/7/1 天）
+    pub fn on_key_expiring<F>(&self, days_before: u32, callback: F)
+    where
+        F: Fn(&KeyMetadata) + Send + Sync + 'static,
+    {
+        self.expiry_hooks.lock().unwrap().push((days_before, Arc::new(callback)));
+    }
+
+    /// 检查密钥过期情况
+    ///
+    /// 已到过期时间的启用或暂停状态密钥标记为 `Expired`，并对即将过期的密钥调用过期回调。
+    /// 返回本次标记为过期的密钥数量
+    pub async fn sweep_expirations(&self) -> usize {
+        // 被淘汰的密钥也要检查，先加载可能过期或需要通知的密钥
+        for status in [KeyStatus::Active, KeyStatus::Suspended] {
+            if let Err(e) = self.load_matching_keys(&KeyQuery::new().with_status(status)).await {
+                eprintln!("加载待检查过期的密钥失败: {}", e);
+            }
+        }
+
+        let now = self.clock.now();
+        let hooks = self.expiry_hooks.lock().unwrap().clone();
+
+        let mut expired = Vec::new();
+        let mut notifications = Vec::new();
+        {
+            let mut keys = self.keys.lock().unwrap();
+            let mut notified = self.expiry_notified.lock().unwrap();
+
+            for metadata in keys.values_mut() {
+                let Some(expiration_date) = metadata.expiration_date else { continue };
+
+                if expiration_date <= now {
+                    if matches!(metadata.status, KeyStatus::Active | KeyStatus::Suspended) {
+                        metadata.status = KeyStatus::Expired;
+                        metadata.updated_at = now;
+                        expired.push(metadata.clone());
+                    }
+                    continue;
+                }
+
+                if metadata.status != KeyStatus::Active {
+                    continue;
+                }
+
+                for (days_before, hook) in &hooks {
+                    if expiration_date - now > chrono::Duration::days(i64::from(*days_before)) {
+                        continue;
+                    }
+                    // 过期时间变化（如续期）后重新通知
+                    let notified_for = notified.insert((metadata.id.clone(), *days_before), expiration_date);
+                    if notified_for != Some(expiration_date) {
+                        notifications.push((Arc::clone(hook), metadata.clone()));
+                    }
+                }
+            }
+
+            // 清理已删除或已过期密钥的通知记录
+            notified.retain(|(key_id, _), _| {
+                keys.get(key_id).is_some_and(|metadata| metadata.status == KeyStatus::Active)
+            });
+        }
+
+        // 回调在释放锁之后执行，回调中可以再调用插件
+        for (hook, metadata) in notifications {
+            hook(&metadata);
+        }
+
+        for metadata in &expired {
+            // 如果有持久化存储，则更新密钥元数据；写入完成前不会被淘汰
+            self.persist_metadata_in_background(metadata.clone());
+
+            self.add_audit_log(AuditLogEntry::new(
+                "KEY_EXPIRED".to_string(),
+                "system".to_string(),
+                Some(metadata.id.clone()),
+                format!("Key expired: {}", metadata.name),
+                true,
+            ).with_tenant(metadata.tenant.clone()));
+        }
+        self.evict_keys();
+
+        expired.len()
+    }
+
+    /// 启动后台过期检查任务，同时彻底删除超过恢复期的软删除密钥、补写之前未能保存的密钥元数据；间隔由 `expiration_sweep_interval` 配置，插件停止后任务退出
+    pub fn start_expiration_sweeper(self: &Arc<Self>) -> JoinHandle<()> {
+        let plugin = Arc::clone(self);
+        tokio::spawn(async move {
+            // 登记为后台操作，可以通过 cancel_operation 停止
+            let operation = match plugin.register_operation(None, "expiration_sweeper", "system") {
+                Ok(operation) => operation,
+                Err(e) => {
+                    eprintln!("启动过期检查任务失败: {}", e);
+                    return;
+                }
+            };
+
+            let health = plugin.base.health();
+            loop {
+                // 每轮重新读取检查间隔，运行时修改后立即生效
+                let interval = plugin.base.settings().get_expiration_sweep_interval();
+                tokio::select! {
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(interval)) => {}
+                    _ = operation.token().cancelled() => break,
+                }
+
+                if !health.is_running() {
+                    break;
+                }
+
+                let count = plugin.sweep_expirations().await;
+                if count > 0 {
+                    println!("已将 {} 个密钥标记为过期", count);
+                }
+
+                let purged = plugin.sweep_deleted_keys().await;
+                if purged > 0 {
+                    println!("已彻底删除 {} 个超过恢复期的密钥", purged);
+                }
+
+                let reconciled = plugin.reconcile_dirty_keys().await;
+                if reconciled > 0 {
+                    println!("已补写 {} 个密钥的元数据", reconciled);
+                }
+            }
+        })
+    }
+
+    /// 获取插件运行指标
+    pub fn metrics(&self) -> PluginMetrics {
+        self.base.metrics()
+    }
+
+    // 记录ID和时间统一取自插件的随机数和时间来源
+    fn add_audit_log(&self, entry: AuditLogEntry) {
+        let mut entry = entry
+            .with_id(self.random.uuid())
+            .with_timestamp(self.clock.now());
+        // 未显式指定租户的记录归入当前命令的租户
+        if entry.tenant == DEFAULT_TENANT {
+            entry.tenant = current_tenant();
+        }
+
+        // 审计级别为 failures 时只记录失败的操作
+        if entry.success && self.base.settings().get_audit_level() == "failures" {
+            return;
+        }
+
+        let mut log = self.audit_log.lock().unwrap();
+        log.push(entry.clone());
+        
+        // 如果有持久化存储，则保存审计日志；插件运行期间由写入队列负责，失败后会重试
+        if let Some(queue) = &self.audit_queue {
+            queue.send(&entry);
+        } else if let Some(persistence) = &self.persistence {
+            let persistence_clone = Arc::clone(persistence);
+            let dead_letters = Arc::clone(&self.dead_letters);
+            let entry_clone = entry.clone();
+            tokio::spawn(async move {
+                if let Err(e) = persistence_clone.save_audit_log(&entry_clone).await {
+                    eprintln!("保存审计日志失败: {}", e);
+                    dead_letters.push(DeadLetterWrite::AuditLog(entry_clone), &e);
+                }
+            });
+        }
+
+        // 配置了 webhook 时推送给外部系统
+        if let Some(webhook) = &self.audit_webhook {
+            webhook.send(&entry);
+        }
+    }
+
+    // 在后台保存密钥元数据，失败时按指数退避重试；重试次数用尽后标记为待补写，由 reconcile_dirty_keys 处理，
+    // 同时放入死信存储
+    fn persist_metadata_in_background(&self, metadata: KeyMetadata) {
+        let Some(persistence) = &self.persistence else { return };
+        let persistence = Arc::clone(persistence);
+        let dirty_keys = Arc::clone(&self.dirty_keys);
+        let dead_letters = Arc::clone(&self.dead_letters);
+        let persisting = Arc::clone(&self.persisting);
+        *persisting.lock().unwrap().entry(metadata.id.clone()).or_insert(0) += 1;
+        tokio::spawn(async move {
+            let key_id = metadata.id.clone();
+            save_metadata_with_retry(persistence.as_ref(), metadata, &dirty_keys, &dead_letters).await;
+
+            let mut persisting = persisting.lock().unwrap();
+            if let Some(count) = persisting.get_mut(&key_id) {
+                *count -= 1;
+                if *count == 0 {
+                    persisting.remove(&key_id);
+                }
+            }
+        });
+    }
+
+    /// 内存中的密钥数超过 max_in_memory_keys 时，淘汰最久未使用的密钥
+    ///
+    /// 被淘汰的密钥仍在持久化存储中，命令用到时会重新加载；元数据尚未写入存储的密钥不会被淘汰。
+    /// 只作用于未删除的密钥，没有配置持久化存储时不淘汰。返回淘汰的数量
+    pub fn evict_keys(&self) -> usize {
+        let Some(max) = self.max_in_memory_keys else { return 0 };
+        if self.persistence.is_none() {
+            return 0;
+        }
+
+        let mut keys = self.keys.lock().unwrap();
+        if keys.len() <= max {
+            return 0;
+        }
+        let mut key_access = self.key_access.lock().unwrap();
+        // 还没有使用记录的是新加入的密钥，按最近使用处理
+        for key_id in keys.keys() {
+            key_access.entry(key_id.clone()).or_insert_with(|| self.access_counter.fetch_add(1, Ordering::SeqCst));
+        }
+        key_access.retain(|key_id, _| keys.contains_key(key_id));
+
+        // 签名命令结果的密钥每个命令都会用到，不淘汰
+        let signing_key = self.response_signing_key.lock().unwrap().clone();
+        let mut candidates: Vec<(u64, String)> = {
+            let dirty_keys = self.dirty_keys.lock().unwrap();
+            let persisting = self.persisting.lock().unwrap();
+            key_access.iter()
+                .filter(|(key_id, _)| {
+                    !dirty_keys.contains(*key_id) && !persisting.contains_key(*key_id) && signing_key.as_ref() != Some(*key_id)
+                })
+                .map(|(key_id, access)| (*access, key_id.clone()))
+                .collect()
+        };
+        candidates.sort();
+
+        let excess = keys.len() - max;
+        let mut evicted = 0;
+        for (_, key_id) in candidates {
+            if evicted == excess {
+                break;
+            }
+            // 使用时间还没写入存储的密钥先在后台补写，写完后的下一轮再淘汰
+            if self.unpersisted_usage.lock().unwrap().remove(&key_id) {
+                if let Some(metadata) = keys.get(&key_id) {
+                    self.persist_metadata_in_background(metadata.clone());
+                }
+                continue;
+            }
+            keys.remove(&key_id);
+            key_access.remove(&key_id);
+            evicted += 1;
+        }
+        evicted
+    }
+
+    // 记录命令使用的密钥；密钥已被淘汰时从持久化存储重新加载
+    async fn ensure_key_loaded(&self, params: &HashMap<String, String>) {
+        let Some(key_id) = params.get("key_id").filter(|key_id| !key_id.is_empty()) else { return };
+        let key_id = self.resolve_key_id(key_id);
+        self.load_key(&key_id).await;
+    }
+
+    // 记录密钥的使用；密钥已被淘汰时从持久化存储重新加载，命令用到的每个密钥都要先经过这里
+    //
+    // 存储中也没有时由命令本身报告密钥不存在
+    async fn load_key(&self, key_id: &str) {
+        if self.max_in_memory_keys.is_none() {
+            return;
+        }
+        self.key_access.lock().unwrap().insert(key_id.to_string(), self.access_counter.fetch_add(1, Ordering::SeqCst));
+
+        let Some(persistence) = &self.persistence else { return };
+        if self.keys.lock().unwrap().contains_key(key_id) || self.deleted_keys.lock().unwrap().contains_key(key_id) {
+            return;
+        }
+        if let Ok(metadata) = persistence.load_key_metadata(key_id).await {
+            self.cache_loaded_key(metadata);
+        }
+    }
+
+    // 将满足条件且已被淘汰的密钥从持久化存储加载回内存，扫描内存中密钥的批量命令在扫描前调用
+    async fn load_matching_keys(&self, query: &KeyQuery) -> Result<(), String> {
+        if self.max_in_memory_keys.is_none() {
+            return Ok(());
+        }
+        let Some(persistence) = &self.persistence else { return Ok(()) };
+
+        for metadata in persistence.query_keys(query).await? {
+            if metadata.deleted_at.is_none() {
+                self.cache_loaded_key(metadata);
+            }
+        }
+        Ok(())
+    }
+
+    // 放回从持久化存储读出的密钥，内存中已有的版本优先
+    fn cache_loaded_key(&self, metadata: KeyMetadata) {
+        if metadata.deleted_at.is_some() {
+            self.deleted_keys.lock().unwrap().entry(metadata.id.clone()).or_insert(metadata);
+        } else {
+            self.keys.lock().unwrap().entry(metadata.id.clone()).or_insert(metadata);
+        }
+    }
+
+    /// 等待补写元数据的密钥ID
+    pub fn dirty_keys(&self) -> Vec<String> {
+        let mut key_ids: Vec<String> = self.dirty_keys.lock().unwrap().iter().cloned().collect();
+        key_ids.sort();
+        key_ids
+    }
+
+    /// 将之前未能写入的密钥元数据重新写入持久化存储，返回补写成功的数量
+    ///
+    /// 写入的是内存中当前的元数据；密钥已被彻底删除时直接移出待补写列表
+    pub async fn reconcile_dirty_keys(&self) -> usize {
+        let Some(persistence) = &self.persistence else { return 0 };
+
+        let mut reconciled = 0;
+        for key_id in self.dirty_keys() {
+            let metadata = self.keys.lock().unwrap().get(&key_id).cloned()
+                .or_else(|| self.deleted_keys.lock().unwrap().get(&key_id).cloned());
+            let Some(metadata) = metadata else {
+                self.dirty_keys.lock().unwrap().remove(&key_id);
+                continue;
+            };
+
+            match persistence.save_key_metadata(&metadata).await {
+                Ok(()) => {
+                    self.dirty_keys.lock().unwrap().remove(&key_id);
+                    self.dead_letters.remove_key_metadata(&key_id);
+                    reconciled += 1;
+                }
+                Err(e) => eprintln!("补写密钥元数据失败: {}: {}", key_id, e),
+            }
+        }
+        reconciled
+    }
+
+    /// 对比内存中的密钥与持久化存储中的密钥元数据并消除差异
+    ///
+    /// 只在内存中的密钥写入存储，只在存储中的密钥载入内存；两侧都有但版本或更新时间不一致时，
+    /// 以版本较高（版本相同时更新时间较晚）的一侧为准。返回处理的结果和发现的差异
+    pub async fn reconcile(&self, user: &str) -> Result<serde_json::Value, String> {
+        let persistence = match &self.persistence {
+            Some(persistence) => Arc::clone(persistence),
+            None => return Err("未配置持久化存储".to_string()),
+        };
+
+        let in_memory: Vec<KeyMetadata> = {
+            let keys = self.keys.lock().unwrap();
+            let deleted_keys = self.deleted_keys.lock().unwrap();
+            keys.values().chain(deleted_keys.values()).cloned().collect()
+        };
+        let stored = persistence.list_key_metadata(None).await?;
+        let diff = keystore_diff::diff_metadata(in_memory.clone(), stored.clone());
+
+        let memory_newer = |drift: &KeyDrift| {
+            (drift.local_version, drift.local_updated_at) > (drift.other_version, drift.other_updated_at)
+        };
+        let to_write: Vec<&String> = diff.only_local.iter()
+            .chain(diff.changed.iter().filter(|drift| memory_newer(drift)).map(|drift| &drift.id))
+            .collect();
+        let to_load: Vec<&String> = diff.only_other.iter()
+            .chain(diff.changed.iter().filter(|drift| !memory_newer(drift)).map(|drift| &drift.id))
+            .collect();
+
+        let mut written = Vec::new();
+        let mut failed = Vec::new();
+        for key_id in to_write {
+            let Some(metadata) = in_memory.iter().find(|metadata| &metadata.id == key_id) else { continue };
+            match persistence.save_key_metadata(metadata).await {
+                Ok(()) => {
+                    self.dirty_keys.lock().unwrap().remove(key_id);
+                    self.dead_letters.remove_key_metadata(key_id);
+                    written.push(key_id.clone());
+                }
+                Err(e) => failed.push(serde_json::json!({ "id": key_id, "error": e })),
+            }
+        }
+
+        let mut loaded = Vec::new();
+        {
+            let mut keys = self.keys.lock().unwrap();
+            let mut deleted_keys = self.deleted_keys.lock().unwrap();
+            for key_id in to_load {
+                let Some(metadata) = stored.iter().find(|metadata| &metadata.id == key_id) else { continue };
+                keys.remove(key_id);
+                deleted_keys.remove(key_id);
+                if metadata.deleted_at.is_some() {
+                    deleted_keys.insert(key_id.clone(), metadata.clone());
+                } else {
+                    keys.insert(key_id.clone(), metadata.clone());
+                }
+                loaded.push(key_id.clone());
+            }
+        }
+
+        let details = format!(
+            "Reconciled with persistence: {} written, {} loaded, {} version mismatches",
+            written.len(), loaded.len(), diff.changed.len()
+        );
+        if failed.is_empty() {
+            self.add_audit_log(AuditLogEntry::new("RECONCILE_KEYS".to_string(), user.to_string(), None, details, true));
+        } else {
+            self.add_audit_log(AuditLogEntry::with_error(
+                "RECONCILE_KEYS".to_string(),
+                user.to_string(),
+                None,
+                details,
+                format!("Failed to write {} keys", failed.len()),
+            ));
+        }
+
+        Ok(serde_json::json!({
+            "only_memory": diff.only_local,
+            "only_persistence": diff.only_other,
+            "changed": diff.changed,
+            "written": written,
+            "loaded": loaded,
+            "failed": failed,
+        }))
+    }
+
+    /// 死信存储中重试用尽、尚未写入的持久化写入
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.list()
+    }
+
+    /// 重新执行死信存储中的写入，成功的从死信存储中移除
+    ///
+    /// 密钥元数据写入死信中和内存中版本较高（版本相同时更新时间较晚）的一份，死信中的较新时同时更新内存，
+    /// 例如插件重启后内存中只有存储里的旧版本。返回重放成功和仍然失败的死信
+    pub async fn replay_dead_letters(&self, user: &str) -> Result<serde_json::Value, String> {
+        let persistence = match &self.persistence {
+            Some(persistence) => Arc::clone(persistence),
+            None => return Err("未配置持久化存储".to_string()),
+        };
+
+        let mut replayed = Vec::new();
+        let mut failed = Vec::new();
+        for letter in self.dead_letters.list() {
+            let result = match &letter.write {
+                DeadLetterWrite::KeyMetadata(metadata) => {
+                    let current = self.keys.lock().unwrap().get(&metadata.id).cloned()
+                        .or_else(|| self.deleted_keys.lock().unwrap().get(&metadata.id).cloned());
+                    let letter_newer = current.as_ref().is_none_or(|current| {
+                        (metadata.version, metadata.updated_at) > (current.version, current.updated_at)
+                    });
+                    let latest = if letter_newer { metadata } else { current.as_ref().unwrap_or(metadata) };
+
+                    let result = persistence.save_key_metadata(latest).await;
+                    if result.is_ok() {
+                        self.dirty_keys.lock().unwrap().remove(&metadata.id);
+                        if letter_newer {
+                            self.keys.lock().unwrap().remove(&metadata.id);
+                            self.deleted_keys.lock().unwrap().remove(&metadata.id);
+                            if metadata.deleted_at.is_some() {
+                                self.deleted_keys.lock().unwrap().insert(metadata.id.clone(), metadata.clone());
+                            } else {
+                                self.keys.lock().unwrap().insert(metadata.id.clone(), metadata.clone());
+                            }
+                        }
+                    }
+                    result
+                }
+                DeadLetterWrite::AuditLog(entry) => persistence.save_audit_log(entry).await,
+            };
+
+            match result {
+                Ok(()) => replayed.push(letter.id.clone()),
+                Err(e) => failed.push(serde_json::json!({
+                    "id": letter.id,
+                    "kind": letter.write.kind(),
+                    "target_id": letter.write.target_id(),
+                    "error": e,
+                })),
+            }
+        }
+
+        self.dead_letters.remove(&replayed);
+
+        let details = format!("Replayed {} dead letters", replayed.len());
+        if failed.is_empty() {
+            self.add_audit_log(AuditLogEntry::new("REPLAY_DEAD_LETTERS".to_string(), user.to_string(), None, details, true));
+        } else {
+            self.add_audit_log(AuditLogEntry::with_error(
+                "REPLAY_DEAD_LETTERS".to_string(),
+                user.to_string(),
+                None,
+                details,
+                format!("Failed to replay {} dead letters", failed.len()),
+            ));
+        }
+
+        Ok(serde_json::json!({
+            "replayed": replayed,
+            "failed": failed,
+            "remaining": self.dead_letters.len(),
+        }))
+    }
+
+    async fn create_key(
+        &self,
+        name: String,
+        description: String,
+        key_type: KeyType,
+        algorithm: KeyAlgorithm,
+        owner: String,
+        requires_approval: bool,
+        tags: Option<HashMap<String, String>>,
+        expiration_date: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<KeyMetadata, String> {
+        // 检查算法与密钥类型是否匹配
+        if !algorithm.supports_key_type(&key_type) {
+            return Err(format!(
+                "Algorithm {} is not compatible with key type {}",
+                algorithm.to_string(),
+                key_type.to_string()
+            ));
+        }
+
+        // 创建密钥元数据
+        let mut metadata = self.new_metadata(
+            name,
+            description,
+            key_type,
+            algorithm.clone(), // 在这里克隆 algorithm
+            owner.clone(),
+            requires_approval,
+        );
+    
+        // 设置标签
+        if let Some(t) = tags {
+            metadata.tags = t;
+        }
+    
+        // 设置过期日期
+        metadata.expiration_date = expiration_date;
+    
+        // 生成实际密钥
+        let security_module = self.security_module_by_id(&metadata.security_module_id)?;
+        let key_data = security_module.generate_key(algorithm).await?;
+    
+        // 存储密钥
+        security_module.store_key(&metadata.id, &key_data).await?;
+    
+        // 保存元数据
+        let mut keys = self.keys.lock().unwrap();
+        keys.insert(metadata.id.clone(), metadata.clone());
+        
+        // 如果有持久化存储，则保存密钥元数据
+        self.persist_metadata_in_background(metadata.clone());
+    
+        // 记录审计日志
+        self.add_audit_log(AuditLogEntry::new(
+            "CREATE_KEY".to_string(),
+            owner,
+            Some(metadata.id.clone()),
+            format!("Created key: {}", metadata.name),
+            true,
+        ));
+    
+        Ok(metadata)
+    }
+
+    // 创建非对称密钥对，私钥和公钥各有一条元数据，通过 pair_id 标签关联
+    //
+    // 公钥（DER 编码的 SubjectPublicKeyInfo）以 base64 形式保存在公钥元数据的 public_key 标签中，
+    // 不依赖私钥是否可导出
+    async fn create_key_pair(
+        &self,
+        name: String,
+        description: String,
+        algorithm: KeyAlgorithm,
+        owner: String,
+        requires_approval: bool,
+        tags: Option<HashMap<String, String>>,
+    ) -> Result<(KeyMetadata, KeyMetadata), String> {
+        if !algorithm.supports_key_type(&KeyType::AsymmetricPrivate) {
+            return Err(format!("Algorithm {} does not support key pairs", algorithm.to_string()));
+        }
+
+        let pair_id = self.random.uuid();
+        let tags = tags.unwrap_or_default();
+
+        let mut private_metadata = self.new_metadata(
+            name.clone(),
+            description.clone(),
+            KeyType::AsymmetricPrivate,
+            algorithm.clone(),
+            owner.clone(),
+            requires_approval,
+        );
+        private_metadata.tags = tags.clone();
+        private_metadata.tags.insert("pair_id".to_string(), pair_id.clone());
+
+        // 生成并存储私钥
+        let security_module = self.security_module_by_id(&private_metadata.security_module_id)?;
+        let key_data = security_module.generate_key(algorithm.clone()).await?;
+        security_module.store_key(&private_metadata.id, &key_data).await?;
+
+        let public_key = match security_module.get_public_key(&private_metadata.id, PublicKeyFormat::Der).await {
+            Ok(public_key) => public_key,
+            Err(e) => {
+                // 公钥提取失败时不保留孤立的私钥
+                let _ = security_module.delete_key(&private_metadata.id).await;
+                return Err(e);
+            }
+        };
+
+        // 公钥不需要审批即可使用
+        let mut public_metadata = self.new_metadata(
+            name,
+            description,
+            KeyType::AsymmetricPublic,
+            algorithm,
+            owner.clone(),
+            false,
+        );
+        public_metadata.tags = tags;
+        public_metadata.tags.insert("pair_id".to_string(), pair_id.clone());
+        public_metadata.tags.insert("public_key".to_string(), BASE64.encode(&public_key));
+
+        // 保存元数据
+        {
+            let mut keys = self.keys.lock().unwrap();
+            keys.insert(private_metadata.id.clone(), private_metadata.clone());
+            keys.insert(public_metadata.id.clone(), public_metadata.clone());
+        }
+
+        // 如果有持久化存储，则保存两条密钥元数据
+        self.persist_metadata_in_background(private_metadata.clone());
+        self.persist_metadata_in_background(public_metadata.clone());
+
+        // 记录审计日志
+        self.add_audit_log(AuditLogEntry::new(
+            "CREATE_KEY_PAIR".to_string(),
+            owner,
+            Some(private_metadata.id.clone()),
+            format!(
+                "Created key pair: {}, public key: {}, pair id: {}",
+                private_metadata.name, public_metadata.id, pair_id
+            ),
+            true,
+        ));
+
+        Ok((private_metadata, public_metadata))
+    }
+
+    // 从口令派生对称密钥，盐以 base64 形式保存在标签中以便重新派生
+    async fn derive_key(&self, request: DeriveKeyRequest, owner: String) -> Result<KeyMetadata, String> {
+        let DeriveKeyRequest { name, description, password, key_type, salt, kdf_params: params, tags } = request;
+        if !matches!(key_type, KeyType::Symmetric | KeyType::Password) {
+            return Err(format!("Invalid key_type for derived key: {}", key_type.to_string()));
+        }
+
+        let salt = match salt {
+            Some(salt) => salt,
+            None => {
+                let mut salt = vec![0u8; KDF_SALT_LEN];
+                self.random.fill(&mut salt);
+                salt
+            }
+        };
+
+        let mut metadata = self.new_metadata(
+            name,
+            description,
+            key_type,
+            KeyAlgorithm::AES256,
+            owner.clone(),
+            false,
+        );
+
+        metadata.tags = tags;
+        metadata.tags.insert("kdf".to_string(), "argon2id".to_string());
+        metadata.tags.insert("kdf_salt".to_string(), BASE64.encode(&salt));
+        // 保存派生时使用的参数，默认值变化后仍能派生出相同的密钥
+        metadata.tags.insert("kdf_memory_kib".to_string(), params.memory_kib.to_string());
+        metadata.tags.insert("kdf_iterations".to_string(), params.iterations.to_string());
+        metadata.tags.insert("kdf_parallelism".to_string(), params.parallelism.to_string());
+
+        // 派生密钥
+        let security_module = self.security_module_by_id(&metadata.security_module_id)?;
+        let key_data = security_module
+            .derive_key(password.as_bytes(), &salt, KeyAlgorithm::AES256, &params)
+            .await?;
+
+        // 存储密钥
+        security_module.store_key(&metadata.id, &key_data).await?;
+
+        // 保存元数据
+        {
+            let mut keys = self.keys.lock().unwrap();
+            keys.insert(metadata.id.clone(), metadata.clone());
+        }
+
+        // 如果有持久化存储，则保存密钥元数据
+        self.persist_metadata_in_background(metadata.clone());
+
+        // 记录审计日志，不记录口令
+        self.add_audit_log(AuditLogEntry::new(
+            "DERIVE_KEY".to_string(),
+            owner,
+            Some(metadata.id.clone()),
+            format!("Derived key: {}", metadata.name),
+            true,
+        ));
+
+        Ok(metadata)
+    }
+
+    // 导出公钥，私钥从安全模块计算，公钥一半直接使用保存的公钥
+    async fn get_public_key(&self, key_id: &str, format: PublicKeyFormat) -> Result<Vec<u8>, String> {
+        let metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        match metadata.key_type {
+            KeyType::AsymmetricPrivate => {
+                self.security_module_by_id(&metadata.security_module_id)?.get_public_key(key_id, format).await
+            }
+            KeyType::AsymmetricPublic => {
+                let public_key = metadata.tags.get("public_key")
+                    .ok_or_else(|| format!("Public key material missing for key: {}", key_id))?;
+                let spki_der = BASE64.decode(public_key)
+                    .map_err(|e| format!("Invalid public key material: {}", e))?;
+                format.encode(&spki_der)
+            }
+            _ => Err(format!(
+                "Key {} is {} and has no public key",
+                key_id,
+                metadata.key_type.to_string()
+            )),
+        }
+    }
+
+    // 检查密钥能否用于签发证书或证书请求：必须是可用的非对称私钥
+    fn signing_key(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        let metadata = self.active_key(key_id)?;
+
+        if metadata.key_type != KeyType::AsymmetricPrivate {
+            return Err(format!(
+                "Key {} is {}, an asymmetric private key is required",
+                key_id,
+                metadata.key_type.to_string()
+            ));
+        }
+
+        Ok(metadata)
+    }
+
+    // 生成 PEM 编码的 PKCS#10 证书请求，由安全模块中的私钥签名
+    async fn generate_csr(&self, key_id: &str, subject: &SubjectName, user: &str) -> Result<String, String> {
+        let metadata = self.signing_key(key_id)?;
+        let signature_algorithm = x509::signature_algorithm(&metadata.algorithm)?;
+
+        let security_module = self.security_module_by_id(&metadata.security_module_id)?;
+        let spki_der = security_module.get_public_key(key_id, PublicKeyFormat::Der).await?;
+        let request_info = x509::certification_request_info(subject, &spki_der);
+        let signature = security_module.sign_data(key_id, &request_info).await?;
+        self.mark_key_used(key_id);
+        let csr = x509::to_pem("CERTIFICATE REQUEST", &x509::signed(&request_info, &signature_algorithm, &signature))?;
+
+        // 记录审计日志
+        self.add_audit_log(AuditLogEntry::new(
+            "GENERATE_CSR".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Generated CSR for CN={}", subject.common_name),
+            true,
+        ));
+
+        Ok(csr)
+    }
+
+    // 生成自签名证书，证书以 PEM 形式保存在密钥元数据的 certificate 标签中
+    async fn generate_self_signed_cert(
+        &self,
+        key_id: &str,
+        subject: &SubjectName,
+        not_before: chrono::DateTime<chrono::Utc>,
+        not_after: chrono::DateTime<chrono::Utc>,
+        user: &str,
+    ) -> Result<String, String> {
+        let metadata = self.signing_key(key_id)?;
+        let signature_algorithm = x509::signature_algorithm(&metadata.algorithm)?;
+
+        // 随机序列号，最高位清零保证为正数
+        let mut serial = [0u8; 16];
+        self.random.fill(&mut serial);
+        serial[0] &= 0x7f;
+
+        let security_module = self.security_module_by_id(&metadata.security_module_id)?;
+        let spki_der = security_module.get_public_key(key_id, PublicKeyFormat::Der).await?;
+        let tbs = x509::tbs_certificate(&serial, &signature_algorithm, subject, not_before, not_after, &spki_der)?;
+        let signature = security_module.sign_data(key_id, &tbs).await?;
+        self.mark_key_used(key_id);
+        let certificate = x509::to_pem("CERTIFICATE", &x509::signed(&tbs, &signature_algorithm, &signature))?;
+
+        // 保存证书
+        let updated = {
+            let mut keys = self.keys.lock().unwrap();
+            let metadata = keys.get_mut(key_id).ok_or_else(|| "Key not found".to_string())?;
+            metadata.tags.insert("certificate".to_string(), certificate.clone());
+            metadata.updated_at = self.clock.now();
+            metadata.clone()
+        };
+
+        // 如果有持久化存储，则更新密钥元数据
+        self.persist_metadata_in_background(updated);
+
+        // 记录审计日志
+        self.add_audit_log(AuditLogEntry::new(
+            "GENERATE_CERTIFICATE".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!(
+                "Generated self-signed certificate for CN={}, valid {} to {}",
+                subject.common_name,
+                not_before.to_rfc3339(),
+                not_after.to_rfc3339()
+            ),
+            true,
+        ));
+
+        Ok(certificate)
+    }
+
+    async fn rotate_key(&self, key_id: &str, user: &str) -> Result<KeyMetadata, String> {
+        // 检查密钥是否存在
+        let metadata = self.keys.lock().unwrap()
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| "Key not found".to_string())?;
+
+        // 检查密钥状态
+        metadata.ensure_usable_at(self.clock.now())?;
+
+        // 检查是否需要审批
+        if metadata.requires_approval {
+            let operation_id = self.random.uuid();
+            let mut approvals = self.pending_approvals.lock().unwrap();
+            approvals.insert(operation_id.clone(), (key_id.to_string(), "ROTATE".to_string()));
+
+            // 记录审计日志
+            self.add_audit_log(AuditLogEntry::new(
+                "REQUEST_KEY_ROTATION".to_string(),
+                user.to_string(),
+                Some(key_id.to_string()),
+                format!("Requested key rotation, approval ID: {}", operation_id),
+                true,
+            ));
+
+            return Err(format!("Key rotation requires approval. Approval ID: {}", operation_id));
+        }
+
+        // 非对称密钥的公私钥需要成对替换，暂不支持轮换
+        if metadata.key_type.is_asymmetric() {
+            return Err("Rotating asymmetric keys is not supported".to_string());
+        }
+
+        // 在原安全模块中生成新密钥
+        let security_module = self.security_module_by_id(&metadata.security_module_id)?;
+        let key_data = security_module.generate_key(metadata.algorithm.clone()).await?;
+
+        // 存储新密钥
+        security_module.store_key(key_id, &key_data).await?;
+
+        // 更新元数据
+        let metadata = {
+            let mut keys = self.keys.lock().unwrap();
+            let metadata = keys.get_mut(key_id).ok_or_else(|| "Key not found".to_string())?;
+            metadata.updated_at = self.clock.now();
+            metadata.version += 1;
+            metadata.clone()
+        };
+
+        // 如果有持久化存储，则更新密钥元数据
+        self.persist_metadata_in_background(metadata.clone());
+
+        // 记录审计日志
+        self.add_audit_log(AuditLogEntry::new(
+            "ROTATE_KEY".to_string(),
+            user.to_string(),
+            Some(key_id.to_string()),
+            format!("Rotated key: {}", metadata.name),
+            true,
+        ));
+
+        Ok(metadata)
+    }
+
+    // 主密钥轮换后逐个重新包装密钥材料，返回 (重新包装数, 无需处理数, 失败的密钥)
+    //
+    // 每个密钥的重新包装是独立且幂等的，中途失败后再次执行会跳过已完成的密钥
+    async fn rewrap_all(&self, user: &str, progress: Option<&ProgressSink>) -> (usize, usize, Vec<(String, String)>) {
+        // 公钥和已销毁的密钥在安全模块中没有材料
+        let mut key_ids: Vec<String> = {
+            let keys = self.keys.lock().unwrap();
+            keys.values()
+                .filter(|metadata| metadata.key_type != KeyType::AsymmetricPublic && metadata.status != KeyStatus::Destroyed)
+                .map(|metadata| metadata.id.clone())
+                .collect()
+        };
+        key_ids.sort();
+
+        self.add_audit_log(AuditLogEntry::new(
+            "REWRAP_KEYS".to_string(),
+            user.to_string(),
+            None,
+            format!("Started rewrapping {} keys", key_ids.len()),
+            true,
+        ));
+
+        report_progress(progress, 0.0, "rewrapping");
+
+        let mut rewrapped = 0;
+        let mut unchanged = 0;
+        let mut failed = Vec::new();
+        for (index, key_id) in key_ids.iter().enumerate() {
+            let rewrapped_key = match self.key_security_module(key_id) {
+                Ok(security_module) => security_module.rewrap_key(key_id).await,
+                Err(e) => Err(e),
+            };
+            match rewrapped_key {
+                Ok(true) => rewrapped += 1,
+                Ok(false) => unchanged += 1,
+                Err(e) => {
+                    self.add_audit_log(AuditLogEntry::with_error(
+                        "REWRAP_KEY".to_string(),
+                        user.to_string(),
+                        Some(key_id.clone()),
+                        "Rewrap failed".to_string(),
+                        e.clone(),
+                    ));
+                    failed.push((key_id.clone(), e));
+                }
+            }
+
+            let processed = index + 1;
+            // 最后一个密钥的进度由最终结果给出
+            if processed < key_ids.len() {
+                report_progress(progress, processed as f32 / key_ids.len() as f32, "rewrapping");
+            }
+            if processed % REWRAP_PROGRESS_INTERVAL == 0 && processed < key_ids.len() {
+                self.add_audit_log(AuditLogEntry::new(
+                    "REWRAP_KEYS".to_string(),
+                    user.to_string(),
+                    None,
+                    format!("Rewrap progress: {}/{}, last key: {}", processed, key_ids.len(), key_id),
+                    true,
+                ));
+            }
+        }
+
+        self.add_audit_log(AuditLogEntry::new(
+            "REWRAP_KEYS".to_string(),
+            user.to_string(),
+            None,
+            format!(
+                "Finished rewrapping keys: {} rewrapped, {} already current, {} failed",
+                rewrapped, unchanged, failed.len()
+            ),
+            failed.is_empty(),
+        ));
+
+        (rewrapped, unchanged, failed)
+    }
+
+    // 将当前租户中源算法的启用密钥迁移到目标算法，返回逐个密钥的迁移结果
+    //
+    // 只迁移用户有 rotate_key 权限的密钥；需要审批的密钥只登记审批请求，不做迁移；公钥随所属私钥一起迁移
+    async fn migrate_algorithm(
+        &self,
+        source: KeyAlgorithm,
+        target: KeyAlgorithm,
+        user: &str,
+        progress: Option<&ProgressSink>,
+    ) -> Vec<serde_json::Value> {
+        let tenant = current_tenant();
+        let mut candidates: Vec<KeyMetadata> = self.keys.lock().unwrap()
+            .values()
+            .filter(|metadata| {
+                metadata.tenant == tenant
+                    && metadata.algorithm == source
+                    && metadata.status == KeyStatus::Active
+                    && metadata.key_type != KeyType::AsymmetricPublic
+                    && metadata.can_access(user, "rotate_key")
+            })
+            .cloned()
+            .collect();
+        candidates.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+
+        report_progress(progress, 0.0, "migrating");
+
+        let mut summary = Vec::new();
+        for (index, metadata) in candidates.iter().enumerate() {
+            let entry = if metadata.requires_approval {
+                let approval_id = self.random.uuid();
+                self.pending_approvals.lock().unwrap()
+                    .insert(approval_id.clone(), (metadata.id.clone(), "MIGRATE_ALGORITHM".to_string()));
+
+                self.add_audit_log(AuditLogEntry::new(
+                    "REQUEST_KEY_MIGRATION".to_string(),
+                    user.to_string(),
+                    Some(metadata.id.clone()),
+                    format!("Requested migration to {}, approval ID: {}", target.to_string(), approval_id),
+                    true,
+                ));
+
+                serde_json::json!({
+                    "key_id": metadata.id,
+                    "name": metadata.name,
+                    "status": "pending_approval",
+                    "approval_id": approval_id,
+                })
+            } else {
+                match self.migrate_key(metadata, target.clone(), user).await {
+                    Ok(new_key_id) => serde_json::json!({
+                        "key_id": metadata.id,
+                        "name": metadata.name,
+                        "status": "migrated",
+                        "new_key_id": new_key_id,
+                    }),
+                    Err(e) => {
+                        self.add_audit_log(AuditLogEntry::with_error(
+                            "MIGRATE_KEY".to_string(),
+                            user.to_string(),
+                            Some(metadata.id.clone()),
+                            format!("Migration to {} failed", target.to_string()),
+                            e.clone(),
+                        ));
+                        serde_json::json!({
+                            "key_id": metadata.id,
+                            "name": metadata.name,
+                            "status": "failed",
+                            "error": e,
+                        })
+                    }
+                }
+            };
+            summary.push(entry);
+
+            // 最后一个密钥的进度由最终结果给出
+            if index + 1 < candidates.len() {
+                report_progress(progress, (index + 1) as f32 / candidates.len() as f32, "migrating");
+            }
+        }
+
+        summary
+    }
+
+    /// 获取密钥对中的另一半：私钥返回公钥，公钥返回私钥
+    ///
+    /// 通过 pair_id 标签关联；不是非对称密钥、没有 pair_id 或另一半已被删除时返回 None。
+    /// 另一半已被淘汰时从持久化存储重新加载
+    pub async fn get_paired_key(&self, key_id: &str) -> Result<Option<KeyMetadata>, String> {
+        self.load_key(key_id).await;
+        let metadata = self.keys.lock().unwrap().get(key_id).cloned().ok_or_else(|| "Key not found".to_string())?;
+        let (Some(paired_type), Some(pair_id)) = (metadata.key_type.paired_type(), metadata.tags.get("pair_id")) else {
+            return Ok(None);
+        };
+
+        let query = KeyQuery::new()
+            .with_tenant(metadata.tenant.clone())
+            .with_key_type(paired_type.clone())
+            .with_tag("pair_id".to_string(), pair_id.clone());
+        self.load_matching_keys(&query).await?;
+
+        Ok(self.keys.lock().unwrap().values()
+            .find(|other| other.id != metadata.id && other.key_type == paired_type && other.tags.get("pair_id") == Some(pair_id))
+            .cloned())
+    }
+
+    /// 查询密钥剩余有效期
+    ///
+    /// 没有过期时间的密钥 seconds_remaining 为 null；已过期的密钥剩余秒数为零或负数
+    pub fn key_validity(&self, key_id: &str) -> Result<serde_json::Value, String> {
+        let expiration_date = self.keys.lock().unwrap().get(key_id)
+            .ok_or_else(|| "Key not found".to_string())?
+            .expiration_date;
+        let now = self.clock.now();
+
+        Ok(serde_json::json!({
+            "key_id": key_id,
+            "expires_at": expiration_date.as_ref().map(timestamp::format),
+            "seconds_remaining": expiration_date.map(|date| (date - now).num_seconds()),
+            "is_expired": expiration_date.is_some_and(|date| date <= now),
+        }))
+    }
+
+    // 为单个密钥生成目标算法的新密钥，复制元数据后将原密钥标记为待销毁，返回新密钥ID
+    //
+    // 新旧密钥通过 migrated_from / migrated_to 标签关联；密钥对迁移为新的密钥对，原公钥一并待销毁
+    async fn migrate_key(&self, metadata: &KeyMetadata, target: KeyAlgorithm, user: &str) -> Result<String, String> {
+        let mut tags = migrated_tags(&metadata.tags);
+        tags.insert("migrated_from".to_string(), metadata.id.clone());
+
+        let old_public_id = self.get_paired_key(&metadata.id).await?.map(|public_metadata| public_metadata.id);
+
+        // 新密钥与原密钥使用同一个安全模块
+        let create = async {
+            Ok::<_, String>(if old_public_id.is_some() {
+                self.create_key_pair(
+                    metadata.name.clone(),
+                    metadata.description.clone(),
+                    target.clone(),
+                    metadata.owner.clone(),
+                    metadata.requires_approval,
+                    Some(tags),
+                ).await?.0.id
+            } else {
+                self.create_key(
+                    metadata.name.clone(),
+                    metadata.description.clone(),
+                    metadata.key_type.clone(),
+                    target.clone(),
+                    metadata.owner.clone(),
+                    metadata.requires_approval,
+                    Some(tags),
+                    metadata.expiration_date,
+                ).await?.id
+            })
+        };
+        let new_key_id = REQUEST_SECURITY_MODULE.scope(metadata.security_module_id.clone(), create).await?;
+
+        let now = self.clock.now();
+        let mut changed = Vec::new();
+        {
+            let mut keys = self.keys.lock().unwrap();
+            // 创建密钥对时不能指定过期时间，这里补上
+            if let Some(new_metadata) = keys.get_mut(&new_key_id)
+                && new_metadata.expiration_date != metadata.expiration_date
+            {
+                new_metadata.expiration_date = metadata.expiration_date;
+                changed.push(new_metadata.clone());
+            }
+            for key_id in std::iter::once(&metadata.id).chain(old_public_id.as_ref()) {
+                if let Some(old_metadata) = keys.get_mut(key_id) {
+                    old_metadata.status = KeyStatus::PendingDestruction;
+                    old_metadata.updated_at = now;
+                    old_metadata.tags.insert("migrated_to".to_string(), new_key_id.clone());
+                    changed.push(old_metadata.clone());
+                }
+            }
+        }
+
+        // 如果有持久化存储，则更新密钥元数据；写入完成前不会被淘汰
+        for metadata in changed {
+            self.persist_metadata_in_background(metadata);
+        }
+
+        self.add_audit_log(AuditLogEntry::new(
+            "MIGRATE_KEY".to_string(),
+            user.to_string(),
+            Some(metadata.id.clone()),
+            format!(
+                "Migrated key {} from {} to {}, new key: {}",
+                metadata.name, metadata.algorithm.to_string(), target.to_string(), new_key_id
+            ),
+            true,
+        ));
+
+        Ok(new_key_id)
+    }
+
+    /// 轮换用于签名命令结果的密钥
+    ///
+    /// 生成新的密钥对并记录检查点：`[原密钥ID, 新密钥ID, 新公钥, 轮换时间]` 组成的 JSON 数组，
+    /// 由新密钥和仍处于启用状态的原密钥分别签名，验证方可以据此确认新密钥由原密钥授权。
+    /// 原密钥标记为 retired，不再用于签名，但保留在安全模块中用于验证轮换前的签名。
+    /// 命令结果的 signing_key_id 指明验证时应使用的密钥。未指定算法时沿用原密钥的算法
+    pub async fn rotate_audit_key(&self, algorithm: Option<KeyAlgorithm>, user: &str) -> Result<serde_json::Value, String> {
+        let previous_key_id = self.response_signing_key.lock().unwrap().clone();
+        let previous = previous_key_id.as_ref().and_then(|key_id| self.keys.lock().unwrap().get(key_id).cloned());
+        let algorithm = match (algorithm, &previous) {
+            (Some(algorithm), _) => algorithm,
+            (None, Some(previous)) => previous.algorithm.clone(),
+            (None, None) => KeyAlgorithm::ECDSA,
+        };
+
+        // 检查点签名完成前新密钥标记为 pending，失败时不会被当作签名密钥加载
+        let tags = HashMap::from([(AUDIT_SIGNING_TAG.to_string(), "pending".to_string())]);
+        let (private_metadata, public_metadata) = self.create_key_pair(
+            "audit-signing-key".to_string(),
+            "Key used to sign command results".to_string(),
+            algorithm,
+            user.to_string(),
+            false,
+            Some(tags),
+        ).await?;
+        let new_key_id = private_metadata.id.clone();
+        let public_key = public_metadata.tags.get("public_key").cloned().unwrap_or_default();
+
+        let rotated_at = self.clock.now();
+        let checkpoint = canonical_json::to_vec(&(&previous_key_id, &new_key_id, &public_key, timestamp::format(&rotated_at)))?;
+        let signature = self.key_security_module(&new_key_id)?.sign_data(&new_key_id, &checkpoint).await?;
+        // 原密钥已不可用（如已泄露而被暂停）时不能为检查点签名，只记录关联关系
+        let previous_signature = match &previous {
+            Some(previous) if previous.is_usable_at(rotated_at) => {
+                Some(self.key_security_module(&previous.id)?.sign_data(&previous.id, &checkpoint).await?)
+            }
+            _ => None,
+        };
+
+        let mut changed = Vec::new();
+        {
+            let mut keys = self.keys.lock().unwrap();
+            if let Some(metadata) = keys.get_mut(&new_key_id) {
+                metadata.tags.insert(AUDIT_SIGNING_TAG.to_string(), "active".to_string());
+                metadata.tags.insert("checkpoint_signature".to_string(), BASE64.encode(&signature));
+                if let Some(key_id) = &previous_key_id {
+                    metadata.tags.insert("rotated_from".to_string(), key_id.clone());
+                }
+                if let Some(previous_signature) = &previous_signature {
+                    metadata.tags.insert("previous_checkpoint_signature".to_string(), BASE64.encode(previous_signature));
+                }
+                metadata.updated_at = rotated_at;
+                changed.push(metadata.clone());
+            }
+            if let Some(metadata) = previous_key_id.as_ref().and_then(|key_id| keys.get_mut(key_id)) {
+                metadata.tags.insert(AUDIT_SIGNING_TAG.to_string(), "retired".to_string());
+                metadata.tags.insert("rotated_to".to_string(), new_key_id.clone());
+                metadata.updated_at = rotated_at;
+                changed.push(metadata.clone());
+            }
+        }
+        *self.response_signing_key.lock().unwrap() = Some(new_key_id.clone());
+
+        // 如果有持久化存储，则更新密钥元数据
+        for metadata in changed {
+            self.persist_metadata_in_background(metadata);
+        }
+
+        self.add_audit_log(AuditLogEntry::new(
+            "ROTATE_AUDIT_KEY".to_string(),
+            user.to_string(),
+            Some(new_key_id.clone()),
+            format!(
+                "Rotated response signing key from {} to {}",
+                previous_key_id.as_deref().unwrap_or("none"),
+                new_key_id
+            ),
+            true,
+        ));
+
+        Ok(serde_json::json!({
+            "previous_key_id": previous_key_id,
+            "key_id": new_key_id,
+            "public_key_id": public_metadata.id,
+            "public_key": public_key,
+            "rotated_at": timestamp::format(&rotated_at),
+            "signature": BASE64.encode(&signature),
+            "previous_signature": previous_signature.map(|signature| BASE64.encode(&signature)),
+        }))
+    }
+
+    /// 用临时密钥检查安全模块是否可用
+    ///
+    /// 对每个算法依次执行 generate→store→retrieve→sign→verify→encrypt→decrypt→delete，
+    /// 返回每个算法各步的 pass/fail/skipped 结果。算法本身不支持的操作（非对称密钥加解密）
+    /// 和前一步失败后无法执行的操作记为 skipped；临时密钥存储成功后总会尝试删除
+    pub async fn selftest_security_module(&self, algorithms: &[KeyAlgorithm]) -> Vec<serde_json::Value> {
+        let mut report = Vec::new();
+        for algorithm in algorithms {
+            let steps = self.selftest_algorithm(algorithm).await;
+            report.push(serde_json::json!({
+                "algorithm": algorithm.to_string(),
+                "passed": !steps.failed,
+                "operations": steps.steps,
+            }));
+        }
+        report
+    }
+
+    async fn selftest_algorithm(&self, algorithm: &KeyAlgorithm) -> SelftestSteps {
+        const DATA: &[u8] = b"security module selftest";
+        let key_id = format!("selftest-{}", self.random.uuid());
+        let mut steps = SelftestSteps::default();
+
+        let key_data = match self.security_module.generate_key(algorithm.clone()).await {
+            Ok(key_data) => {
+                steps.record("generate", Ok(()));
+                key_data
+            }
+            Err(e) => {
+                steps.record("generate", Err(e));
+                for operation in ["store", "retrieve", "sign", "verify", "encrypt", "decrypt", "delete"] {
+                    steps.skip(operation, "generate failed");
+                }
+                return steps;
+            }
+        };
+
+        if !steps.record("store", self.security_module.store_key(&key_id, &key_data).await) {
+            for operation in ["retrieve", "sign", "verify", "encrypt", "decrypt", "delete"] {
+                steps.skip(operation, "store failed");
+            }
+            return steps;
+        }
+
+        let retrieved = self.security_module.retrieve_key(&key_id).await.and_then(|retrieved| {
+            if constant_time::eq(&retrieved, &key_data) { Ok(()) } else { Err("Retrieved key material does not match".to_string()) }
+        });
+        steps.record("retrieve", retrieved);
+
+        let signature = self.security_module.sign_data(&key_id, DATA).await;
+        match signature {
+            Ok(signature) => {
+                steps.record("sign", Ok(()));
+                let verified = self.security_module.verify_signature(&key_id, DATA, &signature).await.and_then(|valid| {
+                    if valid { Ok(()) } else { Err("Signature did not verify".to_string()) }
+                });
+                steps.record("verify", verified);
+            }
+            Err(e) => {
+                steps.record("sign", Err(e));
+                steps.skip("verify", "sign failed");
+            }
+        }
+
+        if algorithm.supports_key_type(&KeyType::Symmetric) {
+            match self.security_module.encrypt_data(&key_id, DATA, &[]).await {
+                Ok(ciphertext) => {
+                    steps.record("encrypt", Ok(()));
+                    let decrypted = self.security_module.decrypt_data(&key_id, &ciphertext, &[]).await.and_then(|plaintext| {
+                        if constant_time::eq(&plaintext, DATA) { Ok(()) } else { Err("Decrypted data does not match".to_string()) }
+                    });
+                    steps.record("decrypt", decrypted);
+                }
+                Err(e) => {
+                    steps.record("encrypt", Err(e));
+                    steps.skip("decrypt", "encrypt failed");
+                }
+            }
+        } else {
+            steps.skip("encrypt", "not supported for asymmetric keys");
+            steps.skip("decrypt", "not supported for asymmetric keys");
+        }
+
+        steps.record("delete", self.security_module.delete_key(&key_id).await);
+        steps
+    }
+
+    // 将 execute_command 方法改为公有
+    pub async fn execute_command(&self, command: &str, params: &HashMap<String, String>) -> CommandResult {
+        self.run_command(command, params, None).await
+    }
+
+    /// 进入排空状态：之后的新命令返回 `DRAINING` 错误，正在执行的命令继续执行完
+    ///
+    /// 同时将插件标记为未就绪。正在执行的命令全部结束后调用排空回调，之后可以安全停止插件。
+    /// 重复调用只返回当前状态。返回仍在执行的命令数
+    pub fn drain(&self, user: &str) -> usize {
+        if !self.draining.swap(true, Ordering::SeqCst) {
+            self.base.set_ready(false);
+            self.add_audit_log(AuditLogEntry::new(
+                "DRAIN".to_string(),
+                user.to_string(),
+                None,
+                format!("Draining with {} commands in flight", *self.in_flight.borrow()),
+                true,
+            ));
+
+            let mut in_flight = self.in_flight.subscribe();
+            let hooks = Arc::clone(&self.drained_hooks);
+            tokio::spawn(async move {
+                let drained = in_flight.wait_for(|count| *count == 0).await.is_ok();
+                if drained {
+                    println!("插件已排空，可以停止");
+                    let hooks = hooks.lock().unwrap().clone();
+                    for hook in hooks {
+                        hook();
+                    }
+                }
+            });
+        }
+        *self.in_flight.borrow()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// 处于排空状态且正在执行的命令已全部结束
+    pub fn is_drained(&self) -> bool {
+        self.is_draining() && *self.in_flight.borrow() == 0
+    }
+
+    /// 注册排空完成的回调
+    pub fn on_drained<F>(&self, callback: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.drained_hooks.lock().unwrap().push(Arc::new(callback));
+    }
+
+    /// 流式执行命令，先返回若干只带进度的中间结果，最后返回最终结果
+    ///
+    /// 批量命令（目前为 `rewrap_all`）在执行过程中给出进度，其他命令只返回最终结果。
+    /// 最终结果的 progress 为 1.0，只有最终结果会被签名
+    pub fn execute_command_stream<'a>(
+        &'a self,
+        command: &'a str,
+        params: &'a HashMap<String, String>,
+    ) -> BoxStream<'a, CommandResult> {
+        let (sender, receiver) = mpsc::unbounded();
+
+        // 最终结果与中间结果走同一个通道，保证排在所有中间结果之后；命令结束后通道关闭，流随之结束
+        let run = async move {
+            let mut result = self.run_command(command, params, Some(&sender)).await;
+            result.set_progress(Some(1.0));
+            result.set_stage(Some("completed".to_string()));
+            let _ = sender.unbounded_send(result);
+        };
+
+        stream::select(receiver, stream::once(run).filter_map(|_| async { None })).boxed()
+    }
+
+    async fn run_command(&self, command: &str, params: &HashMap<String, String>, progress: Option<&ProgressSink>) -> CommandResult {
+        // 先计入正在执行的命令再检查排空状态，排空开始后不会再有命令漏计；drain 本身不计入
+        let _in_flight = (command != "drain").then(|| InFlightGuard::enter(&self.in_flight));
+        if command != "drain" && self.is_draining() {
+            return CommandResult::new(false, String::new(), "Plugin is draining, not accepting new commands".to_string())
+                .with_error_code(ErrorCode::Draining);
+        }
+
+        // 命令在请求的租户下执行，创建的密钥、审计日志和密钥查找都限定在该租户
+        let tenant = params.get("tenant")
+            .filter(|tenant| !tenant.is_empty())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_TENANT.to_string());
+        // 命令创建的密钥保存在 security_module 参数指定的安全模块中
+        let security_module_id = params.get("security_module")
+            .filter(|id| !id.is_empty())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_SECURITY_MODULE_ID.to_string());
+        if let Err(e) = self.security_module_by_id(&security_module_id) {
+            return CommandResult::new(false, String::new(), e).with_error_code(ErrorCode::InvalidParams);
+        }
+        let command = REQUEST_SECURITY_MODULE.scope(security_module_id, self.run_tenant_command(command, params, progress));
+        REQUEST_TENANT.scope(tenant, command).await
+    }
+
+    async fn run_tenant_command(&self, command: &str, params: &HashMap<String, String>, progress: Option<&ProgressSink>) -> CommandResult {
+        if ADMIN_COMMANDS.contains(&command) && current_tenant() != DEFAULT_TENANT {
+            return CommandResult::new(false, String::new(), format!("Command {} is only available to the default tenant", command))
+                .with_error_code(ErrorCode::Unauthorized);
+        }
+
+        self.ensure_key_loaded(params).await;
+
+        if let Err(e) = self.check_key_access(command, params) {
+            return CommandResult::new(false, String::new(), e).with_error_code(ErrorCode::Unauthorized);
+        }
+
+        let timeout = match self.command_timeout(params) {
+            Ok(timeout) => timeout,
+            Err(e) => return CommandResult::new(false, String::new(), e).with_error_code(ErrorCode::InvalidParams),
+        };
+
+        // 查看和取消操作的命令本身不登记
+        let operation = match command {
+            "list_operations" | "cancel_operation" => None,
+            _ => {
+                let user = params.get("user").map(String::as_str).unwrap_or("system");
+                match self.register_operation(params.get("operation_id").cloned(), command, user) {
+                    Ok(operation) => Some(operation),
+                    Err(e) => return CommandResult::new(false, String::new(), e).with_error_code(ErrorCode::InvalidParams),
+                }
+            }
+        };
+
+        // 超时或被取消时丢弃命令的 future，命令在当前的 await 点停止；
+        // 已交给阻塞线程的计算（如RSA密钥生成）会执行完，但结果被丢弃
+        let execution = async {
+            match &operation {
+                Some(operation) => tokio::select! {
+                    result = self.dispatch_command(command, params, progress) => result,
+                    _ = operation.token().cancelled() => self.command_cancelled(command, params, operation.id()),
+                },
+                None => self.dispatch_command(command, params, progress).await,
+            }
+        };
+
+        // 记录每个命令的耗时，慢命令会输出警告
+        let started = Instant::now();
+        let mut result = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, execution).await {
+                Ok(result) => result,
+                Err(_) => self.command_timed_out(command, params, timeout),
+            },
+            None => execution.await,
+        };
+        let elapsed = started.elapsed();
+        drop(operation);
+
+        // 命令内部的错误都是字符串，没有显式设置错误码时根据错误信息推断
+        if !result.is_success() && result.get_error_code().is_none() {
+            result.set_error_code(Some(ErrorCode::from_message(result.get_error_message())));
+        }
+
+        self.base.record_command(command, elapsed);
+        result.set_elapsed_ms(Some(elapsed.as_millis() as u64));
+        self.evict_keys();
+
+        // 配置了签名密钥时对结果签名，签名失败时不返回未签名的结果
+        let signing_key = self.response_signing_key.lock().unwrap().clone();
+        if let Some(key_id) = signing_key {
+            result.canonicalize_result();
+            // 通过 response_signing_key_id 配置的密钥没有元数据，材料在默认安全模块中
+            let security_module = if self.keys.lock().unwrap().contains_key(&key_id) {
+                self.key_security_module(&key_id)
+            } else {
+                self.security_module_by_id(DEFAULT_SECURITY_MODULE_ID)
+            };
+            let signed = match security_module {
+                Ok(security_module) => security_module.sign_data(&key_id, &result.signing_payload()).await,
+                Err(e) => Err(e),
+            };
+            match signed {
+                Ok(signature) => {
+                    result.set_signature(Some(signature));
+                    result.set_signing_key_id(Some(key_id));
+                }
+                Err(e) => {
+                    eprintln!("签名命令结果失败: {}", e);
+                    let mut failed = CommandResult::new(false, String::new(), format!("Failed to sign response: {}", e))
+                        .with_error_code(ErrorCode::Internal);
+                    failed.set_elapsed_ms(result.get_elapsed_ms());
+                    return failed;
+                }
+            }
+        }
+
+        result
+    }
+
+    // 命令超时：参数 timeout_ms 优先，否则使用配置的 command_timeout_ms，0 表示不限制
+    fn command_timeout(&self, params: &HashMap<String, String>) -> Result<Option<std::time::Duration>, String> {
+        let timeout_ms = match params.get("timeout_ms") {
+            Some(value) => value.parse::<u64>()
+                .map_err(|_| format!("Invalid timeout_ms: {}", value))?,
+            None => self.base.settings().get_command_timeout_ms(),
+        };
+
+        Ok((timeout_ms > 0).then(|| std::time::Duration::from_millis(timeout_ms)))
+    }
+
+    // 登记正在执行的操作，未指定ID时随机生成
+    fn register_operation(&self, id: Option<String>, operation_type: &str, user: &str) -> Result<OperationGuard, String> {
+        self.operations.register(OperationInfo {
+            id: id.unwrap_or_else(|| self.random.uuid()),
+            operation_type: operation_type.to_string(),
+            user: user.to_string(),
+            tenant: current_tenant(),
+            started_at: self.clock.now(),
+        })
+    }
+
+    fn command_cancelled(&self, command: &str, params: &HashMap<String, String>, operation_id: &str) -> CommandResult {
+        let error = format!("Operation cancelled: {}", operation_id);
+        self.add_audit_log(AuditLogEntry::with_error(
+            "COMMAND_CANCELLED".to_string(),
+            params.get("user").cloned().unwrap_or_else(|| "system".to_string()),
+            params.get("key_id").cloned(),
+            format!("Command: {}", command),
+            error.clone(),
+        ));
+        CommandResult::new(false, String::new(), error).with_error_code(ErrorCode::Cancelled)
+    }
+
+    fn command_timed_out(&self, command: &str, params: &HashMap<String, String>, timeout: std::time::Duration) -> CommandResult {
+        let error = format!("Command timed out after {} ms", timeout.as_millis());
+        self.base.metrics().record_timeout(command);
+        self.add_audit_log(AuditLogEntry::with_error(
+            "COMMAND_TIMEOUT".to_string(),
+            params.get("user").cloned().unwrap_or_else(|| "system".to_string()),
+            params.get("key_id").cloned(),
+            format!("Command: {}", command),
+            error.clone(),
+        ));
+        CommandResult::new(false, String::new(), error).with_error_code(ErrorCode::Timeout)
+    }
+
+    async fn dispatch_command(&self, command: &str, params: &HashMap<String, String>, progress: Option<&ProgressSink>) -> CommandResult {
+        let user = params.get("user").cloned().unwrap_or_else(|| "system".to_string());
+        
+        match command {
+            "create_key" => {
+                let request = match create_key_request(params) {
+                    Ok(request) => request,
+                    Err(errors) => return CommandResult::validation_failed(&errors),
+                };
+
+                match self.create_key(
+                    request.name,
+                    request.description,
+                    request.key_type,
+                    request.algorithm,
+                    user,
+                    request.requires_approval,
+                    Some(request.tags),
+                    request.expiration_date,
+                ).await {
+                    Ok(metadata) => CommandResult::json(&metadata),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "create_key_pair" => {
+                let name = match params.get("name") {
                     Some(name) => name.clone(),
                     None => return CommandResult::new(false, String::new(), "Missing parameter: name".to_string()),
                 };
-                
-                let description = params.get("description")
-                    .cloned()
-                    .unwrap_or_else(|| "".to_string());
-                    
-                let key_type_str = params.get("key_type")
-                    .cloned()
-                    .unwrap_or_else(|| "SYMMETRIC".to_string());
-                    
-                let key_type = match key_type_str.as_str() {
-                    "SYMMETRIC" => KeyType::Symmetric,
-                    "ASYMMETRIC_PRIVATE" => KeyType::AsymmetricPrivate,
-                    "ASYMMETRIC_PUBLIC" => KeyType::AsymmetricPublic,
-                    "HMAC" => KeyType::HMAC,
-                    "PASSWORD" => KeyType::Password,
-                    _ => return CommandResult::new(false, String::new(), format!("Invalid key type: {}", key_type_str)),
-                };
-                
-                let algorithm_str = params.get("algorithm")
-                    .cloned()
-                    .unwrap_or_else(|| "AES-256".to_string());
-                    
-                let algorithm = match algorithm_str.as_str() {
-                    "AES-256" => KeyAlgorithm::AES256,
-                    "RSA-2048" => KeyAlgorithm::RSA2048,
-                    "RSA-4096" => KeyAlgorithm::RSA4096,
-                    "ECDSA" => KeyAlgorithm::ECDSA,
-                    "ED25519" => KeyAlgorithm::ED25519,
-                    _ => return CommandResult::new(false, String::new(), format!("Invalid algorithm: {}", algorithm_str)),
-                };
-                
-                let requires_approval = params.get("requires_approval")
-                    .map(|v| v.to_lowercase() == "true")
-                    .unwrap_or(false);
-                    
-                // 收集标签
-                let mut tags = HashMap::new();
-                for (key, value) in params {
-                    if key.starts_with("tag.") {
-                        let tag_key = key.strip_prefix("tag.").unwrap();
-                        tags.insert(tag_key.to_string(), value.clone());
-                    }
+
+                let description = params.get("description")
+                    .cloned()
+                    .unwrap_or_else(|| "".to_string());
+
+                let algorithm_str = params.get("algorithm")
+                    .cloned()
+                    .unwrap_or_else(|| "RSA-2048".to_string());
+                let algorithm = match KeyAlgorithm::from_str(&algorithm_str) {
+                    Ok(algorithm) => algorithm,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                let requires_approval = params.get("requires_approval")
+                    .map(|v| v.to_lowercase() == "true")
+                    .unwrap_or(false);
+
+                // 收集标签
+                let mut tags = HashMap::new();
+                for (key, value) in params {
+                    if let Some(tag_key) = key.strip_prefix("tag.") {
+                        tags.insert(tag_key.to_string(), value.clone());
+                    }
+                }
+
+                match self.create_key_pair(name, description, algorithm, user, requires_approval, Some(tags)).await {
+                    Ok((private_metadata, public_metadata)) => {
+                        CommandResult::new(
+                            true,
+                            serde_json::json!({
+                                "private_key": private_metadata,
+                                "public_key": public_metadata,
+                            }).to_string(),
+                            String::new(),
+                        )
+                    }
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "get_paired_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.get_paired_key(&key_id).await {
+                    Ok(paired) => CommandResult::json(&paired),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "get_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                let metadata = match self.keys.lock().unwrap().get(&key_id) {
+                    Some(metadata) => metadata.clone(),
+                    None => return CommandResult::new(false, String::new(), "Key not found".to_string()),
+                };
+
+                let mut value = match serde_json::to_value(&metadata) {
+                    Ok(value) => value,
+                    Err(e) => return CommandResult::new(false, String::new(), format!("Failed to serialize result: {}", e))
+                        .with_error_code(ErrorCode::Internal),
+                };
+                // 公钥一半直接返回公钥内容（base64）
+                if metadata.key_type == KeyType::AsymmetricPublic
+                    && let Some(public_key) = metadata.tags.get("public_key")
+                {
+                    value["public_key"] = serde_json::Value::String(public_key.clone());
+                }
+                // 已生成自签名证书时返回证书（PEM）
+                if let Some(certificate) = metadata.tags.get("certificate") {
+                    value["certificate"] = serde_json::Value::String(certificate.clone());
+                }
+
+                CommandResult::new(true, value.to_string(), String::new())
+            }
+            "key_validity" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.key_validity(&key_id) {
+                    Ok(validity) => CommandResult::new(true, validity.to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "update_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let description = params.get("description").cloned();
+                let notes = params.get("notes").cloned();
+                if description.is_none() && notes.is_none() {
+                    return CommandResult::validation_failed(&[
+                        ValidationError::new("description", "Missing parameter: description or notes"),
+                        ValidationError::new("notes", "Missing parameter: description or notes"),
+                    ]);
+                }
+
+                match self.update_key(&key_id, description, notes, &user).await {
+                    Ok(metadata) => CommandResult::json(&metadata),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "set_alias" => {
+                let alias = match params.get("alias") {
+                    Some(alias) => alias.clone(),
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: alias".to_string()),
+                };
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.set_alias(&alias, &key_id, &user).await {
+                    Ok(()) => CommandResult::new(true, key_id, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "delete_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.delete_key(&key_id, &user).await {
+                    Ok(metadata) => CommandResult::new(true, metadata.id, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "recover_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.recover_key(&key_id, &user).await {
+                    Ok(metadata) => CommandResult::new(true, metadata.id, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "rotate_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.rotate_key(&key_id, &user).await {
+                    Ok(metadata) => CommandResult::json(&metadata),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "suspend_key" | "resume_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let status = if command == "suspend_key" { KeyStatus::Suspended } else { KeyStatus::Active };
+
+                match self.set_key_status(&key_id, status, &user).await {
+                    Ok(metadata) => CommandResult::new(true, metadata.id, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "grant_access" | "revoke_access" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let grantee = match params.get("grantee") {
+                    Some(grantee) if !grantee.is_empty() => grantee.clone(),
+                    _ => return CommandResult::new(false, String::new(), "Missing parameter: grantee".to_string()),
+                };
+                // 操作以逗号分隔
+                let operations: Option<Vec<String>> = params.get("operations").map(|operations| {
+                    operations.split(',')
+                        .map(|operation| operation.trim().to_string())
+                        .filter(|operation| !operation.is_empty())
+                        .collect()
+                });
+
+                let result = if command == "grant_access" {
+                    self.grant_access(&key_id, &grantee, operations.as_deref().unwrap_or_default(), &user).await
+                } else {
+                    self.revoke_access(&key_id, &grantee, operations.as_deref(), &user).await
+                };
+
+                match result {
+                    Ok(metadata) => CommandResult::json(&metadata.acl.unwrap_or_default()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "key_history" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.key_history(&key_id).await {
+                    Ok(history) => CommandResult::new(true, history.to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "list_keys" => {
+                match self.list_keys(params).await {
+                    Ok(list) => CommandResult::json(&list),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "apply_tags" => {
+                match self.apply_tags(params, &user).await {
+                    Ok(updated) => CommandResult::new(true, serde_json::json!({ "updated": updated }).to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "list_unused_keys" => {
+                let since_days = match params.get("since_days").map(|value| value.parse::<u32>()) {
+                    Some(Ok(days)) => days,
+                    Some(Err(_)) => return CommandResult::new(false, String::new(), "Invalid since_days".to_string())
+                        .with_error_code(ErrorCode::InvalidParams),
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: since_days".to_string()),
+                };
+
+                match self.list_unused_keys(since_days).await {
+                    Ok(list) => CommandResult::json(&list),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "put_attachment" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let (name, data) = match (params.get("name"), params.get("data")) {
+                    (Some(name), Some(data)) => (name, data),
+                    _ => return CommandResult::new(false, String::new(), "Missing parameter: name or data".to_string()),
+                };
+
+                match self.put_attachment(&key_id, name, data, &user).await {
+                    Ok(info) => CommandResult::json(&info),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "get_attachment" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let name = match params.get("name") {
+                    Some(name) => name,
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: name".to_string()),
+                };
+                let persistence = match self.attachment_store(&key_id) {
+                    Ok(persistence) => persistence,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match persistence.get_attachment(&key_id, name).await {
+                    Ok(data) => CommandResult::new(true, BASE64.encode(data), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
                 }
-                
-                match self.create_key(
-                    name,
-                    description,
-                    key_type,
-                    algorithm.clone(), // 添加 clone() 以解决所有权问题
+            }
+            "list_attachments" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let persistence = match self.attachment_store(&key_id) {
+                    Ok(persistence) => persistence,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match persistence.list_attachments(&key_id).await {
+                    Ok(list) => CommandResult::json(&list),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "drain" => {
+                let in_flight = self.drain(&user);
+                CommandResult::new(
+                    true,
+                    serde_json::json!({ "draining": true, "in_flight": in_flight, "drained": in_flight == 0 }).to_string(),
+                    String::new(),
+                )
+            }
+            "list_operations" => {
+                let tenant = current_tenant();
+                let list: Vec<OperationInfo> = self.operations.list()
+                    .into_iter()
+                    .filter(|operation| operation.tenant == tenant)
+                    .collect();
+                CommandResult::json(&list)
+            }
+            "cancel_operation" => {
+                let operation_id = match params.get("operation_id") {
+                    Some(operation_id) => operation_id.clone(),
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: operation_id".to_string()),
+                };
+
+                // 只能取消本租户的操作
+                let tenant = current_tenant();
+                let owned = self.operations.list()
+                    .iter()
+                    .any(|operation| operation.id == operation_id && operation.tenant == tenant);
+                if !owned || !self.operations.cancel(&operation_id) {
+                    return CommandResult::new(false, String::new(), "Operation not found".to_string());
+                }
+
+                self.add_audit_log(AuditLogEntry::new(
+                    "CANCEL_OPERATION".to_string(),
                     user,
-                    requires_approval,
-                    Some(tags),
                     None,
-                ).await {
-                    Ok(metadata) => {
+                    format!("Cancelled operation: {}", operation_id),
+                    true,
+                ));
+                CommandResult::new(true, operation_id, String::new())
+            }
+            "key_stats" => {
+                // 有持久化存储时由后端统计（数据库按 GROUP BY），否则统计内存中的密钥
+                let now = self.clock.now();
+                let tenant = current_tenant();
+                let stats = match &self.persistence {
+                    Some(persistence) => persistence.key_stats(Some(&tenant), now).await,
+                    None => Ok(KeyStats::from_keys(
+                        self.keys.lock().unwrap().values().filter(|metadata| metadata.tenant == tenant),
+                        now,
+                    )),
+                };
+
+                match stats {
+                    Ok(stats) => CommandResult::json(&stats),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "list_owners" => {
+                // 与 key_stats 相同，有持久化存储时由后端统计，否则统计内存中的密钥
+                let tenant = current_tenant();
+                let counts = match &self.persistence {
+                    Some(persistence) => persistence.count_keys_by_owner(Some(&tenant)).await,
+                    None => Ok(key_stats::count_by_owner(
+                        self.keys.lock().unwrap().values().filter(|metadata| metadata.tenant == tenant),
+                    )),
+                };
+
+                match counts {
+                    Ok(counts) => {
+                        let owners: Vec<serde_json::Value> = counts
+                            .into_iter()
+                            .map(|(owner, count)| serde_json::json!({ "owner": owner, "key_count": count }))
+                            .collect();
+                        CommandResult::new(true, serde_json::Value::Array(owners).to_string(), String::new())
+                    }
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "generate_data_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let length = match params.get("length") {
+                    Some(value) => match value.parse::<usize>() {
+                        Ok(length) => length,
+                        Err(_) => return CommandResult::new(false, String::new(), format!("Invalid length: {}", value)),
+                    },
+                    None => 32,
+                };
+                let aad = match params.get("aad").map(|aad| BASE64.decode(aad)) {
+                    Some(Ok(aad)) => aad,
+                    Some(Err(e)) => return CommandResult::new(false, String::new(), format!("Invalid aad: {}", e)),
+                    None => Vec::new(),
+                };
+
+                match self.generate_data_key(&key_id, length, &aad, &user).await {
+                    Ok(value) => CommandResult::new(true, value.to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "sign" | "encrypt" | "decrypt" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.data_operation(command, &key_id, params, &user).await {
+                    Ok(output) => CommandResult::new(true, output, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "verify_password" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let password = match params.get("password") {
+                    Some(password) if !password.is_empty() => password,
+                    _ => return CommandResult::new(false, String::new(), "Missing parameter: password".to_string()),
+                };
+
+                match self.verify_password(&key_id, password, &user).await {
+                    Ok(matched) => CommandResult::new(true, serde_json::json!({ "match": matched }).to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "verify_kdf_params" => {
+                let key_id = match params.get("key_id").filter(|key_id| !key_id.is_empty()) {
+                    Some(_) => match self.key_id_param(params) {
+                        Ok(key_id) => Some(key_id),
+                        Err(e) => return CommandResult::new(false, String::new(), e),
+                    },
+                    None => None,
+                };
+
+                match self.verify_kdf_params(key_id.as_deref()).await {
+                    Ok(report) => CommandResult::json(&report),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "rehash_password" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let password = match params.get("password") {
+                    Some(password) if !password.is_empty() => password,
+                    _ => return CommandResult::new(false, String::new(), "Missing parameter: password".to_string()),
+                };
+
+                match self.rehash_password(&key_id, password, &user).await {
+                    Ok(metadata) => CommandResult::json(&metadata),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "get_public_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                let format = match params.get("format") {
+                    Some(format) => match PublicKeyFormat::from_str(format) {
+                        Ok(format) => format,
+                        Err(e) => return CommandResult::new(false, String::new(), e),
+                    },
+                    None => PublicKeyFormat::Pem,
+                };
+
+                match self.get_public_key(&key_id, format).await {
+                    // PEM 直接返回文本，DER 以 base64 返回
+                    Ok(public_key) => match format {
+                        PublicKeyFormat::Pem => CommandResult::new(
+                            true,
+                            String::from_utf8_lossy(&public_key).into_owned(),
+                            String::new(),
+                        ),
+                        PublicKeyFormat::Der => CommandResult::new(true, BASE64.encode(&public_key), String::new()),
+                    },
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "generate_csr" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                let subject = match SubjectName::from_params(params) {
+                    Ok(subject) => subject,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.generate_csr(&key_id, &subject, &user).await {
+                    Ok(csr) => CommandResult::new(true, csr, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "generate_self_signed_cert" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                let subject = match SubjectName::from_params(params) {
+                    Ok(subject) => subject,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                // 默认从当前时间开始，有效期 365 天
+                let not_before = match params.get("not_before") {
+                    Some(value) => match chrono::DateTime::parse_from_rfc3339(value) {
+                        Ok(value) => value.with_timezone(&chrono::Utc),
+                        Err(e) => return CommandResult::new(false, String::new(), format!("Invalid not_before: {}", e)),
+                    },
+                    None => self.clock.now(),
+                };
+
+                let validity_days = match params.get("validity_days") {
+                    Some(days) => match days.parse::<i64>() {
+                        Ok(days) if days > 0 => days,
+                        _ => return CommandResult::new(false, String::new(), format!("Invalid validity_days: {}", days)),
+                    },
+                    None => DEFAULT_CERT_VALIDITY_DAYS,
+                };
+                let not_after = not_before + chrono::Duration::days(validity_days);
+
+                match self.generate_self_signed_cert(&key_id, &subject, not_before, not_after, &user).await {
+                    Ok(certificate) => CommandResult::new(true, certificate, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "derive_key" => {
+                let request = match derive_key_request(params, &self.kdf_params) {
+                    Ok(request) => request,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.derive_key(request, user).await {
+                    Ok(metadata) => CommandResult::json(&metadata),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "backup" => {
+                let path = match params.get("path") {
+                    Some(path) if !path.is_empty() => path.clone(),
+                    _ => return CommandResult::new(false, String::new(), "Missing parameter: path".to_string()),
+                };
+
+                let persistence = match &self.persistence {
+                    Some(persistence) => Arc::clone(persistence),
+                    None => return CommandResult::new(false, String::new(), "未配置持久化存储".to_string()),
+                };
+
+                match persistence.backup_to(&path).await {
+                    Ok(()) => {
+                        self.add_audit_log(AuditLogEntry::new(
+                            "BACKUP".to_string(),
+                            user,
+                            None,
+                            format!("Backed up to: {}", path),
+                            true,
+                        ));
+                        CommandResult::new(true, path, String::new())
+                    }
+                    Err(e) => {
+                        self.add_audit_log(AuditLogEntry::with_error(
+                            "BACKUP".to_string(),
+                            user,
+                            None,
+                            format!("Backup to {} failed", path),
+                            e.clone(),
+                        ));
+                        CommandResult::new(false, String::new(), e)
+                    }
+                }
+            }
+            "restore" => {
+                let path = match params.get("path") {
+                    Some(path) if !path.is_empty() => path.clone(),
+                    _ => return CommandResult::new(false, String::new(), "Missing parameter: path".to_string()),
+                };
+
+                // 恢复会覆盖现有数据，必须显式确认
+                if params.get("confirm").map(|v| v.to_lowercase()) != Some("true".to_string()) {
+                    return CommandResult::new(false, String::new(), "Restore requires confirm=true".to_string());
+                }
+
+                let persistence = match &self.persistence {
+                    Some(persistence) => Arc::clone(persistence),
+                    None => return CommandResult::new(false, String::new(), "未配置持久化存储".to_string()),
+                };
+
+                let restored = match persistence.restore_from(&path).await {
+                    Ok(()) => self.load_from_persistence().await,
+                    Err(e) => Err(e),
+                };
+
+                match restored {
+                    Ok(count) => {
+                        self.add_audit_log(AuditLogEntry::new(
+                            "RESTORE".to_string(),
+                            user,
+                            None,
+                            format!("Restored {} keys from: {}", count, path),
+                            true,
+                        ));
+                        CommandResult::new(true, count.to_string(), String::new())
+                    }
+                    Err(e) => {
+                        self.add_audit_log(AuditLogEntry::with_error(
+                            "RESTORE".to_string(),
+                            user,
+                            None,
+                            format!("Restore from {} failed", path),
+                            e.clone(),
+                        ));
+                        CommandResult::new(false, String::new(), e)
+                    }
+                }
+            }
+            "compact_audit_log" => {
+                let retention = match params.get("retention_days") {
+                    Some(days) => match days.parse::<i64>() {
+                        Ok(days) if days >= 0 => Some(chrono::Duration::days(days)),
+                        _ => return CommandResult::new(false, String::new(), format!("Invalid retention_days: {}", days)),
+                    },
+                    None => None,
+                };
+
+                let dedupe = params.get("dedupe")
+                    .map(|v| v.to_lowercase() == "true")
+                    .unwrap_or(false);
+
+                let persistence = match &self.persistence {
+                    Some(persistence) => Arc::clone(persistence),
+                    None => return CommandResult::new(false, String::new(), "未配置持久化存储".to_string()),
+                };
+
+                match persistence.compact_audit_log(retention, dedupe).await {
+                    Ok((original, remaining)) => {
+                        self.add_audit_log(AuditLogEntry::new(
+                            "COMPACT_AUDIT_LOG".to_string(),
+                            user,
+                            None,
+                            format!("Compacted audit log: {} -> {} entries", original, remaining),
+                            true,
+                        ));
                         CommandResult::new(
                             true,
-                            serde_json::to_string(&metadata).unwrap_or_default(),
+                            serde_json::json!({ "original": original, "remaining": remaining }).to_string(),
                             String::new(),
                         )
                     }
                     Err(e) => CommandResult::new(false, String::new(), e),
                 }
             }
+            "escrow_key" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+
+                match self.escrow_key(&key_id, &user).await {
+                    Ok(value) => CommandResult::new(true, value.to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "recover_from_escrow" => {
+                let key_id = match self.key_id_param(params) {
+                    Ok(key_id) => key_id,
+                    Err(e) => return CommandResult::new(false, String::new(), e),
+                };
+                let recovery_private_key = match params.get("recovery_private_key") {
+                    Some(private_key) => private_key,
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: recovery_private_key".to_string()),
+                };
+
+                match self.recover_from_escrow(&key_id, recovery_private_key, params.get("blob").map(String::as_str), &user).await {
+                    Ok(()) => CommandResult::new(true, key_id, String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "reconcile" => {
+                match self.reconcile(&user).await {
+                    Ok(report) => CommandResult::new(true, report.to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "replay_dead_letters" => {
+                match self.replay_dead_letters(&user).await {
+                    Ok(report) => CommandResult::new(true, report.to_string(), String::new()),
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "rewrap_all" => {
+                if let Err(e) = self.load_matching_keys(&KeyQuery::new()).await {
+                    return CommandResult::new(false, String::new(), e);
+                }
+                let (rewrapped, unchanged, failed) = self.rewrap_all(&user, progress).await;
+                let failed_ids: Vec<&String> = failed.iter().map(|(key_id, _)| key_id).collect();
+                let summary = serde_json::json!({
+                    "rewrapped": rewrapped,
+                    "unchanged": unchanged,
+                    "failed": failed_ids,
+                })
+                .to_string();
+
+                match failed.first() {
+                    None => CommandResult::new(true, summary, String::new()),
+                    Some((key_id, e)) => CommandResult::new(
+                        false,
+                        summary,
+                        format!("Failed to rewrap {} keys, first failure {}: {}", failed.len(), key_id, e),
+                    ),
+                }
+            }
+            "verify_batch" => {
+                let items: Vec<VerifyItem> = match params.get("items").map(|items| serde_json::from_str(items)) {
+                    Some(Ok(items)) => items,
+                    Some(Err(e)) => return CommandResult::new(false, String::new(), format!("Invalid items: {}", e))
+                        .with_error_code(ErrorCode::InvalidParams),
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: items".to_string()),
+                };
+
+                let total = items.len();
+                let results = self.verify_batch(items).await;
+                let valid = results.iter().filter(|result| matches!(result, Ok(true))).count();
+                let failed = results.iter().filter(|result| result.is_err()).count();
+
+                self.add_audit_log(AuditLogEntry::new(
+                    "VERIFY_BATCH".to_string(),
+                    user,
+                    None,
+                    format!("Verified {} signatures: {} valid, {} invalid, {} failed", total, valid, total - valid - failed, failed),
+                    true,
+                ));
+
+                // 无法验证的条目视为无效，并附带原因
+                let results: Vec<serde_json::Value> = results.into_iter()
+                    .map(|result| match result {
+                        Ok(valid) => serde_json::json!({ "valid": valid }),
+                        Err(e) => serde_json::json!({ "valid": false, "error": e }),
+                    })
+                    .collect();
+                CommandResult::json(&results)
+            }
+            "migrate_algorithm" => {
+                let source = match params.get("source_algorithm").map(|value| KeyAlgorithm::from_str(value)) {
+                    Some(Ok(algorithm)) => algorithm,
+                    Some(Err(e)) => return CommandResult::new(false, String::new(), e),
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: source_algorithm".to_string()),
+                };
+                let target = match params.get("target_algorithm").map(|value| KeyAlgorithm::from_str(value)) {
+                    Some(Ok(algorithm)) => algorithm,
+                    Some(Err(e)) => return CommandResult::new(false, String::new(), e),
+                    None => return CommandResult::new(false, String::new(), "Missing parameter: target_algorithm".to_string()),
+                };
+                if source == target {
+                    return CommandResult::new(false, String::new(), "Source and target algorithm must differ".to_string())
+                        .with_error_code(ErrorCode::InvalidParams);
+                }
+                let query = KeyQuery::new().with_tenant(current_tenant()).with_algorithm(source.clone());
+                if let Err(e) = self.load_matching_keys(&query).await {
+                    return CommandResult::new(false, String::new(), e);
+                }
+
+                let keys = self.migrate_algorithm(source, target, &user, progress).await;
+                let count = |status: &str| keys.iter().filter(|entry| entry["status"] == status).count();
+                let failed = count("failed");
+                let summary = serde_json::json!({
+                    "migrated": count("migrated"),
+                    "pending_approval": count("pending_approval"),
+                    "failed": failed,
+                    "keys": keys,
+                })
+                .to_string();
+
+                if failed == 0 {
+                    CommandResult::new(true, summary, String::new())
+                } else {
+                    CommandResult::new(false, summary, format!("Failed to migrate {} keys", failed))
+                }
+            }
+            "selftest_security_module" => {
+                let algorithms = match params.get("algorithms") {
+                    Some(value) => match value.split(',').map(|name| KeyAlgorithm::from_str(name.trim())).collect::<Result<Vec<_>, _>>() {
+                        Ok(algorithms) => algorithms,
+                        Err(e) => return CommandResult::new(false, String::new(), e),
+                    },
+                    None => SELFTEST_ALGORITHMS.to_vec(),
+                };
+
+                let report = self.selftest_security_module(&algorithms).await;
+                let failed: Vec<&str> = report.iter()
+                    .filter(|entry| entry["passed"] == false)
+                    .filter_map(|entry| entry["algorithm"].as_str())
+                    .collect();
+                self.add_audit_log(AuditLogEntry::new(
+                    "SELFTEST_SECURITY_MODULE".to_string(),
+                    user,
+                    None,
+                    format!("Tested {} algorithms, {} failed", report.len(), failed.len()),
+                    failed.is_empty(),
+                ));
+
+                let summary = serde_json::json!({ "passed": failed.is_empty(), "algorithms": report }).to_string();
+                if failed.is_empty() {
+                    CommandResult::new(true, summary, String::new())
+                } else {
+                    CommandResult::new(false, summary, format!("Security module selftest failed for: {}", failed.join(", ")))
+                }
+            }
+            "rotate_audit_key" => {
+                let algorithm = match params.get("algorithm").map(|value| KeyAlgorithm::from_str(value)) {
+                    Some(Ok(algorithm)) => Some(algorithm),
+                    Some(Err(e)) => return CommandResult::new(false, String::new(), e),
+                    None => None,
+                };
+
+                match self.rotate_audit_key(algorithm, &user).await {
+                    Ok(checkpoint) => CommandResult::new(true, checkpoint.to_string(), String::new()),
+                    Err(e) => {
+                        self.add_audit_log(AuditLogEntry::with_error(
+                            "ROTATE_AUDIT_KEY".to_string(),
+                            user,
+                            None,
+                            "Rotate response signing key failed".to_string(),
+                            e.clone(),
+                        ));
+                        CommandResult::new(false, String::new(), e)
+                    }
+                }
+            }
+            "diff_keystore" => {
+                let source = match params.get("source") {
+                    Some(source) if !source.is_empty() => source.clone(),
+                    _ => return CommandResult::new(false, String::new(), "Missing parameter: source".to_string()),
+                };
+
+                let persistence = match &self.persistence {
+                    Some(persistence) => Arc::clone(persistence),
+                    None => return CommandResult::new(false, String::new(), "未配置持久化存储".to_string()),
+                };
+
+                let diff = match keystore_diff::open_source(&source).await {
+                    Ok(other) => keystore_diff::diff_keystores(persistence.as_ref(), other.as_ref()).await,
+                    Err(e) => Err(e),
+                };
+
+                match diff {
+                    Ok(diff) => {
+                        self.add_audit_log(AuditLogEntry::new(
+                            "DIFF_KEYSTORE".to_string(),
+                            user,
+                            None,
+                            format!(
+                                "Compared with {}: {} only local, {} only other, {} changed",
+                                source, diff.only_local.len(), diff.only_other.len(), diff.changed.len()
+                            ),
+                            true,
+                        ));
+                        CommandResult::json(&diff)
+                    }
+                    Err(e) => CommandResult::new(false, String::new(), e),
+                }
+            }
+            "reconfigure" => {
+                match self.base.apply_settings(&base_plugin::setting_changes(params)) {
+                    Ok(changed) => {
+                        self.add_audit_log(AuditLogEntry::new(
+                            "RECONFIGURE".to_string(),
+                            user,
+                            None,
+                            format!("Reconfigured: {}", changed.join(", ")),
+                            true,
+                        ));
+                        CommandResult::json(&changed)
+                    }
+                    Err(e) => {
+                        self.add_audit_log(AuditLogEntry::with_error(
+                            "RECONFIGURE".to_string(),
+                            user,
+                            None,
+                            "Reconfigure rejected".to_string(),
+                            e.clone(),
+                        ));
+                        CommandResult::new(false, String::new(), e)
+                    }
+                }
+            }
             // ... 其他命令实现 ...
             _ => CommandResult::new(
                 false,
                 String::new(),
                 format!("未知命令: {}", command),
-            ),
+            ).with_error_code(ErrorCode::UnknownCommand),
         }
     }
 
@@ -285,15 +4131,111 @@ impl KeyManagementPlugin {
 #[async_trait]
 impl PluginSDK for KeyManagementPlugin {
     async fn initialize(&mut self, config: PluginConfig) -> bool {
-        self.base.initialize(config).await
+        match self.try_initialize(config).await {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{}", e);
+                false
+            }
+        }
+    }
+
+    // 校验配置、连接持久化存储并加载密钥元数据，任何一步失败都返回具体原因
+    async fn try_initialize(&mut self, config: PluginConfig) -> Result<(), String> {
+        self.kdf_params = kdf_params_from(config.get_additional_config(), &KdfParams::default())
+            .map_err(|e| format!("KDF参数配置无效: {}", e))?;
+
+        if let Some(value) = config.get_config("audit_queue_size") {
+            self.audit_queue_size = match value.parse::<usize>() {
+                Ok(size) if size > 0 => size,
+                _ => return Err(format!("审计日志写入队列配置无效: Invalid audit_queue_size: {}", value)),
+            };
+        }
+
+        *self.response_signing_key.lock().unwrap() = config.get_config("response_signing_key_id")
+            .filter(|key_id| !key_id.is_empty())
+            .cloned();
+
+        if let Some(path) = config.get_config("dead_letter_path").filter(|path| !path.is_empty()) {
+            self.dead_letters = Arc::new(DeadLetterStore::open(path).map_err(|e| format!("打开死信存储失败: {}", e))?);
+        }
+
+        if let Some(value) = config.get_config("max_in_memory_keys") {
+            self.max_in_memory_keys = match value.parse::<usize>() {
+                Ok(max) if max > 0 => Some(max),
+                _ => return Err(format!("内存密钥数配置无效: Invalid max_in_memory_keys: {}", value)),
+            };
+        }
+
+        self.escrow_public_key = config.get_config("escrow_public_key")
+            .filter(|value| !value.is_empty())
+            .map(|value| EscrowPublicKey::from_encoded(value))
+            .transpose()
+            .map_err(|e| format!("托管恢复公钥配置无效: {}", e))?;
+
+        self.audit_webhook = match AuditWebhookConfig::from_config(&config) {
+            Ok(Some(webhook_config)) => Some(
+                AuditWebhook::spawn(webhook_config).map_err(|e| format!("启动审计webhook失败: {}", e))?,
+            ),
+            Ok(None) => None,
+            Err(e) => return Err(format!("审计webhook配置无效: {}", e)),
+        };
+
+        // 没有通过 with_persistence 指定持久化存储时，按配置创建（persistence_backend 等）
+        if self.persistence.is_none() && factory::persistence_configured(&config) {
+            self.persistence = Some(factory::build_persistence(&config).await?);
+        }
+
+        let count = self.load_from_persistence()
+            .await
+            .map_err(|e| format!("加载密钥元数据失败: {}", e))?;
+        if self.persistence.is_some() {
+            println!("已从持久化存储加载 {} 个密钥", count);
+        }
+        if self.evict_keys() > 0 {
+            println!("内存中保留 {} 个密钥，其余按需从持久化存储加载", self.keys.lock().unwrap().len());
+        }
+
+        if self.base.initialize(config).await {
+            Ok(())
+        } else {
+            Err("插件初始化失败".to_string())
+        }
     }
 
     async fn start(&mut self) -> bool {
-        self.base.start().await
+        if !self.base.start().await {
+            return false;
+        }
+
+        if let Some(persistence) = &self.persistence
+            && self.audit_queue.is_none()
+        {
+            self.audit_queue = Some(AuditQueue::spawn_with_dead_letters(
+                Arc::clone(persistence),
+                self.audit_queue_size,
+                Arc::clone(&self.dead_letters),
+            ));
+        }
+
+        self.base.set_ready(true);
+        true
     }
 
     async fn stop(&mut self) -> bool {
-        self.base.stop().await
+        let stopped = self.base.stop().await;
+
+        // 发送完队列中剩余的审计日志
+        if let Some(webhook) = self.audit_webhook.take() {
+            webhook.close().await;
+        }
+        // 写入剩余的审计日志，之前写入失败的条目最后再尝试一次
+        if let Some(queue) = self.audit_queue.take() {
+            queue.close().await;
+        }
+        self.flush_key_usage().await;
+
+        stopped
     }
 
     fn get_info(&self) -> crate::plugin_info::PluginInfo {
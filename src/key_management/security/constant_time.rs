@@ -0,0 +1,23 @@
+use subtle::ConstantTimeEq;
+
+/// 常量时间比较两段秘密数据（口令派生结果、密钥材料等）；HMAC 标签用 `ring::hmac::verify` 校验
+///
+/// 耗时只与长度有关，不因第一个不同字节的位置而变化；长度本身不是秘密，长度不同时直接返回 false
+pub fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_contents_and_length() {
+        assert!(eq(b"", b""));
+        assert!(eq(b"secret", b"secret"));
+        assert!(!eq(b"secret", b"secreT"));
+        assert!(!eq(b"secret", b"Secret"));
+        assert!(!eq(b"secret", b"secret!"));
+        assert!(!eq(b"secret", b""));
+    }
+}
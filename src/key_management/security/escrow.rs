@@ -0,0 +1,121 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePublicKey};
+use rsa::sha2::Sha256;
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+
+const BLOB_VERSION: u8 = 1;
+const DATA_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// 托管使用的恢复公钥（RSA）
+///
+/// 托管数据格式：版本(1字节) | 包装后数据密钥长度(2字节，大端) | RSA-OAEP-SHA256 包装的数据密钥 |
+/// 随机数(12字节) | AES-256-GCM 加密的密钥材料。密钥ID作为附加数据，托管数据不能用于恢复其他密钥
+#[derive(Debug, Clone)]
+pub struct EscrowPublicKey {
+    key: RsaPublicKey,
+    fingerprint: String,
+}
+
+impl EscrowPublicKey {
+    /// 解析 PEM 或 base64 编码的 DER（SubjectPublicKeyInfo）公钥
+    pub fn from_encoded(encoded: &str) -> Result<Self, String> {
+        let encoded = encoded.trim();
+        let key = if encoded.starts_with("-----BEGIN") {
+            RsaPublicKey::from_public_key_pem(encoded)
+        } else {
+            let der = BASE64.decode(encoded).map_err(|e| format!("Invalid escrow public key: {}", e))?;
+            RsaPublicKey::from_public_key_der(&der)
+        }
+        .map_err(|e| format!("Invalid escrow public key: {}", e))?;
+
+        let der = key.to_public_key_der().map_err(|e| format!("Invalid escrow public key: {}", e))?;
+        let fingerprint = digest::digest(&digest::SHA256, der.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Ok(Self { key, fingerprint })
+    }
+
+    /// 公钥 DER 的 SHA-256（十六进制），用于记录托管数据对应的恢复密钥
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// 将密钥材料托管到恢复公钥下
+    pub fn seal(&self, key_id: &str, material: &[u8]) -> Result<Vec<u8>, String> {
+        let rng = SystemRandom::new();
+        let mut data_key = [0u8; DATA_KEY_LEN];
+        rng.fill(&mut data_key).map_err(|_| "生成随机数失败".to_string())?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut nonce).map_err(|_| "生成随机数失败".to_string())?;
+
+        let wrapped_key = self.key
+            .encrypt(&mut rand::rngs::OsRng, Oaep::new::<Sha256>(), &data_key)
+            .map_err(|e| format!("包装托管密钥失败: {}", e))?;
+
+        let mut ciphertext = material.to_vec();
+        aead_key(&data_key)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(key_id.as_bytes()), &mut ciphertext)
+            .map_err(|_| "加密托管数据失败".to_string())?;
+
+        let mut blob = Vec::with_capacity(3 + wrapped_key.len() + NONCE_LEN + ciphertext.len());
+        blob.push(BLOB_VERSION);
+        blob.extend_from_slice(&(wrapped_key.len() as u16).to_be_bytes());
+        blob.extend_from_slice(&wrapped_key);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+        Ok(blob)
+    }
+}
+
+/// 用恢复私钥（PEM 或 base64 编码的 PKCS#8 DER）解开托管数据，返回密钥材料
+pub fn open(recovery_private_key: &str, key_id: &str, blob: &[u8]) -> Result<Vec<u8>, String> {
+    let encoded = recovery_private_key.trim();
+    let private_key = if encoded.starts_with("-----BEGIN") {
+        RsaPrivateKey::from_pkcs8_pem(encoded)
+    } else {
+        let der = BASE64.decode(encoded).map_err(|e| format!("Invalid recovery private key: {}", e))?;
+        RsaPrivateKey::from_pkcs8_der(&der)
+    }
+    .map_err(|e| format!("Invalid recovery private key: {}", e))?;
+
+    let (&version, rest) = blob.split_first().ok_or_else(|| "Invalid escrow blob: empty".to_string())?;
+    if version != BLOB_VERSION {
+        return Err(format!("Invalid escrow blob: unsupported version {}", version));
+    }
+    if rest.len() < 2 {
+        return Err("Invalid escrow blob: truncated".to_string());
+    }
+    let (length, rest) = rest.split_at(2);
+    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+    if rest.len() < length + NONCE_LEN + aead::AES_256_GCM.tag_len() {
+        return Err("Invalid escrow blob: truncated".to_string());
+    }
+    let (wrapped_key, rest) = rest.split_at(length);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let data_key = private_key
+        .decrypt(Oaep::new::<Sha256>(), wrapped_key)
+        .map_err(|_| "Failed to open escrow blob: recovery key does not match".to_string())?;
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid escrow blob: bad nonce".to_string())?;
+
+    let mut material = ciphertext.to_vec();
+    let plaintext_len = aead_key(&data_key)?
+        .open_in_place(nonce, Aad::from(key_id.as_bytes()), &mut material)
+        .map_err(|_| "Failed to open escrow blob: data is corrupted or belongs to another key".to_string())?
+        .len();
+    material.truncate(plaintext_len);
+    Ok(material)
+}
+
+fn aead_key(data_key: &[u8]) -> Result<LessSafeKey, String> {
+    let key = UnboundKey::new(&aead::AES_256_GCM, data_key).map_err(|_| "无效的托管数据密钥".to_string())?;
+    Ok(LessSafeKey::new(key))
+}
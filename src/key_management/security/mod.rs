@@ -1 +1,6 @@
-pub mod security_module;
\ No newline at end of file
+mod p256;
+pub mod constant_time;
+pub mod escrow;
+pub mod security_module;
+pub mod software_module;
+pub mod x509;
\ No newline at end of file
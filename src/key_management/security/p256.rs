@@ -0,0 +1,127 @@
+use rsa::BigUint;
+
+use crate::random::RandomSource;
+
+// ring 生成 P-256 PKCS#8 文档时使用的模板：私钥标量和未压缩公钥点分别接在两个前缀之后
+const PKCS8_PREFIX: &str = "308187020100301306072a8648ce3d020106082a8648ce3d030107046d306b0201010420";
+const PKCS8_PUBLIC_KEY_PREFIX: &str = "a144034200";
+
+const P: &str = "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff";
+const N: &str = "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551";
+const GX: &str = "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296";
+const GY: &str = "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5";
+
+type Point = Option<(BigUint, BigUint)>; // None 为无穷远点
+
+/// 用固定种子的随机数来源生成可复现的 P-256 PKCS#8 文档，只用于测试
+///
+/// ring 只能使用系统随机数生成 ECDSA 密钥，这里的标量乘法是手写的，没有做常数时间处理，
+/// 因此拒绝操作系统随机数来源，真实密钥不会经过这里
+pub(crate) fn generate_seeded_pkcs8(random: &RandomSource) -> Result<Vec<u8>, String> {
+    if !random.is_seeded() {
+        return Err("只有固定种子的随机数来源可以使用 P-256 测试实现生成密钥".to_string());
+    }
+
+    let mut scalar = [0u8; 32];
+    loop {
+        random.fill(&mut scalar);
+        if let Some(document) = pkcs8_from_scalar(&scalar) {
+            return Ok(document);
+        }
+    }
+}
+
+// 由私钥标量构造 PKCS#8 文档，标量不在 [1, n-1] 范围内时返回 None
+fn pkcs8_from_scalar(scalar: &[u8; 32]) -> Option<Vec<u8>> {
+    let k = BigUint::from_bytes_be(scalar);
+    if k == BigUint::from(0u32) || k >= hex(N) {
+        return None;
+    }
+
+    let (x, y) = multiply(&k, (hex(GX), hex(GY)))?;
+
+    let mut document = decode_hex(PKCS8_PREFIX);
+    document.extend_from_slice(scalar);
+    document.extend(decode_hex(PKCS8_PUBLIC_KEY_PREFIX));
+    document.push(0x04);
+    document.extend(left_pad(&x.to_bytes_be()));
+    document.extend(left_pad(&y.to_bytes_be()));
+    Some(document)
+}
+
+fn multiply(k: &BigUint, base: (BigUint, BigUint)) -> Point {
+    let p = hex(P);
+    let mut result: Point = None;
+    let mut addend: Point = Some(base);
+
+    for i in 0..k.bits() {
+        if k.clone() >> i & BigUint::from(1u32) == BigUint::from(1u32) {
+            result = add(&p, &result, &addend);
+        }
+        addend = add(&p, &addend, &addend);
+    }
+    result
+}
+
+fn add(p: &BigUint, a: &Point, b: &Point) -> Point {
+    let (x1, y1) = match a {
+        Some(point) => point,
+        None => return b.clone(),
+    };
+    let (x2, y2) = match b {
+        Some(point) => point,
+        None => return a.clone(),
+    };
+
+    let lambda = if x1 == x2 {
+        if (y1 + y2) % p == BigUint::from(0u32) {
+            return None;
+        }
+        // 倍点: (3x² + a) / 2y，P-256 的 a = -3
+        let numerator = (BigUint::from(3u32) * x1 * x1 + p - BigUint::from(3u32)) % p;
+        numerator * inverse(p, &(BigUint::from(2u32) * y1 % p)) % p
+    } else {
+        let numerator = (y2 + p - y1) % p;
+        numerator * inverse(p, &((x2 + p - x1) % p)) % p
+    };
+
+    let x3 = (&lambda * &lambda + p + p - x1 - x2) % p;
+    let y3 = (lambda * ((x1 + p - &x3) % p) + p - y1) % p;
+    Some((x3, y3))
+}
+
+// p 为素数，按费马小定理求逆元
+fn inverse(p: &BigUint, value: &BigUint) -> BigUint {
+    value.modpow(&(p - BigUint::from(2u32)), p)
+}
+
+fn hex(value: &str) -> BigUint {
+    BigUint::parse_bytes(value.as_bytes(), 16).expect("无效的曲线参数")
+}
+
+fn decode_hex(value: &str) -> Vec<u8> {
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).expect("无效的模板"))
+        .collect()
+}
+
+fn left_pad(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = vec![0u8; 32usize.saturating_sub(bytes.len())];
+    padded.extend_from_slice(bytes);
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_os_random_source() {
+        assert!(generate_seeded_pkcs8(&RandomSource::os()).is_err());
+        assert_eq!(
+            generate_seeded_pkcs8(&RandomSource::seeded(1)).unwrap(),
+            generate_seeded_pkcs8(&RandomSource::seeded(1)).unwrap()
+        );
+    }
+}
@@ -1,17 +1,147 @@
 use async_trait::async_trait;
+use rsa::pkcs8::der::pem::{self, LineEnding};
+use std::fmt;
+use std::str::FromStr;
 use crate::key_management::models::key_models::KeyAlgorithm;
 
+/// 完整性错误信息前缀，错误以 String 形式返回时用于识别
+pub const INTEGRITY_ERROR_PREFIX: &str = "INTEGRITY_ERROR";
+
+/// 密钥材料完整性校验失败
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityError {
+    pub key_id: String,
+}
+
+impl IntegrityError {
+    /// 判断错误信息是否为完整性错误
+    pub fn is_integrity_error(message: &str) -> bool {
+        message.starts_with(INTEGRITY_ERROR_PREFIX)
+    }
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: 密钥 {} 的材料完整性校验失败", INTEGRITY_ERROR_PREFIX, self.key_id)
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// Argon2id 参数下限，低于下限的配置会被拒绝（参考 OWASP 建议的 19 MiB / 2 次迭代）
+pub const KDF_MIN_MEMORY_KIB: u32 = 19 * 1024;
+pub const KDF_MIN_ITERATIONS: u32 = 2;
+pub const KDF_MIN_PARALLELISM: u32 = 1;
+
+/// 口令派生密钥参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// 创建派生参数，低于下限时返回错误
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, String> {
+        if memory_kib < KDF_MIN_MEMORY_KIB {
+            return Err(format!("KDF内存参数不能小于 {} KiB: {}", KDF_MIN_MEMORY_KIB, memory_kib));
+        }
+        if iterations < KDF_MIN_ITERATIONS {
+            return Err(format!("KDF迭代次数不能小于 {}: {}", KDF_MIN_ITERATIONS, iterations));
+        }
+        if parallelism < KDF_MIN_PARALLELISM {
+            return Err(format!("KDF并行度不能小于 {}: {}", KDF_MIN_PARALLELISM, parallelism));
+        }
+
+        Ok(Self { memory_kib, iterations, parallelism })
+    }
+
+    /// 各项代价参数都不低于 `policy` 时返回 true
+    pub fn meets(&self, policy: &KdfParams) -> bool {
+        self.memory_kib >= policy.memory_kib && self.iterations >= policy.iterations && self.parallelism >= policy.parallelism
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+/// 公钥导出格式，两种格式都是 SubjectPublicKeyInfo 结构
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicKeyFormat {
+    Der,
+    Pem,
+}
+
+impl fmt::Display for PublicKeyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicKeyFormat::Der => write!(f, "DER"),
+            PublicKeyFormat::Pem => write!(f, "PEM"),
+        }
+    }
+}
+
+impl FromStr for PublicKeyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "DER" => Ok(PublicKeyFormat::Der),
+            "PEM" => Ok(PublicKeyFormat::Pem),
+            _ => Err(format!("Invalid public key format: {}", s)),
+        }
+    }
+}
+
+impl PublicKeyFormat {
+    /// 将 DER 编码的 SubjectPublicKeyInfo 转换为当前格式
+    pub fn encode(&self, spki_der: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            PublicKeyFormat::Der => Ok(spki_der.to_vec()),
+            PublicKeyFormat::Pem => pem::encode_string("PUBLIC KEY", LineEnding::LF, spki_der)
+                .map(String::into_bytes)
+                .map_err(|e| format!("PEM编码失败: {}", e)),
+        }
+    }
+}
+
 /// 安全模块接口
 #[async_trait]
 pub trait SecurityModuleInterface: Send + Sync {
     async fn generate_key(&self, algorithm: KeyAlgorithm) -> Result<Vec<u8>, String>;
     async fn store_key(&self, key_id: &str, key_data: &[u8]) -> Result<(), String>;
+    /// 导入指定算法的密钥材料，按算法校验长度和格式；默认直接调用 `store_key`
+    async fn import_key(&self, key_id: &str, _algorithm: KeyAlgorithm, key_data: &[u8]) -> Result<(), String> {
+        self.store_key(key_id, key_data).await
+    }
     async fn retrieve_key(&self, key_id: &str) -> Result<Vec<u8>, String>;
     async fn delete_key(&self, key_id: &str) -> Result<(), String>;
     async fn sign_data(&self, key_id: &str, data: &[u8]) -> Result<Vec<u8>, String>;
     async fn verify_signature(&self, key_id: &str, data: &[u8], signature: &[u8]) -> Result<bool, String>;
-    async fn encrypt_data(&self, key_id: &str, data: &[u8]) -> Result<Vec<u8>, String>;
-    async fn decrypt_data(&self, key_id: &str, encrypted_data: &[u8]) -> Result<Vec<u8>, String>;
+    /// 加密数据，`aad` 为附加认证数据（可为空），解密时必须提供相同的 `aad`
+    async fn encrypt_data(&self, key_id: &str, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, String>;
+    async fn decrypt_data(&self, key_id: &str, encrypted_data: &[u8], aad: &[u8]) -> Result<Vec<u8>, String>;
+    /// 确定性加密：相同密钥下相同明文总是得到相同密文，密文用 `decrypt_data` 解密
+    ///
+    /// 用于需要按密文查找或去重的场景。代价是密文会暴露明文是否相同，
+    /// 攻击者可以据此做频率分析或确认猜测的明文；低熵数据（如枚举值）不应使用
+    async fn encrypt_data_deterministic(&self, _key_id: &str, _data: &[u8], _aad: &[u8]) -> Result<Vec<u8>, String> {
+        Err("安全模块不支持确定性加密".to_string())
+    }
+    /// 从口令和盐派生密钥材料，相同的口令和盐总是得到相同的结果
+    async fn derive_key(&self, password: &[u8], salt: &[u8], algorithm: KeyAlgorithm, params: &KdfParams) -> Result<Vec<u8>, String>;
+    /// 获取非对称密钥的公钥，私钥本身不可导出时也可以调用，对称密钥返回错误
+    async fn get_public_key(&self, key_id: &str, format: PublicKeyFormat) -> Result<Vec<u8>, String>;
+    /// 主密钥轮换后用当前主密钥重新包装密钥材料，已由当前主密钥包装时返回 false
+    async fn rewrap_key(&self, key_id: &str) -> Result<bool, String>;
 }
 
 /// 模拟HSM实现
@@ -49,13 +179,28 @@ impl SecurityModuleInterface for MockHSM {
         Ok(true)
     }
 
-    async fn encrypt_data(&self, _key_id: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    async fn encrypt_data(&self, _key_id: &str, data: &[u8], _aad: &[u8]) -> Result<Vec<u8>, String> {
         // 模拟加密
         Ok(data.to_vec())
     }
 
-    async fn decrypt_data(&self, _key_id: &str, encrypted_data: &[u8]) -> Result<Vec<u8>, String> {
+    async fn decrypt_data(&self, _key_id: &str, encrypted_data: &[u8], _aad: &[u8]) -> Result<Vec<u8>, String> {
         // 模拟解密
         Ok(encrypted_data.to_vec())
     }
+
+    async fn derive_key(&self, _password: &[u8], _salt: &[u8], _algorithm: KeyAlgorithm, _params: &KdfParams) -> Result<Vec<u8>, String> {
+        // 模拟派生密钥
+        Ok(vec![0; 32])
+    }
+
+    async fn get_public_key(&self, _key_id: &str, _format: PublicKeyFormat) -> Result<Vec<u8>, String> {
+        // 模拟公钥
+        Ok(vec![0; 32])
+    }
+
+    async fn rewrap_key(&self, _key_id: &str) -> Result<bool, String> {
+        // 模拟HSM不包装密钥材料
+        Ok(false)
+    }
 }
\ No newline at end of file
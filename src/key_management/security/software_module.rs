@@ -0,0 +1,869 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use async_trait::async_trait;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, UnparsedPublicKey};
+use rsa::pkcs8::der::asn1::BitString;
+use rsa::pkcs8::der::{Any, Encode};
+use rsa::pkcs8::spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SubjectPublicKeyInfoOwned};
+use rsa::pkcs8::EncodePrivateKey;
+use rsa::RsaPrivateKey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::key_management::models::key_models::KeyAlgorithm;
+use crate::key_management::security::p256;
+use crate::random::RandomSource;
+use crate::key_management::security::security_module::{IntegrityError, KdfParams, PublicKeyFormat, SecurityModuleInterface};
+
+const AES_256_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const ED25519_SEED_LEN: usize = 32;
+
+// 由主密钥派生包装密钥时使用的标签
+const WRAPPING_KEY_LABEL: &[u8] = b"password_manager key wrap";
+
+// 确定性加密时由数据密钥派生 nonce 密钥使用的标签
+const SYNTHETIC_NONCE_LABEL: &[u8] = b"password_manager synthetic nonce";
+
+// 由 32 字节种子构造 Ed25519 PKCS#8 v1 文档时使用的前缀
+const ED25519_PKCS8_V1_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+// SubjectPublicKeyInfo 中使用的算法标识
+const OID_RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const OID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const OID_PRIME256V1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const OID_ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+// 存储的密钥材料：nonce || 密文 || 认证标签，由 master_version 指定的主密钥包装
+//
+// 以材料地址为关联数据包装，refs 为引用该材料的密钥数
+struct StoredKey {
+    wrapped: Vec<u8>,
+    master_version: u32,
+    refs: usize,
+}
+
+// 密钥ID到材料地址的映射和按地址保存的材料
+//
+// 未启用去重时地址就是密钥ID；启用去重后地址由材料内容计算，相同材料只保存一份
+#[derive(Default)]
+struct KeyEntries {
+    addresses: HashMap<String, String>,
+    materials: HashMap<String, StoredKey>,
+}
+
+impl KeyEntries {
+    // 密钥ID对应的材料
+    fn get(&self, key_id: &str) -> Option<(&String, &StoredKey)> {
+        let address = self.addresses.get(key_id)?;
+        self.materials.get(address).map(|stored| (address, stored))
+    }
+
+    // 释放一次对材料的引用，没有密钥引用时删除材料
+    fn release(&mut self, address: &str) {
+        if let Some(stored) = self.materials.get_mut(address) {
+            stored.refs -= 1;
+            if stored.refs == 0 {
+                self.materials.remove(address);
+            }
+        }
+    }
+}
+
+// 各版本主密钥派生出的包装密钥，轮换后旧版本保留到不再有密钥使用为止
+struct MasterKeys {
+    current: u32,
+    keys: HashMap<u32, LessSafeKey>,
+}
+
+// 解析后的密钥材料，根据格式区分算法
+enum ParsedKey {
+    Symmetric(Vec<u8>),
+    Ed25519(Ed25519KeyPair),
+    Ecdsa(EcdsaKeyPair),
+    Rsa(RsaKeyPair),
+}
+
+/// 可共享的密钥存储
+///
+/// 克隆得到的是同一份存储的句柄。密钥材料由存储自身的主密钥以 AES-256-GCM 包装，
+/// 因此所有共享同一存储的安全模块都能读取彼此写入的密钥。
+/// 主密钥轮换后，旧主密钥包装的材料仍可读取，`rewrap_key` 将其改为由当前主密钥包装。
+///
+/// 通过 `with_deduplication` 启用去重后，材料字节完全相同的密钥共享同一份存储，
+/// 删除密钥只有在最后一个引用消失时才删除材料
+#[derive(Clone)]
+pub struct SharedKeyStore {
+    masters: Arc<Mutex<MasterKeys>>,
+    keys: Arc<Mutex<KeyEntries>>,
+    dedup_key: Option<Arc<hmac::Key>>, // 计算材料地址的 HMAC 密钥，启用去重时才有
+}
+
+impl SharedKeyStore {
+    /// 使用随机生成的主密钥创建存储
+    pub fn new() -> Self {
+        let mut master_key = [0u8; AES_256_KEY_LEN];
+        SystemRandom::new().fill(&mut master_key).expect("生成主密钥失败");
+        Self::with_master_key(&master_key)
+    }
+
+    /// 使用指定的主密钥创建存储
+    pub fn with_master_key(master_key: &[u8]) -> Self {
+        Self {
+            masters: Arc::new(Mutex::new(MasterKeys {
+                current: 1,
+                keys: HashMap::from([(1, Self::wrapping_key(master_key))]),
+            })),
+            keys: Arc::new(Mutex::new(KeyEntries::default())),
+            dedup_key: None,
+        }
+    }
+
+    /// 启用按内容去重，应在写入任何密钥之前调用
+    ///
+    /// 材料地址是带密钥的 HMAC-SHA256，密钥随机生成且只保存在内存中，
+    /// 因此地址本身不能用来离线确认猜测的密钥材料
+    pub fn with_deduplication(mut self) -> Self {
+        let mut dedup_key = [0u8; AES_256_KEY_LEN];
+        SystemRandom::new().fill(&mut dedup_key).expect("生成去重密钥失败");
+        self.dedup_key = Some(Arc::new(hmac::Key::new(hmac::HMAC_SHA256, &dedup_key)));
+        self
+    }
+
+    /// 实际保存的密钥材料份数，启用去重时可能少于密钥数
+    pub fn material_count(&self) -> usize {
+        self.keys.lock().unwrap().materials.len()
+    }
+
+    /// 轮换主密钥，之后写入的材料使用新主密钥包装，返回新主密钥的版本
+    ///
+    /// 旧主密钥保留用于读取尚未重新包装的材料，全部重新包装后可以用 `retire_master_key` 删除
+    pub fn rotate_master_key(&self, master_key: &[u8]) -> u32 {
+        let mut masters = self.masters.lock().unwrap();
+        let version = masters.keys.keys().max().copied().unwrap_or(0) + 1;
+        masters.keys.insert(version, Self::wrapping_key(master_key));
+        masters.current = version;
+        version
+    }
+
+    /// 当前主密钥的版本
+    pub fn current_master_version(&self) -> u32 {
+        self.masters.lock().unwrap().current
+    }
+
+    /// 仍由旧主密钥包装的密钥ID
+    pub fn keys_needing_rewrap(&self) -> Vec<String> {
+        let masters = self.masters.lock().unwrap();
+        let keys = self.keys.lock().unwrap();
+        let mut ids: Vec<String> = keys.addresses.keys()
+            .filter(|key_id| keys.get(key_id).is_some_and(|(_, stored)| stored.master_version != masters.current))
+            .cloned()
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// 删除旧主密钥，仍有材料由它包装时返回错误
+    pub fn retire_master_key(&self, version: u32) -> Result<(), String> {
+        let mut masters = self.masters.lock().unwrap();
+        if version == masters.current {
+            return Err(format!("不能删除当前主密钥: {}", version));
+        }
+        let keys = self.keys.lock().unwrap();
+        let in_use = keys.addresses.keys()
+            .filter(|key_id| keys.get(key_id).is_some_and(|(_, stored)| stored.master_version == version))
+            .count();
+        if in_use > 0 {
+            return Err(format!("主密钥 {} 仍包装着 {} 个密钥", version, in_use));
+        }
+        masters.keys.remove(&version)
+            .map(|_| ())
+            .ok_or_else(|| format!("主密钥不存在: {}", version))
+    }
+
+    // 主密钥可以是任意长度，经 HMAC-SHA256 派生出 AES-256 包装密钥
+    fn wrapping_key(master_key: &[u8]) -> LessSafeKey {
+        let derived = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, master_key), WRAPPING_KEY_LABEL);
+        LessSafeKey::new(UnboundKey::new(&aead::AES_256_GCM, derived.as_ref()).expect("派生包装密钥失败"))
+    }
+
+    // 材料地址：未启用去重时为密钥ID，启用后为材料内容的 HMAC
+    fn address(&self, key_id: &str, material: &[u8]) -> String {
+        match &self.dedup_key {
+            Some(dedup_key) => {
+                let tag = hmac::sign(dedup_key, material);
+                let hex: String = tag.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("sha256:{}", hex)
+            }
+            None => key_id.to_string(),
+        }
+    }
+
+    // 关联数据为材料地址，防止材料被挪到其他密钥下使用
+    fn wrap(wrapping_key: &LessSafeKey, address: &str, material: &[u8]) -> Result<Vec<u8>, String> {
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce_bytes).map_err(|_| "生成nonce失败".to_string())?;
+
+        let mut in_out = material.to_vec();
+        wrapping_key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce_bytes), Aad::from(address.as_bytes()), &mut in_out)
+            .map_err(|_| "包装密钥材料失败".to_string())?;
+
+        let mut wrapped = nonce_bytes.to_vec();
+        wrapped.extend(in_out);
+        Ok(wrapped)
+    }
+
+    fn unwrap(wrapping_key: &LessSafeKey, key_id: &str, address: &str, wrapped: &[u8]) -> Result<Vec<u8>, String> {
+        let integrity_error = || IntegrityError { key_id: key_id.to_string() }.to_string();
+        if wrapped.len() < NONCE_LEN {
+            return Err(integrity_error());
+        }
+
+        let (nonce_bytes, ciphertext) = wrapped.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce_bytes).map_err(|_| integrity_error())?;
+        let mut in_out = ciphertext.to_vec();
+        let material = wrapping_key
+            .open_in_place(nonce, Aad::from(address.as_bytes()), &mut in_out)
+            .map_err(|_| integrity_error())?;
+        Ok(material.to_vec())
+    }
+
+    // 写入密钥材料，密钥已存在时释放对旧材料的引用
+    fn store(&self, key_id: &str, material: &[u8]) -> Result<(), String> {
+        let address = self.address(key_id, material);
+        let masters = self.masters.lock().unwrap();
+        let mut keys = self.keys.lock().unwrap();
+
+        // 先包装再修改映射，包装失败时保持原状
+        let wrapped = Self::wrap(&masters.keys[&masters.current], &address, material)?;
+
+        if let Some(previous) = keys.addresses.insert(key_id.to_string(), address.clone()) {
+            keys.release(&previous);
+        }
+        // 已有相同材料时只增加引用，新包装的结果丢弃
+        keys.materials.entry(address)
+            .and_modify(|stored| stored.refs += 1)
+            .or_insert(StoredKey {
+                wrapped,
+                master_version: masters.current,
+                refs: 1,
+            });
+        Ok(())
+    }
+
+    fn load(&self, key_id: &str) -> Result<Vec<u8>, String> {
+        let masters = self.masters.lock().unwrap();
+        let keys = self.keys.lock().unwrap();
+        let (address, stored) = keys.get(key_id).ok_or_else(|| format!("密钥不存在: {}", key_id))?;
+        let wrapping_key = masters.keys.get(&stored.master_version)
+            .ok_or_else(|| format!("主密钥不存在: {}", stored.master_version))?;
+        Self::unwrap(wrapping_key, key_id, address, &stored.wrapped)
+    }
+
+    // 删除密钥，材料在没有其他密钥引用时一并删除
+    fn delete(&self, key_id: &str) {
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(address) = keys.addresses.remove(key_id) {
+            keys.release(&address);
+        }
+    }
+
+    /// 用当前主密钥重新包装一个密钥，已由当前主密钥包装时返回 false
+    ///
+    /// 解包和重新包装在同一次加锁内完成，新材料写入前旧材料一直保留，可以安全地重复执行。
+    /// 启用去重时共享材料的密钥一起完成重新包装
+    pub fn rewrap_key(&self, key_id: &str) -> Result<bool, String> {
+        let masters = self.masters.lock().unwrap();
+        let mut keys = self.keys.lock().unwrap();
+        let address = keys.addresses.get(key_id).cloned().ok_or_else(|| format!("密钥不存在: {}", key_id))?;
+        let stored = keys.materials.get_mut(&address).ok_or_else(|| format!("密钥不存在: {}", key_id))?;
+        if stored.master_version == masters.current {
+            return Ok(false);
+        }
+
+        let old_key = masters.keys.get(&stored.master_version)
+            .ok_or_else(|| format!("主密钥不存在: {}", stored.master_version))?;
+        let material = Self::unwrap(old_key, key_id, &address, &stored.wrapped)?;
+        stored.wrapped = Self::wrap(&masters.keys[&masters.current], &address, &material)?;
+        stored.master_version = masters.current;
+        Ok(true)
+    }
+}
+
+impl Default for SharedKeyStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 软件安全模块
+///
+/// 密钥材料由存储主密钥以 AES-256-GCM 包装后保存在进程内存中，
+/// 解包时校验认证标签，发现损坏返回 `IntegrityError`。
+/// 对称密钥使用 AES-256-GCM 加解密、HMAC-SHA256 签名；非对称密钥以 PKCS#8 DER 存储。
+/// 口令派生密钥使用 Argon2id。
+///
+/// 每个实例默认拥有独立的存储；多个插件需要看到同一批密钥时，
+/// 共享同一个模块实例，或通过 `with_shared_store` 让多个实例使用同一个 `SharedKeyStore`
+pub struct SoftwareSecurityModule {
+    store: SharedKeyStore,
+    rng: SystemRandom,     // ring 签名使用的随机数
+    random: RandomSource, // 密钥材料和 nonce 使用的随机数
+}
+
+impl SoftwareSecurityModule {
+    /// 使用随机生成的主密钥创建安全模块
+    pub fn new() -> Self {
+        Self::with_shared_store(SharedKeyStore::new())
+    }
+
+    /// 使用指定的主密钥创建安全模块
+    pub fn with_master_key(master_key: &[u8]) -> Self {
+        Self::with_shared_store(SharedKeyStore::with_master_key(master_key))
+    }
+
+    /// 使用已有的密钥存储创建安全模块，写入的密钥对共享该存储的其他实例立即可见
+    pub fn with_shared_store(store: SharedKeyStore) -> Self {
+        Self {
+            store,
+            rng: SystemRandom::new(),
+            random: RandomSource::os(),
+        }
+    }
+
+    /// 替换生成密钥材料和 nonce 使用的随机数来源
+    ///
+    /// 使用固定种子的来源时生成的密钥可以复现，只用于测试。ECDSA 签名本身仍使用系统随机数
+    pub fn with_random_source(mut self, random: RandomSource) -> Self {
+        self.random = random;
+        self
+    }
+
+    /// 获取当前使用的密钥存储句柄
+    pub fn shared_store(&self) -> SharedKeyStore {
+        self.store.clone()
+    }
+
+    fn load_verified(&self, key_id: &str) -> Result<Vec<u8>, String> {
+        self.store.load(key_id)
+    }
+
+    // 校验 store_key 收到的密钥材料：必须能识别为支持的密钥，RSA 模数只能是 2048 或 4096 位
+    fn check_material(&self, material: &[u8]) -> Result<(), String> {
+        match self.parse_key(material)? {
+            ParsedKey::Rsa(key_pair) => match key_pair.public().modulus_len() * 8 {
+                2048 | 4096 => Ok(()),
+                bits => Err(format!("RSA密钥长度无效: {} 位，只支持 2048 或 4096 位", bits)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // 按算法校验导入的密钥材料，返回实际存储的内容
+    //
+    // AES-256 必须是 32 字节；RSA 必须是模数位数与算法一致的 PKCS#8 DER；
+    // Ed25519 接受 PKCS#8 或 32 字节的种子，种子转换为 PKCS#8 后存储；ECDSA 只接受 PKCS#8
+    fn import_material(&self, algorithm: &KeyAlgorithm, key_data: &[u8]) -> Result<Vec<u8>, String> {
+        let name = algorithm.to_string();
+        match algorithm {
+            KeyAlgorithm::AES256 if key_data.len() == AES_256_KEY_LEN => Ok(key_data.to_vec()),
+            KeyAlgorithm::AES256 => Err(format!(
+                "{} 密钥材料长度无效: 应为 {} 字节，实际为 {} 字节",
+                name, AES_256_KEY_LEN, key_data.len()
+            )),
+            KeyAlgorithm::RSA2048 | KeyAlgorithm::RSA4096 => {
+                let expected = if *algorithm == KeyAlgorithm::RSA2048 { 2048 } else { 4096 };
+                let key_pair = RsaKeyPair::from_pkcs8(key_data).map_err(|_| format!(
+                    "{} 密钥材料无效: 应为 PKCS#8 DER，{} 字节的数据无法解析",
+                    name, key_data.len()
+                ))?;
+                match key_pair.public().modulus_len() * 8 {
+                    bits if bits == expected => Ok(key_data.to_vec()),
+                    bits => Err(format!("{} 密钥长度无效: 应为 {} 位，实际为 {} 位", name, expected, bits)),
+                }
+            }
+            KeyAlgorithm::ED25519 if key_data.len() == ED25519_SEED_LEN => {
+                let mut document = ED25519_PKCS8_V1_PREFIX.to_vec();
+                document.extend_from_slice(key_data);
+                Ok(document)
+            }
+            KeyAlgorithm::ED25519 => match Ed25519KeyPair::from_pkcs8_maybe_unchecked(key_data) {
+                Ok(_) => Ok(key_data.to_vec()),
+                Err(_) => Err(format!(
+                    "{} 密钥材料无效: 应为 {} 字节的种子或 PKCS#8 文档，实际为 {} 字节",
+                    name, ED25519_SEED_LEN, key_data.len()
+                )),
+            },
+            KeyAlgorithm::ECDSA => {
+                match EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, key_data, &self.rng) {
+                    Ok(_) => Ok(key_data.to_vec()),
+                    Err(_) => Err(format!(
+                        "{} 密钥材料无效: 应为 P-256 的 PKCS#8 文档，{} 字节的数据无法解析",
+                        name, key_data.len()
+                    )),
+                }
+            }
+        }
+    }
+
+    fn parse_key(&self, material: &[u8]) -> Result<ParsedKey, String> {
+        if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(material) {
+            return Ok(ParsedKey::Ed25519(key_pair));
+        }
+        if let Ok(key_pair) = EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, material, &self.rng) {
+            return Ok(ParsedKey::Ecdsa(key_pair));
+        }
+        if let Ok(key_pair) = RsaKeyPair::from_pkcs8(material) {
+            return Ok(ParsedKey::Rsa(key_pair));
+        }
+        if material.len() == AES_256_KEY_LEN {
+            return Ok(ParsedKey::Symmetric(material.to_vec()));
+        }
+
+        Err(format!("无法识别的密钥材料格式 ({} 字节)", material.len()))
+    }
+
+    // 按 RFC 5280 构造 DER 编码的 SubjectPublicKeyInfo
+    fn spki_der(oid: ObjectIdentifier, parameters: Option<Any>, public_key: &[u8]) -> Result<Vec<u8>, String> {
+        let spki = SubjectPublicKeyInfoOwned {
+            algorithm: AlgorithmIdentifierOwned { oid, parameters },
+            subject_public_key: BitString::from_bytes(public_key).map_err(|e| format!("编码公钥失败: {}", e))?,
+        };
+        spki.to_der().map_err(|e| format!("编码公钥失败: {}", e))
+    }
+
+    fn aead_key(material: &[u8]) -> Result<LessSafeKey, String> {
+        let key = UnboundKey::new(&aead::AES_256_GCM, material)
+            .map_err(|_| "无效的AES-256密钥".to_string())?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+impl Default for SoftwareSecurityModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl SecurityModuleInterface for SoftwareSecurityModule {
+    async fn generate_key(&self, algorithm: KeyAlgorithm) -> Result<Vec<u8>, String> {
+        match algorithm {
+            KeyAlgorithm::AES256 => {
+                let mut key = vec![0u8; AES_256_KEY_LEN];
+                self.random.fill(&mut key);
+                Ok(key)
+            }
+            KeyAlgorithm::RSA2048 | KeyAlgorithm::RSA4096 => {
+                let bits = if algorithm == KeyAlgorithm::RSA2048 { 2048 } else { 4096 };
+                let mut random = self.random.clone();
+                // RSA密钥生成耗时较长，放到阻塞线程中执行
+                tokio::task::spawn_blocking(move || {
+                    let private_key = RsaPrivateKey::new(&mut random, bits)
+                        .map_err(|e| format!("生成RSA密钥失败: {}", e))?;
+                    private_key.to_pkcs8_der()
+                        .map(|der| der.as_bytes().to_vec())
+                        .map_err(|e| format!("编码RSA密钥失败: {}", e))
+                })
+                .await
+                .map_err(|e| format!("生成RSA密钥失败: {}", e))?
+            }
+            // ring 只接受系统随机数，固定种子时由测试用的 P-256 实现构造 PKCS#8 文档
+            KeyAlgorithm::ECDSA if self.random.is_seeded() => p256::generate_seeded_pkcs8(&self.random),
+            KeyAlgorithm::ED25519 if self.random.is_seeded() => {
+                let mut document = ED25519_PKCS8_V1_PREFIX.to_vec();
+                let mut seed = [0u8; ED25519_SEED_LEN];
+                self.random.fill(&mut seed);
+                document.extend_from_slice(&seed);
+                Ok(document)
+            }
+            KeyAlgorithm::ECDSA => {
+                EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, &self.rng)
+                    .map(|doc| doc.as_ref().to_vec())
+                    .map_err(|_| "生成ECDSA密钥失败".to_string())
+            }
+            KeyAlgorithm::ED25519 => {
+                Ed25519KeyPair::generate_pkcs8(&self.rng)
+                    .map(|doc| doc.as_ref().to_vec())
+                    .map_err(|_| "生成Ed25519密钥失败".to_string())
+            }
+        }
+    }
+
+    async fn store_key(&self, key_id: &str, key_data: &[u8]) -> Result<(), String> {
+        self.check_material(key_data)?;
+        self.store.store(key_id, key_data)
+    }
+
+    async fn import_key(&self, key_id: &str, algorithm: KeyAlgorithm, key_data: &[u8]) -> Result<(), String> {
+        let material = self.import_material(&algorithm, key_data)?;
+        self.store.store(key_id, &material)
+    }
+
+    async fn retrieve_key(&self, key_id: &str) -> Result<Vec<u8>, String> {
+        self.load_verified(key_id)
+    }
+
+    async fn delete_key(&self, key_id: &str) -> Result<(), String> {
+        self.store.delete(key_id);
+        Ok(())
+    }
+
+    async fn sign_data(&self, key_id: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+        let material = self.load_verified(key_id)?;
+
+        match self.parse_key(&material)? {
+            ParsedKey::Symmetric(key) => {
+                let key = hmac::Key::new(hmac::HMAC_SHA256, &key);
+                Ok(hmac::sign(&key, data).as_ref().to_vec())
+            }
+            ParsedKey::Ed25519(key_pair) => Ok(key_pair.sign(data).as_ref().to_vec()),
+            ParsedKey::Ecdsa(key_pair) => key_pair.sign(&self.rng, data)
+                .map(|sig| sig.as_ref().to_vec())
+                .map_err(|_| "ECDSA签名失败".to_string()),
+            ParsedKey::Rsa(key_pair) => {
+                let mut signature = vec![0u8; key_pair.public().modulus_len()];
+                key_pair.sign(&signature::RSA_PKCS1_SHA256, &self.rng, data, &mut signature)
+                    .map_err(|_| "RSA签名失败".to_string())?;
+                Ok(signature)
+            }
+        }
+    }
+
+    async fn verify_signature(&self, key_id: &str, data: &[u8], signature: &[u8]) -> Result<bool, String> {
+        let material = self.load_verified(key_id)?;
+
+        let valid = match self.parse_key(&material)? {
+            ParsedKey::Symmetric(key) => {
+                let key = hmac::Key::new(hmac::HMAC_SHA256, &key);
+                hmac::verify(&key, data, signature).is_ok()
+            }
+            ParsedKey::Ed25519(key_pair) => {
+                UnparsedPublicKey::new(&signature::ED25519, key_pair.public_key().as_ref())
+                    .verify(data, signature)
+                    .is_ok()
+            }
+            ParsedKey::Ecdsa(key_pair) => {
+                UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, key_pair.public_key().as_ref())
+                    .verify(data, signature)
+                    .is_ok()
+            }
+            ParsedKey::Rsa(key_pair) => {
+                UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, key_pair.public().as_ref())
+                    .verify(data, signature)
+                    .is_ok()
+            }
+        };
+
+        Ok(valid)
+    }
+
+    async fn encrypt_data(&self, key_id: &str, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+        let material = self.load_verified(key_id)?;
+        let key = match self.parse_key(&material)? {
+            ParsedKey::Symmetric(key) => Self::aead_key(&key)?,
+            _ => return Err("只支持使用对称密钥加密".to_string()),
+        };
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        self.random.fill(&mut nonce_bytes);
+
+        // 输出格式: nonce || 密文 || 认证标签
+        let mut in_out = data.to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce_bytes), Aad::from(aad), &mut in_out)
+            .map_err(|_| "加密失败".to_string())?;
+
+        let mut result = nonce_bytes.to_vec();
+        result.extend_from_slice(&in_out);
+        Ok(result)
+    }
+
+    // 与 AES-GCM-SIV 思路相同的合成 IV 构造：nonce 取自明文的 HMAC-SHA256，再用 AES-256-GCM 加密，
+    // 输出格式与随机模式相同。ring 不提供 RFC 8452 的 AES-GCM-SIV；nonce 只有 96 位，
+    // n 个不同明文出现 nonce 碰撞的概率约为 n²/2^97，碰撞会破坏 GCM 的安全性，
+    // 因此同一密钥下确定性加密的不同明文数量应远小于 2^32
+    async fn encrypt_data_deterministic(&self, key_id: &str, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+        let material = self.load_verified(key_id)?;
+        let (key, nonce_key) = match self.parse_key(&material)? {
+            ParsedKey::Symmetric(key) => {
+                // nonce 密钥与加密密钥分离
+                let nonce_key = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), SYNTHETIC_NONCE_LABEL);
+                (Self::aead_key(&key)?, hmac::Key::new(hmac::HMAC_SHA256, nonce_key.as_ref()))
+            }
+            _ => return Err("只支持使用对称密钥加密".to_string()),
+        };
+
+        // nonce 同时覆盖 aad 和明文，aad 前加长度避免两者的边界产生歧义
+        let mut context = hmac::Context::with_key(&nonce_key);
+        context.update(&(aad.len() as u64).to_be_bytes());
+        context.update(aad);
+        context.update(data);
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        nonce_bytes.copy_from_slice(&context.sign().as_ref()[..NONCE_LEN]);
+
+        // 输出格式: nonce || 密文 || 认证标签
+        let mut in_out = data.to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce_bytes), Aad::from(aad), &mut in_out)
+            .map_err(|_| "加密失败".to_string())?;
+
+        let mut result = nonce_bytes.to_vec();
+        result.extend_from_slice(&in_out);
+        Ok(result)
+    }
+
+    async fn decrypt_data(&self, key_id: &str, encrypted_data: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+        let material = self.load_verified(key_id)?;
+        let key = match self.parse_key(&material)? {
+            ParsedKey::Symmetric(key) => Self::aead_key(&key)?,
+            _ => return Err("只支持使用对称密钥解密".to_string()),
+        };
+
+        if encrypted_data.len() < NONCE_LEN + aead::AES_256_GCM.tag_len() {
+            return Err("密文长度无效".to_string());
+        }
+
+        let (nonce_bytes, ciphertext) = encrypted_data.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce_bytes)
+            .map_err(|_| "无效的nonce".to_string())?;
+
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = key.open_in_place(nonce, Aad::from(aad), &mut in_out)
+            .map_err(|_| "解密失败: 密文、密钥或附加认证数据不匹配".to_string())?;
+        Ok(plaintext.to_vec())
+    }
+
+    async fn derive_key(&self, password: &[u8], salt: &[u8], algorithm: KeyAlgorithm, params: &KdfParams) -> Result<Vec<u8>, String> {
+        if algorithm != KeyAlgorithm::AES256 {
+            return Err(format!("口令派生只支持对称算法: {}", algorithm.to_string()));
+        }
+
+        // 调用方可能绕过 KdfParams::new 直接构造参数，这里再检查一次下限
+        let params = KdfParams::new(params.memory_kib, params.iterations, params.parallelism)?;
+        let params = Params::new(
+            params.memory_kib,
+            params.iterations,
+            params.parallelism,
+            Some(AES_256_KEY_LEN),
+        ).map_err(|e| format!("无效的Argon2参数: {}", e))?;
+
+        let password = password.to_vec();
+        let salt = salt.to_vec();
+        // Argon2 计算开销较大，放到阻塞线程中执行
+        tokio::task::spawn_blocking(move || {
+            let mut key = vec![0u8; AES_256_KEY_LEN];
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(&password, &salt, &mut key)
+                .map_err(|e| format!("派生密钥失败: {}", e))?;
+            Ok(key)
+        })
+        .await
+        .map_err(|e| format!("派生密钥失败: {}", e))?
+    }
+
+    async fn get_public_key(&self, key_id: &str, format: PublicKeyFormat) -> Result<Vec<u8>, String> {
+        let material = self.load_verified(key_id)?;
+
+        // ring 给出的公钥分别是 Ed25519 原始公钥、ECDSA 未压缩点和 PKCS#1 RSAPublicKey
+        let spki_der = match self.parse_key(&material)? {
+            ParsedKey::Symmetric(_) => return Err(format!("对称密钥没有公钥: {}", key_id)),
+            ParsedKey::Ed25519(key_pair) => {
+                Self::spki_der(OID_ED25519, None, key_pair.public_key().as_ref())?
+            }
+            ParsedKey::Ecdsa(key_pair) => {
+                let curve = Any::encode_from(&OID_PRIME256V1).map_err(|e| format!("编码公钥失败: {}", e))?;
+                Self::spki_der(OID_EC_PUBLIC_KEY, Some(curve), key_pair.public_key().as_ref())?
+            }
+            ParsedKey::Rsa(key_pair) => {
+                Self::spki_der(OID_RSA_ENCRYPTION, Some(Any::null()), key_pair.public().as_ref())?
+            }
+        };
+
+        format.encode(&spki_der)
+    }
+    async fn rewrap_key(&self, key_id: &str) -> Result<bool, String> {
+        self.store.rewrap_key(key_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 翻转存储中某个密钥材料的一个字节
+    fn flip_byte(module: &SoftwareSecurityModule, key_id: &str, index: usize) {
+        let mut store = module.store.keys.lock().unwrap();
+        let address = store.addresses[key_id].clone();
+        store.materials.get_mut(&address).unwrap().wrapped[NONCE_LEN + index] ^= 0x01;
+    }
+
+    async fn stored_module(key_id: &str) -> (SoftwareSecurityModule, Vec<u8>) {
+        let module = SoftwareSecurityModule::with_master_key(&[7u8; AES_256_KEY_LEN]);
+        let material = module.generate_key(KeyAlgorithm::AES256).await.unwrap();
+        module.store_key(key_id, &material).await.unwrap();
+        (module, material)
+    }
+
+    #[tokio::test]
+    async fn flipped_byte_is_reported_as_integrity_error() {
+        for index in [0, AES_256_KEY_LEN / 2, AES_256_KEY_LEN - 1] {
+            let (module, material) = stored_module("k1").await;
+            assert_eq!(module.retrieve_key("k1").await.unwrap(), material);
+
+            flip_byte(&module, "k1", index);
+
+            let err = module.retrieve_key("k1").await.unwrap_err();
+            assert!(IntegrityError::is_integrity_error(&err), "{}", err);
+            assert!(err.contains("k1"), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn corrupted_key_cannot_be_used() {
+        let (module, _) = stored_module("k1").await;
+        flip_byte(&module, "k1", 0);
+
+        let err = module.encrypt_data("k1", b"data", &[]).await.unwrap_err();
+        assert!(IntegrityError::is_integrity_error(&err), "{}", err);
+        let err = module.sign_data("k1", b"data").await.unwrap_err();
+        assert!(IntegrityError::is_integrity_error(&err), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn material_moved_to_another_key_fails_integrity_check() {
+        let (module, _) = stored_module("k1").await;
+        module.store_key("k2", &module.generate_key(KeyAlgorithm::AES256).await.unwrap()).await.unwrap();
+
+        // 关联数据为材料地址（未启用去重时即密钥ID），挪到其他密钥下无法通过校验
+        {
+            let mut store = module.store.keys.lock().unwrap();
+            let moved = StoredKey {
+                wrapped: store.materials["k1"].wrapped.clone(),
+                master_version: store.materials["k1"].master_version,
+                refs: 1,
+            };
+            store.materials.insert("k2".to_string(), moved);
+        }
+
+        let err = module.retrieve_key("k2").await.unwrap_err();
+        assert!(IntegrityError::is_integrity_error(&err), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn rewrap_moves_keys_to_the_current_master_key() {
+        let (module, material) = stored_module("k1").await;
+        let store = module.shared_store();
+        let old_wrapped = store.keys.lock().unwrap().materials["k1"].wrapped.clone();
+
+        assert_eq!(store.rotate_master_key(&[8u8; AES_256_KEY_LEN]), 2);
+        assert_eq!(store.keys_needing_rewrap(), vec!["k1".to_string()]);
+        assert!(store.retire_master_key(1).is_err());
+
+        assert!(store.rewrap_key("k1").unwrap());
+        assert!(!store.rewrap_key("k1").unwrap());
+        assert!(store.keys_needing_rewrap().is_empty());
+        assert_ne!(store.keys.lock().unwrap().materials["k1"].wrapped, old_wrapped);
+
+        // 旧主密钥删除后材料仍可由新主密钥解包
+        store.retire_master_key(1).unwrap();
+        assert!(store.retire_master_key(2).is_err());
+        assert_eq!(module.retrieve_key("k1").await.unwrap(), material);
+    }
+
+    #[tokio::test]
+    async fn deduplicated_material_survives_deleting_one_key() {
+        let store = SharedKeyStore::new().with_deduplication();
+        let module = SoftwareSecurityModule::with_shared_store(store.clone());
+        let material = module.generate_key(KeyAlgorithm::AES256).await.unwrap();
+
+        module.store_key("k1", &material).await.unwrap();
+        module.store_key("k2", &material).await.unwrap();
+        assert_eq!(store.material_count(), 1);
+
+        let ciphertext = module.encrypt_data("k1", b"secret", b"").await.unwrap();
+
+        module.delete_key("k1").await.unwrap();
+        assert!(module.retrieve_key("k1").await.is_err());
+        assert_eq!(store.material_count(), 1);
+
+        // 另一个密钥仍可使用共享的材料
+        assert_eq!(module.retrieve_key("k2").await.unwrap(), material);
+        assert_eq!(module.decrypt_data("k2", &ciphertext, b"").await.unwrap(), b"secret");
+        let signature = module.sign_data("k2", b"data").await.unwrap();
+        assert!(module.verify_signature("k2", b"data", &signature).await.unwrap());
+
+        // 最后一个引用删除后材料一并删除
+        module.delete_key("k2").await.unwrap();
+        assert_eq!(store.material_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn replacing_deduplicated_material_releases_old_reference() {
+        let store = SharedKeyStore::new().with_deduplication();
+        let module = SoftwareSecurityModule::with_shared_store(store.clone());
+        let shared = module.generate_key(KeyAlgorithm::AES256).await.unwrap();
+        let other = module.generate_key(KeyAlgorithm::AES256).await.unwrap();
+
+        module.store_key("k1", &shared).await.unwrap();
+        module.store_key("k2", &shared).await.unwrap();
+        module.store_key("k1", &other).await.unwrap();
+        assert_eq!(store.material_count(), 2);
+
+        module.delete_key("k2").await.unwrap();
+        assert_eq!(store.material_count(), 1);
+        assert_eq!(module.retrieve_key("k1").await.unwrap(), other);
+    }
+
+    #[tokio::test]
+    async fn identical_material_is_stored_separately_without_deduplication() {
+        let store = SharedKeyStore::new();
+        let module = SoftwareSecurityModule::with_shared_store(store.clone());
+        let material = module.generate_key(KeyAlgorithm::AES256).await.unwrap();
+
+        module.store_key("k1", &material).await.unwrap();
+        module.store_key("k2", &material).await.unwrap();
+        assert_eq!(store.material_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn wrong_sized_material_is_rejected_for_each_algorithm() {
+        let module = SoftwareSecurityModule::new();
+        let rsa_2048 = module.generate_key(KeyAlgorithm::RSA2048).await.unwrap();
+        let ed25519 = module.generate_key(KeyAlgorithm::ED25519).await.unwrap();
+        let ecdsa = module.generate_key(KeyAlgorithm::ECDSA).await.unwrap();
+        let cases: [(KeyAlgorithm, Vec<u8>); 7] = [
+            (KeyAlgorithm::AES256, vec![1u8; 5]),
+            (KeyAlgorithm::AES256, vec![1u8; AES_256_KEY_LEN + 1]),
+            (KeyAlgorithm::RSA2048, vec![1u8; 256]),
+            (KeyAlgorithm::RSA4096, rsa_2048.clone()),
+            (KeyAlgorithm::ED25519, vec![1u8; ED25519_SEED_LEN - 1]),
+            (KeyAlgorithm::ECDSA, vec![1u8; 31]),
+            // 原始私钥标量不接受
+            (KeyAlgorithm::ECDSA, vec![7u8; 32]),
+        ];
+        for (algorithm, material) in cases {
+            let err = module.import_key("k1", algorithm.clone(), &material).await.unwrap_err();
+            assert!(err.starts_with(&algorithm.to_string()), "{}", err);
+            assert!(module.retrieve_key("k1").await.is_err(), "{}", algorithm.to_string());
+        }
+
+        // store_key 不知道算法，也拒绝无法识别的材料
+        let err = module.store_key("k1", &[1u8; 5]).await.unwrap_err();
+        assert!(err.contains("5 字节"), "{}", err);
+
+        // 长度正确的材料可以导入，Ed25519 种子转换为 PKCS#8 后存储
+        module.import_key("aes", KeyAlgorithm::AES256, &[7u8; AES_256_KEY_LEN]).await.unwrap();
+        module.import_key("rsa", KeyAlgorithm::RSA2048, &rsa_2048).await.unwrap();
+        module.import_key("ed25519", KeyAlgorithm::ED25519, &ed25519).await.unwrap();
+        module.import_key("seed", KeyAlgorithm::ED25519, &[7u8; ED25519_SEED_LEN]).await.unwrap();
+        module.import_key("ecdsa", KeyAlgorithm::ECDSA, &ecdsa).await.unwrap();
+        for key_id in ["seed", "ecdsa"] {
+            let signature = module.sign_data(key_id, b"data").await.unwrap();
+            assert!(module.verify_signature(key_id, b"data", &signature).await.unwrap(), "{}", key_id);
+        }
+    }
+}
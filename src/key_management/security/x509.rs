@@ -0,0 +1,209 @@
+use chrono::{DateTime, Datelike, Utc};
+use rsa::pkcs8::der::pem::{self, LineEnding};
+use rsa::pkcs8::spki::ObjectIdentifier;
+use std::collections::HashMap;
+
+use crate::key_management::models::key_models::KeyAlgorithm;
+
+// DER 标签
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_PRINTABLE_STRING: u8 = 0x13;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xa0;
+
+// 主题属性
+const OID_COUNTRY: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.6");
+const OID_STATE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.8");
+const OID_LOCALITY: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.7");
+const OID_ORGANIZATION: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.10");
+const OID_ORGANIZATIONAL_UNIT: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.11");
+const OID_COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+
+// 签名算法，与安全模块 sign_data 使用的算法一致
+const OID_SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+const OID_ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const OID_ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+/// 证书主题
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubjectName {
+    pub common_name: String,
+    pub organization: Option<String>,
+    pub organizational_unit: Option<String>,
+    pub country: Option<String>,
+    pub state: Option<String>,
+    pub locality: Option<String>,
+}
+
+impl SubjectName {
+    pub fn new(common_name: String) -> Self {
+        Self {
+            common_name,
+            ..Self::default()
+        }
+    }
+
+    /// 从命令参数读取主题，参数名为 CN、O、OU、C、ST、L，其中 CN 必填
+    pub fn from_params(params: &HashMap<String, String>) -> Result<Self, String> {
+        let common_name = match params.get("CN") {
+            Some(common_name) if !common_name.is_empty() => common_name.clone(),
+            _ => return Err("Missing parameter: CN".to_string()),
+        };
+
+        let country = params.get("C").cloned();
+        if let Some(country) = &country
+            && (country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            return Err(format!("Invalid country code: {}", country));
+        }
+
+        Ok(Self {
+            common_name,
+            organization: params.get("O").cloned(),
+            organizational_unit: params.get("OU").cloned(),
+            country,
+            state: params.get("ST").cloned(),
+            locality: params.get("L").cloned(),
+        })
+    }
+
+    /// DER 编码的 Name，每个属性单独一个 RDN
+    pub fn to_der(&self) -> Vec<u8> {
+        let attributes = [
+            (OID_COUNTRY, self.country.as_ref()),
+            (OID_STATE, self.state.as_ref()),
+            (OID_LOCALITY, self.locality.as_ref()),
+            (OID_ORGANIZATION, self.organization.as_ref()),
+            (OID_ORGANIZATIONAL_UNIT, self.organizational_unit.as_ref()),
+            (OID_COMMON_NAME, Some(&self.common_name)),
+        ];
+
+        let mut rdns = Vec::new();
+        for (oid, value) in attributes {
+            let Some(value) = value else { continue };
+            // 国家代码必须是 PrintableString，其余属性使用 UTF8String
+            let value_tag = if oid == OID_COUNTRY { TAG_PRINTABLE_STRING } else { TAG_UTF8_STRING };
+            let attribute = sequence(&[oid_der(&oid), tlv(value_tag, value.as_bytes())]);
+            rdns.extend(tlv(TAG_SET, &attribute));
+        }
+
+        tlv(TAG_SEQUENCE, &rdns)
+    }
+}
+
+/// 与密钥算法对应的签名算法标识（DER 编码的 AlgorithmIdentifier）
+pub fn signature_algorithm(algorithm: &KeyAlgorithm) -> Result<Vec<u8>, String> {
+    match algorithm {
+        // RSA 签名算法的参数为 NULL，ECDSA 和 Ed25519 省略参数
+        KeyAlgorithm::RSA2048 | KeyAlgorithm::RSA4096 => {
+            Ok(sequence(&[oid_der(&OID_SHA256_WITH_RSA), tlv(TAG_NULL, &[])]))
+        }
+        KeyAlgorithm::ECDSA => Ok(sequence(&[oid_der(&OID_ECDSA_WITH_SHA256)])),
+        KeyAlgorithm::ED25519 => Ok(sequence(&[oid_der(&OID_ED25519)])),
+        KeyAlgorithm::AES256 => Err(format!("Algorithm {} cannot sign certificates", algorithm.to_string())),
+    }
+}
+
+/// PKCS#10 中待签名的 CertificationRequestInfo，不带扩展属性
+pub fn certification_request_info(subject: &SubjectName, spki_der: &[u8]) -> Vec<u8> {
+    sequence(&[
+        integer(&[0]),
+        subject.to_der(),
+        spki_der.to_vec(),
+        tlv(TAG_CONTEXT_0, &[]),
+    ])
+}
+
+/// X.509 v3 证书中待签名的 TBSCertificate，颁发者与主题相同（自签名），不带扩展
+pub fn tbs_certificate(
+    serial: &[u8],
+    signature_algorithm: &[u8],
+    subject: &SubjectName,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+    spki_der: &[u8],
+) -> Result<Vec<u8>, String> {
+    if not_after <= not_before {
+        return Err("Certificate not_after must be later than not_before".to_string());
+    }
+
+    let name = subject.to_der();
+    Ok(sequence(&[
+        tlv(TAG_CONTEXT_0, &integer(&[2])), // 版本 v3
+        integer(serial),
+        signature_algorithm.to_vec(),
+        name.clone(),
+        sequence(&[time(not_before), time(not_after)]),
+        name,
+        spki_der.to_vec(),
+    ]))
+}
+
+/// 组装签名后的结构（CertificationRequest 或 Certificate），两者都是 待签名数据、算法、签名 三段
+pub fn signed(to_be_signed: &[u8], signature_algorithm: &[u8], signature: &[u8]) -> Vec<u8> {
+    sequence(&[to_be_signed.to_vec(), signature_algorithm.to_vec(), bit_string(signature)])
+}
+
+/// PEM 编码
+pub fn to_pem(label: &'static str, der: &[u8]) -> Result<String, String> {
+    pem::encode_string(label, LineEnding::LF, der).map_err(|e| format!("PEM编码失败: {}", e))
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        // 长格式：首字节为长度字段的字节数
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(TAG_SEQUENCE, &items.concat())
+}
+
+fn oid_der(oid: &ObjectIdentifier) -> Vec<u8> {
+    tlv(TAG_OID, oid.as_bytes())
+}
+
+// 按无符号大端整数编码，最高位为 1 时补 0 保证为正数
+fn integer(value: &[u8]) -> Vec<u8> {
+    let skip = value.iter().take_while(|b| **b == 0).count().min(value.len().saturating_sub(1));
+    let value = &value[skip..];
+    let mut content = Vec::with_capacity(value.len() + 1);
+    if value.first().is_some_and(|b| b & 0x80 != 0) {
+        content.push(0);
+    }
+    content.extend_from_slice(value);
+    tlv(TAG_INTEGER, &content)
+}
+
+// RFC 5280: 2049 年及以前使用 UTCTime，之后使用 GeneralizedTime，精确到秒
+fn time(value: DateTime<Utc>) -> Vec<u8> {
+    if (1950..2050).contains(&value.year()) {
+        tlv(TAG_UTC_TIME, value.format("%y%m%d%H%M%SZ").to_string().as_bytes())
+    } else {
+        tlv(TAG_GENERALIZED_TIME, value.format("%Y%m%d%H%M%SZ").to_string().as_bytes())
+    }
+}
+
+fn bit_string(value: &[u8]) -> Vec<u8> {
+    let mut content = Vec::with_capacity(value.len() + 1);
+    content.push(0); // 未使用的位数
+    content.extend_from_slice(value);
+    tlv(TAG_BIT_STRING, &content)
+}
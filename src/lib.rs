@@ -1,16 +1,40 @@
 pub mod base_plugin;
+pub mod canonical_json;
+pub mod clock;
 pub mod command_result;
 pub mod example_plugin;
 pub mod key_management;  // 新的模块
+pub mod operation_registry;
 pub mod persistence;
 pub mod plugin_config;
+pub mod plugin_identity;
 pub mod plugin_info;
+pub mod plugin_metrics;
 pub mod plugin_sdk;
+#[cfg(feature = "grpc")]
+pub mod plugin_server;
+pub mod plugin_status;
+pub mod random;
+pub mod retry_jitter;
+pub mod server_features;
+pub mod shutdown;
+pub mod timestamp;
 
-pub use base_plugin::BasePlugin;
-pub use command_result::CommandResult;
+pub use base_plugin::{BasePlugin, OfflineHook};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use command_result::{CommandResult, ErrorCode, ValidationError};
 pub use example_plugin::ExamplePlugin;
 pub use key_management::KeyManagementPlugin;  // 从新模块导出
-pub use plugin_config::PluginConfig;
+pub use operation_registry::{OperationGuard, OperationInfo, OperationRegistry};
+pub use plugin_config::{PluginConfig, RuntimeSettings};
+pub use plugin_identity::{PluginIdentity, RegistrationOutcome};
 pub use plugin_info::PluginInfo;
-pub use plugin_sdk::PluginSDK;
\ No newline at end of file
+pub use plugin_metrics::{CommandStats, PluginMetrics};
+pub use plugin_sdk::PluginSDK;
+#[cfg(feature = "grpc")]
+pub use plugin_server::PluginServer;
+pub use plugin_status::{PluginHealth, PluginState};
+pub use random::RandomSource;
+pub use retry_jitter::JitterStrategy;
+pub use server_features::ServerFeatures;
+pub use shutdown::ShutdownSignals;
\ No newline at end of file
@@ -1,7 +1,6 @@
 
 use std::error::Error;
 use password_manager::{ExamplePlugin, PluginConfig, PluginSDK};
-use tokio::signal;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -26,19 +25,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
     
-    // 启动插件
-    if !plugin.start().await {
-        eprintln!("插件启动失败");
+    println!("插件启动中，按 Ctrl+C 停止...");
+    
+    // 启动插件并运行到收到停止信号
+    if let Err(e) = plugin.run_until_shutdown().await {
+        eprintln!("{}", e);
         return Ok(());
     }
-    
-    println!("插件已启动，按 Ctrl+C 停止...");
-    
-    // 等待中断信号
-    signal::ctrl_c().await?;
-    
-    // 停止插件
-    plugin.stop().await;
     println!("插件已停止");
     
     Ok(())
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// 正在执行的操作
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperationInfo {
+    pub id: String,
+    pub operation_type: String, // 命令名或后台任务名
+    pub user: String,
+    pub tenant: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// 正在执行的命令和后台任务登记表，在插件和后台任务之间共享
+///
+/// 登记时返回 `OperationGuard`，操作结束（guard 被丢弃）时自动注销；
+/// `cancel` 通过操作的取消令牌通知执行方停止
+#[derive(Debug, Clone, Default)]
+pub struct OperationRegistry {
+    operations: Arc<Mutex<HashMap<String, (OperationInfo, CancellationToken)>>>,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记操作，ID 已被占用时返回错误
+    pub fn register(&self, info: OperationInfo) -> Result<OperationGuard, String> {
+        let mut operations = self.operations.lock().unwrap();
+        if operations.contains_key(&info.id) {
+            return Err(format!("Operation already running: {}", info.id));
+        }
+
+        let token = CancellationToken::new();
+        let id = info.id.clone();
+        operations.insert(id.clone(), (info, token.clone()));
+
+        Ok(OperationGuard {
+            id,
+            token,
+            registry: self.clone(),
+        })
+    }
+
+    /// 列出正在执行的操作，按开始时间排序
+    pub fn list(&self) -> Vec<OperationInfo> {
+        let mut list: Vec<OperationInfo> = self.operations.lock().unwrap()
+            .values()
+            .map(|(info, _)| info.clone())
+            .collect();
+        list.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.id.cmp(&b.id)));
+        list
+    }
+
+    /// 取消操作，返回操作是否存在；操作在执行方响应取消后才会从登记表中移除
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.operations.lock().unwrap().get(id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// 已登记的操作，丢弃时从登记表中注销
+pub struct OperationGuard {
+    id: String,
+    token: CancellationToken,
+    registry: OperationRegistry,
+}
+
+impl OperationGuard {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 操作的取消令牌，执行方通过 `cancelled()` 等待取消
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.registry.operations.lock().unwrap().remove(&self.id);
+    }
+}
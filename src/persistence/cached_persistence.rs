@@ -0,0 +1,319 @@
+use async_trait::async_trait;
+use chrono::Duration;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::key_management::models::key_models::{AttachmentInfo, AuditLogEntry, KeyMetadata};
+use super::{KeyQuery, PersistenceInterface};
+
+// 缓存内容
+#[derive(Default)]
+struct CacheState {
+    keys: HashMap<String, KeyMetadata>,
+    complete: bool, // 是否已加载底层存储的全部密钥
+    dirty: HashMap<String, (u64, Option<KeyMetadata>)>, // 回写模式下尚未写入底层存储的修改及其序号，None 表示删除
+    sequence: u64,  // 修改序号，flush 期间被再次修改的密钥不会被误清除
+}
+
+impl CacheState {
+    fn mark_dirty(&mut self, key_id: &str, change: Option<KeyMetadata>) {
+        self.sequence += 1;
+        self.dirty.insert(key_id.to_string(), (self.sequence, change));
+    }
+}
+
+/// 回写模式下 flush 的统计，包括显式调用和后台定时执行的 flush
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FlushStats {
+    pub flushes: u64,         // 成功完成的 flush 次数
+    pub failed_flushes: u64,  // 写入底层存储失败的 flush 次数
+    pub flushed_entries: u64, // 累计写入底层存储的修改数
+    pub last_flush_ms: u64,
+    pub max_flush_ms: u64,
+    pub total_flush_ms: u64,
+}
+
+// 缓存和后台 flush 任务共享的状态
+#[derive(Clone)]
+struct Shared {
+    inner: Arc<dyn PersistenceInterface + Send + Sync>,
+    state: Arc<Mutex<CacheState>>,
+    flush_order: Arc<tokio::sync::Mutex<()>>, // 同一时间只有一个 flush 在写底层存储
+    stats: Arc<Mutex<FlushStats>>,
+}
+
+impl Shared {
+    // 按修改顺序写入积累的修改，遇到失败时停止，未写入的修改保留到下次 flush
+    async fn flush(&self) -> Result<usize, String> {
+        let _order = self.flush_order.lock().await;
+
+        let mut pending: Vec<(String, (u64, Option<KeyMetadata>))> = {
+            let state = self.state.lock().unwrap();
+            state.dirty.iter().map(|(key_id, change)| (key_id.clone(), change.clone())).collect()
+        };
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        pending.sort_by_key(|(_, (sequence, _))| *sequence);
+
+        let started = Instant::now();
+        let mut flushed = 0;
+        let mut result = Ok(());
+        for (key_id, (sequence, change)) in pending {
+            let written = match &change {
+                Some(metadata) => self.inner.save_key_metadata(metadata).await,
+                None => self.inner.delete_key_metadata(&key_id).await,
+            };
+            if let Err(e) = written {
+                result = Err(e);
+                break;
+            }
+
+            // flush 期间同一个密钥又被修改时保留新的修改
+            let mut state = self.state.lock().unwrap();
+            if state.dirty.get(&key_id).is_some_and(|(current, _)| *current == sequence) {
+                state.dirty.remove(&key_id);
+            }
+            flushed += 1;
+        }
+
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let mut stats = self.stats.lock().unwrap();
+        match result {
+            Ok(()) => stats.flushes += 1,
+            Err(_) => stats.failed_flushes += 1,
+        }
+        stats.flushed_entries += flushed as u64;
+        stats.last_flush_ms = elapsed_ms;
+        stats.max_flush_ms = stats.max_flush_ms.max(elapsed_ms);
+        stats.total_flush_ms += elapsed_ms;
+
+        result.map(|()| flushed)
+    }
+}
+
+// 后台定时 flush，停止时再 flush 一次；正在进行的 flush 完成后才响应停止
+async fn flush_loop(shared: Shared, interval: std::time::Duration, token: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = token.cancelled() => break,
+        }
+        if let Err(e) = shared.flush().await {
+            eprintln!("定时写入缓存修改失败: {}", e);
+        }
+    }
+
+    if let Err(e) = shared.flush().await {
+        eprintln!("停止时写入缓存修改失败: {}", e);
+    }
+}
+
+/// 带内存缓存的持久化存储
+///
+/// 包装任意持久化后端，密钥元数据的读取优先命中缓存，列表查询在首次加载全部密钥后在内存中过滤。
+/// 默认写穿：先写底层存储，成功后更新缓存。`with_write_back` 启用回写：修改只更新缓存，
+/// 调用 `flush` 时再写入底层存储，`with_flush_interval` 可以在后台定时 flush。审计日志不缓存，直接读写底层存储
+pub struct CachedPersistence {
+    shared: Shared,
+    write_back: bool,
+    flusher: Mutex<Option<(CancellationToken, JoinHandle<()>)>>, // 后台定时 flush 任务
+}
+
+impl CachedPersistence {
+    pub fn new(inner: Arc<dyn PersistenceInterface + Send + Sync>) -> Self {
+        Self {
+            shared: Shared {
+                inner,
+                state: Arc::new(Mutex::new(CacheState::default())),
+                flush_order: Arc::new(tokio::sync::Mutex::new(())),
+                stats: Arc::new(Mutex::new(FlushStats::default())),
+            },
+            write_back: false,
+            flusher: Mutex::new(None),
+        }
+    }
+
+    /// 启用回写模式，未 flush 的修改在进程退出时会丢失
+    pub fn with_write_back(mut self) -> Self {
+        self.write_back = true;
+        self
+    }
+
+    /// 启用回写模式并每隔 interval 在后台 flush 一次，限制异常退出时丢失的修改；必须在 tokio 运行时中调用
+    ///
+    /// 调用 `stop` 或释放缓存时停止后台任务，停止前再 flush 一次
+    pub fn with_flush_interval(mut self, interval: std::time::Duration) -> Self {
+        self.write_back = true;
+        let token = CancellationToken::new();
+        let task = tokio::spawn(flush_loop(self.shared.clone(), interval, token.clone()));
+        if let Some((previous, _)) = self.flusher.get_mut().unwrap().replace((token, task)) {
+            previous.cancel();
+        }
+        self
+    }
+
+    /// 将回写模式下积累的修改按修改顺序写入底层存储，写入失败的修改保留到下次 flush
+    pub async fn flush(&self) -> Result<usize, String> {
+        self.shared.flush().await
+    }
+
+    /// 停止后台定时 flush，等待最后一次 flush 完成
+    pub async fn stop(&self) {
+        let flusher = self.flusher.lock().unwrap().take();
+        if let Some((token, task)) = flusher {
+            token.cancel();
+            let _ = task.await;
+        }
+    }
+
+    /// flush 次数和耗时统计
+    pub fn flush_stats(&self) -> FlushStats {
+        self.shared.stats.lock().unwrap().clone()
+    }
+
+    /// 清空缓存，之后的读取重新从底层存储加载；回写模式下未 flush 的修改保留
+    pub fn invalidate(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.keys.clear();
+        state.complete = false;
+    }
+
+    // 首次列表查询时加载全部密钥，回写模式下未 flush 的修改优先
+    async fn ensure_complete(&self) -> Result<(), String> {
+        if self.shared.state.lock().unwrap().complete {
+            return Ok(());
+        }
+
+        let list = self.shared.inner.query_keys(&KeyQuery::new()).await?;
+
+        let mut state = self.shared.state.lock().unwrap();
+        for metadata in list {
+            if !state.dirty.contains_key(&metadata.id) {
+                state.keys.insert(metadata.id.clone(), metadata);
+            }
+        }
+        state.complete = true;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PersistenceInterface for CachedPersistence {
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        if !self.write_back {
+            self.shared.inner.save_key_metadata(metadata).await?;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        state.keys.insert(metadata.id.clone(), metadata.clone());
+        if self.write_back {
+            state.mark_dirty(&metadata.id, Some(metadata.clone()));
+        }
+        Ok(())
+    }
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        {
+            let state = self.shared.state.lock().unwrap();
+            if let Some(metadata) = state.keys.get(key_id) {
+                return Ok(metadata.clone());
+            }
+            // 已加载全部密钥或已在回写模式下删除时，缓存中没有就是不存在
+            if state.complete || matches!(state.dirty.get(key_id), Some((_, None))) {
+                return Err(format!("密钥不存在: {}", key_id));
+            }
+        }
+
+        let metadata = self.shared.inner.load_key_metadata(key_id).await?;
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.dirty.contains_key(key_id) {
+            state.keys.insert(key_id.to_string(), metadata.clone());
+        }
+        Ok(metadata)
+    }
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        if !self.write_back {
+            self.shared.inner.delete_key_metadata(key_id).await?;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        state.keys.remove(key_id);
+        if self.write_back {
+            state.mark_dirty(key_id, None);
+        }
+        Ok(())
+    }
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        self.ensure_complete().await?;
+
+        let state = self.shared.state.lock().unwrap();
+        Ok(state.keys.values().filter(|metadata| query.matches(metadata)).cloned().collect())
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        self.shared.inner.save_audit_log(log).await
+    }
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        self.shared.inner.load_audit_logs(filters, limit).await
+    }
+
+    async fn save_alias(&self, alias: &str, key_id: &str) -> Result<(), String> {
+        self.shared.inner.save_alias(alias, key_id).await
+    }
+
+    async fn delete_alias(&self, alias: &str) -> Result<(), String> {
+        self.shared.inner.delete_alias(alias).await
+    }
+
+    async fn load_aliases(&self) -> Result<HashMap<String, String>, String> {
+        self.shared.inner.load_aliases().await
+    }
+
+    async fn put_attachment(&self, key_id: &str, name: &str, data: &[u8]) -> Result<(), String> {
+        self.shared.inner.put_attachment(key_id, name, data).await
+    }
+
+    async fn get_attachment(&self, key_id: &str, name: &str) -> Result<Vec<u8>, String> {
+        self.shared.inner.get_attachment(key_id, name).await
+    }
+
+    async fn list_attachments(&self, key_id: &str) -> Result<Vec<AttachmentInfo>, String> {
+        self.shared.inner.list_attachments(key_id).await
+    }
+
+    async fn backup_to(&self, dest_path: &str) -> Result<(), String> {
+        // 备份前先写入未 flush 的修改，否则备份中缺少这部分数据
+        self.flush().await?;
+        self.shared.inner.backup_to(dest_path).await
+    }
+
+    async fn restore_from(&self, src_path: &str) -> Result<(), String> {
+        self.shared.inner.restore_from(src_path).await?;
+
+        // 恢复后底层数据整体替换，缓存和未 flush 的修改都作废
+        let mut state = self.shared.state.lock().unwrap();
+        *state = CacheState::default();
+        Ok(())
+    }
+
+    async fn compact_audit_log(&self, retention: Option<Duration>, dedupe: bool) -> Result<(usize, usize), String> {
+        self.shared.inner.compact_audit_log(retention, dedupe).await
+    }
+}
+
+impl Drop for CachedPersistence {
+    // 通知后台任务停止，剩余的修改由后台任务写入
+    fn drop(&mut self) {
+        if let Some((token, _)) = self.flusher.get_mut().unwrap().take() {
+            token.cancel();
+        }
+    }
+}
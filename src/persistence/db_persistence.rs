@@ -1,18 +1,105 @@
 // 持久化
 use async_trait::async_trait;
-// 移除未使用的导入
-// use chrono::{DateTime, Utc};
-use sqlx::{Pool, Sqlite, SqlitePool};
-use std::collections::HashMap;
-// 移除未使用的导入
-// use std::str::FromStr;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, QueryBuilder, Row, Sqlite, SqlitePool};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
 
 // 修改导入，只保留需要的类型
-use crate::key_management::models::key_models::{AuditLogEntry, KeyMetadata};
+use crate::key_management::models::key_models::{
+    AttachmentInfo, AuditLogEntry, KeyAlgorithm, KeyMetadata, KeyStatus, KeyType,
+};
 use crate::persistence::PersistenceInterface;
+use crate::persistence::key_query::KeyQuery;
+use crate::persistence::key_stats::{self, KeyStats};
+use crate::timestamp;
+
+// 版本 6 之前的时间戳精度和时区不固定，升级时统一为 timestamp::format 的格式
+const NORMALIZE_TIMESTAMPS: [&str; 2] = [
+    "UPDATE key_metadata SET
+        created_at = strftime('%Y-%m-%dT%H:%M:%fZ', created_at),
+        updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', updated_at),
+        expires_at = strftime('%Y-%m-%dT%H:%M:%fZ', expires_at),
+        deleted_at = strftime('%Y-%m-%dT%H:%M:%fZ', deleted_at)",
+    "UPDATE audit_logs SET timestamp = strftime('%Y-%m-%dT%H:%M:%fZ', timestamp)",
+];
+
+// 流式读取密钥元数据时每页的行数
+const STREAM_PAGE_SIZE: i64 = 100;
+
+/// 当前数据库结构版本，修改表结构时递增并在 init_db 中补充迁移
+pub const SCHEMA_VERSION: i64 = 10;
+
+/// 密钥状态、类型和算法在数据库中的存储形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnumEncoding {
+    /// 名称，如 ACTIVE、AES-256，便于直接查看数据库
+    #[default]
+    Name,
+    /// 数字编码（as_code），占用空间更小，也不受外部工具拼写错误影响
+    Code,
+}
+
+impl FromStr for EnumEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "name" | "string" => Ok(EnumEncoding::Name),
+            "code" | "numeric" => Ok(EnumEncoding::Code),
+            _ => Err(format!("Invalid enum encoding: {} (expected name or code)", s)),
+        }
+    }
+}
+
+// 可以按名称或数字编码存储的枚举
+trait StoredEnum: FromStr<Err = String> + ToString + Sized {
+    fn as_code(&self) -> u8;
+    fn from_code(code: u8) -> Result<Self, String>;
+}
+
+impl StoredEnum for KeyStatus {
+    fn as_code(&self) -> u8 {
+        KeyStatus::as_code(self)
+    }
+    fn from_code(code: u8) -> Result<Self, String> {
+        KeyStatus::from_code(code)
+    }
+}
+
+impl StoredEnum for KeyType {
+    fn as_code(&self) -> u8 {
+        KeyType::as_code(self)
+    }
+    fn from_code(code: u8) -> Result<Self, String> {
+        KeyType::from_code(code)
+    }
+}
+
+impl StoredEnum for KeyAlgorithm {
+    fn as_code(&self) -> u8 {
+        KeyAlgorithm::as_code(self)
+    }
+    fn from_code(code: u8) -> Result<Self, String> {
+        KeyAlgorithm::from_code(code)
+    }
+}
+
+// 读取时两种形式都接受，切换存储形式后已有的行不需要迁移
+fn decode_enum<T: StoredEnum>(value: &str) -> Result<T, String> {
+    match value.parse::<u8>() {
+        Ok(code) => T::from_code(code),
+        Err(_) => T::from_str(value),
+    }
+}
 
 pub struct DbPersistence {
     pool: Pool<Sqlite>,
+    enum_encoding: EnumEncoding,
 }
 
 impl DbPersistence {
@@ -24,7 +111,94 @@ impl DbPersistence {
         // 初始化数据库表
         Self::init_db(&pool).await?;
         
-        Ok(Self { pool })
+        Ok(Self { pool, enum_encoding: EnumEncoding::default() })
+    }
+
+    /// 指定新写入的密钥状态、类型和算法的存储形式，默认按名称存储
+    pub fn with_enum_encoding(mut self, enum_encoding: EnumEncoding) -> Self {
+        self.enum_encoding = enum_encoding;
+        self
+    }
+
+    fn encode_enum<T: StoredEnum>(&self, value: &T) -> String {
+        match self.enum_encoding {
+            EnumEncoding::Name => value.to_string(),
+            EnumEncoding::Code => value.as_code().to_string(),
+        }
+    }
+
+    // 在事务中写入一个密钥的元数据，并用新的标签替换旧标签
+    async fn write_key_metadata(&self, conn: &mut sqlx::SqliteConnection, metadata: &KeyMetadata) -> Result<(), String> {
+        let acl = match &metadata.acl {
+            Some(acl) => Some(serde_json::to_string(acl).map_err(|e| format!("序列化访问控制列表失败: {}", e))?),
+            None => None,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO key_metadata
+            (id, name, description, key_type, algorithm, status, owner, created_at, updated_at, expires_at, version, requires_approval, deleted_at, tenant, acl, notes, security_module_id, last_used_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                description = excluded.description,
+                key_type = excluded.key_type,
+                algorithm = excluded.algorithm,
+                status = excluded.status,
+                owner = excluded.owner,
+                updated_at = excluded.updated_at,
+                expires_at = excluded.expires_at,
+                version = excluded.version,
+                requires_approval = excluded.requires_approval,
+                deleted_at = excluded.deleted_at,
+                tenant = excluded.tenant,
+                acl = excluded.acl,
+                notes = excluded.notes,
+                security_module_id = excluded.security_module_id,
+                last_used_at = excluded.last_used_at
+            "#
+        )
+        .bind(&metadata.id)
+        .bind(&metadata.name)
+        .bind(&metadata.description)
+        .bind(self.encode_enum(&metadata.key_type))
+        .bind(self.encode_enum(&metadata.algorithm))
+        .bind(self.encode_enum(&metadata.status))
+        .bind(&metadata.owner)
+        .bind(timestamp::format(&metadata.created_at))
+        .bind(timestamp::format(&metadata.updated_at))
+        .bind(metadata.expiration_date.as_ref().map(timestamp::format))
+        .bind(metadata.version)
+        .bind(metadata.requires_approval as i32)
+        .bind(metadata.deleted_at.as_ref().map(timestamp::format))
+        .bind(&metadata.tenant)
+        .bind(acl)
+        .bind(&metadata.notes)
+        .bind(&metadata.security_module_id)
+        .bind(metadata.last_used_at.as_ref().map(timestamp::format))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("保存密钥元数据失败: {}", e))?;
+
+        // 删除旧标签
+        sqlx::query("DELETE FROM key_tags WHERE key_id = ?")
+            .bind(&metadata.id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("删除旧标签失败: {}", e))?;
+
+        // 保存新标签
+        for (key, value) in &metadata.tags {
+            sqlx::query("INSERT INTO key_tags (key_id, tag_key, tag_value) VALUES (?, ?, ?)")
+                .bind(&metadata.id)
+                .bind(key)
+                .bind(value)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| format!("保存标签失败: {}", e))?;
+        }
+
+        Ok(())
     }
     
     async fn init_db(pool: &Pool<Sqlite>) -> Result<(), String> {
@@ -43,13 +217,38 @@ impl DbPersistence {
                 updated_at TEXT NOT NULL,
                 expires_at TEXT,
                 version INTEGER NOT NULL,
-                requires_approval INTEGER NOT NULL
+                requires_approval INTEGER NOT NULL,
+                deleted_at TEXT,
+                tenant TEXT NOT NULL DEFAULT 'default',
+                acl TEXT,
+                notes TEXT,
+                security_module_id TEXT NOT NULL DEFAULT 'default',
+                last_used_at TEXT
             )
             "#
         )
         .execute(pool)
         .await
         .map_err(|e| format!("创建密钥元数据表失败: {}", e))?;
+
+        // 版本 3 增加软删除时间，版本 5 增加租户，版本 7 增加访问控制列表，版本 8 增加备注，
+        // 版本 9 增加安全模块ID，版本 10 增加最近使用时间，旧库需要补充这些列
+        let key_columns = [
+            ("deleted_at", "ALTER TABLE key_metadata ADD COLUMN deleted_at TEXT"),
+            ("tenant", "ALTER TABLE key_metadata ADD COLUMN tenant TEXT NOT NULL DEFAULT 'default'"),
+            ("acl", "ALTER TABLE key_metadata ADD COLUMN acl TEXT"),
+            ("notes", "ALTER TABLE key_metadata ADD COLUMN notes TEXT"),
+            ("security_module_id", "ALTER TABLE key_metadata ADD COLUMN security_module_id TEXT NOT NULL DEFAULT 'default'"),
+            ("last_used_at", "ALTER TABLE key_metadata ADD COLUMN last_used_at TEXT"),
+        ];
+        for (column, statement) in key_columns {
+            if !has_column(pool, "main", "key_metadata", column).await? {
+                sqlx::query(statement)
+                    .execute(pool)
+                    .await
+                    .map_err(|e| format!("升级密钥元数据表失败: {}", e))?;
+            }
+        }
         
         // 创建标签表
         sqlx::query(
@@ -78,48 +277,1228 @@ impl DbPersistence {
                 key_id TEXT,
                 details TEXT,
                 success INTEGER NOT NULL,
-                error TEXT
+                error TEXT,
+                tenant TEXT NOT NULL DEFAULT 'default'
             )
             "#
         )
         .execute(pool)
         .await
         .map_err(|e| format!("创建审计日志表失败: {}", e))?;
+
+        // 版本 5 增加租户
+        if !has_column(pool, "main", "audit_logs", "tenant").await? {
+            sqlx::query("ALTER TABLE audit_logs ADD COLUMN tenant TEXT NOT NULL DEFAULT 'default'")
+                .execute(pool)
+                .await
+                .map_err(|e| format!("升级审计日志表失败: {}", e))?;
+        }
+
+        // 创建别名表（版本 2）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS key_aliases (
+                alias TEXT PRIMARY KEY,
+                key_id TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| format!("创建别名表失败: {}", e))?;
+
+        // 创建附件表（版本 4）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS key_attachments (
+                key_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                data BLOB NOT NULL,
+                PRIMARY KEY (key_id, name)
+            )
+            "#
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| format!("创建附件表失败: {}", e))?;
+
+        // 创建索引，审计日志常按时间倒序查询并按密钥或用户过滤
+        let indexes = [
+            "CREATE INDEX IF NOT EXISTS idx_audit_logs_timestamp ON audit_logs(timestamp DESC)",
+            "CREATE INDEX IF NOT EXISTS idx_audit_logs_key_id ON audit_logs(key_id, timestamp DESC)",
+            "CREATE INDEX IF NOT EXISTS idx_audit_logs_user ON audit_logs(user, timestamp DESC)",
+            "CREATE INDEX IF NOT EXISTS idx_key_metadata_owner ON key_metadata(owner)",
+            "CREATE INDEX IF NOT EXISTS idx_key_metadata_status ON key_metadata(status)",
+            "CREATE INDEX IF NOT EXISTS idx_key_aliases_key_id ON key_aliases(key_id)",
+            "CREATE INDEX IF NOT EXISTS idx_key_metadata_tenant ON key_metadata(tenant)",
+            "CREATE INDEX IF NOT EXISTS idx_audit_logs_tenant ON audit_logs(tenant, timestamp DESC)",
+        ];
+
+        for statement in indexes {
+            sqlx::query(statement)
+                .execute(pool)
+                .await
+                .map_err(|e| format!("创建索引失败: {}", e))?;
+        }
+
+        // 创建迁移记录表，记录数据库结构版本
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                applied_at TEXT NOT NULL
+            )
+            "#
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| format!("创建迁移记录表失败: {}", e))?;
+
+        let version: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_migrations")
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("读取数据库版本失败: {}", e))?;
+        if version < 6 {
+            for statement in NORMALIZE_TIMESTAMPS {
+                sqlx::query(statement)
+                    .execute(pool)
+                    .await
+                    .map_err(|e| format!("升级时间戳格式失败: {}", e))?;
+            }
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO schema_migrations (version, applied_at) VALUES (?, ?)")
+            .bind(SCHEMA_VERSION)
+            .bind(timestamp::format(&timestamp::now()))
+            .execute(pool)
+            .await
+            .map_err(|e| format!("记录数据库版本失败: {}", e))?;
         
         Ok(())
     }
+
+    /// 使用 SQLite 的 `VACUUM INTO` 生成数据库快照
+    ///
+    /// 快照在单个读事务中完成，并发写入不会导致备份不一致；目标文件已存在时拒绝覆盖
+    pub async fn backup_to(&self, dest_path: &str) -> Result<(), String> {
+        if Path::new(dest_path).exists() {
+            return Err(format!("备份文件已存在: {}", dest_path));
+        }
+
+        sqlx::query("VACUUM INTO ?")
+            .bind(dest_path)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("备份数据库失败: {}", e))?;
+
+        Ok(())
+    }
+
+    /// 从备份文件恢复数据，替换当前库中的全部密钥元数据和审计日志
+    ///
+    /// 恢复前检查备份的结构版本，拒绝恢复比当前程序更新的备份；数据在单个事务中替换
+    pub async fn restore_from(&self, src_path: &str) -> Result<(), String> {
+        if !Path::new(src_path).is_file() {
+            return Err(format!("备份文件不存在: {}", src_path));
+        }
+
+        // ATTACH 只对当前连接有效，整个恢复过程使用同一个连接
+        let mut conn = self.pool.acquire()
+            .await
+            .map_err(|e| format!("获取数据库连接失败: {}", e))?;
+
+        sqlx::query("ATTACH DATABASE ? AS restore_src")
+            .bind(src_path)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("打开备份文件失败: {}", e))?;
+
+        let result = Self::restore_attached(&mut conn).await;
+
+        if let Err(e) = sqlx::query("DETACH DATABASE restore_src").execute(&mut *conn).await {
+            eprintln!("关闭备份文件失败: {}", e);
+        }
+
+        result
+    }
+
+    async fn restore_attached(conn: &mut PoolConnection<Sqlite>) -> Result<(), String> {
+        let tables: Vec<String> = sqlx::query_scalar("SELECT name FROM restore_src.sqlite_master WHERE type = 'table'")
+            .fetch_all(&mut **conn)
+            .await
+            .map_err(|e| format!("读取备份结构失败: {}", e))?;
+
+        for required in ["key_metadata", "key_tags", "audit_logs"] {
+            if !tables.iter().any(|t| t == required) {
+                return Err(format!("备份文件缺少数据表: {}", required));
+            }
+        }
+
+        // 没有迁移记录表的备份来自引入版本记录之前，按版本 0 处理
+        let version: i64 = if tables.iter().any(|t| t == "schema_migrations") {
+            sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM restore_src.schema_migrations")
+                .fetch_one(&mut **conn)
+                .await
+                .map_err(|e| format!("读取备份版本失败: {}", e))?
+        } else {
+            0
+        };
+
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "备份的数据库版本 {} 高于当前支持的版本 {}，拒绝恢复",
+                version, SCHEMA_VERSION
+            ));
+        }
+
+        // 旧版本的备份缺少后来增加的列：没有 deleted_at 的恢复为未删除，没有 tenant 的归入默认租户，
+        // 没有 acl 的不限制访问，没有 notes 的没有备注，没有 security_module_id 的使用默认安全模块，
+        // 没有 last_used_at 的视为从未使用
+        let mut key_columns = vec![
+            "id", "name", "description", "key_type", "algorithm", "status", "owner",
+            "created_at", "updated_at", "expires_at", "version", "requires_approval",
+        ];
+        for column in ["deleted_at", "tenant", "acl", "notes", "security_module_id", "last_used_at"] {
+            if has_column(&mut **conn, "restore_src", "key_metadata", column).await? {
+                key_columns.push(column);
+            }
+        }
+        let mut audit_columns = vec!["id", "timestamp", "user", "action", "key_id", "details", "success", "error"];
+        if has_column(&mut **conn, "restore_src", "audit_logs", "tenant").await? {
+            audit_columns.push("tenant");
+        }
+        let key_columns = key_columns.join(", ");
+        let audit_columns = audit_columns.join(", ");
+
+        let mut tx = sqlx::Connection::begin(&mut **conn)
+            .await
+            .map_err(|e| format!("开始事务失败: {}", e))?;
+
+        let mut statements = vec![
+            "DELETE FROM key_tags".to_string(),
+            "DELETE FROM key_aliases".to_string(),
+            "DELETE FROM key_attachments".to_string(),
+            "DELETE FROM key_metadata".to_string(),
+            "DELETE FROM audit_logs".to_string(),
+            format!("INSERT INTO key_metadata ({key_columns}) SELECT {key_columns} FROM restore_src.key_metadata"),
+            "INSERT INTO key_tags (key_id, tag_key, tag_value)
+             SELECT key_id, tag_key, tag_value FROM restore_src.key_tags".to_string(),
+            format!("INSERT INTO audit_logs ({audit_columns}) SELECT {audit_columns} FROM restore_src.audit_logs"),
+        ];
+        // 版本 2 之前的备份没有别名表
+        if tables.iter().any(|t| t == "key_aliases") {
+            statements.push("INSERT INTO key_aliases (alias, key_id) SELECT alias, key_id FROM restore_src.key_aliases".to_string());
+        }
+        // 版本 4 之前的备份没有附件表
+        if tables.iter().any(|t| t == "key_attachments") {
+            statements.push("INSERT INTO key_attachments (key_id, name, data) SELECT key_id, name, data FROM restore_src.key_attachments".to_string());
+        }
+        if version < 6 {
+            statements.extend(NORMALIZE_TIMESTAMPS.map(String::from));
+        }
+
+        for statement in &statements {
+            sqlx::query(statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("恢复数据失败: {}", e))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("提交事务失败: {}", e))?;
+
+        Ok(())
+    }
+
+    // 按列分组统计未删除的密钥，列名只来自代码中的固定值；tenant 为 None 时统计全部租户，结果统一以名称为键（同一取值可能同时以名称和数字编码存储）
+    async fn count_grouped_by<T: StoredEnum>(&self, column: &str, tenant: Option<&str>) -> Result<BTreeMap<String, usize>, String> {
+        let sql = format!(
+            "SELECT {column} AS value, COUNT(*) AS count FROM key_metadata
+             WHERE deleted_at IS NULL AND (?1 IS NULL OR tenant = ?1) GROUP BY {column}"
+        );
+        let rows = sqlx::query(&sql)
+            .bind(tenant)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("统计密钥失败: {}", e))?;
+
+        let mut counts = BTreeMap::new();
+        for row in &rows {
+            let value = decode_enum::<T>(&row.get::<String, _>("value"))?;
+            *counts.entry(value.to_string()).or_insert(0) += row.get::<i64, _>("count") as usize;
+        }
+        Ok(counts)
+    }
+
+    async fn load_tags(&self, key_id: &str) -> Result<HashMap<String, String>, String> {
+        let rows = sqlx::query("SELECT tag_key, tag_value FROM key_tags WHERE key_id = ?")
+            .bind(key_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("查询标签失败: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("tag_key"), row.get("tag_value")))
+            .collect())
+    }
+
+    async fn row_to_metadata(&self, row: &SqliteRow) -> Result<KeyMetadata, String> {
+        let id: String = row.get("id");
+        let tags = self.load_tags(&id).await?;
+
+        let expires_at: Option<String> = row.get("expires_at");
+        let expiration_date = match expires_at {
+            Some(expires) => Some(parse_timestamp(&expires)?),
+            None => None,
+        };
+
+        // 访问控制列表以 JSON 保存
+        let acl: Option<String> = row.get("acl");
+        let acl = match acl {
+            Some(acl) => Some(serde_json::from_str(&acl).map_err(|e| format!("解析访问控制列表失败: {}", e))?),
+            None => None,
+        };
+
+        let deleted_at: Option<String> = row.get("deleted_at");
+        let deleted_at = match deleted_at {
+            Some(deleted) => Some(parse_timestamp(&deleted)?),
+            None => None,
+        };
+
+        let last_used_at: Option<String> = row.get("last_used_at");
+        let last_used_at = match last_used_at {
+            Some(last_used) => Some(parse_timestamp(&last_used)?),
+            None => None,
+        };
+
+        Ok(KeyMetadata {
+            id,
+            name: row.get("name"),
+            description: row.get::<Option<String>, _>("description").unwrap_or_default(),
+            notes: row.get("notes"),
+            key_type: decode_enum(&row.get::<String, _>("key_type"))?,
+            algorithm: decode_enum(&row.get::<String, _>("algorithm"))?,
+            status: decode_enum(&row.get::<String, _>("status"))?,
+            owner: row.get("owner"),
+            created_at: parse_timestamp(&row.get::<String, _>("created_at"))?,
+            updated_at: parse_timestamp(&row.get::<String, _>("updated_at"))?,
+            expiration_date,
+            version: row.get("version"),
+            requires_approval: row.get::<i32, _>("requires_approval") != 0,
+            tags,
+            deleted_at,
+            last_used_at,
+            tenant: row.get("tenant"),
+            security_module_id: row.get("security_module_id"),
+            acl,
+        })
+    }
+}
+
+fn push_enum_condition<T: StoredEnum>(query: &mut QueryBuilder<'_, Sqlite>, column: &str, value: &T) {
+    query.push(format!(" AND {} IN (", column));
+    query.push_bind(value.to_string());
+    query.push(", ");
+    query.push_bind(value.as_code().to_string());
+    query.push(")");
+}
+
+// 根据查询条件构造密钥元数据查询，条件值全部通过参数绑定传入
+//
+// after 和 limit 用于按 (created_at, id) 分页
+fn key_metadata_query<'a>(
+    key_query: &'a KeyQuery,
+    after: Option<(&'a str, &'a str)>,
+    limit: Option<i64>,
+) -> QueryBuilder<'a, Sqlite> {
+    let mut query = QueryBuilder::new("SELECT * FROM key_metadata WHERE 1 = 1");
+
+    // 状态、类型和算法可能以名称或数字编码存储，两种形式都匹配
+    if let Some(status) = &key_query.status {
+        push_enum_condition(&mut query, "status", status);
+    }
+    if let Some(key_type) = &key_query.key_type {
+        push_enum_condition(&mut query, "key_type", key_type);
+    }
+    if let Some(algorithm) = &key_query.algorithm {
+        push_enum_condition(&mut query, "algorithm", algorithm);
+    }
+    if let Some(owner) = &key_query.owner {
+        query.push(" AND owner = ");
+        query.push_bind(owner.as_str());
+    }
+    if let Some(tenant) = &key_query.tenant {
+        query.push(" AND tenant = ");
+        query.push_bind(tenant.as_str());
+    }
+    if let Some(name) = &key_query.name_contains {
+        // instr 区分大小写且不需要转义通配符，与文件存储的 contains 行为一致
+        query.push(" AND instr(name, ");
+        query.push_bind(name.as_str());
+        query.push(") > 0");
+    }
+    for (tag_key, tag_value) in &key_query.tags {
+        query.push(" AND id IN (SELECT key_id FROM key_tags WHERE tag_key = ");
+        query.push_bind(tag_key.as_str());
+        query.push(" AND tag_value = ");
+        query.push_bind(tag_value.as_str());
+        query.push(")");
+    }
+
+    if let Some((created_at, id)) = after {
+        query.push(" AND (created_at, id) > (");
+        query.push_bind(created_at);
+        query.push(", ");
+        query.push_bind(id);
+        query.push(")");
+    }
+
+    query.push(" ORDER BY created_at, id");
+    if let Some(limit) = limit {
+        query.push(" LIMIT ");
+        query.push_bind(limit);
+    }
+    query
+}
+
+// 根据过滤条件构造审计日志查询，结果按时间倒序
+fn audit_log_query(filters: Option<&HashMap<String, String>>, limit: Option<usize>) -> QueryBuilder<'_, Sqlite> {
+    let mut query = QueryBuilder::new("SELECT * FROM audit_logs");
+    let mut has_where = false;
+
+    for (key, value) in filters.into_iter().flatten() {
+        let column = match key.as_str() {
+            "action" => "action",
+            "user" => "user",
+            "key_id" => "key_id",
+            "success" => "success",
+            "tenant" => "tenant",
+            // 未知的过滤条件忽略
+            _ => continue,
+        };
+
+        query.push(if has_where { " AND " } else { " WHERE " });
+        has_where = true;
+        query.push(column);
+        query.push(" = ");
+
+        if column == "success" {
+            query.push_bind(value.parse::<bool>().unwrap_or(false) as i32);
+        } else {
+            query.push_bind(value.as_str());
+        }
+    }
+
+    query.push(" ORDER BY timestamp DESC LIMIT ");
+    query.push_bind(limit.map(|l| l as i64).unwrap_or(-1));
+    query
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>, String> {
+    timestamp::parse(value)
+}
+
+// 检查指定库中的表是否包含某一列，用于兼容旧版本的数据库和备份
+async fn has_column<'e, E>(executor: E, schema: &str, table: &str, column: &str) -> Result<bool, String>
+where
+    E: sqlx::Executor<'e, Database = Sqlite>,
+{
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?, ?) WHERE name = ?")
+        .bind(table)
+        .bind(schema)
+        .bind(column)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| format!("读取表结构失败: {}", e))?;
+    Ok(count > 0)
 }
 
 #[async_trait]
 impl PersistenceInterface for DbPersistence {
-    // 添加下划线前缀表示有意不使用这些变量
-    async fn save_key_metadata(&self, _metadata: &KeyMetadata) -> Result<(), String> {
-        // 示例实现
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        self.save_key_metadata_batch(std::slice::from_ref(metadata)).await
+    }
+
+    async fn save_key_metadata_batch(&self, list: &[KeyMetadata]) -> Result<(), String> {
+        // 元数据和标签在同一事务中写入，任一密钥失败时整批回滚
+        let mut tx = self.pool.begin()
+            .await
+            .map_err(|e| format!("开始事务失败: {}", e))?;
+
+        for metadata in list {
+            self.write_key_metadata(&mut tx, metadata).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("提交事务失败: {}", e))?;
+
         Ok(())
     }
-    
-    async fn load_key_metadata(&self, _key_id: &str) -> Result<KeyMetadata, String> {
-        // 示例实现
-        Err("未实现".to_string())
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        let row = sqlx::query("SELECT * FROM key_metadata WHERE id = ?")
+            .bind(key_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("查询密钥元数据失败: {}", e))?
+            .ok_or_else(|| format!("密钥不存在: {}", key_id))?;
+
+        self.row_to_metadata(&row).await
     }
-    
-    async fn delete_key_metadata(&self, _key_id: &str) -> Result<(), String> {
-        // 示例实现
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        let mut tx = self.pool.begin()
+            .await
+            .map_err(|e| format!("开始事务失败: {}", e))?;
+
+        sqlx::query("DELETE FROM key_tags WHERE key_id = ?")
+            .bind(key_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("删除标签失败: {}", e))?;
+
+        // 附件随密钥一起删除
+        sqlx::query("DELETE FROM key_attachments WHERE key_id = ?")
+            .bind(key_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("删除附件失败: {}", e))?;
+
+        sqlx::query("DELETE FROM key_metadata WHERE id = ?")
+            .bind(key_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("删除密钥元数据失败: {}", e))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("提交事务失败: {}", e))?;
+
         Ok(())
     }
-    
-    async fn list_key_metadata(&self, _filters: Option<HashMap<String, String>>) -> Result<Vec<KeyMetadata>, String> {
-        // 示例实现
-        Ok(Vec::new())
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        let rows = key_metadata_query(query, None, None)
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("查询密钥元数据失败: {}", e))?;
+
+        let mut result = Vec::with_capacity(rows.len());
+        for row in &rows {
+            result.push(self.row_to_metadata(row).await?);
+        }
+
+        Ok(result)
     }
-    
-    async fn save_audit_log(&self, _log: &AuditLogEntry) -> Result<(), String> {
-        // 示例实现
+
+    async fn key_stats(&self, tenant: Option<&str>, now: DateTime<Utc>) -> Result<KeyStats, String> {
+        let by_status = self.count_grouped_by::<KeyStatus>("status", tenant).await?;
+        let by_key_type = self.count_grouped_by::<KeyType>("key_type", tenant).await?;
+        let by_algorithm = self.count_grouped_by::<KeyAlgorithm>("algorithm", tenant).await?;
+
+        // 时间带有不同位数的小数秒，按 julianday 比较而不是按字符串比较
+        let expiring: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM key_metadata
+             WHERE deleted_at IS NULL AND (?1 IS NULL OR tenant = ?1) AND expires_at IS NOT NULL
+             AND julianday(expires_at) > julianday(?2) AND julianday(expires_at) <= julianday(?3)"
+        )
+        .bind(tenant)
+        .bind(timestamp::format(&now))
+        .bind(timestamp::format(&key_stats::expiring_before(now)))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| format!("统计即将过期的密钥失败: {}", e))?;
+
+        Ok(KeyStats {
+            total: by_status.values().sum(),
+            by_status,
+            by_key_type,
+            by_algorithm,
+            expiring_within_30_days: expiring as usize,
+        })
+    }
+
+    async fn count_keys_by_owner(&self, tenant: Option<&str>) -> Result<BTreeMap<String, usize>, String> {
+        let rows = sqlx::query(
+            "SELECT owner, COUNT(*) AS count FROM key_metadata
+             WHERE deleted_at IS NULL AND (?1 IS NULL OR tenant = ?1) GROUP BY owner"
+        )
+        .bind(tenant)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("统计密钥所有者失败: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("owner"), row.get::<i64, _>("count") as usize))
+            .collect())
+    }
+
+    fn query_keys_stream(&self, query: KeyQuery) -> BoxStream<'_, Result<KeyMetadata, String>> {
+        // 按 (created_at, id) 分页读取，每次只加载一页
+        stream::try_unfold((query, None::<(String, String)>), move |(query, cursor)| async move {
+            let after = cursor.as_ref().map(|(created_at, id)| (created_at.as_str(), id.as_str()));
+            let rows = key_metadata_query(&query, after, Some(STREAM_PAGE_SIZE))
+                .build()
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| format!("查询密钥元数据失败: {}", e))?;
+
+            let Some(last) = rows.last() else {
+                return Ok(None);
+            };
+            let cursor = Some((last.get("created_at"), last.get("id")));
+
+            let mut page = Vec::with_capacity(rows.len());
+            for row in &rows {
+                page.push(Ok(self.row_to_metadata(row).await?));
+            }
+
+            Ok::<_, String>(Some((stream::iter(page), (query, cursor))))
+        })
+        .try_flatten()
+        .boxed()
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_logs
+            (id, timestamp, user, action, key_id, details, success, error, tenant)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&log.id)
+        .bind(timestamp::format(&log.timestamp))
+        .bind(&log.user)
+        .bind(&log.action)
+        .bind(&log.key_id)
+        .bind(&log.details)
+        .bind(log.success as i32)
+        .bind(&log.error)
+        .bind(&log.tenant)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("保存审计日志失败: {}", e))?;
+
         Ok(())
     }
-    
-    async fn load_audit_logs(&self, _filters: Option<HashMap<String, String>>, _limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
-        // 示例实现
-        Ok(Vec::new())
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        let rows = audit_log_query(filters.as_ref(), limit)
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("查询审计日志失败: {}", e))?;
+
+        let mut result = Vec::with_capacity(rows.len());
+        for row in rows {
+            result.push(AuditLogEntry {
+                id: row.get("id"),
+                timestamp: parse_timestamp(&row.get::<String, _>("timestamp"))?,
+                user: row.get("user"),
+                action: row.get("action"),
+                key_id: row.get("key_id"),
+                details: row.get::<Option<String>, _>("details").unwrap_or_default(),
+                success: row.get::<i32, _>("success") != 0,
+                error: row.get("error"),
+                tenant: row.get("tenant"),
+            });
+        }
+
+        Ok(result)
     }
-}
\ No newline at end of file
+
+    async fn save_alias(&self, alias: &str, key_id: &str) -> Result<(), String> {
+        sqlx::query("INSERT OR REPLACE INTO key_aliases (alias, key_id) VALUES (?, ?)")
+            .bind(alias)
+            .bind(key_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("保存别名失败: {}", e))?;
+
+        Ok(())
+    }
+
+    async fn delete_alias(&self, alias: &str) -> Result<(), String> {
+        sqlx::query("DELETE FROM key_aliases WHERE alias = ?")
+            .bind(alias)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("删除别名失败: {}", e))?;
+
+        Ok(())
+    }
+
+    async fn load_aliases(&self) -> Result<HashMap<String, String>, String> {
+        let rows: Vec<(String, String)> = sqlx::query_as("SELECT alias, key_id FROM key_aliases")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("加载别名失败: {}", e))?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    async fn put_attachment(&self, key_id: &str, name: &str, data: &[u8]) -> Result<(), String> {
+        sqlx::query(
+            "INSERT INTO key_attachments (key_id, name, data) VALUES (?, ?, ?)
+             ON CONFLICT(key_id, name) DO UPDATE SET data = excluded.data"
+        )
+        .bind(key_id)
+        .bind(name)
+        .bind(data)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("保存附件失败: {}", e))?;
+
+        Ok(())
+    }
+
+    async fn get_attachment(&self, key_id: &str, name: &str) -> Result<Vec<u8>, String> {
+        sqlx::query_scalar("SELECT data FROM key_attachments WHERE key_id = ? AND name = ?")
+            .bind(key_id)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("读取附件失败: {}", e))?
+            .ok_or_else(|| format!("附件不存在: {}", name))
+    }
+
+    async fn list_attachments(&self, key_id: &str) -> Result<Vec<AttachmentInfo>, String> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT name, length(data) FROM key_attachments WHERE key_id = ? ORDER BY name"
+        )
+        .bind(key_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("列出附件失败: {}", e))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(name, size)| AttachmentInfo { name, size: size as usize })
+            .collect())
+    }
+
+    async fn backup_to(&self, dest_path: &str) -> Result<(), String> {
+        DbPersistence::backup_to(self, dest_path).await
+    }
+
+    async fn restore_from(&self, src_path: &str) -> Result<(), String> {
+        DbPersistence::restore_from(self, src_path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn temp_db_path() -> PathBuf {
+        std::env::temp_dir().join(format!("password_manager_test_{}.db", uuid::Uuid::new_v4()))
+    }
+
+    async fn open(path: &Path) -> DbPersistence {
+        DbPersistence::new(&format!("sqlite:{}?mode=rwc", path.display())).await.unwrap()
+    }
+
+    // 临时数据库文件，测试结束时删除
+    struct TempDb {
+        persistence: DbPersistence,
+        path: PathBuf,
+    }
+
+    impl TempDb {
+        async fn new() -> Self {
+            Self::with_enum_encoding(EnumEncoding::default()).await
+        }
+
+        async fn with_enum_encoding(enum_encoding: EnumEncoding) -> Self {
+            let path = temp_db_path();
+            let persistence = open(&path).await.with_enum_encoding(enum_encoding);
+            Self { persistence, path }
+        }
+    }
+
+    // 保存一个测试密钥并返回其ID
+    async fn save_key(persistence: &DbPersistence, name: &str, owner: &str) -> String {
+        save_metadata(persistence, name, owner, |metadata| {
+            metadata.tags.insert("env".to_string(), "prod".to_string());
+        }).await
+    }
+
+    // 保存一个测试密钥，保存前由 customize 修改元数据
+    async fn save_metadata(
+        persistence: &DbPersistence,
+        name: &str,
+        owner: &str,
+        customize: impl FnOnce(&mut KeyMetadata),
+    ) -> String {
+        let mut metadata = KeyMetadata::new(
+            name.to_string(),
+            String::new(),
+            KeyType::Symmetric,
+            KeyAlgorithm::AES256,
+            owner.to_string(),
+            false,
+        );
+        customize(&mut metadata);
+        persistence.save_key_metadata(&metadata).await.unwrap();
+        metadata.id
+    }
+
+    // 一组 (条件名, 条件值)
+    type Conditions<'a> = &'a [(&'a str, &'a str)];
+
+    fn filters(pairs: Conditions) -> Option<HashMap<String, String>> {
+        Some(pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect())
+    }
+
+    fn ids(list: &[KeyMetadata]) -> Vec<&str> {
+        let mut ids: Vec<&str> = list.iter().map(|metadata| metadata.id.as_str()).collect();
+        ids.sort();
+        ids
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    #[tokio::test]
+    async fn backup_opens_as_database_with_same_keys() {
+        let db = TempDb::new().await;
+        save_key(&db.persistence, "a", "alice").await;
+        save_key(&db.persistence, "b", "bob").await;
+
+        let dest = temp_db_path();
+        db.persistence.backup_to(dest.to_str().unwrap()).await.unwrap();
+
+        let backup = open(&dest).await;
+        let original = db.persistence.list_key_metadata(None).await.unwrap();
+        let restored = backup.list_key_metadata(None).await.unwrap();
+        assert_eq!(ids(&restored), ids(&original));
+        assert!(restored.iter().all(|metadata| metadata.tags.get("env").map(String::as_str) == Some("prod")));
+
+        // 目标文件已存在时拒绝覆盖
+        assert!(db.persistence.backup_to(dest.to_str().unwrap()).await.is_err());
+        let _ = std::fs::remove_file(dest);
+    }
+
+    #[tokio::test]
+    async fn backup_is_consistent_under_concurrent_writes() {
+        let db = TempDb::new().await;
+        let persistence = Arc::new(open(&db.path).await);
+
+        let writer = {
+            let persistence = Arc::clone(&persistence);
+            tokio::spawn(async move {
+                for i in 0..50 {
+                    save_key(&persistence, &format!("k{}", i), "alice").await;
+                }
+            })
+        };
+
+        let dest = temp_db_path();
+        persistence.backup_to(dest.to_str().unwrap()).await.unwrap();
+        writer.await.unwrap();
+
+        // 快照中的每个密钥都带有完整的标签，不会出现写了一半的记录
+        let backup = open(&dest).await;
+        for metadata in backup.list_key_metadata(None).await.unwrap() {
+            assert_eq!(metadata.tags.get("env").map(String::as_str), Some("prod"));
+        }
+        let _ = std::fs::remove_file(dest);
+    }
+
+    #[tokio::test]
+    async fn restore_replaces_data_with_backup() {
+        let db = TempDb::new().await;
+        let kept = save_key(&db.persistence, "kept", "alice").await;
+
+        let dest = temp_db_path();
+        db.persistence.backup_to(dest.to_str().unwrap()).await.unwrap();
+        save_key(&db.persistence, "added-after-backup", "alice").await;
+        db.persistence.delete_key_metadata(&kept).await.unwrap();
+
+        db.persistence.restore_from(dest.to_str().unwrap()).await.unwrap();
+        let list = db.persistence.list_key_metadata(None).await.unwrap();
+        assert_eq!(ids(&list), vec![kept.as_str()]);
+        assert_eq!(list[0].tags.get("env").map(String::as_str), Some("prod"));
+        let _ = std::fs::remove_file(dest);
+    }
+
+    #[tokio::test]
+    async fn restore_carries_aliases_and_accepts_backups_without_alias_table() {
+        let db = TempDb::new().await;
+        let kept = save_key(&db.persistence, "kept", "alice").await;
+        db.persistence.save_alias("primary", &kept).await.unwrap();
+
+        let dest = temp_db_path();
+        db.persistence.backup_to(dest.to_str().unwrap()).await.unwrap();
+        db.persistence.delete_alias("primary").await.unwrap();
+        db.persistence.save_alias("added-after-backup", &kept).await.unwrap();
+
+        db.persistence.restore_from(dest.to_str().unwrap()).await.unwrap();
+        let aliases = db.persistence.load_aliases().await.unwrap();
+        assert_eq!(aliases, HashMap::from([("primary".to_string(), kept.clone())]));
+        let _ = std::fs::remove_file(dest);
+
+        // 版本 1 的备份没有别名表，恢复后别名为空
+        let old = TempDb::new().await;
+        save_key(&old.persistence, "old", "alice").await;
+        sqlx::query("DROP TABLE key_aliases").execute(&old.persistence.pool).await.unwrap();
+        db.persistence.restore_from(old.path.to_str().unwrap()).await.unwrap();
+        assert!(db.persistence.load_aliases().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deleted_at_round_trips_and_is_added_to_old_tables() {
+        let db = TempDb::new().await;
+        let deleted_at = Utc::now();
+        let key_id = save_metadata(&db.persistence, "deleted", "alice", |metadata| metadata.deleted_at = Some(deleted_at)).await;
+        let loaded = db.persistence.load_key_metadata(&key_id).await.unwrap();
+        assert_eq!(loaded.deleted_at.map(|t| t.timestamp_millis()), Some(deleted_at.timestamp_millis()));
+
+        // 版本 3 之前的库没有 deleted_at 列，打开时补充
+        let old = TempDb::new().await;
+        let kept = save_key(&old.persistence, "kept", "alice").await;
+        sqlx::query("ALTER TABLE key_metadata DROP COLUMN deleted_at").execute(&old.persistence.pool).await.unwrap();
+        let reopened = open(&old.path).await;
+        assert!(reopened.load_key_metadata(&kept).await.unwrap().deleted_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn notes_round_trip_and_are_added_to_old_tables() {
+        let db = TempDb::new().await;
+        let key_id = save_metadata(&db.persistence, "noted", "alice", |metadata| metadata.notes = Some("轮换前通知支付组".to_string())).await;
+        let loaded = db.persistence.load_key_metadata(&key_id).await.unwrap();
+        assert_eq!(loaded.notes.as_deref(), Some("轮换前通知支付组"));
+
+        // 版本 8 之前的库没有 notes 列，打开时补充，已有的密钥没有备注
+        let old = TempDb::new().await;
+        let kept = save_key(&old.persistence, "kept", "alice").await;
+        sqlx::query("ALTER TABLE key_metadata DROP COLUMN notes").execute(&old.persistence.pool).await.unwrap();
+        let reopened = open(&old.path).await;
+        let mut metadata = reopened.load_key_metadata(&kept).await.unwrap();
+        assert!(metadata.notes.is_none());
+
+        metadata.notes = Some("migrated".to_string());
+        reopened.save_key_metadata(&metadata).await.unwrap();
+        assert_eq!(reopened.load_key_metadata(&kept).await.unwrap().notes.as_deref(), Some("migrated"));
+    }
+
+    #[tokio::test]
+    async fn security_module_id_round_trips_and_defaults_on_old_tables() {
+        let db = TempDb::new().await;
+        let key_id = save_metadata(&db.persistence, "hsm", "alice", |metadata| metadata.security_module_id = "hsm".to_string()).await;
+        assert_eq!(db.persistence.load_key_metadata(&key_id).await.unwrap().security_module_id, "hsm");
+
+        // 版本 9 之前的库没有 security_module_id 列，已有的密钥属于默认安全模块
+        let old = TempDb::new().await;
+        let kept = save_key(&old.persistence, "kept", "alice").await;
+        sqlx::query("ALTER TABLE key_metadata DROP COLUMN security_module_id").execute(&old.persistence.pool).await.unwrap();
+        let reopened = open(&old.path).await;
+        assert_eq!(reopened.load_key_metadata(&kept).await.unwrap().security_module_id, "default");
+    }
+
+    #[tokio::test]
+    async fn last_used_at_round_trips_and_is_added_to_old_tables() {
+        let db = TempDb::new().await;
+        let used_at = timestamp::now();
+        let used = save_metadata(&db.persistence, "used", "alice", |metadata| metadata.last_used_at = Some(used_at)).await;
+        let unused = save_key(&db.persistence, "unused", "alice").await;
+        assert_eq!(db.persistence.load_key_metadata(&used).await.unwrap().last_used_at, Some(used_at));
+        assert_eq!(db.persistence.load_key_metadata(&unused).await.unwrap().last_used_at, None);
+
+        // 版本 10 之前的库没有 last_used_at 列，已有的密钥视为从未使用
+        let old = TempDb::new().await;
+        let kept = save_key(&old.persistence, "kept", "alice").await;
+        sqlx::query("ALTER TABLE key_metadata DROP COLUMN last_used_at").execute(&old.persistence.pool).await.unwrap();
+        let reopened = open(&old.path).await;
+        assert_eq!(reopened.load_key_metadata(&kept).await.unwrap().last_used_at, None);
+    }
+
+    #[tokio::test]
+    async fn timestamps_from_older_versions_are_normalized() {
+        let old = TempDb::new().await;
+        let key_id = save_key(&old.persistence, "legacy", "alice").await;
+        // 版本 6 之前按 to_rfc3339 保存，精度和时区不固定
+        sqlx::query("UPDATE key_metadata SET created_at = '2024-01-02T11:04:05.123456789+08:00' WHERE id = ?")
+            .bind(&key_id)
+            .execute(&old.persistence.pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM schema_migrations WHERE version >= 6").execute(&old.persistence.pool).await.unwrap();
+
+        let reopened = open(&old.path).await;
+        let created_at: String = sqlx::query_scalar("SELECT created_at FROM key_metadata WHERE id = ?")
+            .bind(&key_id)
+            .fetch_one(&reopened.pool)
+            .await
+            .unwrap();
+        assert_eq!(created_at, "2024-01-02T03:04:05.123Z");
+        let loaded = reopened.load_key_metadata(&key_id).await.unwrap();
+        assert_eq!(timestamp::format(&loaded.created_at), created_at);
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_newer_schema_version() {
+        let db = TempDb::new().await;
+        let kept = save_key(&db.persistence, "kept", "alice").await;
+
+        let newer = TempDb::new().await;
+        save_key(&newer.persistence, "from-newer", "alice").await;
+        sqlx::query("INSERT INTO schema_migrations (version, applied_at) VALUES (?, ?)")
+            .bind(SCHEMA_VERSION + 1)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&newer.persistence.pool)
+            .await
+            .unwrap();
+
+        let err = db.persistence.restore_from(newer.path.to_str().unwrap()).await.unwrap_err();
+        assert!(err.contains("拒绝恢复"), "{}", err);
+        let list = db.persistence.list_key_metadata(None).await.unwrap();
+        assert_eq!(ids(&list), vec![kept.as_str()]);
+
+        assert!(db.persistence.restore_from("/nonexistent/backup.db").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_database_without_key_tables() {
+        let db = TempDb::new().await;
+        let other = temp_db_path();
+        let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", other.display())).await.unwrap();
+        sqlx::query("CREATE TABLE unrelated (id INTEGER)").execute(&pool).await.unwrap();
+        pool.close().await;
+
+        let err = db.persistence.restore_from(other.to_str().unwrap()).await.unwrap_err();
+        assert!(err.contains("key_metadata"), "{}", err);
+        let _ = std::fs::remove_file(other);
+    }
+
+    #[tokio::test]
+    async fn metadata_stream_yields_every_row() {
+        let db = TempDb::new().await;
+        for index in 0..20 {
+            save_key(&db.persistence, &format!("key-{}", index), "alice").await;
+        }
+
+        let streamed: Vec<KeyMetadata> = db.persistence.list_key_metadata_stream(None)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 20);
+        assert!(streamed.iter().all(|metadata| metadata.tags.get("env").map(String::as_str) == Some("prod")));
+    }
+
+    #[tokio::test]
+    async fn init_db_creates_query_indexes() {
+        let db = TempDb::new().await;
+
+        let indexes: Vec<String> = sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'index'")
+            .fetch_all(&db.persistence.pool)
+            .await
+            .unwrap();
+        for expected in [
+            "idx_audit_logs_timestamp",
+            "idx_audit_logs_key_id",
+            "idx_audit_logs_user",
+            "idx_key_metadata_owner",
+            "idx_key_metadata_status",
+            "idx_key_metadata_tenant",
+            "idx_audit_logs_tenant",
+            "idx_key_aliases_key_id",
+        ] {
+            assert!(indexes.iter().any(|name| name == expected), "缺少索引 {}", expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn audit_log_lookup_by_key_uses_index() {
+        let db = TempDb::new().await;
+
+        let plan: Vec<String> = sqlx::query("EXPLAIN QUERY PLAN SELECT * FROM audit_logs WHERE key_id = ? ORDER BY timestamp DESC")
+            .bind("k1")
+            .fetch_all(&db.persistence.pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get::<String, _>("detail"))
+            .collect();
+        assert!(plan.iter().any(|detail| detail.contains("idx_audit_logs_key_id")), "{:?}", plan);
+    }
+
+    #[tokio::test]
+    async fn owner_filter_restricts_results() {
+        let db = TempDb::new().await;
+        let alice = save_key(&db.persistence, "a", "alice").await;
+        let bob = save_key(&db.persistence, "b", "bob").await;
+
+        let list = db.persistence.list_key_metadata(filters(&[("owner", "alice")])).await.unwrap();
+        assert_eq!(ids(&list), vec![alice.as_str()]);
+
+        let list = db.persistence.list_key_metadata(filters(&[("owner", "carol")])).await.unwrap();
+        assert!(list.is_empty());
+
+        let list = db.persistence.list_key_metadata(filters(&[("tag.env", "prod"), ("owner", "bob")])).await.unwrap();
+        assert_eq!(ids(&list), vec![bob.as_str()]);
+
+        // 未知的过滤条件被忽略
+        let list = db.persistence.list_key_metadata(filters(&[("colour", "blue")])).await.unwrap();
+        assert_eq!(list.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn filter_values_are_bound_not_interpolated() {
+        let owner = "alice' OR '1'='1".to_string();
+        let query = KeyQuery::new().with_owner(owner.clone()).with_tag("env".to_string(), "prod".to_string());
+        let sql = key_metadata_query(&query, None, None).into_sql();
+        assert!(!sql.contains("alice"), "{}", sql);
+        assert!(!sql.contains("prod"), "{}", sql);
+        assert_eq!(sql.matches('?').count(), 3, "{}", sql);
+
+        // 带引号的值按字面匹配，不会改变查询条件
+        let db = TempDb::new().await;
+        save_key(&db.persistence, "a", "alice").await;
+        let quoted = save_key(&db.persistence, "b", &owner).await;
+        let list = db.persistence.list_key_metadata(filters(&[("owner", &owner)])).await.unwrap();
+        assert_eq!(ids(&list), vec![quoted.as_str()]);
+    }
+
+    #[tokio::test]
+    async fn every_key_filter_restricts_results() {
+        for enum_encoding in [EnumEncoding::Name, EnumEncoding::Code] {
+            let db = TempDb::with_enum_encoding(enum_encoding).await;
+            assert_filters_restrict_results(&db.persistence).await;
+        }
+    }
+
+    async fn assert_filters_restrict_results(persistence: &DbPersistence) {
+        let k1 = save_metadata(persistence, "k1", "alice", |m| {
+            m.tags.insert("env".to_string(), "prod".to_string());
+        }).await;
+        let k2 = save_metadata(persistence, "k2", "bob", |m| {
+            m.key_type = KeyType::AsymmetricPrivate;
+            m.algorithm = KeyAlgorithm::ED25519;
+            m.status = KeyStatus::Suspended;
+            m.tags.insert("env".to_string(), "dev".to_string());
+        }).await;
+        let k3 = save_metadata(persistence, "k3", "alice", |m| {
+            m.status = KeyStatus::Suspended;
+            m.tags.insert("env".to_string(), "prod".to_string());
+            m.tags.insert("team".to_string(), "infra".to_string());
+        }).await;
+
+        let cases: [(Conditions, Vec<&str>); 8] = [
+            (&[("status", "SUSPENDED")], vec![&k2, &k3]),
+            (&[("type", "ASYMMETRIC_PRIVATE")], vec![&k2]),
+            (&[("algorithm", "AES-256")], vec![&k1, &k3]),
+            (&[("owner", "alice")], vec![&k1, &k3]),
+            (&[("tag.env", "prod")], vec![&k1, &k3]),
+            (&[("tag.env", "prod"), ("tag.team", "infra")], vec![&k3]),
+            (&[("owner", "alice"), ("status", "ACTIVE")], vec![&k1]),
+            (&[("tag.env", "staging")], vec![]),
+        ];
+        for (conditions, expected) in cases {
+            let list = persistence.list_key_metadata(filters(conditions)).await.unwrap();
+            let mut expected = expected;
+            expected.sort();
+            assert_eq!(ids(&list), expected, "{:?}", conditions);
+
+            // 流式读取使用同样的过滤条件
+            let streamed: Vec<KeyMetadata> = persistence.list_key_metadata_stream(filters(conditions))
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            assert_eq!(ids(&streamed), expected, "{:?}", conditions);
+        }
+    }
+
+    #[tokio::test]
+    async fn enum_codes_are_stored_and_mixed_rows_are_counted_together() {
+        let db = TempDb::with_enum_encoding(EnumEncoding::Code).await;
+        let coded = save_metadata(&db.persistence, "coded", "alice", |m| m.status = KeyStatus::Suspended).await;
+        let stored: (String, String, String) = sqlx::query_as("SELECT status, key_type, algorithm FROM key_metadata WHERE id = ?")
+            .bind(&coded)
+            .fetch_one(&db.persistence.pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, ("2".to_string(), "1".to_string(), "1".to_string()));
+        assert_eq!(db.persistence.load_key_metadata(&coded).await.unwrap().status, KeyStatus::Suspended);
+
+        // 切换存储形式后按名称写入的行与按编码写入的行一起统计
+        let named = open(&db.path).await;
+        save_metadata(&named, "named", "alice", |m| m.status = KeyStatus::Suspended).await;
+        let stats = named.key_stats(None, Utc::now()).await.unwrap();
+        assert_eq!(stats.by_status.get("SUSPENDED"), Some(&2));
+        assert_eq!(stats.by_algorithm.get("AES-256"), Some(&2));
+
+        assert!("code".parse::<EnumEncoding>().is_ok());
+        assert!("binary".parse::<EnumEncoding>().is_err());
+    }
+
+    #[tokio::test]
+    async fn every_audit_log_filter_restricts_results() {
+        let db = TempDb::new().await;
+        let persistence = &db.persistence;
+        let now = Utc::now();
+        let entry = |id: &str, action: &str, user: &str, key_id: &str, success: bool, age: i64| {
+            let mut entry = AuditLogEntry::new(action.to_string(), user.to_string(), Some(key_id.to_string()), String::new(), success);
+            entry.id = id.to_string();
+            entry.timestamp = now - chrono::Duration::seconds(age);
+            entry
+        };
+        for entry in [
+            entry("a1", "CREATE_KEY", "alice", "k1", true, 3),
+            entry("a2", "SIGN", "bob", "k2", true, 2),
+            entry("a3", "SIGN", "alice", "k1", false, 1).with_tenant("acme".to_string()),
+        ] {
+            persistence.save_audit_log(&entry).await.unwrap();
+        }
+
+        // 结果按时间倒序
+        let cases: [(Conditions, &[&str]); 9] = [
+            (&[("action", "SIGN")], &["a3", "a2"]),
+            (&[("user", "alice")], &["a3", "a1"]),
+            (&[("key_id", "k2")], &["a2"]),
+            (&[("success", "true")], &["a2", "a1"]),
+            (&[("success", "false")], &["a3"]),
+            (&[("action", "SIGN"), ("user", "alice")], &["a3"]),
+            (&[("user", "alice' OR '1'='1")], &[]),
+            (&[("tenant", "acme")], &["a3"]),
+            (&[("tenant", "default")], &["a2", "a1"]),
+        ];
+        for (conditions, expected) in cases {
+            let logs = persistence.load_audit_logs(filters(conditions), None).await.unwrap();
+            let logs: Vec<&str> = logs.iter().map(|log| log.id.as_str()).collect();
+            assert_eq!(logs, expected, "{:?}", conditions);
+        }
+
+        let logs = persistence.load_audit_logs(None, Some(1)).await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].id, "a3");
+    }
+
+    #[tokio::test]
+    async fn metadata_stream_pages_past_page_size() {
+        let db = TempDb::new().await;
+        let total = STREAM_PAGE_SIZE as usize * 2 + 5;
+        for index in 0..total {
+            save_key(&db.persistence, &format!("key-{}", index), "alice").await;
+        }
+
+        let streamed: Vec<KeyMetadata> = db.persistence.list_key_metadata_stream(None)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let listed = db.persistence.list_key_metadata(None).await.unwrap();
+        assert_eq!(streamed.len(), total);
+        assert_eq!(ids(&streamed), ids(&listed));
+    }
+}
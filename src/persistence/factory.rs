@@ -0,0 +1,67 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::plugin_config::PluginConfig;
+use super::{FilePersistence, MemoryPersistence, PersistenceInterface};
+#[cfg(feature = "sqlite")]
+use super::{DbPersistence, EnumEncoding};
+
+/// 选择持久化后端的配置项，取值为 file、sqlite 或 memory
+pub const PERSISTENCE_BACKEND: &str = "persistence_backend";
+/// 文件存储的目录，不存在时自动创建
+pub const PERSISTENCE_PATH: &str = "persistence_path";
+/// 数据库连接 URL，如 `sqlite:keys.db?mode=rwc`
+pub const DB_URL: &str = "db_url";
+/// 数据库中密钥状态、类型和算法的存储形式，取值为 name（默认）或 code
+pub const ENUM_ENCODING: &str = "enum_encoding";
+
+/// 配置中是否指定了持久化存储
+pub fn persistence_configured(config: &PluginConfig) -> bool {
+    [PERSISTENCE_BACKEND, PERSISTENCE_PATH, DB_URL]
+        .iter()
+        .any(|key| config.get_config(key).is_some_and(|value| !value.is_empty()))
+}
+
+/// 按配置创建持久化存储
+///
+/// 未配置 `persistence_backend` 时，配置了 `db_url` 的使用 sqlite，配置了 `persistence_path` 的使用文件存储
+pub async fn build_persistence(config: &PluginConfig) -> Result<Arc<dyn PersistenceInterface + Send + Sync>, String> {
+    let value = |key: &str| config.get_config(key).filter(|value| !value.is_empty());
+
+    let backend = match value(PERSISTENCE_BACKEND) {
+        Some(backend) => backend.to_lowercase(),
+        None if value(DB_URL).is_some() => "sqlite".to_string(),
+        None if value(PERSISTENCE_PATH).is_some() => "file".to_string(),
+        None => return Err(format!("Missing config: {}", PERSISTENCE_BACKEND)),
+    };
+
+    match backend.as_str() {
+        "file" => {
+            let path = value(PERSISTENCE_PATH)
+                .ok_or_else(|| format!("Missing config: {} (required by file persistence)", PERSISTENCE_PATH))?;
+            // FilePersistence::new 只输出创建目录的错误，这里先创建以便报告原因
+            std::fs::create_dir_all(Path::new(path).join("metadata"))
+                .map_err(|e| format!("持久化存储不可用 ({}): 创建目录失败: {}", path, e))?;
+            Ok(Arc::new(FilePersistence::new(path)))
+        }
+        "sqlite" => {
+            let db_url = value(DB_URL)
+                .ok_or_else(|| format!("Missing config: {} (required by sqlite persistence)", DB_URL))?;
+            #[cfg(feature = "sqlite")]
+            {
+                let enum_encoding = match value(ENUM_ENCODING) {
+                    Some(encoding) => encoding.parse::<EnumEncoding>()?,
+                    None => EnumEncoding::default(),
+                };
+                return match DbPersistence::new(db_url).await {
+                    Ok(persistence) => Ok(Arc::new(persistence.with_enum_encoding(enum_encoding))),
+                    Err(e) => Err(format!("持久化存储不可用 ({}): {}", db_url, e)),
+                };
+            }
+            #[cfg(not(feature = "sqlite"))]
+            return Err(format!("未启用 sqlite 特性，不能连接数据库: {}", db_url));
+        }
+        "memory" => Ok(Arc::new(MemoryPersistence::new())),
+        other => Err(format!("Invalid {}: {} (expected file, sqlite or memory)", PERSISTENCE_BACKEND, other)),
+    }
+}
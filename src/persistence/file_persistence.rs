@@ -1,22 +1,37 @@
 use async_trait::async_trait;
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Write};
-use std::path::Path;
+use chrono::{Duration, Utc};
+use futures::future;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
 
 // 修改导入路径，使用新的模块结构
-use crate::key_management::models::key_models::{AuditLogEntry, KeyMetadata};
+use crate::key_management::models::key_models::{AttachmentInfo, AuditLogEntry, KeyMetadata};
 use crate::persistence::PersistenceInterface;
+use crate::persistence::key_query::KeyQuery;
+
+// 默认并发读取的元数据文件数
+const DEFAULT_LIST_CONCURRENCY: usize = 16;
 
 pub struct FilePersistence {
     metadata_dir: String,
     audit_log_file: String,
+    aliases_file: String,
+    attachments_dir: String, // 每个密钥一个子目录，附件名即文件名
+    audit_lock: Mutex<()>, // 追加写入和压缩审计日志互斥，避免压缩期间写入的日志丢失
+    list_concurrency: usize, // 列出密钥时并发读取的文件数
+    alias_lock: Mutex<()>,   // 别名文件整体读写，修改时互斥
 }
 
 impl FilePersistence {
     pub fn new(base_dir: &str) -> Self {
         let metadata_dir = format!("{}/metadata", base_dir);
         let audit_log_file = format!("{}/audit.log", base_dir);
+        let aliases_file = format!("{}/aliases.json", base_dir);
+        let attachments_dir = format!("{}/attachments", base_dir);
         
         // 确保目录存在
         std::fs::create_dir_all(&metadata_dir).unwrap_or_else(|e| {
@@ -26,7 +41,148 @@ impl FilePersistence {
         Self {
             metadata_dir,
             audit_log_file,
+            aliases_file,
+            attachments_dir,
+            audit_lock: Mutex::new(()),
+            list_concurrency: DEFAULT_LIST_CONCURRENCY,
+            alias_lock: Mutex::new(()),
+        }
+    }
+
+    /// 设置列出密钥时并发读取的文件数，最小为 1
+    pub fn with_list_concurrency(mut self, concurrency: usize) -> Self {
+        self.list_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// 压缩审计日志，只保留保留期内的条目，可选按ID去重
+    ///
+    /// 先写入临时文件再重命名替换，返回压缩前后的行数；无法解析的行原样保留
+    pub async fn compact_audit_log(&self, retention: Option<Duration>, dedupe: bool) -> Result<(usize, usize), String> {
+        let _guard = self.audit_lock.lock().await;
+
+        if !self.audit_log_exists().await {
+            return Ok((0, 0));
+        }
+
+        let file = File::open(&self.audit_log_file)
+            .await
+            .map_err(|e| format!("打开审计日志文件失败: {}", e))?;
+        let cutoff = retention.map(|retention| Utc::now() - retention);
+
+        let mut original = 0;
+        let mut kept = Vec::new();
+        let mut seen = HashSet::new();
+
+        let mut lines = BufReader::new(file).lines();
+        while let Some(line) = lines.next_line()
+            .await
+            .map_err(|e| format!("读取审计日志行失败: {}", e))?
+        {
+            if line.trim().is_empty() {
+                continue;
+            }
+            original += 1;
+
+            if let Ok(log) = serde_json::from_str::<AuditLogEntry>(&line) {
+                if cutoff.is_some_and(|cutoff| log.timestamp < cutoff) {
+                    continue;
+                }
+                if dedupe && !seen.insert(log.id) {
+                    continue;
+                }
+            }
+
+            kept.push(line);
+        }
+
+        let temp_file = format!("{}.tmp", self.audit_log_file);
+        {
+            let mut file = File::create(&temp_file)
+                .await
+                .map_err(|e| format!("创建临时审计日志文件失败: {}", e))?;
+            for line in &kept {
+                file.write_all(format!("{}\n", line).as_bytes())
+                    .await
+                    .map_err(|e| format!("写入临时审计日志文件失败: {}", e))?;
+            }
+            file.sync_all()
+                .await
+                .map_err(|e| format!("写入临时审计日志文件失败: {}", e))?;
+        }
+
+        fs::rename(&temp_file, &self.audit_log_file)
+            .await
+            .map_err(|e| format!("替换审计日志文件失败: {}", e))?;
+
+        Ok((original, kept.len()))
+    }
+
+    // 读取目录中的下一个元数据文件路径，跳过非 json 文件
+    async fn next_metadata_path(entries: &mut fs::ReadDir) -> Result<Option<PathBuf>, String> {
+        while let Some(entry) = entries.next_entry()
+            .await
+            .map_err(|e| format!("读取目录条目失败: {}", e))?
+        {
+            let path = entry.path();
+            let is_file = entry.file_type().await.map(|t| t.is_file()).unwrap_or(false);
+
+            if is_file && path.extension().is_some_and(|ext| ext == "json") {
+                return Ok(Some(path));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn read_metadata_file(path: PathBuf) -> Result<KeyMetadata, String> {
+        let json = fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("读取元数据文件失败: {}", e))?;
+
+        serde_json::from_str::<KeyMetadata>(&json)
+            .map_err(|e| format!("解析元数据失败: {}", e))
+    }
+
+    async fn read_aliases(&self) -> Result<HashMap<String, String>, String> {
+        match fs::read_to_string(&self.aliases_file).await {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| format!("解析别名文件失败: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(format!("读取别名文件失败: {}", e)),
+        }
+    }
+
+    // 先写临时文件再重命名，避免写入中途失败损坏别名文件
+    async fn write_aliases(&self, aliases: &HashMap<String, String>) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(aliases)
+            .map_err(|e| format!("序列化别名失败: {}", e))?;
+
+        let temp_file = format!("{}.tmp", self.aliases_file);
+        fs::write(&temp_file, json)
+            .await
+            .map_err(|e| format!("写入别名文件失败: {}", e))?;
+        fs::rename(&temp_file, &self.aliases_file)
+            .await
+            .map_err(|e| format!("替换别名文件失败: {}", e))
+    }
+
+    // 附件所在目录，拒绝可能跳出附件目录的密钥ID
+    fn attachment_dir(&self, key_id: &str) -> Result<PathBuf, String> {
+        if key_id.is_empty() || key_id.contains(['/', '\\']) || key_id.starts_with('.') {
+            return Err(format!("无效的密钥ID: {}", key_id));
+        }
+        Ok(PathBuf::from(&self.attachments_dir).join(key_id))
+    }
+
+    fn attachment_path(&self, key_id: &str, name: &str) -> Result<PathBuf, String> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(format!("无效的附件名: {}", name));
         }
+        Ok(self.attachment_dir(key_id)?.join(name))
+    }
+
+    async fn audit_log_exists(&self) -> bool {
+        fs::try_exists(&self.audit_log_file).await.unwrap_or(false)
     }
 }
 
@@ -38,6 +194,7 @@ impl PersistenceInterface for FilePersistence {
             .map_err(|e| format!("序列化元数据失败: {}", e))?;
         
         fs::write(&file_path, json)
+            .await
             .map_err(|e| format!("写入元数据文件失败: {}", e))?;
         
         Ok(())
@@ -46,6 +203,7 @@ impl PersistenceInterface for FilePersistence {
     async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
         let file_path = format!("{}/{}.json", self.metadata_dir, key_id);
         let json = fs::read_to_string(&file_path)
+            .await
             .map_err(|e| format!("读取元数据文件失败: {}", e))?;
         
         serde_json::from_str(&json)
@@ -55,100 +213,100 @@ impl PersistenceInterface for FilePersistence {
     async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
         let file_path = format!("{}/{}.json", self.metadata_dir, key_id);
         
-        if Path::new(&file_path).exists() {
-            fs::remove_file(&file_path)
-                .map_err(|e| format!("删除元数据文件失败: {}", e))?;
+        match fs::remove_file(&file_path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("删除元数据文件失败: {}", e)),
+        }
+
+        // 附件随密钥一起删除
+        match fs::remove_dir_all(self.attachment_dir(key_id)?).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("删除附件失败: {}", e)),
         }
         
         Ok(())
     }
     
-    async fn list_key_metadata(&self, filters: Option<HashMap<String, String>>) -> Result<Vec<KeyMetadata>, String> {
-        let mut result = Vec::new();
-        
-        let entries = fs::read_dir(&self.metadata_dir)
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        let mut paths = Vec::new();
+
+        let mut entries = fs::read_dir(&self.metadata_dir)
+            .await
             .map_err(|e| format!("读取元数据目录失败: {}", e))?;
-        
-        for entry in entries {
-            let entry = entry.map_err(|e| format!("读取目录条目失败: {}", e))?;
-            let path = entry.path();
-            
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
-                let json = fs::read_to_string(&path)
-                    .map_err(|e| format!("读取元数据文件失败: {}", e))?;
-                
-                let metadata: KeyMetadata = serde_json::from_str(&json)
-                    .map_err(|e| format!("解析元数据失败: {}", e))?;
-                
-                // 应用过滤器
-                if let Some(filters) = &filters {
-                    let mut match_all = true;
-                    
-                    for (key, value) in filters {
-                        match key.as_str() {
-                            "status" => {
-                                if metadata.status.to_string() != *value {
-                                    match_all = false;
-                                    break;
-                                }
-                            }
-                            "type" => {
-                                if metadata.key_type.to_string() != *value {
-                                    match_all = false;
-                                    break;
-                                }
-                            }
-                            "algorithm" => {
-                                if metadata.algorithm.to_string() != *value {
-                                    match_all = false;
-                                    break;
-                                }
-                            }
-                            "owner" => {
-                                if metadata.owner != *value {
-                                    match_all = false;
-                                    break;
-                                }
-                            }
-                            _ => {
-                                // 检查是否是标签过滤器
-                                if key.starts_with("tag.") {
-                                    let tag_key = key.strip_prefix("tag.").unwrap();
-                                    match metadata.tags.get(tag_key) {
-                                        Some(tag_value) if tag_value == value => {}
-                                        _ => {
-                                            match_all = false;
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    if !match_all {
-                        continue;
-                    }
-                }
-                
+
+        while let Some(path) = Self::next_metadata_path(&mut entries).await? {
+            paths.push(path);
+        }
+
+        // 并发读取和解析元数据文件，并发数受 list_concurrency 限制
+        let loaded: Vec<Result<KeyMetadata, String>> = stream::iter(paths)
+            .map(Self::read_metadata_file)
+            .buffer_unordered(self.list_concurrency)
+            .collect()
+            .await;
+
+        let mut result = Vec::with_capacity(loaded.len());
+        for metadata in loaded {
+            let metadata = metadata?;
+            if query.matches(&metadata) {
                 result.push(metadata);
             }
         }
-        
+
+        // 并发读取的完成顺序不确定，按创建时间排序保证结果稳定
+        result.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+
         Ok(result)
     }
     
+    fn query_keys_stream(&self, query: KeyQuery) -> BoxStream<'_, Result<KeyMetadata, String>> {
+        let metadata_dir = self.metadata_dir.clone();
+
+        // 每次只读取一个文件，出错后结束流
+        let entries = async move {
+            fs::read_dir(&metadata_dir)
+                .await
+                .map_err(|e| format!("读取元数据目录失败: {}", e))
+        };
+
+        stream::once(entries)
+            .flat_map(|entries| {
+                stream::unfold(Some(entries), |state| async move {
+                    let mut entries = match state? {
+                        Ok(entries) => entries,
+                        Err(e) => return Some((Err(e), None)),
+                    };
+
+                    match Self::next_metadata_path(&mut entries).await {
+                        Ok(Some(path)) => Some((Self::read_metadata_file(path).await, Some(Ok(entries)))),
+                        Ok(None) => None,
+                        Err(e) => Some((Err(e), None)),
+                    }
+                })
+            })
+            .filter(move |result| future::ready(match result {
+                Ok(metadata) => query.matches(metadata),
+                Err(_) => true,
+            }))
+            .boxed()
+    }
+
     async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
         let json = serde_json::to_string(log)
             .map_err(|e| format!("序列化审计日志失败: {}", e))?;
         
+        let _guard = self.audit_lock.lock().await;
         let mut file = fs::OpenOptions::new()
             .create(true)
             .append(true)
             .open(&self.audit_log_file)
+            .await
             .map_err(|e| format!("打开审计日志文件失败: {}", e))?;
         
-        writeln!(file, "{}", json)
+        file.write_all(format!("{}\n", json).as_bytes())
+            .await
             .map_err(|e| format!("写入审计日志失败: {}", e))?;
         
         Ok(())
@@ -157,17 +315,20 @@ impl PersistenceInterface for FilePersistence {
     async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
         let mut result = Vec::new();
         
-        if !Path::new(&self.audit_log_file).exists() {
+        if !self.audit_log_exists().await {
             return Ok(result);
         }
         
         let file = File::open(&self.audit_log_file)
+            .await
             .map_err(|e| format!("打开审计日志文件失败: {}", e))?;
         
-        let reader = BufReader::new(file);
+        let mut lines = BufReader::new(file).lines();
         
-        for line in reader.lines() {
-            let line = line.map_err(|e| format!("读取审计日志行失败: {}", e))?;
+        while let Some(line) = lines.next_line()
+            .await
+            .map_err(|e| format!("读取审计日志行失败: {}", e))?
+        {
             
             let log: AuditLogEntry = serde_json::from_str(&line)
                 .map_err(|e| format!("解析审计日志失败: {}", e))?;
@@ -196,6 +357,10 @@ impl PersistenceInterface for FilePersistence {
                                 break;
                             }
                         }
+                        "tenant" if log.tenant != *value => {
+                            match_all = false;
+                            break;
+                        }
                         "success" => {
                             let success_value = value.parse::<bool>().unwrap_or(false);
                             if log.success != success_value {
@@ -224,4 +389,256 @@ impl PersistenceInterface for FilePersistence {
         
         Ok(result)
     }
-}
\ No newline at end of file
+
+    async fn save_alias(&self, alias: &str, key_id: &str) -> Result<(), String> {
+        let _guard = self.alias_lock.lock().await;
+        let mut aliases = self.read_aliases().await?;
+        aliases.insert(alias.to_string(), key_id.to_string());
+        self.write_aliases(&aliases).await
+    }
+
+    async fn delete_alias(&self, alias: &str) -> Result<(), String> {
+        let _guard = self.alias_lock.lock().await;
+        let mut aliases = self.read_aliases().await?;
+        if aliases.remove(alias).is_some() {
+            self.write_aliases(&aliases).await?;
+        }
+        Ok(())
+    }
+
+    async fn load_aliases(&self) -> Result<HashMap<String, String>, String> {
+        let _guard = self.alias_lock.lock().await;
+        self.read_aliases().await
+    }
+
+    async fn put_attachment(&self, key_id: &str, name: &str, data: &[u8]) -> Result<(), String> {
+        let path = self.attachment_path(key_id, name)?;
+        fs::create_dir_all(self.attachment_dir(key_id)?)
+            .await
+            .map_err(|e| format!("创建附件目录失败: {}", e))?;
+
+        // 先写临时文件再重命名，读取时不会读到写了一半的附件；附件名不能以 . 开头，临时文件名不会冲突
+        let temp_path = self.attachment_dir(key_id)?.join(format!(".{}.tmp", name));
+        fs::write(&temp_path, data)
+            .await
+            .map_err(|e| format!("写入附件失败: {}", e))?;
+        fs::rename(&temp_path, &path)
+            .await
+            .map_err(|e| format!("替换附件失败: {}", e))
+    }
+
+    async fn get_attachment(&self, key_id: &str, name: &str) -> Result<Vec<u8>, String> {
+        match fs::read(self.attachment_path(key_id, name)?).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(format!("附件不存在: {}", name)),
+            Err(e) => Err(format!("读取附件失败: {}", e)),
+        }
+    }
+
+    async fn list_attachments(&self, key_id: &str) -> Result<Vec<AttachmentInfo>, String> {
+        let mut entries = match fs::read_dir(self.attachment_dir(key_id)?).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("读取附件目录失败: {}", e)),
+        };
+
+        let mut list = Vec::new();
+        while let Some(entry) = entries.next_entry()
+            .await
+            .map_err(|e| format!("读取附件目录失败: {}", e))?
+        {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // 跳过写入中途留下的临时文件
+            if name.starts_with('.') {
+                continue;
+            }
+            let metadata = entry.metadata()
+                .await
+                .map_err(|e| format!("读取附件信息失败: {}", e))?;
+            list.push(AttachmentInfo { name, size: metadata.len() as usize });
+        }
+
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(list)
+    }
+
+    async fn compact_audit_log(&self, retention: Option<Duration>, dedupe: bool) -> Result<(usize, usize), String> {
+        FilePersistence::compact_audit_log(self, retention, dedupe).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_management::models::key_models::{KeyAlgorithm, KeyType};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    struct TempDir {
+        persistence: FilePersistence,
+        path: PathBuf,
+    }
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("password_manager_test_{}", uuid::Uuid::new_v4()));
+            let persistence = FilePersistence::new(path.to_str().unwrap());
+            Self { persistence, path }
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+
+    fn metadata(index: usize) -> KeyMetadata {
+        let mut metadata = KeyMetadata::new(
+            format!("key-{}", index),
+            String::new(),
+            KeyType::Symmetric,
+            KeyAlgorithm::AES256,
+            if index.is_multiple_of(2) { "alice" } else { "bob" }.to_string(),
+            false,
+        );
+        metadata.created_at = Utc::now() - Duration::seconds(index as i64);
+        metadata
+    }
+
+    fn entry(action: &str, age: Duration) -> AuditLogEntry {
+        let mut entry = AuditLogEntry::new(action.to_string(), "alice".to_string(), None, String::new(), true);
+        entry.timestamp = Utc::now() - age;
+        entry
+    }
+
+    fn line_count(dir: &TempDir) -> usize {
+        std::fs::read_to_string(&dir.persistence.audit_log_file).unwrap().lines().count()
+    }
+
+    #[tokio::test]
+    async fn compaction_drops_entries_outside_retention() {
+        let dir = TempDir::new();
+        for age in [Duration::days(90), Duration::days(40), Duration::days(1), Duration::zero()] {
+            dir.persistence.save_audit_log(&entry(&format!("AGE_{}", age.num_days()), age)).await.unwrap();
+        }
+
+        let (original, remaining) = dir.persistence.compact_audit_log(Some(Duration::days(30)), false).await.unwrap();
+        assert_eq!((original, remaining), (4, 2));
+        assert_eq!(line_count(&dir), 2);
+        assert!(!std::path::Path::new(&format!("{}.tmp", dir.persistence.audit_log_file)).exists());
+
+        let mut actions: Vec<String> = dir.persistence.load_audit_logs(None, None).await.unwrap()
+            .into_iter().map(|log| log.action).collect();
+        actions.sort();
+        assert_eq!(actions, vec!["AGE_0", "AGE_1"]);
+    }
+
+    #[tokio::test]
+    async fn compaction_can_remove_duplicate_entries() {
+        let dir = TempDir::new();
+        let duplicated = entry("CREATE_KEY", Duration::zero());
+        dir.persistence.save_audit_log(&duplicated).await.unwrap();
+        dir.persistence.save_audit_log(&duplicated).await.unwrap();
+        dir.persistence.save_audit_log(&entry("DELETE_KEY", Duration::zero())).await.unwrap();
+
+        assert_eq!(dir.persistence.compact_audit_log(None, false).await.unwrap(), (3, 3));
+        assert_eq!(dir.persistence.compact_audit_log(None, true).await.unwrap(), (3, 2));
+        assert_eq!(line_count(&dir), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_listing_returns_every_key_in_creation_order() {
+        let dir = TempDir::new();
+        let persistence = FilePersistence::new(dir.path.to_str().unwrap()).with_list_concurrency(8);
+        for index in 0..300 {
+            persistence.save_key_metadata(&metadata(index)).await.unwrap();
+        }
+
+        let started = Instant::now();
+        let listed = persistence.list_key_metadata(None).await.unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert_eq!(listed.len(), 300);
+        // 索引越大创建时间越早，排序后名称依次为 key-299 .. key-0
+        let names: Vec<String> = listed.iter().map(|metadata| metadata.name.clone()).collect();
+        let expected: Vec<String> = (0..300).rev().map(|index| format!("key-{}", index)).collect();
+        assert_eq!(names, expected);
+
+        let filters = HashMap::from([("owner".to_string(), "bob".to_string())]);
+        let bobs = persistence.list_key_metadata(Some(filters)).await.unwrap();
+        assert_eq!(bobs.len(), 150);
+        assert!(bobs.iter().all(|metadata| metadata.owner == "bob"));
+
+        // 并发数为 0 时按 1 处理
+        let serial = FilePersistence::new(dir.path.to_str().unwrap()).with_list_concurrency(0);
+        assert_eq!(serial.list_key_metadata(None).await.unwrap().len(), 300);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn concurrent_saves_do_not_starve_other_tasks() {
+        let dir = TempDir::new();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = Arc::clone(&ticks);
+            async move {
+                loop {
+                    tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        let saves = (0..200).map(|index| {
+            let persistence = &dir.persistence;
+            async move {
+                persistence.save_key_metadata(&metadata(index)).await?;
+                persistence.save_audit_log(&entry("CREATE_KEY", Duration::zero())).await
+            }
+        });
+        let results = futures::future::join_all(saves).await;
+        ticker.abort();
+
+        assert!(results.iter().all(Result::is_ok));
+        // 单线程运行时上，同步IO会一直占用线程，计时任务没有机会运行
+        assert!(ticks.load(Ordering::SeqCst) > 0);
+        assert_eq!(dir.persistence.list_key_metadata(None).await.unwrap().len(), 200);
+        assert_eq!(line_count(&dir), 200);
+    }
+
+    #[tokio::test]
+    async fn metadata_stream_yields_every_matching_key() {
+        let dir = TempDir::new();
+        for index in 0..50 {
+            dir.persistence.save_key_metadata(&metadata(index)).await.unwrap();
+        }
+        std::fs::write(dir.path.join("metadata/notes.txt"), "not metadata").unwrap();
+
+        let count = dir.persistence.list_key_metadata_stream(None)
+            .fold(0, |count, result| async move {
+                result.unwrap();
+                count + 1
+            })
+            .await;
+        assert_eq!(count, 50);
+
+        let filters = HashMap::from([("owner".to_string(), "alice".to_string())]);
+        let alices: Vec<KeyMetadata> = dir.persistence.list_key_metadata_stream(Some(filters))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(alices.len(), 25);
+        assert!(alices.iter().all(|metadata| metadata.owner == "alice"));
+    }
+
+    #[tokio::test]
+    async fn metadata_stream_reports_unreadable_directory() {
+        let dir = TempDir::new();
+        std::fs::remove_dir_all(dir.path.join("metadata")).unwrap();
+
+        let results: Vec<Result<KeyMetadata, String>> = dir.persistence.list_key_metadata_stream(None).collect().await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::key_management::models::key_models::{KeyAlgorithm, KeyMetadata, KeyStatus, KeyType};
+
+/// 密钥元数据查询条件
+///
+/// 所有条件同时满足才算匹配，未设置的条件不参与过滤
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyQuery {
+    pub status: Option<KeyStatus>,
+    pub key_type: Option<KeyType>,
+    pub algorithm: Option<KeyAlgorithm>,
+    pub owner: Option<String>,
+    pub tags: HashMap<String, String>,
+    pub name_contains: Option<String>, // 名称包含的子串，区分大小写
+    pub tenant: Option<String>,
+}
+
+impl KeyQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_status(mut self, status: KeyStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_key_type(mut self, key_type: KeyType) -> Self {
+        self.key_type = Some(key_type);
+        self
+    }
+
+    pub fn with_algorithm(mut self, algorithm: KeyAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    pub fn with_owner(mut self, owner: String) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub fn with_tag(mut self, key: String, value: String) -> Self {
+        self.tags.insert(key, value);
+        self
+    }
+
+    pub fn with_name_contains(mut self, name: String) -> Self {
+        self.name_contains = Some(name);
+        self
+    }
+
+    pub fn with_tenant(mut self, tenant: String) -> Self {
+        self.tenant = Some(tenant);
+        self
+    }
+
+    /// 从字符串过滤条件转换
+    ///
+    /// 支持 status、type、algorithm、owner、name_contains、tenant 和 tag.* 条件，
+    /// 状态、类型、算法的值无效时返回错误，未知的条件忽略
+    pub fn from_filters(filters: Option<&HashMap<String, String>>) -> Result<Self, String> {
+        let mut query = Self::new();
+
+        for (key, value) in filters.into_iter().flatten() {
+            match key.as_str() {
+                "status" => query.status = Some(KeyStatus::from_str(value)?),
+                "type" => query.key_type = Some(KeyType::from_str(value)?),
+                "algorithm" => query.algorithm = Some(KeyAlgorithm::from_str(value)?),
+                "owner" => query.owner = Some(value.clone()),
+                "name_contains" => query.name_contains = Some(value.clone()),
+                "tenant" => query.tenant = Some(value.clone()),
+                _ => {
+                    if let Some(tag_key) = key.strip_prefix("tag.") {
+                        query.tags.insert(tag_key.to_string(), value.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(query)
+    }
+
+    /// 判断元数据是否满足查询条件
+    pub fn matches(&self, metadata: &KeyMetadata) -> bool {
+        self.status.as_ref().is_none_or(|status| metadata.status == *status)
+            && self.key_type.as_ref().is_none_or(|key_type| metadata.key_type == *key_type)
+            && self.algorithm.as_ref().is_none_or(|algorithm| metadata.algorithm == *algorithm)
+            && self.owner.as_ref().is_none_or(|owner| metadata.owner == *owner)
+            && self.name_contains.as_ref().is_none_or(|name| metadata.name.contains(name.as_str()))
+            && self.tenant.as_ref().is_none_or(|tenant| metadata.tenant == *tenant)
+            && self.tags.iter().all(|(key, value)| metadata.tags.get(key) == Some(value))
+    }
+}
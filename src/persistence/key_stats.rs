@@ -0,0 +1,60 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::key_management::models::key_models::KeyMetadata;
+
+/// 统计即将过期密钥时使用的天数
+pub const EXPIRING_WITHIN_DAYS: i64 = 30;
+
+/// 密钥统计，软删除的密钥不计入
+///
+/// 分组的键与持久化时使用的字符串一致，如 `ACTIVE`、`SYMMETRIC`、`AES-256`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KeyStats {
+    pub total: usize,
+    pub by_status: BTreeMap<String, usize>,
+    pub by_key_type: BTreeMap<String, usize>,
+    pub by_algorithm: BTreeMap<String, usize>,
+    pub expiring_within_30_days: usize, // 过期时间在 (now, now + 30 天] 内的密钥
+}
+
+impl KeyStats {
+    /// 在内存中统计密钥
+    pub fn from_keys<'a>(keys: impl IntoIterator<Item = &'a KeyMetadata>, now: DateTime<Utc>) -> Self {
+        let expiring_before = expiring_before(now);
+        let mut stats = Self::default();
+
+        for metadata in keys {
+            if metadata.deleted_at.is_some() {
+                continue;
+            }
+
+            stats.total += 1;
+            *stats.by_status.entry(metadata.status.to_string()).or_default() += 1;
+            *stats.by_key_type.entry(metadata.key_type.to_string()).or_default() += 1;
+            *stats.by_algorithm.entry(metadata.algorithm.to_string()).or_default() += 1;
+            if metadata.expiration_date.is_some_and(|date| date > now && date <= expiring_before) {
+                stats.expiring_within_30_days += 1;
+            }
+        }
+
+        stats
+    }
+}
+
+/// 按所有者统计未删除的密钥数量
+pub fn count_by_owner<'a>(keys: impl IntoIterator<Item = &'a KeyMetadata>) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for metadata in keys {
+        if metadata.deleted_at.is_none() {
+            *counts.entry(metadata.owner.clone()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// 即将过期统计的截止时间
+pub fn expiring_before(now: DateTime<Utc>) -> DateTime<Utc> {
+    now + Duration::days(EXPIRING_WITHIN_DAYS)
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::key_management::models::key_models::KeyMetadata;
+use super::{FilePersistence, KeyQuery, PersistenceInterface};
+#[cfg(feature = "sqlite")]
+use super::DbPersistence;
+
+/// 两侧都存在但版本或更新时间不一致的密钥
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyDrift {
+    pub id: String,
+    pub local_version: u32,
+    pub other_version: u32,
+    pub local_updated_at: DateTime<Utc>,
+    pub other_updated_at: DateTime<Utc>,
+}
+
+/// 两个密钥库之间的差异，密钥ID均按字典序排列
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KeystoreDiff {
+    pub only_local: Vec<String>, // 只存在于本地的密钥
+    pub only_other: Vec<String>, // 只存在于另一侧的密钥
+    pub changed: Vec<KeyDrift>,
+}
+
+impl KeystoreDiff {
+    /// 两侧是否一致
+    pub fn is_empty(&self) -> bool {
+        self.only_local.is_empty() && self.only_other.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// 列出两侧全部密钥元数据，按 ID 对比
+pub async fn diff_keystores(
+    local: &dyn PersistenceInterface,
+    other: &dyn PersistenceInterface,
+) -> Result<KeystoreDiff, String> {
+    Ok(diff_metadata(
+        local.query_keys(&KeyQuery::new()).await?,
+        other.query_keys(&KeyQuery::new()).await?,
+    ))
+}
+
+/// 按 ID 对比两组密钥元数据
+pub fn diff_metadata(local: Vec<KeyMetadata>, other: Vec<KeyMetadata>) -> KeystoreDiff {
+    let local_keys = index_by_id(local);
+    let other_keys = index_by_id(other);
+
+    let mut diff = KeystoreDiff::default();
+    for (id, local_metadata) in &local_keys {
+        match other_keys.get(id) {
+            None => diff.only_local.push(id.clone()),
+            Some(other_metadata) => {
+                if local_metadata.version != other_metadata.version
+                    || local_metadata.updated_at != other_metadata.updated_at
+                {
+                    diff.changed.push(KeyDrift {
+                        id: id.clone(),
+                        local_version: local_metadata.version,
+                        other_version: other_metadata.version,
+                        local_updated_at: local_metadata.updated_at,
+                        other_updated_at: other_metadata.updated_at,
+                    });
+                }
+            }
+        }
+    }
+    diff.only_other = other_keys.keys()
+        .filter(|id| !local_keys.contains_key(*id))
+        .cloned()
+        .collect();
+
+    diff.only_local.sort();
+    diff.only_other.sort();
+    diff.changed.sort_by(|a, b| a.id.cmp(&b.id));
+    diff
+}
+
+/// 按来源字符串打开持久化存储：`sqlite:` 开头的按数据库 URL 连接，其余按文件存储目录打开
+///
+/// 文件存储目录必须已经存在，避免拼错路径时对比到一个新建的空目录
+pub async fn open_source(source: &str) -> Result<Arc<dyn PersistenceInterface + Send + Sync>, String> {
+    if source.starts_with("sqlite:") {
+        #[cfg(feature = "sqlite")]
+        return Ok(Arc::new(DbPersistence::new(source).await?));
+        #[cfg(not(feature = "sqlite"))]
+        return Err("未启用 sqlite 特性，不能打开数据库持久化存储".to_string());
+    }
+
+    if !Path::new(source).is_dir() {
+        return Err(format!("持久化目录不存在: {}", source));
+    }
+    Ok(Arc::new(FilePersistence::new(source)))
+}
+
+fn index_by_id(list: Vec<KeyMetadata>) -> HashMap<String, KeyMetadata> {
+    list.into_iter().map(|metadata| (metadata.id.clone(), metadata)).collect()
+}
@@ -0,0 +1,135 @@
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::key_management::models::key_models::{AttachmentInfo, AuditLogEntry, KeyMetadata};
+use super::{KeyQuery, PersistenceInterface};
+
+#[derive(Default)]
+struct MemoryState {
+    keys: HashMap<String, KeyMetadata>,
+    audit_logs: Vec<AuditLogEntry>,
+    aliases: HashMap<String, String>,
+    attachments: HashMap<String, BTreeMap<String, Vec<u8>>>, // 密钥ID -> 附件名 -> 内容
+}
+
+/// 内存持久化存储
+///
+/// 数据只保存在进程内，进程退出后丢失，用于开发、测试或不需要保留密钥的场景
+#[derive(Default)]
+pub struct MemoryPersistence {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryPersistence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl PersistenceInterface for MemoryPersistence {
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        self.state.lock().unwrap().keys.insert(metadata.id.clone(), metadata.clone());
+        Ok(())
+    }
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        self.state.lock().unwrap()
+            .keys
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| format!("密钥不存在: {}", key_id))
+    }
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state.keys.remove(key_id);
+        // 附件随密钥一起删除
+        state.attachments.remove(key_id);
+        Ok(())
+    }
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        let mut result: Vec<KeyMetadata> = self.state.lock().unwrap()
+            .keys
+            .values()
+            .filter(|metadata| query.matches(metadata))
+            .cloned()
+            .collect();
+
+        // 与文件存储一致，按创建时间排序
+        result.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(result)
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        self.state.lock().unwrap().audit_logs.push(log.clone());
+        Ok(())
+    }
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        let state = self.state.lock().unwrap();
+        let matches = |log: &AuditLogEntry| {
+            filters.iter().flatten().all(|(key, value)| match key.as_str() {
+                "action" => log.action == *value,
+                "user" => log.user == *value,
+                "key_id" => log.key_id.as_deref() == Some(value),
+                "tenant" => log.tenant == *value,
+                "success" => log.success == value.parse::<bool>().unwrap_or(false),
+                _ => true,
+            })
+        };
+
+        Ok(state.audit_logs
+            .iter()
+            .filter(|log| matches(log))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect())
+    }
+
+    async fn save_alias(&self, alias: &str, key_id: &str) -> Result<(), String> {
+        self.state.lock().unwrap().aliases.insert(alias.to_string(), key_id.to_string());
+        Ok(())
+    }
+
+    async fn delete_alias(&self, alias: &str) -> Result<(), String> {
+        self.state.lock().unwrap().aliases.remove(alias);
+        Ok(())
+    }
+
+    async fn load_aliases(&self) -> Result<HashMap<String, String>, String> {
+        Ok(self.state.lock().unwrap().aliases.clone())
+    }
+
+    async fn put_attachment(&self, key_id: &str, name: &str, data: &[u8]) -> Result<(), String> {
+        self.state.lock().unwrap()
+            .attachments
+            .entry(key_id.to_string())
+            .or_default()
+            .insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    async fn get_attachment(&self, key_id: &str, name: &str) -> Result<Vec<u8>, String> {
+        self.state.lock().unwrap()
+            .attachments
+            .get(key_id)
+            .and_then(|attachments| attachments.get(name))
+            .cloned()
+            .ok_or_else(|| format!("附件不存在: {}", name))
+    }
+
+    async fn list_attachments(&self, key_id: &str) -> Result<Vec<AttachmentInfo>, String> {
+        Ok(self.state.lock().unwrap()
+            .attachments
+            .get(key_id)
+            .map(|attachments| {
+                attachments.iter()
+                    .map(|(name, data)| AttachmentInfo { name: name.clone(), size: data.len() })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
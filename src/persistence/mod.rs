@@ -1,20 +1,138 @@
 pub mod file_persistence;
+#[cfg(feature = "sqlite")]
 pub mod db_persistence;
+pub mod cached_persistence;
+pub mod factory;
+pub mod key_query;
+pub mod key_stats;
+pub mod keystore_diff;
+pub mod memory_persistence;
+pub mod replicating_persistence;
 
 use async_trait::async_trait;
-use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::{BTreeMap, HashMap};
 // 修改导入路径，使用新的模块结构
-use crate::key_management::models::key_models::{AuditLogEntry, KeyMetadata};
+use crate::key_management::models::key_models::{AttachmentInfo, AuditLogEntry, KeyMetadata};
 
 #[async_trait]
 pub trait PersistenceInterface: Send + Sync {
     async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String>;
     async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String>;
     async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String>;
-    async fn list_key_metadata(&self, filters: Option<HashMap<String, String>>) -> Result<Vec<KeyMetadata>, String>;
+
+    /// 批量保存密钥元数据，默认逐个保存，持久化后端可以覆盖为在同一事务中写入
+    async fn save_key_metadata_batch(&self, list: &[KeyMetadata]) -> Result<(), String> {
+        for metadata in list {
+            self.save_key_metadata(metadata).await?;
+        }
+        Ok(())
+    }
+
+    /// 按类型化的查询条件列出密钥元数据
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String>;
+
+    /// 按字符串过滤条件列出密钥元数据，条件格式见 `KeyQuery::from_filters`
+    async fn list_key_metadata(&self, filters: Option<HashMap<String, String>>) -> Result<Vec<KeyMetadata>, String> {
+        let query = KeyQuery::from_filters(filters.as_ref())?;
+        self.query_keys(&query).await
+    }
+
+    /// 以流的形式逐个返回满足查询条件的密钥元数据，适合处理大量密钥
+    ///
+    /// 默认实现先完整加载再逐个返回，持久化后端可以覆盖为按需读取
+    fn query_keys_stream(&self, query: KeyQuery) -> BoxStream<'_, Result<KeyMetadata, String>> {
+        stream::once(async move { self.query_keys(&query).await })
+            .flat_map(|result| match result {
+                Ok(list) => stream::iter(list.into_iter().map(Ok)).boxed(),
+                Err(e) => stream::iter(vec![Err(e)]).boxed(),
+            })
+            .boxed()
+    }
+
+    /// `query_keys_stream` 的字符串过滤条件版本
+    fn list_key_metadata_stream(&self, filters: Option<HashMap<String, String>>) -> BoxStream<'_, Result<KeyMetadata, String>> {
+        match KeyQuery::from_filters(filters.as_ref()) {
+            Ok(query) => self.query_keys_stream(query),
+            Err(e) => stream::iter(vec![Err(e)]).boxed(),
+        }
+    }
+
+    /// 按状态、类型、算法统计未删除的密钥，tenant 为 None 时统计全部租户；默认加载密钥后在内存中统计
+    async fn key_stats(&self, tenant: Option<&str>, now: DateTime<Utc>) -> Result<KeyStats, String> {
+        let mut query = KeyQuery::new();
+        query.tenant = tenant.map(str::to_string);
+        let list = self.query_keys(&query).await?;
+        Ok(KeyStats::from_keys(&list, now))
+    }
+
+    /// 按所有者统计未删除的密钥数量，tenant 为 None 时统计全部租户；默认加载密钥后在内存中统计
+    async fn count_keys_by_owner(&self, tenant: Option<&str>) -> Result<BTreeMap<String, usize>, String> {
+        let mut query = KeyQuery::new();
+        query.tenant = tenant.map(str::to_string);
+        let list = self.query_keys(&query).await?;
+        Ok(key_stats::count_by_owner(&list))
+    }
+
     async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String>;
     async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String>;
+
+    /// 保存密钥别名，别名已存在时改为指向新的密钥，默认不支持
+    async fn save_alias(&self, _alias: &str, _key_id: &str) -> Result<(), String> {
+        Err("当前持久化后端不支持别名".to_string())
+    }
+
+    /// 删除密钥别名，默认不支持
+    async fn delete_alias(&self, _alias: &str) -> Result<(), String> {
+        Err("当前持久化后端不支持别名".to_string())
+    }
+
+    /// 加载全部别名（别名 -> 密钥ID），默认没有别名
+    async fn load_aliases(&self) -> Result<HashMap<String, String>, String> {
+        Ok(HashMap::new())
+    }
+
+    /// 保存密钥附件，同名附件会被覆盖，默认不支持
+    ///
+    /// 附件随密钥一起删除：支持附件的后端在 `delete_key_metadata` 中同时删除该密钥的附件
+    async fn put_attachment(&self, _key_id: &str, _name: &str, _data: &[u8]) -> Result<(), String> {
+        Err("当前持久化后端不支持附件".to_string())
+    }
+
+    /// 读取密钥附件，默认不支持
+    async fn get_attachment(&self, _key_id: &str, _name: &str) -> Result<Vec<u8>, String> {
+        Err("当前持久化后端不支持附件".to_string())
+    }
+
+    /// 列出密钥的全部附件，按名称排序，默认没有附件
+    async fn list_attachments(&self, _key_id: &str) -> Result<Vec<AttachmentInfo>, String> {
+        Ok(Vec::new())
+    }
+
+    /// 将持久化数据备份到指定路径，默认不支持
+    async fn backup_to(&self, _dest_path: &str) -> Result<(), String> {
+        Err("当前持久化后端不支持备份".to_string())
+    }
+
+    /// 从指定路径的备份恢复持久化数据，默认不支持
+    async fn restore_from(&self, _src_path: &str) -> Result<(), String> {
+        Err("当前持久化后端不支持恢复".to_string())
+    }
+
+    /// 压缩审计日志，删除保留期之前的条目，返回压缩前后的条目数，默认不支持
+    async fn compact_audit_log(&self, _retention: Option<Duration>, _dedupe: bool) -> Result<(usize, usize), String> {
+        Err("当前持久化后端不支持压缩审计日志".to_string())
+    }
 }
 
 pub use file_persistence::FilePersistence;
-pub use db_persistence::DbPersistence;
\ No newline at end of file
+#[cfg(feature = "sqlite")]
+pub use db_persistence::{DbPersistence, EnumEncoding};
+pub use cached_persistence::{CachedPersistence, FlushStats};
+pub use factory::build_persistence;
+pub use memory_persistence::MemoryPersistence;
+pub use key_query::KeyQuery;
+pub use key_stats::KeyStats;
+pub use replicating_persistence::ReplicatingPersistence;
+pub use keystore_diff::{diff_keystores, diff_metadata, KeyDrift, KeystoreDiff};
\ No newline at end of file
@@ -0,0 +1,210 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::BoxStream;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::key_management::models::key_models::{AttachmentInfo, AuditLogEntry, KeyMetadata};
+use super::{KeyQuery, KeyStats, PersistenceInterface};
+
+const DEFAULT_QUEUE_SIZE: usize = 1024;
+const RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+const RETRY_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+// 需要同步到从存储的写操作
+enum ReplicationOp {
+    SaveKey(KeyMetadata),
+    DeleteKey(String),
+    SaveAudit(AuditLogEntry),
+    SaveAlias(String, String),
+    DeleteAlias(String),
+    PutAttachment(String, String, Vec<u8>),
+    Flush(oneshot::Sender<()>), // 之前的操作全部完成后通知
+}
+
+/// 主从复制的持久化存储
+///
+/// 写操作先写入主存储，主存储失败直接返回错误；成功后放入每个从存储各自的队列，
+/// 由后台任务按顺序写入，失败时按指数退避一直重试，因此单个从存储不可用不会影响其他从存储。
+/// 队列满时丢弃新操作并输出错误，可以用 `diff_keystore` 检查从存储是否一致。
+/// 读取、备份、恢复和压缩只作用于主存储
+pub struct ReplicatingPersistence {
+    primary: Arc<dyn PersistenceInterface + Send + Sync>,
+    queues: Vec<mpsc::Sender<ReplicationOp>>,
+}
+
+impl ReplicatingPersistence {
+    /// 为每个从存储启动后台同步任务，必须在 tokio 运行时中调用
+    pub fn new(
+        primary: Arc<dyn PersistenceInterface + Send + Sync>,
+        secondaries: Vec<Arc<dyn PersistenceInterface + Send + Sync>>,
+    ) -> Self {
+        Self::with_queue_size(primary, secondaries, DEFAULT_QUEUE_SIZE)
+    }
+
+    /// 指定每个从存储的队列长度
+    pub fn with_queue_size(
+        primary: Arc<dyn PersistenceInterface + Send + Sync>,
+        secondaries: Vec<Arc<dyn PersistenceInterface + Send + Sync>>,
+        queue_size: usize,
+    ) -> Self {
+        let queues = secondaries
+            .into_iter()
+            .enumerate()
+            .map(|(index, secondary)| {
+                let (sender, receiver) = mpsc::channel(queue_size.max(1));
+                tokio::spawn(replication_loop(index, secondary, receiver));
+                sender
+            })
+            .collect();
+
+        Self { primary, queues }
+    }
+
+    /// 等待所有从存储写完调用前已入队的操作
+    pub async fn flush(&self) {
+        let mut pending = Vec::with_capacity(self.queues.len());
+        for queue in &self.queues {
+            let (done, wait) = oneshot::channel();
+            if queue.send(ReplicationOp::Flush(done)).await.is_ok() {
+                pending.push(wait);
+            }
+        }
+        for wait in pending {
+            let _ = wait.await;
+        }
+    }
+
+    fn replicate(&self, op: impl Fn() -> ReplicationOp) {
+        for (index, queue) in self.queues.iter().enumerate() {
+            match queue.try_send(op()) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => eprintln!("从存储 {} 的复制队列已满，丢弃写操作", index),
+                Err(TrySendError::Closed(_)) => eprintln!("从存储 {} 的复制任务已退出，丢弃写操作", index),
+            }
+        }
+    }
+}
+
+async fn replication_loop(
+    index: usize,
+    secondary: Arc<dyn PersistenceInterface + Send + Sync>,
+    mut receiver: mpsc::Receiver<ReplicationOp>,
+) {
+    while let Some(op) = receiver.recv().await {
+        let mut delay = RETRY_BASE_DELAY;
+        loop {
+            let result = match &op {
+                ReplicationOp::SaveKey(metadata) => secondary.save_key_metadata(metadata).await,
+                ReplicationOp::DeleteKey(key_id) => secondary.delete_key_metadata(key_id).await,
+                ReplicationOp::SaveAudit(entry) => secondary.save_audit_log(entry).await,
+                ReplicationOp::SaveAlias(alias, key_id) => secondary.save_alias(alias, key_id).await,
+                ReplicationOp::DeleteAlias(alias) => secondary.delete_alias(alias).await,
+                ReplicationOp::PutAttachment(key_id, name, data) => secondary.put_attachment(key_id, name, data).await,
+                ReplicationOp::Flush(_) => Ok(()),
+            };
+
+            match result {
+                Ok(()) => break,
+                Err(e) => {
+                    eprintln!("同步到从存储 {} 失败，{} 毫秒后重试: {}", index, delay.as_millis(), e);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(RETRY_MAX_DELAY);
+                }
+            }
+        }
+
+        if let ReplicationOp::Flush(done) = op {
+            let _ = done.send(());
+        }
+    }
+}
+
+#[async_trait]
+impl PersistenceInterface for ReplicatingPersistence {
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        self.primary.save_key_metadata(metadata).await?;
+        self.replicate(|| ReplicationOp::SaveKey(metadata.clone()));
+        Ok(())
+    }
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        self.primary.load_key_metadata(key_id).await
+    }
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        self.primary.delete_key_metadata(key_id).await?;
+        self.replicate(|| ReplicationOp::DeleteKey(key_id.to_string()));
+        Ok(())
+    }
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        self.primary.query_keys(query).await
+    }
+
+    fn query_keys_stream(&self, query: KeyQuery) -> BoxStream<'_, Result<KeyMetadata, String>> {
+        self.primary.query_keys_stream(query)
+    }
+
+    async fn key_stats(&self, tenant: Option<&str>, now: DateTime<Utc>) -> Result<KeyStats, String> {
+        self.primary.key_stats(tenant, now).await
+    }
+
+    async fn count_keys_by_owner(&self, tenant: Option<&str>) -> Result<BTreeMap<String, usize>, String> {
+        self.primary.count_keys_by_owner(tenant).await
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        self.primary.save_audit_log(log).await?;
+        self.replicate(|| ReplicationOp::SaveAudit(log.clone()));
+        Ok(())
+    }
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        self.primary.load_audit_logs(filters, limit).await
+    }
+
+    async fn save_alias(&self, alias: &str, key_id: &str) -> Result<(), String> {
+        self.primary.save_alias(alias, key_id).await?;
+        self.replicate(|| ReplicationOp::SaveAlias(alias.to_string(), key_id.to_string()));
+        Ok(())
+    }
+
+    async fn delete_alias(&self, alias: &str) -> Result<(), String> {
+        self.primary.delete_alias(alias).await?;
+        self.replicate(|| ReplicationOp::DeleteAlias(alias.to_string()));
+        Ok(())
+    }
+
+    async fn load_aliases(&self) -> Result<HashMap<String, String>, String> {
+        self.primary.load_aliases().await
+    }
+
+    async fn put_attachment(&self, key_id: &str, name: &str, data: &[u8]) -> Result<(), String> {
+        self.primary.put_attachment(key_id, name, data).await?;
+        self.replicate(|| ReplicationOp::PutAttachment(key_id.to_string(), name.to_string(), data.to_vec()));
+        Ok(())
+    }
+
+    async fn get_attachment(&self, key_id: &str, name: &str) -> Result<Vec<u8>, String> {
+        self.primary.get_attachment(key_id, name).await
+    }
+
+    async fn list_attachments(&self, key_id: &str) -> Result<Vec<AttachmentInfo>, String> {
+        self.primary.list_attachments(key_id).await
+    }
+
+    async fn backup_to(&self, dest_path: &str) -> Result<(), String> {
+        self.primary.backup_to(dest_path).await
+    }
+
+    async fn restore_from(&self, src_path: &str) -> Result<(), String> {
+        self.primary.restore_from(src_path).await
+    }
+
+    async fn compact_audit_log(&self, retention: Option<Duration>, dedupe: bool) -> Result<(usize, usize), String> {
+        self.primary.compact_audit_log(retention, dedupe).await
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use crate::retry_jitter::JitterStrategy;
+
 /// 插件配置结构体
 #[derive(Debug, Clone)]
 pub struct PluginConfig {
@@ -105,4 +107,263 @@ impl PluginConfig {
     pub fn get_config(&self, key: &str) -> Option<&String> {
         self.additional_config.get(key)
     }
-}
\ No newline at end of file
+
+    /// 连续心跳失败多少次后自动停止插件，未配置或为0时不启用
+    pub fn get_max_heartbeat_failures(&self) -> Option<u32> {
+        self.get_config("max_heartbeat_failures")
+            .and_then(|s| s.parse::<u32>().ok())
+            .filter(|n| *n > 0)
+    }
+
+    /// 与服务器失联多少秒后自动停止插件，未配置或为0时不启用
+    pub fn get_max_offline_secs(&self) -> Option<u64> {
+        self.get_config("max_offline_secs")
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|n| *n > 0)
+    }
+
+    /// 心跳是否附带运行指标（运行时长、命令数等），默认不附带
+    pub fn get_heartbeat_metrics(&self) -> bool {
+        self.get_config("heartbeat_metrics").is_some_and(|s| s.eq_ignore_ascii_case("true"))
+    }
+
+    /// 注册重试和心跳失败后重连的抖动策略（none、full、equal），未配置或无效时不加抖动
+    pub fn get_retry_jitter(&self) -> JitterStrategy {
+        self.get_config("retry_jitter")
+            .and_then(|s| s.parse::<JitterStrategy>().ok())
+            .unwrap_or_default()
+    }
+
+    /// 从当前进程的命令行参数读取配置
+    #[cfg(feature = "cli")]
+    pub fn from_args() -> Result<Self, String> {
+        Self::new().with_args(std::env::args_os())
+    }
+
+    /// 用命令行参数覆盖已有配置，第一个参数为程序名
+    ///
+    /// 只覆盖命令行中给出的项，先从文件、环境变量加载配置再调用即可得到
+    /// 参数 > 环境变量 > 文件 > 默认值 的优先级。`--config key=value` 可以重复，
+    /// 写入 additional_config，同一个键以最后一次为准
+    #[cfg(feature = "cli")]
+    pub fn with_args<I, T>(mut self, args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        use clap::Parser;
+
+        let args = ConfigArgs::try_parse_from(args).map_err(|e| e.to_string())?;
+        let fields = [
+            (args.server_host, &mut self.server_host),
+            (args.plugin_id, &mut self.plugin_id),
+            (args.plugin_name, &mut self.plugin_name),
+            (args.plugin_version, &mut self.plugin_version),
+            (args.plugin_type, &mut self.plugin_type),
+            (args.plugin_description, &mut self.plugin_description),
+        ];
+        for (value, field) in fields {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        if let Some(server_port) = args.server_port {
+            self.server_port = server_port;
+        }
+        self.additional_config.extend(args.config);
+
+        Ok(self)
+    }
+}
+
+// PluginConfig::with_args 接受的命令行参数
+#[cfg(feature = "cli")]
+#[derive(clap::Parser, Debug)]
+struct ConfigArgs {
+    #[arg(long)]
+    server_host: Option<String>,
+    #[arg(long)]
+    server_port: Option<i32>,
+    #[arg(long)]
+    plugin_id: Option<String>,
+    #[arg(long)]
+    plugin_name: Option<String>,
+    #[arg(long)]
+    plugin_version: Option<String>,
+    #[arg(long)]
+    plugin_type: Option<String>,
+    #[arg(long)]
+    plugin_description: Option<String>,
+    /// 额外配置项，可以重复
+    #[arg(long = "config", value_name = "KEY=VALUE", value_parser = parse_config_entry)]
+    config: Vec<(String, String)>,
+}
+
+#[cfg(feature = "cli")]
+fn parse_config_entry(entry: &str) -> Result<(String, String), String> {
+    match entry.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("Invalid config entry, expected key=value: {}", entry)),
+    }
+}
+
+/// 可在运行时修改的配置项
+pub const HOT_RELOADABLE_KEYS: [&str; 10] = [
+    "heartbeat_interval",
+    "request_timeout",
+    "connect_timeout",
+    "audit_level",
+    "log_level",
+    "slow_command_threshold_ms",
+    "expiration_sweep_interval",
+    "soft_delete_window",
+    "max_attachment_size",
+    "command_timeout_ms",
+];
+
+/// 运行时配置，可在不重启插件、不断开gRPC连接的情况下修改
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeSettings {
+    heartbeat_interval: u64, // 心跳间隔（秒）
+    request_timeout: u64,    // gRPC请求超时（秒）
+    connect_timeout: u64,    // gRPC连接超时（秒）
+    audit_level: String,     // all: 记录全部审计日志, failures: 只记录失败的操作
+    log_level: String,       // debug/info/warn/error
+    slow_command_threshold_ms: u64, // 命令耗时超过该值时输出警告（毫秒），0 表示不检查
+    expiration_sweep_interval: u64, // 密钥过期检查间隔（秒）
+    soft_delete_window: u64, // 删除的密钥可恢复的时间（秒），0 表示直接删除
+    max_attachment_size: usize, // 单个密钥附件的最大字节数
+    command_timeout_ms: u64, // 单个命令的执行超时（毫秒），0 表示不限制
+}
+
+impl Default for RuntimeSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuntimeSettings {
+    pub fn new() -> Self {
+        Self {
+            heartbeat_interval: 5,
+            request_timeout: 30,
+            connect_timeout: 15,
+            audit_level: "all".to_string(),
+            log_level: "info".to_string(),
+            slow_command_threshold_ms: 1000,
+            expiration_sweep_interval: 60,
+            soft_delete_window: 0,
+            max_attachment_size: 1024 * 1024,
+            command_timeout_ms: 0,
+        }
+    }
+
+    /// 从插件配置读取运行时配置，无效的值会被忽略并使用默认值
+    pub fn from_config(config: &PluginConfig) -> Self {
+        let mut settings = Self::new();
+        for key in HOT_RELOADABLE_KEYS {
+            let Some(value) = config.get_config(key) else {
+                continue;
+            };
+            if let Err(e) = settings.apply(key, value) {
+                eprintln!("忽略无效配置: {}", e);
+            }
+        }
+        settings
+    }
+
+    /// 修改单个配置项，配置项不支持运行时修改或值无效时返回错误
+    pub fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let parse_secs = |value: &str| -> Result<u64, String> {
+            match value.parse::<u64>() {
+                Ok(secs) if secs > 0 => Ok(secs),
+                _ => Err(format!("配置项 {} 的值必须是正整数秒数: {}", key, value)),
+            }
+        };
+
+        match key {
+            "heartbeat_interval" => self.heartbeat_interval = parse_secs(value)?,
+            "request_timeout" => self.request_timeout = parse_secs(value)?,
+            "connect_timeout" => self.connect_timeout = parse_secs(value)?,
+            "expiration_sweep_interval" => self.expiration_sweep_interval = parse_secs(value)?,
+            "audit_level" => match value {
+                "all" | "failures" => self.audit_level = value.to_string(),
+                _ => return Err(format!("无效的审计级别: {}，可选值: all, failures", value)),
+            },
+            "log_level" => match value {
+                "debug" | "info" | "warn" | "error" => self.log_level = value.to_string(),
+                _ => return Err(format!("无效的日志级别: {}，可选值: debug, info, warn, error", value)),
+            },
+            "command_timeout_ms" => {
+                self.command_timeout_ms = value.parse::<u64>()
+                    .map_err(|_| format!("配置项 {} 的值必须是非负整数毫秒数: {}", key, value))?;
+            }
+            "slow_command_threshold_ms" => {
+                self.slow_command_threshold_ms = value.parse::<u64>()
+                    .map_err(|_| format!("配置项 {} 的值必须是非负整数毫秒数: {}", key, value))?;
+            }
+            "soft_delete_window" => {
+                self.soft_delete_window = value.parse::<u64>()
+                    .map_err(|_| format!("配置项 {} 的值必须是非负整数秒数: {}", key, value))?;
+            }
+            "max_attachment_size" => match value.parse::<usize>() {
+                Ok(size) if size > 0 => self.max_attachment_size = size,
+                _ => return Err(format!("配置项 {} 的值必须是正整数字节数: {}", key, value)),
+            },
+            _ => return Err(format!("配置项 {} 不支持运行时修改，请重启插件", key)),
+        }
+
+        Ok(())
+    }
+
+    pub fn get_heartbeat_interval(&self) -> u64 {
+        self.heartbeat_interval
+    }
+
+    pub fn get_request_timeout(&self) -> u64 {
+        self.request_timeout
+    }
+
+    pub fn get_connect_timeout(&self) -> u64 {
+        self.connect_timeout
+    }
+
+    pub fn get_audit_level(&self) -> &str {
+        &self.audit_level
+    }
+
+    pub fn get_log_level(&self) -> &str {
+        &self.log_level
+    }
+
+    pub fn get_slow_command_threshold_ms(&self) -> u64 {
+        self.slow_command_threshold_ms
+    }
+
+    pub fn get_expiration_sweep_interval(&self) -> u64 {
+        self.expiration_sweep_interval
+    }
+
+    pub fn get_soft_delete_window(&self) -> u64 {
+        self.soft_delete_window
+    }
+
+    pub fn get_max_attachment_size(&self) -> usize {
+        self.max_attachment_size
+    }
+
+    pub fn get_command_timeout_ms(&self) -> u64 {
+        self.command_timeout_ms
+    }
+
+    /// 判断指定级别的日志是否需要输出
+    pub fn log_enabled(&self, level: &str) -> bool {
+        let rank = |level: &str| match level {
+            "debug" => 0,
+            "info" => 1,
+            "warn" => 2,
+            _ => 3,
+        };
+        rank(level) >= rank(&self.log_level)
+    }
+}
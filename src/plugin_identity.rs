@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+/// 注册响应的处理结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationOutcome {
+    /// 首次获得服务器分配的ID
+    Assigned,
+    /// 与当前ID相同或为空的响应，已忽略
+    Ignored,
+    /// 服务器返回了不同的ID，已改用新ID
+    Reconciled { previous: String },
+}
+
+/// 插件ID，在插件和心跳线程之间共享
+///
+/// 注册成功前使用本地ID；获得服务器分配的ID后不再重新注册，
+/// 之后相同ID的注册响应被忽略，不同ID的响应以最新的为准并输出警告
+#[derive(Debug, Clone, Default)]
+pub struct PluginIdentity {
+    state: Arc<Mutex<IdentityState>>,
+}
+
+#[derive(Debug, Default)]
+struct IdentityState {
+    id: String,
+    server_assigned: bool,
+}
+
+impl PluginIdentity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前使用的插件ID，尚未分配时为空
+    pub fn id(&self) -> String {
+        self.state.lock().unwrap().id.clone()
+    }
+
+    /// 当前ID是否由服务器分配
+    pub fn is_server_assigned(&self) -> bool {
+        self.state.lock().unwrap().server_assigned
+    }
+
+    /// 使用本地ID，已有服务器分配的ID时忽略并返回 false
+    pub fn set_local_id(&self, id: String) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.server_assigned {
+            return false;
+        }
+        state.id = id;
+        true
+    }
+
+    /// 处理一次成功的注册响应
+    pub fn accept_registration(&self, id: &str) -> RegistrationOutcome {
+        let mut state = self.state.lock().unwrap();
+        if id.is_empty() || (state.server_assigned && state.id == id) {
+            return RegistrationOutcome::Ignored;
+        }
+
+        let previous = std::mem::replace(&mut state.id, id.to_string());
+        if std::mem::replace(&mut state.server_assigned, true) {
+            eprintln!("警告: 服务器返回了不同的插件ID {}，原ID为 {}，改用新ID", id, previous);
+            RegistrationOutcome::Reconciled { previous }
+        } else {
+            RegistrationOutcome::Assigned
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_registration_keeps_a_single_id() {
+        let identity = PluginIdentity::new();
+        identity.set_local_id("local".to_string());
+        assert!(!identity.is_server_assigned());
+
+        assert_eq!(identity.accept_registration("server1"), RegistrationOutcome::Assigned);
+        assert_eq!(identity.accept_registration("server1"), RegistrationOutcome::Ignored);
+        assert_eq!(identity.accept_registration(""), RegistrationOutcome::Ignored);
+        assert_eq!(identity.id(), "server1");
+
+        // 已有服务器分配的ID后不再退回本地ID
+        assert!(!identity.set_local_id("local".to_string()));
+        assert_eq!(identity.id(), "server1");
+    }
+
+    #[test]
+    fn different_server_id_is_reconciled_to_the_latest() {
+        let identity = PluginIdentity::new();
+        let shared = identity.clone();
+        identity.accept_registration("server1");
+
+        assert_eq!(shared.accept_registration("server2"), RegistrationOutcome::Reconciled { previous: "server1".to_string() });
+        assert_eq!(identity.id(), "server2");
+        assert!(identity.is_server_assigned());
+    }
+}
@@ -1,11 +1,22 @@
+use serde::Serialize;
+
+#[cfg(feature = "grpc")]
+use crate::base_plugin::plugin;
+
 /// 插件信息结构体
-#[derive(Debug, Clone)]
+///
+/// `BasePlugin::get_info` 返回的是副本，修改插件自身的信息（注册和心跳使用的名称、状态、支持的命令等）
+/// 通过 `BasePlugin::info_mut`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct PluginInfo {
     id: String,
     name: String,
     version: String,
+    #[serde(rename = "type")]
     plugin_type: String,
     description: String,
+    host: String,
+    port: i32,
     status: String,
     supported_commands: Vec<String>,
     supported_events: Vec<String>,
@@ -13,16 +24,7 @@ pub struct PluginInfo {
 
 impl PluginInfo {
     pub fn new() -> Self {
-        Self {
-            id: String::new(),
-            name: String::new(),
-            version: String::new(),
-            plugin_type: String::new(),
-            description: String::new(),
-            status: String::new(),
-            supported_commands: Vec::new(),
-            supported_events: Vec::new(),
-        }
+        Self::default()
     }
 
     pub fn get_id(&self) -> &str {
@@ -65,6 +67,22 @@ impl PluginInfo {
         self.description = description;
     }
 
+    pub fn get_host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn set_host(&mut self, host: String) {
+        self.host = host;
+    }
+
+    pub fn get_port(&self) -> i32 {
+        self.port
+    }
+
+    pub fn set_port(&mut self, port: i32) {
+        self.port = port;
+    }
+
     pub fn get_status(&self) -> &str {
         &self.status
     }
@@ -96,4 +114,109 @@ impl PluginInfo {
     pub fn add_supported_event(&mut self, event: String) {
         self.supported_events.push(event);
     }
-}
\ No newline at end of file
+}
+
+/// 从服务端返回的 proto 插件信息转换
+#[cfg(feature = "grpc")]
+impl From<plugin::PluginInfo> for PluginInfo {
+    fn from(info: plugin::PluginInfo) -> Self {
+        let mut result = PluginInfo::new();
+        result.set_id(info.plugin_id);
+        result.set_name(info.name);
+        result.set_version(info.version);
+        result.set_type(info.r#type);
+        result.set_description(info.description);
+        result.set_host(info.host);
+        result.set_port(info.port);
+        result.set_status(info.status);
+        result
+    }
+}
+
+/// 转换为 proto 插件信息，支持的命令和事件在 proto 中没有对应字段
+#[cfg(feature = "grpc")]
+impl From<PluginInfo> for plugin::PluginInfo {
+    fn from(info: PluginInfo) -> Self {
+        Self {
+            plugin_id: info.id,
+            name: info.name,
+            version: info.version,
+            r#type: info.plugin_type,
+            description: info.description,
+            host: info.host,
+            port: info.port,
+            status: info.status,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base_plugin::BasePlugin;
+    use crate::plugin_sdk::PluginSDK;
+
+    #[test]
+    fn accessors_round_trip() {
+        let mut info = PluginInfo::new();
+        assert_eq!(info, PluginInfo::default());
+
+        info.set_id("id-1".to_string());
+        info.set_name("vault".to_string());
+        info.set_version("1.2.0".to_string());
+        info.set_type("PASSWORD_MANAGER".to_string());
+        info.set_description("密钥管理".to_string());
+        info.set_host("10.0.0.5".to_string());
+        info.set_port(50051);
+        info.set_status("RUNNING".to_string());
+        assert_eq!(info.get_id(), "id-1");
+        assert_eq!(info.get_name(), "vault");
+        assert_eq!(info.get_version(), "1.2.0");
+        assert_eq!(info.get_type(), "PASSWORD_MANAGER");
+        assert_eq!(info.get_description(), "密钥管理");
+        assert_eq!(info.get_host(), "10.0.0.5");
+        assert_eq!(info.get_port(), 50051);
+        assert_eq!(info.get_status(), "RUNNING");
+
+        info.add_supported_command("sign".to_string());
+        info.add_supported_command("verify".to_string());
+        assert_eq!(info.get_supported_commands(), &vec!["sign".to_string(), "verify".to_string()]);
+        info.set_supported_commands(vec!["encrypt".to_string()]);
+        assert_eq!(info.get_supported_commands(), &vec!["encrypt".to_string()]);
+
+        info.add_supported_event("startup".to_string());
+        assert_eq!(info.get_supported_events(), &vec!["startup".to_string()]);
+        info.set_supported_events(Vec::new());
+        assert!(info.get_supported_events().is_empty());
+    }
+
+    #[test]
+    fn serializes_with_type_field() {
+        let mut info = PluginInfo::new();
+        info.set_type("PASSWORD_MANAGER".to_string());
+        info.add_supported_command("sign".to_string());
+
+        let value = serde_json::to_value(&info).unwrap();
+        assert_eq!(value["type"], "PASSWORD_MANAGER");
+        assert_eq!(value["supported_commands"], serde_json::json!(["sign"]));
+        assert_eq!(value["port"], 0);
+    }
+
+    #[test]
+    fn mutations_through_base_plugin_are_kept() {
+        let mut plugin = BasePlugin::new();
+        plugin.info_mut().set_description("密钥管理服务".to_string());
+        plugin.info_mut().set_status("READY".to_string());
+        plugin.info_mut().add_supported_command("create_key".to_string());
+        plugin.info_mut().add_supported_event("key_expiring".to_string());
+
+        // get_info 返回副本，修改副本不影响插件
+        let mut copy = plugin.get_info();
+        copy.set_description("ignored".to_string());
+
+        let info = plugin.get_info();
+        assert_eq!(info.get_description(), "密钥管理服务");
+        assert_eq!(info.get_status(), "READY");
+        assert_eq!(info.get_supported_commands(), &vec!["create_key".to_string()]);
+        assert_eq!(info.get_supported_events(), &vec!["key_expiring".to_string()]);
+    }
+}
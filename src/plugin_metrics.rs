@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 单个命令的执行统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    pub count: u64,
+    pub slow_count: u64,
+    pub timeout_count: u64, // 超时被取消的次数
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+impl CommandStats {
+    /// 平均耗时（毫秒）
+    pub fn average_ms(&self) -> u64 {
+        self.total_ms.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+/// 插件运行指标，在插件和后台任务之间共享
+#[derive(Debug, Clone, Default)]
+pub struct PluginMetrics {
+    commands: Arc<Mutex<HashMap<String, CommandStats>>>,
+}
+
+impl PluginMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次命令执行
+    pub fn record_command(&self, command: &str, elapsed: Duration, slow: bool) {
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let mut commands = self.commands.lock().unwrap();
+        let stats = commands.entry(command.to_string()).or_default();
+
+        stats.count += 1;
+        stats.total_ms += elapsed_ms;
+        stats.max_ms = stats.max_ms.max(elapsed_ms);
+        if slow {
+            stats.slow_count += 1;
+        }
+    }
+
+    /// 记录一次命令超时
+    pub fn record_timeout(&self, command: &str) {
+        let mut commands = self.commands.lock().unwrap();
+        commands.entry(command.to_string()).or_default().timeout_count += 1;
+    }
+
+    /// 获取指定命令的统计
+    pub fn command_stats(&self, command: &str) -> Option<CommandStats> {
+        self.commands.lock().unwrap().get(command).cloned()
+    }
+
+    /// 已执行的命令总数
+    pub fn total_commands(&self) -> u64 {
+        self.commands.lock().unwrap().values().map(|stats| stats.count).sum()
+    }
+
+    /// 获取全部命令的统计
+    pub fn all_command_stats(&self) -> HashMap<String, CommandStats> {
+        self.commands.lock().unwrap().clone()
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use crate::command_result::CommandResult;
 use crate::plugin_config::PluginConfig;
 use crate::plugin_info::PluginInfo;
+use crate::shutdown::ShutdownSignals;
 
 /// 插件SDK trait定义
 /// 其他语言实现插件时可以参考此接口
@@ -20,6 +21,22 @@ pub trait PluginSDK {
     /// 是否初始化成功
     async fn initialize(&mut self, config: PluginConfig) -> bool;
 
+    /// 初始化插件，失败时返回原因
+    ///
+    /// 默认调用 `initialize`，只能报告初始化失败；需要报告具体原因的插件覆盖此方法，
+    /// 并让 `initialize` 调用它
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - 插件配置
+    async fn try_initialize(&mut self, config: PluginConfig) -> Result<(), String> {
+        if self.initialize(config).await {
+            Ok(())
+        } else {
+            Err("插件初始化失败".to_string())
+        }
+    }
+
     /// 启动插件
     /// 
     /// # Returns
@@ -63,4 +80,35 @@ pub trait PluginSDK {
     /// 
     /// 处理结果
     async fn handle_message(&self, message: &str) -> String;
+
+    /// 启动插件，等待 Ctrl+C 或 SIGTERM（Unix）后停止插件
+    ///
+    /// 插件需要已初始化。启动或停止失败时返回错误
+    async fn run_until_shutdown(&mut self) -> Result<(), String>
+    where
+        Self: Send,
+    {
+        self.run_until_signal(&ShutdownSignals::default()).await
+    }
+
+    /// 启动插件，等待指定的停止信号后停止插件
+    ///
+    /// 等待信号之前先启动插件；安装信号处理失败时同样停止插件并返回错误
+    async fn run_until_signal(&mut self, signals: &ShutdownSignals) -> Result<(), String>
+    where
+        Self: Send,
+    {
+        if !self.start().await {
+            return Err("插件启动失败".to_string());
+        }
+
+        let received = signals.wait().await;
+        if let Ok(signal) = &received {
+            println!("收到停止信号 {}，正在停止插件...", signal);
+        }
+
+        let stopped = self.stop().await;
+        received?;
+        if stopped { Ok(()) } else { Err("插件停止失败".to_string()) }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+use tonic::{Request, Response, Status};
+
+use crate::base_plugin::plugin::plugin_service_server::PluginService;
+use crate::base_plugin::plugin::{
+    CommandRequest, CommandResponse, FindPluginRequest, FindPluginResponse, GetPluginByNameRequest,
+    GetPluginByNameResponse, HeartbeatRequest, HeartbeatResponse, PluginRegistration,
+    RegistrationResponse, StatusRequest, StatusResponse, StopRequest, StopResponse,
+    UpdatePluginRequest, UpdatePluginResponse,
+};
+use crate::command_result::{CommandResult, ErrorCode};
+use crate::plugin_sdk::PluginSDK;
+use crate::plugin_status::PluginHealth;
+
+/// 插件入站gRPC服务
+///
+/// 主应用通过 ExecuteCommand/GetStatus/StopPlugin 回调插件，调用转发给插件自身的方法；
+/// 注册、心跳、查找等属于主应用一侧的接口，插件端不提供
+pub struct PluginServer<P> {
+    plugin: Arc<P>,
+    health: PluginHealth,
+    shutdown_tx: watch::Sender<bool>,
+}
+
+impl<P> PluginServer<P> {
+    pub(crate) fn new(plugin: Arc<P>, health: PluginHealth, shutdown_tx: watch::Sender<bool>) -> Self {
+        Self {
+            plugin,
+            health,
+            shutdown_tx,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl<P> PluginService for PluginServer<P>
+where
+    P: PluginSDK + Send + Sync + 'static,
+{
+    async fn register_plugin(
+        &self,
+        _request: Request<PluginRegistration>,
+    ) -> Result<Response<RegistrationResponse>, Status> {
+        Err(Status::unimplemented("插件端不支持注册插件"))
+    }
+
+    async fn heartbeat(
+        &self,
+        _request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        Err(Status::unimplemented("插件端不支持心跳检测"))
+    }
+
+    async fn get_status(
+        &self,
+        _request: Request<StatusRequest>,
+    ) -> Result<Response<StatusResponse>, Status> {
+        Ok(Response::new(StatusResponse {
+            status: self.health.state().to_string(),
+            details: self.health.details(),
+            uptime: self.health.uptime().map_or(0, |uptime| uptime.as_secs() as i64),
+        }))
+    }
+
+    async fn execute_command(
+        &self,
+        request: Request<CommandRequest>,
+    ) -> Result<Response<CommandResponse>, Status> {
+        let request = request.into_inner();
+        println!("收到远程命令: {}", request.command);
+
+        let info = self.plugin.get_info();
+        if !info.get_id().is_empty() && !request.plugin_id.is_empty() && request.plugin_id != info.get_id() {
+            let result = CommandResult::new(false, String::new(), format!("插件ID不匹配: {}", request.plugin_id))
+                .with_error_code(ErrorCode::Unauthorized);
+            return Ok(Response::new(result.into()));
+        }
+
+        // 参数原样传递给插件
+        let result = self.plugin.execute_command(&request.command, &request.parameters).await;
+
+        Ok(Response::new(result.into()))
+    }
+
+    async fn stop_plugin(
+        &self,
+        request: Request<StopRequest>,
+    ) -> Result<Response<StopResponse>, Status> {
+        let request = request.into_inner();
+        let info = self.plugin.get_info();
+
+        if !info.get_id().is_empty() && request.plugin_id != info.get_id() {
+            return Ok(Response::new(StopResponse {
+                success: false,
+                message: format!("插件ID不匹配: {}", request.plugin_id),
+            }));
+        }
+
+        // 心跳线程检测到运行标志为false后自行退出，服务在响应返回后关闭
+        {
+            let mut guard = self.health.running.lock().unwrap();
+            *guard = false;
+        }
+        self.shutdown_tx.send_replace(true);
+
+        Ok(Response::new(StopResponse {
+            success: true,
+            message: "插件已停止".to_string(),
+        }))
+    }
+
+    async fn find_plugin(
+        &self,
+        _request: Request<FindPluginRequest>,
+    ) -> Result<Response<FindPluginResponse>, Status> {
+        Err(Status::unimplemented("插件端不支持查找插件"))
+    }
+
+    async fn update_plugin(
+        &self,
+        _request: Request<UpdatePluginRequest>,
+    ) -> Result<Response<UpdatePluginResponse>, Status> {
+        Err(Status::unimplemented("插件端不支持更新插件"))
+    }
+
+    async fn get_plugin_by_name(
+        &self,
+        _request: Request<GetPluginByNameRequest>,
+    ) -> Result<Response<GetPluginByNameResponse>, Status> {
+        Err(Status::unimplemented("插件端不支持按名称获取插件"))
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 插件运行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginState {
+    /// 已启动，但本地数据尚未加载完成
+    Starting,
+    /// 本地就绪且与服务器连接正常
+    Ready,
+    /// 本地就绪，但与服务器的连接中断
+    Degraded,
+    /// 正在停止
+    Stopping,
+    /// 未运行
+    Stopped,
+}
+
+impl fmt::Display for PluginState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PluginState::Starting => "STARTING",
+            PluginState::Ready => "READY",
+            PluginState::Degraded => "DEGRADED",
+            PluginState::Stopping => "STOPPING",
+            PluginState::Stopped => "STOPPED",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// 插件健康状态，在插件、心跳线程和入站gRPC服务之间共享
+#[derive(Debug, Clone)]
+pub struct PluginHealth {
+    pub(crate) running: Arc<Mutex<bool>>,
+    ready: Arc<AtomicBool>,
+    server_connected: Arc<AtomicBool>,
+    stopping: Arc<AtomicBool>,
+    started_at: Arc<Mutex<Option<Instant>>>,
+    last_heartbeat_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl PluginHealth {
+    pub(crate) fn new(running: Arc<Mutex<bool>>) -> Self {
+        Self {
+            running,
+            ready: Arc::new(AtomicBool::new(false)),
+            server_connected: Arc::new(AtomicBool::new(false)),
+            stopping: Arc::new(AtomicBool::new(false)),
+            started_at: Arc::new(Mutex::new(None)),
+            last_heartbeat_at: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        let guard = self.running.lock().unwrap();
+        *guard
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::SeqCst);
+    }
+
+    pub fn is_server_connected(&self) -> bool {
+        self.server_connected.load(Ordering::SeqCst)
+    }
+
+    pub fn set_server_connected(&self, connected: bool) {
+        self.server_connected.store(connected, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_stopping(&self, stopping: bool) {
+        self.stopping.store(stopping, Ordering::SeqCst);
+    }
+
+    // 插件启动时调用，重新开始计算运行时长，清除上一次运行的心跳时间
+    pub(crate) fn mark_started(&self) {
+        *self.started_at.lock().unwrap() = Some(Instant::now());
+        *self.last_heartbeat_at.lock().unwrap() = None;
+    }
+
+    pub(crate) fn mark_stopped(&self) {
+        *self.started_at.lock().unwrap() = None;
+    }
+
+    /// 记录一次成功的心跳
+    pub fn record_heartbeat(&self) {
+        *self.last_heartbeat_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// 最近一次心跳成功的时间，本次启动后还没有成功的心跳时为 None
+    pub fn last_heartbeat_at(&self) -> Option<Instant> {
+        *self.last_heartbeat_at.lock().unwrap()
+    }
+
+    /// 自启动以来的运行时长，未运行时为 None
+    pub fn uptime(&self) -> Option<Duration> {
+        self.started_at.lock().unwrap().map(|started_at| started_at.elapsed())
+    }
+
+    /// 根据运行标志、本地就绪情况和服务器连接情况计算当前状态
+    pub fn state(&self) -> PluginState {
+        if self.stopping.load(Ordering::SeqCst) {
+            return PluginState::Stopping;
+        }
+
+        if !self.is_running() {
+            return PluginState::Stopped;
+        }
+
+        if !self.is_ready() {
+            return PluginState::Starting;
+        }
+
+        if self.is_server_connected() {
+            PluginState::Ready
+        } else {
+            PluginState::Degraded
+        }
+    }
+
+    /// 当前状态的说明，用于 GetStatus 响应的 details，有过成功的心跳时附带距今时间
+    pub fn details(&self) -> String {
+        let details = self.state_details();
+        match self.last_heartbeat_at() {
+            Some(last_heartbeat_at) => format!("{}，上次心跳在 {} 秒前", details, last_heartbeat_at.elapsed().as_secs()),
+            None => details,
+        }
+    }
+
+    fn state_details(&self) -> String {
+        match self.state() {
+            PluginState::Starting => "插件正在加载本地数据".to_string(),
+            PluginState::Ready => "插件已就绪".to_string(),
+            PluginState::Degraded => "与服务器的连接中断，插件本地功能可用".to_string(),
+            PluginState::Stopping => "插件正在停止".to_string(),
+            PluginState::Stopped => "插件未运行".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_follows_startup_and_connection() {
+        let running = Arc::new(Mutex::new(false));
+        let health = PluginHealth::new(Arc::clone(&running));
+        assert_eq!(health.state(), PluginState::Stopped);
+
+        *running.lock().unwrap() = true;
+        assert_eq!(health.state(), PluginState::Starting);
+
+        // 未连接服务器时本地就绪为 DEGRADED，心跳成功后变为 READY
+        health.set_ready(true);
+        assert_eq!(health.state(), PluginState::Degraded);
+        health.set_server_connected(true);
+        assert_eq!(health.state(), PluginState::Ready);
+        health.set_server_connected(false);
+        assert_eq!(health.state(), PluginState::Degraded);
+
+        health.set_stopping(true);
+        assert_eq!(health.state(), PluginState::Stopping);
+        assert_eq!(health.state().to_string(), "STOPPING");
+    }
+}
@@ -0,0 +1,71 @@
+use rand::rngs::{OsRng, StdRng};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+/// 随机数来源
+///
+/// 默认使用操作系统随机数。`seeded` 创建的来源输出固定序列，只用于测试中复现密钥ID和密钥材料，
+/// 不能用于生产环境。克隆得到的是同一个来源的句柄，共享同一个序列
+#[derive(Debug, Clone, Default)]
+pub struct RandomSource {
+    seeded: Option<Arc<Mutex<StdRng>>>,
+}
+
+impl RandomSource {
+    /// 操作系统随机数
+    pub fn os() -> Self {
+        Self::default()
+    }
+
+    /// 使用固定种子的伪随机数，相同种子得到相同的输出序列
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seeded: Some(Arc::new(Mutex::new(StdRng::seed_from_u64(seed)))),
+        }
+    }
+
+    pub fn is_seeded(&self) -> bool {
+        self.seeded.is_some()
+    }
+
+    /// 生成随机的 UUID v4 字符串
+    pub fn uuid(&self) -> String {
+        let mut bytes = [0u8; 16];
+        self.fill(&mut bytes);
+        uuid::Builder::from_random_bytes(bytes).into_uuid().to_string()
+    }
+
+    /// 填充随机字节
+    pub fn fill(&self, dest: &mut [u8]) {
+        match &self.seeded {
+            Some(rng) => rng.lock().unwrap().fill_bytes(dest),
+            None => OsRng.fill_bytes(dest),
+        }
+    }
+}
+
+impl RngCore for RandomSource {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.fill(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill(dest);
+        Ok(())
+    }
+}
+
+// 两种来源都是密码学安全的生成器（StdRng 为 ChaCha12）
+impl CryptoRng for RandomSource {}
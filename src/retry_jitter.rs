@@ -0,0 +1,83 @@
+use rand::Rng;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 重试等待时间的随机抖动策略
+///
+/// 多个插件同时重启时，固定的重试间隔会让它们在同一时刻重连服务器。
+/// 抖动把每次等待打散到一个区间内，公式与 AWS 架构博客 "Exponential Backoff And Jitter" 相同
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JitterStrategy {
+    /// 不加抖动，按原间隔等待
+    #[default]
+    None,
+    /// 在 `[0, base]` 内均匀取值
+    Full,
+    /// 保留一半间隔，另一半随机：`[base/2, base]`
+    Equal,
+}
+
+impl JitterStrategy {
+    /// 对基础等待时间加抖动
+    pub fn apply<R: Rng + ?Sized>(&self, base: Duration, rng: &mut R) -> Duration {
+        match self {
+            JitterStrategy::None => base,
+            JitterStrategy::Full => full_jitter(base, rng),
+            JitterStrategy::Equal => equal_jitter(base, rng),
+        }
+    }
+}
+
+impl FromStr for JitterStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(JitterStrategy::None),
+            "full" => Ok(JitterStrategy::Full),
+            "equal" => Ok(JitterStrategy::Equal),
+            _ => Err(format!("Invalid jitter strategy: {}", s)),
+        }
+    }
+}
+
+/// sleep = random_between(0, base)
+pub fn full_jitter<R: Rng + ?Sized>(base: Duration, rng: &mut R) -> Duration {
+    base.mul_f64(rng.gen_range(0.0..=1.0))
+}
+
+/// sleep = base / 2 + random_between(0, base / 2)
+pub fn equal_jitter<R: Rng + ?Sized>(base: Duration, rng: &mut R) -> Duration {
+    let half = base / 2;
+    half + full_jitter(base - half, rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn jitter_stays_within_strategy_bounds() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let base = Duration::from_secs(3);
+
+        for _ in 0..1000 {
+            assert!(full_jitter(base, &mut rng) <= base);
+
+            let equal = equal_jitter(base, &mut rng);
+            assert!(equal >= base / 2 && equal <= base, "{:?}", equal);
+        }
+        assert_eq!(JitterStrategy::None.apply(base, &mut rng), base);
+        assert_eq!(full_jitter(Duration::ZERO, &mut rng), Duration::ZERO);
+    }
+
+    #[test]
+    fn parses_strategy_names() {
+        assert_eq!("FULL".parse::<JitterStrategy>(), Ok(JitterStrategy::Full));
+        assert_eq!("equal".parse::<JitterStrategy>(), Ok(JitterStrategy::Equal));
+        assert_eq!("none".parse::<JitterStrategy>(), Ok(JitterStrategy::None));
+        assert!("decorrelated".parse::<JitterStrategy>().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+/// 注册响应中携带服务器版本的字段，如 `注册成功 server_version=1.2.0`
+const SERVER_VERSION_KEY: &str = "server_version=";
+
+/// 从该版本起服务器能解析心跳中附带的插件能力
+const INLINE_CAPABILITIES_SINCE: (u32, u32, u32) = (1, 1, 0);
+
+/// 与服务器协商得到的协议特性
+///
+/// 目前没有单独查询特性的RPC，根据注册响应中的服务器版本判断；
+/// 未注册或响应中没有版本时按最早的服务器处理，所有可选特性关闭
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerFeatures {
+    version: Option<(u32, u32, u32)>,
+    inline_capabilities: bool, // 心跳的 status_info 中附带支持的命令
+}
+
+impl ServerFeatures {
+    /// 根据注册响应的消息协商
+    pub fn negotiate(message: &str) -> Self {
+        Self::for_version(parse_server_version(message))
+    }
+
+    /// 指定服务器版本下可用的特性
+    pub fn for_version(version: Option<(u32, u32, u32)>) -> Self {
+        Self {
+            version,
+            inline_capabilities: version.is_some_and(|version| version >= INLINE_CAPABILITIES_SINCE),
+        }
+    }
+
+    /// 服务器版本，未知时为 None
+    pub fn server_version(&self) -> Option<(u32, u32, u32)> {
+        self.version
+    }
+
+    pub fn supports_inline_capabilities(&self) -> bool {
+        self.inline_capabilities
+    }
+
+    /// 心跳请求的 status_info
+    ///
+    /// 支持的服务器收到 `{"status": ..., "commands": [...]}`，旧服务器只收到状态字符串
+    pub fn status_info(&self, status: &str, commands: &[String]) -> String {
+        self.status_info_with_metrics(status, commands, None)
+    }
+
+    /// 附带运行指标的 status_info，指标放在 `metrics` 字段中；旧服务器仍只收到状态字符串
+    pub fn status_info_with_metrics(&self, status: &str, commands: &[String], metrics: Option<&serde_json::Value>) -> String {
+        if !self.inline_capabilities {
+            return status.to_string();
+        }
+        let mut info = serde_json::json!({ "status": status, "commands": commands });
+        if let Some(metrics) = metrics {
+            info["metrics"] = metrics.clone();
+        }
+        info.to_string()
+    }
+}
+
+// 从消息中读取 server_version=主.次.修订，缺省的部分为0，忽略 v 前缀和 - 之后的预发布标记
+fn parse_server_version(message: &str) -> Option<(u32, u32, u32)> {
+    let value = message
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .find_map(|part| part.strip_prefix(SERVER_VERSION_KEY))?;
+    let value = value.trim_start_matches('v');
+    let value = value.split('-').next().unwrap_or_default();
+
+    let mut parts = value.split('.').map(|part| part.parse::<u32>().ok());
+    let major = parts.next().flatten()?;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_version_is_read_from_registration_message() {
+        let cases = [
+            ("注册成功 server_version=1.2.3", Some((1, 2, 3))),
+            ("ok, server_version=v2.0-beta; region=cn", Some((2, 0, 0))),
+            ("server_version=3", Some((3, 0, 0))),
+            ("server_version=1.x", None),
+            ("注册成功", None),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(ServerFeatures::negotiate(message).server_version(), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn older_server_only_receives_the_status_string() {
+        let commands = vec!["create_key".to_string()];
+        let old = ServerFeatures::for_version(Some((1, 0, 9)));
+        assert!(!old.supports_inline_capabilities());
+        assert_eq!(old.status_info("running", &commands), "running");
+        assert_eq!(ServerFeatures::default(), ServerFeatures::for_version(None));
+
+        let current = ServerFeatures::for_version(Some(INLINE_CAPABILITIES_SINCE));
+        assert!(current.supports_inline_capabilities());
+        let status: serde_json::Value = serde_json::from_str(&current.status_info("running", &commands)).unwrap();
+        assert_eq!(status, serde_json::json!({"status": "running", "commands": ["create_key"]}));
+    }
+
+    #[test]
+    fn metrics_are_only_sent_to_servers_with_inline_capabilities() {
+        let commands = vec!["create_key".to_string()];
+        let metrics = serde_json::json!({"uptime_secs": 5});
+        let old = ServerFeatures::for_version(Some((1, 0, 9)));
+        assert_eq!(old.status_info_with_metrics("running", &commands, Some(&metrics)), "running");
+
+        let current = ServerFeatures::for_version(Some(INLINE_CAPABILITIES_SINCE));
+        let status: serde_json::Value = serde_json::from_str(&current.status_info_with_metrics("running", &commands, Some(&metrics))).unwrap();
+        assert_eq!(status["metrics"], metrics);
+        assert_eq!(status["status"], "running");
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// 插件运行循环等待的停止信号
+///
+/// 默认监听 Ctrl+C（SIGINT），Unix 上同时监听 SIGTERM（容器停止时发送）。
+/// 克隆得到的是同一组信号，任一克隆调用 `trigger` 都会让等待方返回，可用于在程序内部停止插件
+#[derive(Debug, Clone)]
+pub struct ShutdownSignals {
+    ctrl_c: bool,
+    #[cfg_attr(not(unix), allow(dead_code))] // 非 Unix 平台没有 SIGTERM
+    sigterm: bool,
+    trigger: Arc<Notify>,
+}
+
+impl Default for ShutdownSignals {
+    fn default() -> Self {
+        Self {
+            ctrl_c: true,
+            sigterm: true,
+            trigger: Arc::new(Notify::new()),
+        }
+    }
+}
+
+impl ShutdownSignals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 不监听任何系统信号，只能通过 `trigger` 停止
+    pub fn manual() -> Self {
+        Self::new().with_ctrl_c(false).with_sigterm(false)
+    }
+
+    pub fn with_ctrl_c(mut self, enabled: bool) -> Self {
+        self.ctrl_c = enabled;
+        self
+    }
+
+    pub fn with_sigterm(mut self, enabled: bool) -> Self {
+        self.sigterm = enabled;
+        self
+    }
+
+    /// 在程序内部触发停止；还没有开始等待时，下一次 `wait` 立即返回
+    pub fn trigger(&self) {
+        self.trigger.notify_one();
+    }
+
+    /// 等待任一停止信号，返回信号名称；安装信号处理失败时返回错误
+    ///
+    /// Unix 上 SIGINT 与 SIGTERM 都通过 `tokio::signal::unix` 监听，两者在等待前一起安装
+    pub async fn wait(&self) -> Result<&'static str, String> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, Signal, SignalKind};
+
+            let install = |enabled: bool, kind: SignalKind, name: &str| -> Result<Option<Signal>, String> {
+                if !enabled {
+                    return Ok(None);
+                }
+                signal(kind).map(Some).map_err(|e| format!("监听 {} 失败: {}", name, e))
+            };
+            let mut sigint = install(self.ctrl_c, SignalKind::interrupt(), "SIGINT")?;
+            let mut sigterm = install(self.sigterm, SignalKind::terminate(), "SIGTERM")?;
+
+            tokio::select! {
+                _ = recv(&mut sigint) => Ok("SIGINT"),
+                _ = recv(&mut sigterm) => Ok("SIGTERM"),
+                _ = self.trigger.notified() => Ok("trigger"),
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let ctrl_c = async {
+                if self.ctrl_c {
+                    tokio::signal::ctrl_c().await.map_err(|e| format!("监听 Ctrl+C 失败: {}", e))
+                } else {
+                    std::future::pending().await
+                }
+            };
+
+            tokio::select! {
+                result = ctrl_c => result.map(|_| "SIGINT"),
+                _ = self.trigger.notified() => Ok("trigger"),
+            }
+        }
+    }
+}
+
+/// 未启用的信号永远不会返回
+#[cfg(unix)]
+async fn recv(signal: &mut Option<tokio::signal::unix::Signal>) {
+    match signal {
+        Some(signal) => {
+            signal.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BasePlugin, PluginConfig, PluginSDK, PluginState};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn trigger_stops_running_plugin() {
+        let mut plugin = BasePlugin::new();
+        assert!(plugin.initialize(PluginConfig::new()).await);
+
+        let signals = ShutdownSignals::manual();
+        let trigger = signals.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            trigger.trigger();
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), plugin.run_until_signal(&signals))
+            .await
+            .expect("插件应在收到停止信号后返回")
+            .unwrap();
+        assert_eq!(plugin.state(), PluginState::Stopped);
+    }
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// 截断到毫秒精度
+pub fn truncate(value: DateTime<Utc>) -> DateTime<Utc> {
+    value.trunc_subsecs(3)
+}
+
+/// 当前时间（毫秒精度）
+pub fn now() -> DateTime<Utc> {
+    truncate(Utc::now())
+}
+
+/// 格式化为持久化使用的统一格式：UTC、毫秒精度、`Z` 后缀，如 `2024-01-02T03:04:05.678Z`
+///
+/// 固定宽度的字符串按字典序比较与按时间比较一致，数据库中可以直接比较和排序；
+/// 时间在产生时就截断到毫秒，保存后再读取得到的值与原值完全相同。
+/// 结构体字段可以用 `#[serde(with = "crate::timestamp")]` 按该格式序列化
+pub fn format(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// 解析 RFC3339 时间，其他时区转换为 UTC，超出毫秒的精度截断
+pub fn parse(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| truncate(dt.with_timezone(&Utc)))
+        .map_err(|e| format!("解析时间失败: {}", e))
+}
+
+pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(value))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse(&value).map_err(serde::de::Error::custom)
+}
+
+/// 可选时间字段的序列化，用法 `#[serde(default, with = "crate::timestamp::option")]`
+pub mod option {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&super::format(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => super::parse(&value).map(Some).map_err(serde::de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "cli")]
+
+use password_manager::PluginConfig;
+
+#[test]
+fn args_map_onto_config_fields() {
+    let config = PluginConfig::new()
+        .with_args([
+            "plugin",
+            "--server-host", "10.0.0.5",
+            "--server-port", "6000",
+            "--plugin-name", "vault",
+            "--config", "heartbeat_interval=5",
+            "--config", "audit_level=all",
+            "--config", "heartbeat_interval=10",
+        ])
+        .unwrap();
+
+    assert_eq!(config.get_server_host(), "10.0.0.5");
+    assert_eq!(config.get_server_port(), 6000);
+    assert_eq!(config.get_plugin_name(), "vault");
+    assert_eq!(config.get_config("audit_level").map(String::as_str), Some("all"));
+    // 同一个键以最后一次为准
+    assert_eq!(config.get_config("heartbeat_interval").map(String::as_str), Some("10"));
+}
+
+#[test]
+fn args_only_override_given_fields() {
+    let mut base = PluginConfig::new();
+    base.set_server_host("config-file-host".to_string());
+    base.set_plugin_type("storage".to_string());
+    base.add_config("log_level".to_string(), "debug".to_string());
+
+    let config = base.with_args(["plugin", "--server-host", "cli-host"]).unwrap();
+    assert_eq!(config.get_server_host(), "cli-host");
+    assert_eq!(config.get_plugin_type(), "storage");
+    assert_eq!(config.get_config("log_level").map(String::as_str), Some("debug"));
+}
+
+#[test]
+fn malformed_args_are_rejected() {
+    assert!(PluginConfig::new().with_args(["plugin", "--config", "no-separator"]).is_err());
+    assert!(PluginConfig::new().with_args(["plugin", "--server-port", "not-a-port"]).is_err());
+    assert!(PluginConfig::new().with_args(["plugin", "--unknown-flag"]).is_err());
+}
@@ -0,0 +1,711 @@
+#![cfg(feature = "grpc")]
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use password_manager::base_plugin::plugin::plugin_service_client::PluginServiceClient;
+use password_manager::base_plugin::plugin::plugin_service_server::{PluginService, PluginServiceServer};
+use password_manager::base_plugin::plugin::{
+    CommandRequest, CommandResponse, FindPluginRequest, FindPluginResponse, GetPluginByNameRequest,
+    GetPluginByNameResponse, HeartbeatRequest, HeartbeatResponse, PluginInfo as ProtoPluginInfo, PluginRegistration,
+    RegistrationResponse,
+    StatusRequest, StatusResponse, StopRequest, StopResponse, UpdatePluginRequest, UpdatePluginResponse,
+};
+use password_manager::persistence::{FilePersistence, PersistenceInterface};
+use password_manager::{BasePlugin, ExamplePlugin, KeyManagementPlugin, PluginConfig, PluginInfo, PluginSDK, PluginState};
+
+// 模拟主应用，记录插件的调用并按预设返回
+#[derive(Default)]
+struct MockServer {
+    registrations: AtomicUsize,
+    heartbeats: AtomicUsize,
+    stops: AtomicUsize,
+    stop_success: bool,
+    registration_message: String,
+    registered_ports: Mutex<Vec<i32>>,
+    heartbeat_statuses: Mutex<Vec<String>>,
+    server_times: Mutex<VecDeque<i64>>,
+    plugins: Vec<ProtoPluginInfo>,
+    updates: Mutex<Vec<UpdatePluginRequest>>,
+}
+
+impl MockServer {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_stop_success(mut self, success: bool) -> Self {
+        self.stop_success = success;
+        self
+    }
+
+    fn with_registration_message(mut self, message: &str) -> Self {
+        self.registration_message = message.to_string();
+        self
+    }
+
+    fn with_plugin(mut self, plugin: ProtoPluginInfo) -> Self {
+        self.plugins.push(plugin);
+        self
+    }
+
+    fn with_server_times(self, times: &[i64]) -> Self {
+        *self.server_times.lock().unwrap() = times.iter().copied().collect();
+        self
+    }
+}
+
+#[tonic::async_trait]
+impl PluginService for MockServer {
+    async fn register_plugin(
+        &self,
+        request: Request<PluginRegistration>,
+    ) -> Result<Response<RegistrationResponse>, Status> {
+        self.registered_ports.lock().unwrap().push(request.into_inner().port);
+        let n = self.registrations.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(Response::new(RegistrationResponse {
+            plugin_id: format!("server{}", n),
+            success: true,
+            message: self.registration_message.clone(),
+        }))
+    }
+
+    async fn heartbeat(&self, request: Request<HeartbeatRequest>) -> Result<Response<HeartbeatResponse>, Status> {
+        self.heartbeat_statuses.lock().unwrap().push(request.into_inner().status_info);
+        self.heartbeats.fetch_add(1, Ordering::SeqCst);
+        let server_time = self.server_times.lock().unwrap().pop_front().unwrap_or(0);
+        Ok(Response::new(HeartbeatResponse {
+            received: true,
+            server_time,
+        }))
+    }
+
+    async fn get_status(&self, _request: Request<StatusRequest>) -> Result<Response<StatusResponse>, Status> {
+        Err(Status::unimplemented("get_status"))
+    }
+
+    async fn execute_command(&self, _request: Request<CommandRequest>) -> Result<Response<CommandResponse>, Status> {
+        Err(Status::unimplemented("execute_command"))
+    }
+
+    async fn stop_plugin(&self, _request: Request<StopRequest>) -> Result<Response<StopResponse>, Status> {
+        self.stops.fetch_add(1, Ordering::SeqCst);
+        Ok(Response::new(StopResponse {
+            success: self.stop_success,
+            message: String::new(),
+        }))
+    }
+
+    async fn find_plugin(&self, request: Request<FindPluginRequest>) -> Result<Response<FindPluginResponse>, Status> {
+        let request = request.into_inner();
+        let plugin = self.plugins.iter()
+            .find(|p| p.name == request.name && p.r#type == request.r#type)
+            .cloned();
+        Ok(Response::new(FindPluginResponse {
+            found: plugin.is_some(),
+            plugin,
+        }))
+    }
+
+    async fn update_plugin(
+        &self,
+        request: Request<UpdatePluginRequest>,
+    ) -> Result<Response<UpdatePluginResponse>, Status> {
+        self.updates.lock().unwrap().push(request.into_inner());
+        Ok(Response::new(UpdatePluginResponse {
+            success: true,
+            message: String::new(),
+        }))
+    }
+
+    async fn get_plugin_by_name(
+        &self,
+        request: Request<GetPluginByNameRequest>,
+    ) -> Result<Response<GetPluginByNameResponse>, Status> {
+        let name = request.into_inner().name;
+        let plugin = self.plugins.iter().find(|p| p.name == name).cloned();
+        Ok(Response::new(GetPluginByNameResponse {
+            found: plugin.is_some(),
+            plugin,
+        }))
+    }
+}
+
+// 在系统分配的端口上启动模拟主应用，返回端口
+async fn start_mock_server(mock: Arc<MockServer>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(PluginServiceServer::from_arc(mock))
+            .serve_with_incoming(TcpIncoming::from(listener))
+            .await
+            .unwrap();
+    });
+    port
+}
+
+// 连接到模拟主应用的插件配置，插件自身端口由系统分配
+fn test_config(server_port: u16) -> PluginConfig {
+    let mut config = PluginConfig::new();
+    config.set_server_host("127.0.0.1".to_string());
+    config.set_server_port(i32::from(server_port));
+    config.set_plugin_name("grpc-test".to_string());
+    config.set_plugin_version("1.0.0".to_string());
+    config.set_plugin_type("test".to_string());
+    config.add_config("plugin_grpc_port".to_string(), "0".to_string());
+    config.add_config("host_address".to_string(), "127.0.0.1".to_string());
+    config
+}
+
+async fn started_plugin(server_port: u16) -> BasePlugin {
+    started_plugin_with(test_config(server_port)).await
+}
+
+async fn started_plugin_with(config: PluginConfig) -> BasePlugin {
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(config).await);
+    assert!(plugin.start().await);
+    plugin
+}
+
+// 等待计数达到 expected，超时返回 false
+async fn wait_for_count(counter: &AtomicUsize, expected: usize, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while counter.load(Ordering::SeqCst) < expected {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    true
+}
+
+#[tokio::test]
+async fn stop_with_result_reports_server_ack() {
+    let mock = Arc::new(MockServer::new().with_stop_success(true));
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = started_plugin(port).await;
+    assert_eq!(plugin.stop_with_result().await, Ok(true));
+    assert_eq!(mock.stops.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn stop_with_result_reports_server_rejection() {
+    let mock = Arc::new(MockServer::new().with_stop_success(false));
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = started_plugin(port).await;
+    assert_eq!(plugin.stop_with_result().await, Ok(false));
+    assert_eq!(mock.stops.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn stop_with_result_skips_server_when_not_running() {
+    let mock = Arc::new(MockServer::new().with_stop_success(true));
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(test_config(port)).await);
+    assert_eq!(plugin.stop_with_result().await, Ok(false));
+    assert_eq!(mock.stops.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn heartbeat_reregisters_after_server_time_goes_back() {
+    let mock = Arc::new(MockServer::new().with_server_times(&[2000, 1000]));
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut config = test_config(port);
+    config.add_config("heartbeat_interval".to_string(), "1".to_string());
+    let mut plugin = started_plugin_with(config).await;
+    assert_eq!(mock.registrations.load(Ordering::SeqCst), 1);
+
+    assert!(wait_for_count(&mock.registrations, 2, Duration::from_secs(10)).await);
+    assert_eq!(mock.heartbeats.load(Ordering::SeqCst), 2);
+    // 重新注册得到的新ID取代原ID
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while plugin.get_info().get_id() != "server2" && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(plugin.get_info().get_id(), "server2");
+    plugin.stop().await;
+}
+
+#[tokio::test]
+async fn heartbeat_keeps_registration_while_server_time_advances() {
+    let mock = Arc::new(MockServer::new().with_server_times(&[1000, 2000, 3000]));
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut config = test_config(port);
+    config.add_config("heartbeat_interval".to_string(), "1".to_string());
+    let mut plugin = started_plugin_with(config).await;
+
+    assert!(wait_for_count(&mock.heartbeats, 3, Duration::from_secs(10)).await);
+    assert_eq!(mock.registrations.load(Ordering::SeqCst), 1);
+    assert_eq!(plugin.get_info().get_id(), "server1");
+    plugin.stop().await;
+}
+
+#[tokio::test]
+async fn successful_heartbeat_updates_last_heartbeat_at() {
+    let mock = Arc::new(MockServer::new());
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.uptime().is_none());
+    let mut config = test_config(port);
+    config.add_config("heartbeat_interval".to_string(), "1".to_string());
+    assert!(plugin.initialize(config).await);
+    assert!(plugin.start().await);
+    assert!(plugin.uptime().is_some());
+
+    assert!(wait_for_count(&mock.heartbeats, 1, Duration::from_secs(10)).await);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while plugin.last_heartbeat_at().is_none() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let last_heartbeat_at = plugin.last_heartbeat_at().expect("心跳成功后应记录时间");
+    assert!(last_heartbeat_at.elapsed() < Duration::from_secs(5));
+    assert!(plugin.health().details().contains("上次心跳"), "{}", plugin.health().details());
+
+    plugin.stop().await;
+    assert!(plugin.uptime().is_none());
+}
+
+#[tokio::test]
+async fn heartbeat_omits_capabilities_for_older_server() {
+    for (message, inline) in [("注册成功 server_version=1.0.3", false), ("注册成功 server_version=1.2.0", true), ("注册成功", false)] {
+        let mock = Arc::new(MockServer::new().with_registration_message(message));
+        let port = start_mock_server(Arc::clone(&mock)).await;
+
+        let mut plugin = BasePlugin::new();
+        assert!(!plugin.server_features().supports_inline_capabilities());
+        assert!(plugin.initialize(test_config(port)).await);
+        assert!(plugin.start().await);
+        assert_eq!(plugin.server_features().supports_inline_capabilities(), inline, "{}", message);
+
+        assert!(plugin.send_heartbeat().await.unwrap());
+        let status_info = mock.heartbeat_statuses.lock().unwrap().last().cloned().unwrap();
+        match serde_json::from_str::<serde_json::Value>(&status_info) {
+            Ok(status) if inline => assert!(status["status"].is_string() && status["commands"].is_array(), "{}", status_info),
+            _ => assert!(!inline && !status_info.starts_with('{'), "{}: {}", message, status_info),
+        }
+        plugin.stop().await;
+    }
+}
+
+#[tokio::test]
+async fn heartbeat_carries_metrics_when_enabled() {
+    for enabled in [true, false] {
+        let mock = Arc::new(MockServer::new().with_registration_message("注册成功 server_version=1.2.0"));
+        let port = start_mock_server(Arc::clone(&mock)).await;
+        let mut config = test_config(port);
+        config.add_config("heartbeat_metrics".to_string(), enabled.to_string());
+        let mut plugin = started_plugin_with(config).await;
+
+        plugin.record_command("create_key", Duration::from_millis(1));
+        plugin.record_command("get_key", Duration::from_millis(1));
+        assert!(plugin.send_heartbeat().await.unwrap());
+        assert!(plugin.send_heartbeat().await.unwrap());
+
+        let statuses = mock.heartbeat_statuses.lock().unwrap().clone();
+        let statuses: Vec<serde_json::Value> = statuses[statuses.len() - 2..].iter()
+            .map(|status| serde_json::from_str(status).unwrap())
+            .collect();
+        if enabled {
+            assert_eq!(statuses[0]["metrics"]["commands_since_last_heartbeat"], 2);
+            assert!(statuses[0]["metrics"]["uptime_secs"].is_u64());
+            // 命令数按上次成功心跳以来的增量计算
+            assert_eq!(statuses[1]["metrics"]["commands_since_last_heartbeat"], 0);
+        } else {
+            assert!(statuses.iter().all(|status| status.get("metrics").is_none()), "{:?}", statuses);
+        }
+        plugin.stop().await;
+    }
+}
+
+fn sample_proto_info() -> ProtoPluginInfo {
+    ProtoPluginInfo {
+        plugin_id: "plugin-1".to_string(),
+        name: "vault".to_string(),
+        version: "2.1.0".to_string(),
+        r#type: "security".to_string(),
+        description: "密钥管理".to_string(),
+        host: "10.0.0.5".to_string(),
+        port: 19092,
+        status: "RUNNING".to_string(),
+    }
+}
+
+#[test]
+fn plugin_info_round_trips_through_proto() {
+    let proto = sample_proto_info();
+    let local = PluginInfo::from(proto.clone());
+    assert_eq!(local.get_id(), "plugin-1");
+    assert_eq!(local.get_name(), "vault");
+    assert_eq!(local.get_version(), "2.1.0");
+    assert_eq!(local.get_type(), "security");
+    assert_eq!(local.get_description(), "密钥管理");
+    assert_eq!(local.get_host(), "10.0.0.5");
+    assert_eq!(local.get_port(), 19092);
+    assert_eq!(local.get_status(), "RUNNING");
+
+    assert_eq!(ProtoPluginInfo::from(local), proto);
+}
+
+#[test]
+fn plugin_info_to_proto_drops_commands_and_events() {
+    let mut local = PluginInfo::from(sample_proto_info());
+    local.add_supported_command("create_key".to_string());
+    local.add_supported_event("key_rotated".to_string());
+
+    let back = PluginInfo::from(ProtoPluginInfo::from(local.clone()));
+    assert!(back.get_supported_commands().is_empty());
+    assert!(back.get_supported_events().is_empty());
+    assert_eq!(back.get_id(), local.get_id());
+    assert_eq!(back.get_port(), local.get_port());
+}
+
+#[tokio::test]
+async fn discovery_responses_map_to_local_plugin_info() {
+    let mock = Arc::new(MockServer::new().with_plugin(sample_proto_info()));
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(test_config(port)).await);
+
+    let found = plugin.find_plugin("vault", "security").await.unwrap().unwrap();
+    assert_eq!(found.get_id(), "plugin-1");
+    assert_eq!(found.get_host(), "10.0.0.5");
+    assert_eq!(found.get_port(), 19092);
+
+    let by_name = plugin.get_plugin_by_name("vault").await.unwrap().unwrap();
+    assert_eq!(by_name.get_status(), "RUNNING");
+
+    assert!(plugin.find_plugin("vault", "other").await.unwrap().is_none());
+    assert!(plugin.get_plugin_by_name("missing").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn state_moves_from_starting_to_ready_after_local_load() {
+    let mock = Arc::new(MockServer::new());
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(test_config(port)).await);
+    assert_eq!(plugin.state(), PluginState::Stopped);
+
+    assert!(plugin.start().await);
+    assert_eq!(plugin.state(), PluginState::Starting);
+    plugin.set_ready(true);
+    assert_eq!(plugin.state(), PluginState::Ready);
+
+    plugin.stop().await;
+    assert_eq!(plugin.state(), PluginState::Stopped);
+}
+
+#[tokio::test]
+async fn state_is_degraded_without_server() {
+    let port = free_port().await;
+
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(test_config(port)).await);
+    assert!(plugin.start().await);
+    plugin.set_ready(true);
+    assert_eq!(plugin.state(), PluginState::Degraded);
+    plugin.stop().await;
+}
+
+#[tokio::test]
+async fn changed_address_is_pushed_with_update_plugin() {
+    let mock = Arc::new(MockServer::new());
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = started_plugin(port).await;
+    plugin.set_advertised_address("10.1.2.3".to_string(), 20000).await.unwrap();
+
+    {
+        let updates = mock.updates.lock().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].plugin_id, "server1");
+        assert_eq!(updates[0].status, "RUNNING");
+        assert_eq!(updates[0].host, "10.1.2.3");
+        assert_eq!(updates[0].port, 20000);
+    }
+
+    // 地址未变化时不重复通知
+    plugin.set_advertised_address("10.1.2.3".to_string(), 20000).await.unwrap();
+    assert_eq!(mock.updates.lock().unwrap().len(), 1);
+    plugin.stop().await;
+}
+
+#[tokio::test]
+async fn address_change_before_start_is_not_pushed() {
+    let mock = Arc::new(MockServer::new());
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(test_config(port)).await);
+    plugin.set_advertised_address("10.1.2.3".to_string(), 20000).await.unwrap();
+    assert!(mock.updates.lock().unwrap().is_empty());
+}
+
+// 先占用一个系统分配的空闲端口再释放，供插件入站服务使用
+async fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port()
+}
+
+// 在系统分配的端口上启动插件入站服务，返回插件和连接到该服务的客户端
+async fn serve_plugin<P>(plugin: Arc<P>) -> (BasePlugin, PluginServiceClient<tonic::transport::Channel>, tokio::task::JoinHandle<()>)
+where
+    P: PluginSDK + Send + Sync + 'static,
+{
+    let mut base = BasePlugin::new();
+    let mut config = PluginConfig::new();
+    config.add_config("plugin_grpc_port".to_string(), "0".to_string());
+    assert!(base.initialize(config).await);
+
+    let handle = base.serve(plugin).await.unwrap();
+    let port = base.bound_port().expect("serve 后应记录实际绑定的端口");
+    let client = PluginServiceClient::connect(format!("http://127.0.0.1:{}", port)).await.unwrap();
+    (base, client, handle)
+}
+
+#[tokio::test]
+async fn ephemeral_port_is_bound_before_registration_and_registered() {
+    let mock = Arc::new(MockServer::new());
+    let port = start_mock_server(Arc::clone(&mock)).await;
+    let mut plugin = started_plugin(port).await;
+
+    let bound = plugin.bound_port().expect("端口由系统分配时 start 应预先绑定");
+    assert_ne!(bound, 0);
+    assert_eq!(*mock.registered_ports.lock().unwrap(), vec![i32::from(bound)]);
+    assert_eq!(plugin.get_info().get_port(), i32::from(bound));
+
+    // serve 使用预先绑定的监听，不再重新分配端口
+    let _handle = plugin.serve(Arc::new(ExamplePlugin::new())).await.unwrap();
+    assert_eq!(plugin.bound_port(), Some(bound));
+    let mut client = PluginServiceClient::connect(format!("http://127.0.0.1:{}", bound)).await.unwrap();
+    let response = client.execute_command(CommandRequest {
+        plugin_id: String::new(),
+        command: "echo".to_string(),
+        parameters: [("message".to_string(), "ping".to_string())].into(),
+    }).await.unwrap().into_inner();
+    assert!(response.success);
+    plugin.stop().await;
+}
+
+#[tokio::test]
+async fn serve_dispatches_execute_command_to_plugin() {
+    let (_base, mut client, _handle) = serve_plugin(Arc::new(ExamplePlugin::new())).await;
+
+    let mut parameters = std::collections::HashMap::new();
+    parameters.insert("message".to_string(), "ping".to_string());
+    let response = client.execute_command(CommandRequest {
+        plugin_id: String::new(),
+        command: "echo".to_string(),
+        parameters,
+    }).await.unwrap().into_inner();
+    assert!(response.success);
+    assert_eq!(response.result, "Echo: ping");
+
+    let response = client.execute_command(CommandRequest {
+        plugin_id: String::new(),
+        command: "no_such_command".to_string(),
+        parameters: Default::default(),
+    }).await.unwrap().into_inner();
+    assert!(!response.success);
+    assert!(!response.error_message.is_empty());
+}
+
+#[tokio::test]
+async fn serve_stops_after_stop_plugin() {
+    let (_base, mut client, handle) = serve_plugin(Arc::new(ExamplePlugin::new())).await;
+
+    let status = client.get_status(StatusRequest { plugin_id: String::new() }).await.unwrap().into_inner();
+    assert_eq!(status.status, "STOPPED");
+
+    let response = client.stop_plugin(StopRequest { plugin_id: String::new() }).await.unwrap().into_inner();
+    assert!(response.success);
+    tokio::time::timeout(Duration::from_secs(5), handle).await
+        .expect("StopPlugin 后入站服务应关闭")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn key_management_plugin_creates_key_over_grpc() {
+    let dir = std::env::temp_dir().join(format!("grpc-keys-{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FilePersistence::new(dir.to_str().unwrap()));
+    let port = free_port().await;
+
+    let mut plugin = KeyManagementPlugin::new().with_persistence(persistence.clone());
+    let mut config = PluginConfig::new();
+    config.add_config("plugin_grpc_port".to_string(), port.to_string());
+    assert!(plugin.initialize(config).await);
+    let plugin = Arc::new(plugin);
+
+    let _handle = plugin.serve().await.unwrap();
+    let mut client = PluginServiceClient::connect(format!("http://127.0.0.1:{}", port)).await.unwrap();
+
+    let mut parameters = std::collections::HashMap::new();
+    parameters.insert("name".to_string(), "grpc-key".to_string());
+    parameters.insert("key_type".to_string(), "SYMMETRIC".to_string());
+    parameters.insert("algorithm".to_string(), "AES-256".to_string());
+    parameters.insert("user".to_string(), "alice".to_string());
+    parameters.insert("tag.env".to_string(), "prod".to_string());
+    let response = client.execute_command(CommandRequest {
+        plugin_id: String::new(),
+        command: "create_key".to_string(),
+        parameters,
+    }).await.unwrap().into_inner();
+    assert!(response.success, "{}", response.error_message);
+    assert!(response.error_code.is_empty());
+
+    let created: serde_json::Value = serde_json::from_str(&response.result).unwrap();
+    let key_id = created["id"].as_str().unwrap().to_string();
+    assert_eq!(created["name"], "grpc-key");
+    assert_eq!(created["owner"], "alice");
+
+    // 参数原样传给插件，密钥已在插件中创建并写入持久化存储
+    let mut stored = persistence.load_key_metadata(&key_id).await;
+    for _ in 0..50 {
+        if stored.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        stored = persistence.load_key_metadata(&key_id).await;
+    }
+    let stored = stored.unwrap();
+    assert_eq!(stored.name, "grpc-key");
+    assert_eq!(stored.tags.get("env").map(String::as_str), Some("prod"));
+
+    // 失败结果带有稳定的错误码
+    let mut parameters = std::collections::HashMap::new();
+    parameters.insert("key_id".to_string(), "missing".to_string());
+    let response = client.execute_command(CommandRequest {
+        plugin_id: String::new(),
+        command: "get_key".to_string(),
+        parameters,
+    }).await.unwrap().into_inner();
+    assert!(!response.success);
+    assert_eq!(response.error_code, "KEY_NOT_FOUND");
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn reconfigured_heartbeat_interval_takes_effect_without_restart() {
+    let mock = Arc::new(MockServer::new());
+    let port = start_mock_server(Arc::clone(&mock)).await;
+
+    let mut config = test_config(port);
+    config.add_config("heartbeat_interval".to_string(), "2".to_string());
+    let mut plugin = started_plugin_with(config.clone()).await;
+
+    // 第一次心跳仍按旧间隔（2 秒）发送，之后每秒一次：4 次心跳约 5 秒，旧间隔下需要 8 秒
+    config.add_config("heartbeat_interval".to_string(), "1".to_string());
+    assert_eq!(plugin.reconfigure(config), Ok(vec!["heartbeat_interval".to_string()]));
+    assert_eq!(plugin.settings().get_heartbeat_interval(), 1);
+
+    assert!(wait_for_count(&mock.heartbeats, 4, Duration::from_millis(6500)).await);
+    assert_eq!(mock.registrations.load(Ordering::SeqCst), 1);
+    plugin.stop().await;
+}
+
+#[tokio::test]
+async fn reconfigure_rejects_fields_that_need_a_restart() {
+    let port = free_port().await;
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(test_config(port)).await);
+
+    assert!(plugin.reconfigure(test_config(port + 1)).unwrap_err().contains("server_port"));
+
+    let mut config = test_config(port);
+    config.set_plugin_type("other".to_string());
+    assert!(plugin.reconfigure(config).unwrap_err().contains("plugin_type"));
+
+    // 任一配置项无效时整体不生效
+    let mut changes = std::collections::HashMap::new();
+    changes.insert("heartbeat_interval".to_string(), "3".to_string());
+    changes.insert("log_level".to_string(), "verbose".to_string());
+    assert!(plugin.apply_settings(&changes).is_err());
+    assert_eq!(plugin.settings().get_heartbeat_interval(), 5);
+}
+
+// 连接到一个没有服务监听的端口，每次心跳都会失败
+async fn offline_config(policy: &[(&str, &str)]) -> PluginConfig {
+    let mut config = test_config(free_port().await);
+    config.add_config("heartbeat_interval".to_string(), "1".to_string());
+    for (key, value) in policy {
+        config.add_config(key.to_string(), value.to_string());
+    }
+    config
+}
+
+#[tokio::test]
+async fn offline_policy_stops_plugin_and_runs_hook() {
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::clone(&hook_calls);
+
+    let mut plugin = BasePlugin::new();
+    plugin.set_offline_hook(Arc::new(move || {
+        calls.fetch_add(1, Ordering::SeqCst);
+    }));
+    assert!(plugin.initialize(offline_config(&[("max_heartbeat_failures", "2")]).await).await);
+    assert!(plugin.start().await);
+
+    assert!(wait_for_count(&hook_calls, 1, Duration::from_secs(10)).await);
+    assert_eq!(plugin.state(), PluginState::Stopped);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(hook_calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn offline_policy_is_disabled_by_default() {
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::clone(&hook_calls);
+
+    let mut plugin = BasePlugin::new();
+    plugin.set_offline_hook(Arc::new(move || {
+        calls.fetch_add(1, Ordering::SeqCst);
+    }));
+    assert!(plugin.initialize(offline_config(&[("max_heartbeat_failures", "0")]).await).await);
+    assert!(plugin.start().await);
+
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+    assert_eq!(hook_calls.load(Ordering::SeqCst), 0);
+    assert_ne!(plugin.state(), PluginState::Stopped);
+    plugin.stop().await;
+}
+
+#[tokio::test]
+async fn offline_policy_clears_key_management_keys() {
+    let mut plugin = KeyManagementPlugin::new();
+    assert!(plugin.initialize(offline_config(&[("max_heartbeat_failures", "2")]).await).await);
+
+    let mut params = std::collections::HashMap::new();
+    params.insert("name".to_string(), "wiped".to_string());
+    let created = plugin.execute_command("create_key", &params).await;
+    assert!(created.is_success(), "{}", created.get_error_message());
+    let created: serde_json::Value = serde_json::from_str(created.get_result()).unwrap();
+
+    let mut params = std::collections::HashMap::new();
+    params.insert("key_id".to_string(), created["id"].as_str().unwrap().to_string());
+    assert!(plugin.execute_command("get_key", &params).await.is_success());
+
+    assert!(plugin.start().await);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    while plugin.execute_command("get_key", &params).await.is_success() {
+        assert!(tokio::time::Instant::now() < deadline, "密钥未被清除");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(plugin.execute_command("get_key", &params).await.get_error_message(), "Key not found");
+}
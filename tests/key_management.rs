@@ -0,0 +1,2607 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ring::signature::{self, UnparsedPublicKey};
+use rsa::pkcs1::EncodeRsaPublicKey;
+use rsa::pkcs8::der::asn1::{BitStringRef, GeneralizedTime, ObjectIdentifier, PrintableStringRef, UtcTime, Utf8StringRef};
+use rsa::pkcs8::der::{self, AnyRef, Decode, Encode, Reader, SliceReader, Tag, Tagged};
+use rsa::pkcs8::spki::{AlgorithmIdentifierRef, SubjectPublicKeyInfoRef};
+use rsa::pkcs8::{DecodePublicKey, EncodePrivateKey, EncodePublicKey};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use serde_json::Value;
+
+use password_manager::canonical_json;
+use password_manager::key_management::{AuditLogEntry, DeadLetterStore, DeadLetterWrite, KdfParams, KeyAlgorithm, KeyMetadata, KeyStatus, KeyType, MockHSM, PublicKeyFormat, SecurityModuleInterface, SharedKeyStore, SoftwareSecurityModule};
+#[cfg(feature = "sqlite")]
+use password_manager::persistence::DbPersistence;
+use password_manager::persistence::{FilePersistence, KeyQuery, MemoryPersistence, PersistenceInterface};
+use password_manager::{CommandResult, ErrorCode, KeyManagementPlugin, MockClock, PluginConfig, PluginSDK, RandomSource, ValidationError};
+
+fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+async fn initialized(mut plugin: KeyManagementPlugin) -> KeyManagementPlugin {
+    assert!(plugin.initialize(PluginConfig::new()).await);
+    plugin
+}
+
+async fn run(plugin: &KeyManagementPlugin, command: &str, pairs: &[(&str, &str)]) -> CommandResult {
+    plugin.execute_command(command, &params(pairs)).await
+}
+
+fn json(result: &CommandResult) -> Value {
+    assert!(result.is_success(), "{}", result.get_error_message());
+    serde_json::from_str(result.get_result()).unwrap()
+}
+
+#[tokio::test]
+async fn reconfigure_command_applies_hot_reloadable_settings() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+
+    let changed = json(&run(&plugin, "reconfigure", &[("heartbeat_interval", "10"), ("log_level", "warn"), ("user", "admin")]).await);
+    assert_eq!(changed, serde_json::json!(["heartbeat_interval", "log_level"]));
+
+    // 通用参数由命令执行流程处理，不作为配置项
+    let changed = json(&run(&plugin, "reconfigure", &[("log_level", "info"), ("timeout_ms", "5000"), ("operation_id", "reconfigure-1"), ("tenant", "default")]).await);
+    assert_eq!(changed, serde_json::json!(["log_level"]));
+
+    // 重新设置为当前值不算变化
+    let changed = json(&run(&plugin, "reconfigure", &[("log_level", "info"), ("heartbeat_interval", "10")]).await);
+    assert_eq!(changed, serde_json::json!([]));
+
+    let result = run(&plugin, "reconfigure", &[("server_host", "10.0.0.1"), ("user", "admin")]).await;
+    assert!(!result.is_success());
+    assert!(result.get_error_message().contains("server_host"));
+
+    let result = run(&plugin, "reconfigure", &[("heartbeat_interval", "0")]).await;
+    assert!(!result.is_success());
+}
+
+#[tokio::test]
+async fn create_key_rejects_incompatible_algorithm_and_key_type() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+
+    for (key_type, algorithm) in [("SYMMETRIC", "RSA-2048"), ("HMAC", "ED25519"), ("ASYMMETRIC_PRIVATE", "AES-256")] {
+        let result = run(&plugin, "create_key", &[("name", "k"), ("key_type", key_type), ("algorithm", algorithm)]).await;
+        assert!(!result.is_success(), "{} / {}", key_type, algorithm);
+        assert!(result.get_error_message().contains("not compatible"), "{}", result.get_error_message());
+    }
+
+    for (key_type, algorithm) in [("SYMMETRIC", "AES-256"), ("PASSWORD", "AES-256"), ("ASYMMETRIC_PRIVATE", "ECDSA")] {
+        let created = json(&run(&plugin, "create_key", &[("name", "k"), ("key_type", key_type), ("algorithm", algorithm)]).await);
+        assert!(created["id"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn derive_key_is_reproducible_from_password_and_salt() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let plugin = initialized(KeyManagementPlugin::with_security_module(security_module.clone())).await;
+    let salt = BASE64.encode([5u8; 16]);
+
+    let first = json(&run(&plugin, "derive_key", &[("name", "vault"), ("password", "correct horse"), ("salt", &salt)]).await);
+    let second = json(&run(&plugin, "derive_key", &[("name", "vault"), ("password", "correct horse"), ("salt", &salt)]).await);
+    let other = json(&run(&plugin, "derive_key", &[("name", "vault"), ("password", "battery staple"), ("salt", &salt)]).await);
+    assert_eq!(first["key_type"], "Symmetric");
+    assert_eq!(first["tags"]["kdf"], "argon2id");
+    assert_eq!(first["tags"]["kdf_salt"], salt.as_str());
+
+    let material = |created: &Value| {
+        let security_module = Arc::clone(&security_module);
+        let key_id = created["id"].as_str().unwrap().to_string();
+        async move { security_module.retrieve_key(&key_id).await.unwrap() }
+    };
+    assert_ne!(first["id"], second["id"]);
+    assert_eq!(material(&first).await, material(&second).await);
+    assert_ne!(material(&first).await, material(&other).await);
+
+    // 未指定盐时生成随机盐并记录在标签中
+    let salted = json(&run(&plugin, "derive_key", &[("name", "vault"), ("password", "correct horse")]).await);
+    assert_eq!(BASE64.decode(salted["tags"]["kdf_salt"].as_str().unwrap()).unwrap().len(), 16);
+    assert!(!run(&plugin, "derive_key", &[("name", "vault"), ("password", "")]).await.is_success());
+}
+
+#[tokio::test]
+async fn verify_password_matches_only_the_stored_password() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let stored = json(&run(&plugin, "derive_key", &[("name", "login"), ("password", "correct horse"), ("key_type", "PASSWORD")]).await);
+    assert_eq!(stored["key_type"], "Password");
+    let key_id = stored["id"].as_str().unwrap();
+
+    let correct = run(&plugin, "verify_password", &[("key_id", key_id), ("password", "correct horse"), ("user", "alice")]).await;
+    assert_eq!(json(&correct), serde_json::json!({ "match": true }));
+    assert!(!correct.get_result().contains("correct horse"));
+    let wrong = json(&run(&plugin, "verify_password", &[("key_id", key_id), ("password", "battery staple")]).await);
+    assert_eq!(wrong, serde_json::json!({ "match": false }));
+
+    // 审计日志记录结果但不记录口令
+    let history = json(&run(&plugin, "key_history", &[("key_id", key_id)]).await);
+    let verified: Vec<&Value> = history["events"].as_array().unwrap().iter().filter(|event| event["action"] == "VERIFY_PASSWORD").collect();
+    assert_eq!(verified.iter().map(|event| event["success"].as_bool().unwrap()).collect::<Vec<_>>(), vec![true, false]);
+    assert!(!history.to_string().contains("correct horse") && !history.to_string().contains("battery staple"));
+
+    let symmetric = json(&run(&plugin, "derive_key", &[("name", "vault"), ("password", "correct horse")]).await);
+    let result = run(&plugin, "verify_password", &[("key_id", symmetric["id"].as_str().unwrap()), ("password", "correct horse")]).await;
+    assert!(!result.is_success());
+}
+
+#[tokio::test]
+async fn password_key_below_kdf_policy_is_flagged_and_rehashed() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let weak = [("kdf_memory_kib", "19456"), ("kdf_iterations", "2"), ("kdf_parallelism", "1")];
+    let mut pairs = vec![("name", "legacy"), ("password", "hunter2"), ("key_type", "PASSWORD")];
+    pairs.extend(weak);
+    let key_id = json(&run(&plugin, "derive_key", &pairs).await)["id"].as_str().unwrap().to_string();
+
+    let report = json(&run(&plugin, "verify_kdf_params", &[]).await);
+    assert_eq!(report.as_array().unwrap().len(), 1);
+    assert_eq!(report[0]["key_id"], key_id.as_str());
+    assert_eq!(report[0]["kdf_memory_kib"], 19456);
+    assert_eq!(report[0]["needs_rehash"], true);
+
+    assert!(!run(&plugin, "rehash_password", &[("key_id", &key_id), ("password", "wrong")]).await.is_success());
+    let rehashed = json(&run(&plugin, "rehash_password", &[("key_id", &key_id), ("password", "hunter2")]).await);
+    assert_eq!(rehashed["version"], 2);
+    assert_eq!(rehashed["tags"]["kdf_memory_kib"], "65536");
+
+    let report = json(&run(&plugin, "verify_kdf_params", &[("key_id", &key_id)]).await);
+    assert_eq!(report[0]["needs_rehash"], false);
+    assert_eq!(json(&run(&plugin, "verify_password", &[("key_id", &key_id), ("password", "hunter2")]).await)["match"], true);
+    assert_eq!(json(&run(&plugin, "verify_password", &[("key_id", &key_id), ("password", "wrong")]).await)["match"], false);
+
+    // 不是口令密钥等无法校验的情况也记录失败的审计日志
+    let symmetric = json(&run(&plugin, "create_key", &[("name", "plain")]).await)["id"].as_str().unwrap().to_string();
+    let result = run(&plugin, "rehash_password", &[("key_id", &symmetric), ("password", "hunter2")]).await;
+    assert!(result.get_error_message().contains("not a password key"), "{}", result.get_error_message());
+    let history = json(&run(&plugin, "key_history", &[("key_id", &symmetric)]).await);
+    let rehashed: Vec<&Value> = history["events"].as_array().unwrap().iter().filter(|event| event["action"] == "REHASH_PASSWORD").collect();
+    assert_eq!(rehashed.len(), 1);
+    assert_eq!(rehashed[0]["success"], false);
+    assert_eq!(history["version"], 1);
+}
+
+#[tokio::test]
+async fn derive_key_with_custom_kdf_params_is_reproducible() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let plugin = initialized(KeyManagementPlugin::with_security_module(security_module.clone())).await;
+    let salt = BASE64.encode([9u8; 16]);
+    let minimum = [("kdf_memory_kib", "19456"), ("kdf_iterations", "2"), ("kdf_parallelism", "1")];
+
+    let mut pairs = vec![("name", "vault"), ("password", "pw"), ("salt", salt.as_str())];
+    pairs.extend_from_slice(&minimum);
+    let first = json(&run(&plugin, "derive_key", &pairs).await);
+    let second = json(&run(&plugin, "derive_key", &pairs).await);
+    assert_eq!(first["tags"]["kdf_memory_kib"], "19456");
+    assert_eq!(first["tags"]["kdf_iterations"], "2");
+    assert_eq!(first["tags"]["kdf_parallelism"], "1");
+
+    let first_material = security_module.retrieve_key(first["id"].as_str().unwrap()).await.unwrap();
+    let second_material = security_module.retrieve_key(second["id"].as_str().unwrap()).await.unwrap();
+    assert_eq!(first_material, second_material);
+
+    // 迭代次数不同派生出不同的密钥
+    let mut pairs = vec![("name", "vault"), ("password", "pw"), ("salt", salt.as_str())];
+    pairs.extend_from_slice(&[("kdf_memory_kib", "19456"), ("kdf_iterations", "3")]);
+    let other = json(&run(&plugin, "derive_key", &pairs).await);
+    let other_material = security_module.retrieve_key(other["id"].as_str().unwrap()).await.unwrap();
+    assert_ne!(first_material, other_material);
+}
+
+#[tokio::test]
+async fn kdf_params_below_minimum_are_rejected() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    for (key, value) in [("kdf_memory_kib", "1024"), ("kdf_iterations", "1"), ("kdf_parallelism", "0")] {
+        let result = run(&plugin, "derive_key", &[("name", "vault"), ("password", "pw"), (key, value)]).await;
+        assert!(!result.is_success(), "{}={}", key, value);
+    }
+
+    let mut config = PluginConfig::new();
+    config.add_config("kdf_iterations".to_string(), "1".to_string());
+    let mut plugin = KeyManagementPlugin::new();
+    assert!(!plugin.initialize(config).await);
+}
+
+// 临时 SQLite 数据库文件路径
+#[cfg(feature = "sqlite")]
+fn temp_db_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("password_manager_it_{}.db", uuid::Uuid::new_v4()))
+}
+
+#[cfg(feature = "sqlite")]
+async fn open_db(path: &std::path::Path) -> Arc<DbPersistence> {
+    Arc::new(DbPersistence::new(&format!("sqlite:{}?mode=rwc", path.display())).await.unwrap())
+}
+
+// 等待后台写入持久化存储
+async fn settle() {
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn backup_command_snapshots_the_database() {
+    let db_path = temp_db_path();
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(open_db(&db_path).await)).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "backed-up"), ("user", "alice")]).await);
+    settle().await;
+
+    let backup_path = temp_db_path();
+    let backup_str = backup_path.to_str().unwrap();
+    let result = run(&plugin, "backup", &[("path", backup_str), ("user", "admin")]).await;
+    assert!(result.is_success(), "{}", result.get_error_message());
+    assert_eq!(result.get_result(), backup_str);
+
+    let backup = open_db(&backup_path).await;
+    let restored = backup.load_key_metadata(created["id"].as_str().unwrap()).await.unwrap();
+    assert_eq!(restored.name, "backed-up");
+
+    assert!(!run(&plugin, "backup", &[]).await.is_success());
+    let no_persistence = initialized(KeyManagementPlugin::new()).await;
+    assert!(!run(&no_persistence, "backup", &[("path", backup_str)]).await.is_success());
+
+    let _ = std::fs::remove_file(db_path);
+    let _ = std::fs::remove_file(backup_path);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn restore_command_requires_confirmation_and_reloads_keys() {
+    let db_path = temp_db_path();
+    let persistence = open_db(&db_path).await;
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(persistence.clone())).await;
+    let kept = json(&run(&plugin, "create_key", &[("name", "kept"), ("user", "alice")]).await);
+    settle().await;
+
+    let backup_path = temp_db_path();
+    let backup_str = backup_path.to_str().unwrap();
+    assert!(run(&plugin, "backup", &[("path", backup_str)]).await.is_success());
+    json(&run(&plugin, "create_key", &[("name", "added-after-backup"), ("user", "alice")]).await);
+    settle().await;
+
+    let result = run(&plugin, "restore", &[("path", backup_str)]).await;
+    assert!(!result.is_success());
+    assert!(result.get_error_message().contains("confirm=true"));
+    assert_eq!(persistence.list_key_metadata(None).await.unwrap().len(), 2);
+
+    let result = run(&plugin, "restore", &[("path", backup_str), ("confirm", "true"), ("user", "admin")]).await;
+    assert!(result.is_success(), "{}", result.get_error_message());
+    assert_eq!(result.get_result(), "1");
+    let restored = persistence.list_key_metadata(None).await.unwrap();
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].id, kept["id"].as_str().unwrap());
+
+    let _ = std::fs::remove_file(db_path);
+    let _ = std::fs::remove_file(backup_path);
+}
+
+// 不依赖 sqlite 特性，`--no-default-features` 下插件只使用文件存储也能正常工作
+#[tokio::test]
+async fn plugin_persists_keys_with_file_persistence_only() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FilePersistence::new(dir.to_str().unwrap()));
+    let store = SharedKeyStore::new();
+
+    let plugin = initialized(
+        KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::with_shared_store(store.clone())))
+            .with_persistence(persistence.clone()),
+    ).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "file-backed"), ("user", "alice")]).await);
+    let key_id = created["id"].as_str().unwrap();
+    settle().await;
+    assert_eq!(persistence.load_key_metadata(key_id).await.unwrap().name, "file-backed");
+
+    // 新的插件实例启动时从文件存储加载密钥
+    let mut reopened = initialized(
+        KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::with_shared_store(store)))
+            .with_persistence(Arc::new(FilePersistence::new(dir.to_str().unwrap()))),
+    ).await;
+    assert!(reopened.start().await);
+    let loaded = json(&run(&reopened, "get_key", &[("key_id", key_id)]).await);
+    assert_eq!(loaded["name"], "file-backed");
+    assert!(reopened.stop().await);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// 关闭 grpc 特性时插件不连接服务器，启动、执行命令和停止都只在本地完成
+#[cfg(not(feature = "grpc"))]
+#[tokio::test]
+async fn plugin_runs_locally_without_grpc() {
+    let mut plugin = initialized(KeyManagementPlugin::new()).await;
+    assert!(plugin.start().await);
+
+    let created = json(&run(&plugin, "create_key", &[("name", "offline"), ("user", "alice")]).await);
+    let loaded = json(&run(&plugin, "get_key", &[("key_id", created["id"].as_str().unwrap())]).await);
+    assert_eq!(loaded["name"], "offline");
+
+    assert!(plugin.stop().await);
+}
+
+#[tokio::test]
+async fn plugins_sharing_a_key_store_see_each_others_keys() {
+    let store = SharedKeyStore::new();
+    let first_module = Arc::new(SoftwareSecurityModule::with_shared_store(store.clone()));
+    let second_module = Arc::new(SoftwareSecurityModule::with_shared_store(store));
+    let first = initialized(KeyManagementPlugin::with_security_module(first_module.clone())).await;
+    let _second = initialized(KeyManagementPlugin::with_security_module(second_module.clone())).await;
+
+    let created = json(&run(&first, "create_key", &[("name", "shared")]).await);
+    let key_id = created["id"].as_str().unwrap();
+    let material = first_module.retrieve_key(key_id).await.unwrap();
+    assert_eq!(second_module.retrieve_key(key_id).await.unwrap(), material);
+
+    let ciphertext = first_module.encrypt_data(key_id, b"secret", &[]).await.unwrap();
+    assert_eq!(second_module.decrypt_data(key_id, &ciphertext, &[]).await.unwrap(), b"secret");
+
+    // 独立构造的模块看不到共享存储中的密钥
+    assert!(SoftwareSecurityModule::new().retrieve_key(key_id).await.is_err());
+}
+
+/// 生成密钥时故意变慢的安全模块，其余操作交给 MockHSM
+struct SlowHsm(Duration);
+
+#[async_trait]
+impl SecurityModuleInterface for SlowHsm {
+    async fn generate_key(&self, algorithm: KeyAlgorithm) -> Result<Vec<u8>, String> {
+        tokio::time::sleep(self.0).await;
+        MockHSM.generate_key(algorithm).await
+    }
+
+    async fn store_key(&self, key_id: &str, key_data: &[u8]) -> Result<(), String> {
+        MockHSM.store_key(key_id, key_data).await
+    }
+
+    async fn retrieve_key(&self, key_id: &str) -> Result<Vec<u8>, String> {
+        MockHSM.retrieve_key(key_id).await
+    }
+
+    async fn delete_key(&self, key_id: &str) -> Result<(), String> {
+        MockHSM.delete_key(key_id).await
+    }
+
+    async fn sign_data(&self, key_id: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+        MockHSM.sign_data(key_id, data).await
+    }
+
+    async fn verify_signature(&self, key_id: &str, data: &[u8], signature: &[u8]) -> Result<bool, String> {
+        MockHSM.verify_signature(key_id, data, signature).await
+    }
+
+    async fn encrypt_data(&self, key_id: &str, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+        MockHSM.encrypt_data(key_id, data, aad).await
+    }
+
+    async fn decrypt_data(&self, key_id: &str, encrypted_data: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+        MockHSM.decrypt_data(key_id, encrypted_data, aad).await
+    }
+
+    async fn derive_key(&self, password: &[u8], salt: &[u8], algorithm: KeyAlgorithm, params: &KdfParams) -> Result<Vec<u8>, String> {
+        MockHSM.derive_key(password, salt, algorithm, params).await
+    }
+
+    async fn get_public_key(&self, key_id: &str, format: PublicKeyFormat) -> Result<Vec<u8>, String> {
+        MockHSM.get_public_key(key_id, format).await
+    }
+
+    async fn rewrap_key(&self, key_id: &str) -> Result<bool, String> {
+        MockHSM.rewrap_key(key_id).await
+    }
+}
+
+#[tokio::test]
+async fn slow_command_is_timed_and_counted_as_slow() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SlowHsm(Duration::from_millis(50))))).await;
+    json(&run(&plugin, "reconfigure", &[("slow_command_threshold_ms", "20"), ("user", "admin")]).await);
+
+    let result = run(&plugin, "create_key", &[("name", "slow")]).await;
+    assert!(result.is_success(), "{}", result.get_error_message());
+    assert!(result.get_elapsed_ms().unwrap() >= 50);
+
+    let stats = plugin.metrics().command_stats("create_key").unwrap();
+    assert_eq!((stats.count, stats.slow_count), (1, 1));
+    assert!(stats.max_ms >= 50);
+
+    // 阈值为 0 时不再判定为慢命令
+    json(&run(&plugin, "reconfigure", &[("slow_command_threshold_ms", "0"), ("user", "admin")]).await);
+    json(&run(&plugin, "create_key", &[("name", "slow")]).await);
+    let stats = plugin.metrics().command_stats("create_key").unwrap();
+    assert_eq!((stats.count, stats.slow_count), (2, 1));
+}
+
+#[tokio::test]
+async fn slow_command_times_out_and_is_recorded() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SlowHsm(Duration::from_secs(5))))).await;
+
+    let result = run(&plugin, "create_key", &[("name", "stuck"), ("timeout_ms", "50")]).await;
+    assert!(!result.is_success());
+    assert_eq!(result.get_error_message(), "Command timed out after 50 ms");
+    assert_eq!(result.get_error_code(), Some(ErrorCode::Timeout));
+    assert!(result.get_elapsed_ms().unwrap() < 5000);
+    assert_eq!(plugin.metrics().command_stats("create_key").unwrap().timeout_count, 1);
+
+    // 配置的超时对所有命令生效，参数 timeout_ms 优先
+    json(&run(&plugin, "reconfigure", &[("command_timeout_ms", "50"), ("user", "admin")]).await);
+    assert!(!run(&plugin, "create_key", &[("name", "stuck")]).await.is_success());
+    assert_eq!(plugin.metrics().command_stats("create_key").unwrap().timeout_count, 2);
+    assert!(run(&plugin, "key_stats", &[("timeout_ms", "0")]).await.is_success());
+    assert!(!run(&plugin, "key_stats", &[("timeout_ms", "soon")]).await.is_success());
+}
+
+#[tokio::test]
+async fn running_operation_can_be_listed_and_cancelled() {
+    let plugin = Arc::new(initialized(KeyManagementPlugin::with_security_module(Arc::new(SlowHsm(Duration::from_secs(5))))).await);
+    let running = {
+        let plugin = Arc::clone(&plugin);
+        tokio::spawn(async move { run(&plugin, "create_key", &[("name", "long"), ("operation_id", "op1"), ("user", "alice")]).await })
+    };
+
+    let mut listed = Value::Null;
+    for _ in 0..50 {
+        listed = json(&run(&plugin, "list_operations", &[]).await);
+        if !listed.as_array().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(listed[0]["id"], "op1");
+    assert_eq!(listed[0]["operation_type"], "create_key");
+    assert_eq!(listed[0]["user"], "alice");
+
+    // 同一ID不能重复登记
+    let duplicate = run(&plugin, "key_stats", &[("operation_id", "op1")]).await;
+    assert_eq!(duplicate.get_error_message(), "Operation already running: op1");
+
+    assert_eq!(run(&plugin, "cancel_operation", &[("operation_id", "op1")]).await.get_result(), "op1");
+    let result = tokio::time::timeout(Duration::from_secs(1), running).await.unwrap().unwrap();
+    assert_eq!(result.get_error_message(), "Operation cancelled: op1");
+    assert_eq!(result.get_error_code(), Some(ErrorCode::Cancelled));
+    assert_eq!(json(&run(&plugin, "list_operations", &[]).await), serde_json::json!([]));
+    assert!(!run(&plugin, "cancel_operation", &[("operation_id", "op1")]).await.is_success());
+}
+
+#[tokio::test]
+async fn drain_rejects_new_commands_and_lets_running_command_finish() {
+    let plugin = Arc::new(initialized(KeyManagementPlugin::with_security_module(Arc::new(SlowHsm(Duration::from_millis(200))))).await);
+    let drained = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&drained);
+    plugin.on_drained(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let running = {
+        let plugin = Arc::clone(&plugin);
+        tokio::spawn(async move { run(&plugin, "create_key", &[("name", "in-flight")]).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let status = json(&run(&plugin, "drain", &[]).await);
+    assert_eq!(status["in_flight"], 1);
+    assert_eq!(status["drained"], false);
+    assert!(!plugin.is_drained());
+
+    let rejected = run(&plugin, "create_key", &[("name", "late")]).await;
+    assert!(!rejected.is_success());
+    assert_eq!(rejected.get_error_code(), Some(ErrorCode::Draining));
+
+    let finished = tokio::time::timeout(Duration::from_secs(1), running).await.unwrap().unwrap();
+    assert!(finished.is_success(), "{}", finished.get_error_message());
+    settle().await;
+    assert!(plugin.is_drained());
+    assert_eq!(drained.load(Ordering::SeqCst), 1);
+    assert_eq!(json(&run(&plugin, "drain", &[]).await)["drained"], true);
+    assert_eq!(drained.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn create_key_pair_links_private_and_public_halves() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let plugin = initialized(KeyManagementPlugin::with_security_module(security_module.clone())).await;
+
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "signer"), ("algorithm", "ED25519"), ("tag.team", "release")]).await);
+    let private_id = pair["private_key"]["id"].as_str().unwrap();
+    let public_id = pair["public_key"]["id"].as_str().unwrap();
+    assert_ne!(private_id, public_id);
+
+    let private_key = json(&run(&plugin, "get_key", &[("key_id", private_id)]).await);
+    let public_key = json(&run(&plugin, "get_key", &[("key_id", public_id)]).await);
+    assert_eq!(private_key["key_type"], "AsymmetricPrivate");
+    assert_eq!(public_key["key_type"], "AsymmetricPublic");
+    assert!(private_key["tags"]["pair_id"].is_string());
+    assert_eq!(private_key["tags"]["pair_id"], public_key["tags"]["pair_id"]);
+    assert_eq!(public_key["tags"]["team"], "release");
+
+    // 公钥一半返回公钥内容，私钥一半不返回
+    let expected = BASE64.encode(security_module.get_public_key(private_id, PublicKeyFormat::Der).await.unwrap());
+    assert_eq!(public_key["public_key"], expected.as_str());
+    assert!(private_key.get("public_key").is_none());
+}
+
+#[tokio::test]
+async fn each_half_of_a_key_pair_resolves_to_the_other() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "signer"), ("algorithm", "ED25519")]).await);
+    let private_id = pair["private_key"]["id"].as_str().unwrap();
+    let public_id = pair["public_key"]["id"].as_str().unwrap();
+
+    assert_eq!(plugin.get_paired_key(private_id).await.unwrap().unwrap().id, public_id);
+    let paired = json(&run(&plugin, "get_paired_key", &[("key_id", public_id)]).await);
+    assert_eq!(paired["id"], private_id);
+
+    // 对称密钥没有另一半
+    let symmetric = json(&run(&plugin, "create_key", &[("name", "symmetric")]).await);
+    let symmetric_id = symmetric["id"].as_str().unwrap();
+    assert!(plugin.get_paired_key(symmetric_id).await.unwrap().is_none());
+    assert!(json(&run(&plugin, "get_paired_key", &[("key_id", symmetric_id)]).await).is_null());
+    assert_eq!(run(&plugin, "get_paired_key", &[("key_id", "missing")]).await.get_error_message(), "Key not found");
+}
+
+#[tokio::test]
+async fn create_key_pair_rejects_symmetric_algorithm() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+
+    let result = run(&plugin, "create_key_pair", &[("name", "pair"), ("algorithm", "AES-256")]).await;
+    assert!(!result.is_success());
+    assert!(result.get_error_message().contains("does not support key pairs"));
+
+    let result = run(&plugin, "get_key", &[("key_id", "missing")]).await;
+    assert_eq!(result.get_error_message(), "Key not found");
+}
+
+async fn exported_public_key(plugin: &KeyManagementPlugin, key_id: &str, format: &str) -> String {
+    let result = run(plugin, "get_public_key", &[("key_id", key_id), ("format", format)]).await;
+    assert!(result.is_success(), "{}", result.get_error_message());
+    result.get_result().to_string()
+}
+
+#[tokio::test]
+async fn rsa_public_key_export_parses_as_pem_and_der() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let plugin = initialized(KeyManagementPlugin::with_security_module(security_module.clone())).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "rsa"), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "RSA-2048")]).await);
+    let key_id = created["id"].as_str().unwrap();
+
+    let pem = exported_public_key(&plugin, key_id, "PEM").await;
+    assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----"), "{}", pem);
+    let from_pem = RsaPublicKey::from_public_key_pem(&pem).unwrap();
+    let der = BASE64.decode(exported_public_key(&plugin, key_id, "DER").await).unwrap();
+    let from_der = RsaPublicKey::from_public_key_der(&der).unwrap();
+    assert_eq!(from_pem, from_der);
+
+    // 导出的公钥可以验证该密钥的签名
+    let signature = security_module.sign_data(key_id, b"payload").await.unwrap();
+    let pkcs1 = from_der.to_pkcs1_der().unwrap();
+    UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, pkcs1.as_bytes())
+        .verify(b"payload", &signature)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn ed25519_public_key_export_parses_as_pem_and_der() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let plugin = initialized(KeyManagementPlugin::with_security_module(security_module.clone())).await;
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "ed"), ("algorithm", "ED25519")]).await);
+    let private_id = pair["private_key"]["id"].as_str().unwrap();
+    let public_id = pair["public_key"]["id"].as_str().unwrap();
+
+    let pem = exported_public_key(&plugin, private_id, "pem").await;
+    let (label, pem_der) = der::pem::decode_vec(pem.as_bytes()).unwrap();
+    assert_eq!(label, "PUBLIC KEY");
+    let der = BASE64.decode(exported_public_key(&plugin, private_id, "der").await).unwrap();
+    assert_eq!(pem_der, der);
+
+    // 公钥一半导出的内容与私钥计算出的一致
+    assert_eq!(exported_public_key(&plugin, public_id, "PEM").await, pem);
+
+    let spki = SubjectPublicKeyInfoRef::from_der(&der).unwrap();
+    assert_eq!(spki.algorithm.oid.to_string(), "1.3.101.112");
+    assert!(spki.algorithm.parameters.is_none());
+    let raw = spki.subject_public_key.raw_bytes();
+    assert_eq!(raw.len(), 32);
+
+    let signature = security_module.sign_data(private_id, b"payload").await.unwrap();
+    UnparsedPublicKey::new(&signature::ED25519, raw).verify(b"payload", &signature).unwrap();
+}
+
+#[tokio::test]
+async fn public_key_export_rejects_symmetric_keys_and_unknown_formats() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "aes")]).await);
+    let key_id = created["id"].as_str().unwrap();
+
+    let result = run(&plugin, "get_public_key", &[("key_id", key_id)]).await;
+    assert!(!result.is_success());
+    assert!(result.get_error_message().contains("has no public key"), "{}", result.get_error_message());
+
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "ec"), ("algorithm", "ECDSA")]).await);
+    let result = run(&plugin, "get_public_key", &[("key_id", pair["private_key"]["id"].as_str().unwrap()), ("format", "JWK")]).await;
+    assert_eq!(result.get_error_message(), "Invalid public key format: JWK");
+}
+
+// 依次解码 DER 结构中的各个元素
+fn der_children(any: AnyRef<'_>) -> Vec<AnyRef<'_>> {
+    let mut reader = SliceReader::new(any.value()).unwrap();
+    let mut children = Vec::new();
+    while !reader.is_finished() {
+        children.push(reader.decode::<AnyRef>().unwrap());
+    }
+    children
+}
+
+// 解析 X.509 Name，返回 (属性OID, 属性值) 列表
+fn name_attributes(name: AnyRef<'_>) -> Vec<(String, String)> {
+    assert_eq!(name.tag(), Tag::Sequence);
+    der_children(name)
+        .into_iter()
+        .map(|rdn| {
+            assert_eq!(rdn.tag(), Tag::Set);
+            let attribute = der_children(rdn);
+            assert_eq!(attribute.len(), 1);
+            let fields = der_children(attribute[0]);
+            let oid = fields[0].decode_as::<ObjectIdentifier>().unwrap().to_string();
+            let value = match fields[1].tag() {
+                Tag::PrintableString => fields[1].decode_as::<PrintableStringRef>().unwrap().to_string(),
+                _ => fields[1].decode_as::<Utf8StringRef>().unwrap().to_string(),
+            };
+            (oid, value)
+        })
+        .collect()
+}
+
+// 拆分签名结构（证书请求或证书）为 待签名数据、签名算法、签名
+fn signed_parts(der: &[u8]) -> (Vec<u8>, String, Vec<u8>) {
+    let outer = AnyRef::from_der(der).unwrap();
+    assert_eq!(outer.to_der().unwrap(), der);
+    let parts = der_children(outer);
+    assert_eq!(parts.len(), 3);
+    let algorithm_der = parts[1].to_der().unwrap();
+    let algorithm = AlgorithmIdentifierRef::from_der(&algorithm_der).unwrap();
+    let signature = parts[2].decode_as::<BitStringRef>().unwrap();
+    (parts[0].to_der().unwrap(), algorithm.oid.to_string(), signature.raw_bytes().to_vec())
+}
+
+// 用 SubjectPublicKeyInfo 中的公钥校验签名
+fn verify_with_spki(spki_der: &[u8], message: &[u8], signature_bytes: &[u8]) {
+    let spki = SubjectPublicKeyInfoRef::from_der(spki_der).unwrap();
+    let public_key = spki.subject_public_key.raw_bytes();
+    let algorithm: &dyn signature::VerificationAlgorithm = match spki.algorithm.oid.to_string().as_str() {
+        "1.3.101.112" => &signature::ED25519,
+        "1.2.840.10045.2.1" => &signature::ECDSA_P256_SHA256_ASN1,
+        oid => panic!("未预期的公钥算法: {}", oid),
+    };
+    UnparsedPublicKey::new(algorithm, public_key).verify(message, signature_bytes).unwrap();
+}
+
+fn pem_body(pem: &str, expected_label: &str) -> Vec<u8> {
+    let (label, der) = der::pem::decode_vec(pem.as_bytes()).unwrap();
+    assert_eq!(label, expected_label);
+    der
+}
+
+#[tokio::test]
+async fn generated_csr_decodes_with_subject_and_valid_signature() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+
+    for (algorithm, signature_oid) in [("ED25519", "1.3.101.112"), ("ECDSA", "1.2.840.10045.4.3.2")] {
+        let pair = json(&run(&plugin, "create_key_pair", &[("name", "tls"), ("algorithm", algorithm)]).await);
+        let key_id = pair["private_key"]["id"].as_str().unwrap();
+
+        let result = run(&plugin, "generate_csr", &[
+            ("key_id", key_id),
+            ("CN", "api.example.com"),
+            ("O", "Example 公司"),
+            ("C", "CN"),
+        ]).await;
+        assert!(result.is_success(), "{}", result.get_error_message());
+        let csr = pem_body(result.get_result(), "CERTIFICATE REQUEST");
+
+        let (request_info, algorithm_oid, signature_bytes) = signed_parts(&csr);
+        assert_eq!(algorithm_oid, signature_oid);
+
+        let fields = der_children(AnyRef::from_der(&request_info).unwrap());
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields[0].decode_as::<u8>().unwrap(), 0);
+        assert_eq!(name_attributes(fields[1]), vec![
+            ("2.5.4.6".to_string(), "CN".to_string()),
+            ("2.5.4.10".to_string(), "Example 公司".to_string()),
+            ("2.5.4.3".to_string(), "api.example.com".to_string()),
+        ]);
+        assert_eq!(fields[3].tag().octet(), 0xa0);
+
+        // 请求中的公钥就是该密钥的公钥，签名可以用它校验
+        let spki = fields[2].to_der().unwrap();
+        let exported = BASE64.decode(exported_public_key(&plugin, key_id, "DER").await).unwrap();
+        assert_eq!(spki, exported);
+        verify_with_spki(&spki, &request_info, &signature_bytes);
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn generate_csr_requires_active_asymmetric_private_key() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let db_path = temp_db_path();
+    let persistence = open_db(&db_path).await;
+    let plugin = initialized(KeyManagementPlugin::with_security_module(security_module.clone()).with_persistence(persistence.clone())).await;
+
+    let symmetric = json(&run(&plugin, "create_key", &[("name", "aes")]).await);
+    let result = run(&plugin, "generate_csr", &[("key_id", symmetric["id"].as_str().unwrap()), ("CN", "a")]).await;
+    assert!(result.get_error_message().contains("asymmetric private key is required"), "{}", result.get_error_message());
+
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "ed"), ("algorithm", "ED25519")]).await);
+    let public_id = pair["public_key"]["id"].as_str().unwrap();
+    let result = run(&plugin, "generate_csr", &[("key_id", public_id), ("CN", "a")]).await;
+    assert!(result.get_error_message().contains("asymmetric private key is required"), "{}", result.get_error_message());
+
+    let private_id = pair["private_key"]["id"].as_str().unwrap();
+    let result = run(&plugin, "generate_csr", &[("key_id", private_id)]).await;
+    assert_eq!(result.get_error_message(), "Missing parameter: CN");
+    let result = run(&plugin, "generate_csr", &[("key_id", private_id), ("CN", "a"), ("C", "China")]).await;
+    assert_eq!(result.get_error_message(), "Invalid country code: China");
+
+    // 通过备份恢复载入一个已暂停的密钥
+    settle().await;
+    let mut suspended = persistence.load_key_metadata(private_id).await.unwrap();
+    suspended.status = KeyStatus::Suspended;
+    persistence.save_key_metadata(&suspended).await.unwrap();
+    let backup_path = temp_db_path();
+    let backup = backup_path.to_str().unwrap();
+    assert!(run(&plugin, "backup", &[("path", backup)]).await.is_success());
+    assert!(run(&plugin, "restore", &[("path", backup), ("confirm", "true")]).await.is_success());
+
+    let result = run(&plugin, "generate_csr", &[("key_id", private_id), ("CN", "a")]).await;
+    assert!(result.get_error_message().contains("not active"), "{}", result.get_error_message());
+
+    let _ = std::fs::remove_file(db_path);
+    let _ = std::fs::remove_file(backup_path);
+}
+
+// 解码 UTCTime 或 GeneralizedTime 为 Unix 时间戳（秒）
+fn der_time(any: AnyRef<'_>) -> (Tag, i64) {
+    let since_epoch = match any.tag() {
+        Tag::UtcTime => any.decode_as::<UtcTime>().unwrap().to_unix_duration(),
+        Tag::GeneralizedTime => any.decode_as::<GeneralizedTime>().unwrap().to_unix_duration(),
+        tag => panic!("未预期的时间类型: {}", tag),
+    };
+    (any.tag(), since_epoch.as_secs() as i64)
+}
+
+#[tokio::test]
+async fn self_signed_cert_decodes_with_subject_validity_and_signature() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "tls"), ("algorithm", "ECDSA")]).await);
+    let key_id = pair["private_key"]["id"].as_str().unwrap();
+
+    let not_before = "2049-12-01T08:30:15Z";
+    let result = run(&plugin, "generate_self_signed_cert", &[
+        ("key_id", key_id),
+        ("CN", "self.example.com"),
+        ("OU", "运维"),
+        ("not_before", not_before),
+        ("validity_days", "90"),
+    ]).await;
+    assert!(result.is_success(), "{}", result.get_error_message());
+    let certificate = result.get_result().to_string();
+    let (tbs, algorithm_oid, signature_bytes) = signed_parts(&pem_body(&certificate, "CERTIFICATE"));
+    assert_eq!(algorithm_oid, "1.2.840.10045.4.3.2");
+
+    let fields = der_children(AnyRef::from_der(&tbs).unwrap());
+    assert_eq!(fields.len(), 7);
+    assert_eq!(fields[0].tag().octet(), 0xa0);
+    assert_eq!(AnyRef::from_der(fields[0].value()).unwrap().decode_as::<u8>().unwrap(), 2);
+    let inner_algorithm = fields[2].to_der().unwrap();
+    assert_eq!(AlgorithmIdentifierRef::from_der(&inner_algorithm).unwrap().oid.to_string(), algorithm_oid);
+
+    // 自签名证书的颁发者与主题相同
+    let subject = vec![
+        ("2.5.4.11".to_string(), "运维".to_string()),
+        ("2.5.4.3".to_string(), "self.example.com".to_string()),
+    ];
+    assert_eq!(name_attributes(fields[3]), subject);
+    assert_eq!(name_attributes(fields[5]), subject);
+
+    // 2050 年之前用 UTCTime，之后用 GeneralizedTime
+    let validity = der_children(fields[4]);
+    let start = chrono::DateTime::parse_from_rfc3339(not_before).unwrap().timestamp();
+    assert_eq!(der_time(validity[0]), (Tag::UtcTime, start));
+    assert_eq!(der_time(validity[1]), (Tag::GeneralizedTime, start + 90 * 86400));
+
+    let spki = fields[6].to_der().unwrap();
+    verify_with_spki(&spki, &tbs, &signature_bytes);
+
+    // 证书保存在密钥上，可以通过 get_key 取回
+    let key = json(&run(&plugin, "get_key", &[("key_id", key_id)]).await);
+    assert_eq!(key["certificate"], certificate.as_str());
+}
+
+#[tokio::test]
+async fn self_signed_cert_defaults_to_one_year_from_now() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "tls"), ("algorithm", "ED25519")]).await);
+    let key_id = pair["private_key"]["id"].as_str().unwrap();
+
+    let before = chrono::Utc::now().timestamp();
+    let result = run(&plugin, "generate_self_signed_cert", &[("key_id", key_id), ("CN", "default")]).await;
+    let after = chrono::Utc::now().timestamp();
+    assert!(result.is_success(), "{}", result.get_error_message());
+
+    let (tbs, _, _) = signed_parts(&pem_body(result.get_result(), "CERTIFICATE"));
+    let fields = der_children(AnyRef::from_der(&tbs).unwrap());
+    let validity = der_children(fields[4]);
+    let (_, start) = der_time(validity[0]);
+    let (_, end) = der_time(validity[1]);
+    assert!(before <= start && start <= after);
+    assert_eq!(end - start, 365 * 86400);
+
+    for days in ["0", "-1", "soon"] {
+        let result = run(&plugin, "generate_self_signed_cert", &[("key_id", key_id), ("CN", "x"), ("validity_days", days)]).await;
+        assert_eq!(result.get_error_message(), format!("Invalid validity_days: {}", days));
+    }
+    let result = run(&plugin, "generate_self_signed_cert", &[("key_id", key_id), ("CN", "x"), ("not_before", "yesterday")]).await;
+    assert!(result.get_error_message().starts_with("Invalid not_before"));
+}
+
+fn in_days(days: i64) -> String {
+    (chrono::Utc::now() + chrono::Duration::days(days)).to_rfc3339()
+}
+
+#[tokio::test]
+async fn expiry_hook_fires_once_per_threshold_and_expiration() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    let fired: Arc<Mutex<Vec<(u32, String)>>> = Arc::new(Mutex::new(Vec::new()));
+    for days_before in [30, 7, 1] {
+        let fired = Arc::clone(&fired);
+        plugin.on_key_expiring(days_before, move |metadata| {
+            fired.lock().unwrap().push((days_before, metadata.name.clone()));
+        });
+    }
+
+    json(&run(&plugin, "create_key", &[("name", "soon"), ("expiration_date", &in_days(5))]).await);
+    json(&run(&plugin, "create_key", &[("name", "later"), ("expiration_date", &in_days(20))]).await);
+    json(&run(&plugin, "create_key", &[("name", "never")]).await);
+
+    assert_eq!(plugin.sweep_expirations().await, 0);
+    let mut first = fired.lock().unwrap().clone();
+    first.sort();
+    assert_eq!(first, vec![
+        (7, "soon".to_string()),
+        (30, "later".to_string()),
+        (30, "soon".to_string()),
+    ]);
+
+    // 同一过期时间下再次检查不会重复通知
+    plugin.sweep_expirations().await;
+    plugin.sweep_expirations().await;
+    assert_eq!(fired.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn sweep_marks_past_due_keys_expired_without_notifying() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    let calls = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&calls);
+    plugin.on_key_expiring(7, move |_| *counter.lock().unwrap() += 1);
+
+    let expired = json(&run(&plugin, "create_key", &[("name", "old"), ("expiration_date", &in_days(-1))]).await);
+    assert_eq!(plugin.sweep_expirations().await, 1);
+    assert_eq!(plugin.sweep_expirations().await, 0);
+    assert_eq!(*calls.lock().unwrap(), 0);
+
+    let key = json(&run(&plugin, "get_key", &[("key_id", expired["id"].as_str().unwrap())]).await);
+    assert_eq!(key["status"], "Expired");
+
+    let result = run(&plugin, "create_key", &[("name", "bad"), ("expiration_date", "next week")]).await;
+    assert!(result.get_error_message().contains("Invalid expiration_date"), "{}", result.get_error_message());
+}
+
+fn start_time() -> chrono::DateTime<chrono::Utc> {
+    chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2030, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn timestamp(value: &Value) -> chrono::DateTime<chrono::Utc> {
+    value.as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn advancing_mock_clock_expires_key() {
+    let clock = MockClock::new(start_time());
+    let plugin = initialized(KeyManagementPlugin::new().with_clock(Arc::new(clock.clone()))).await;
+
+    let created = json(&run(&plugin, "create_key", &[("name", "timed"), ("expiration_date", "2030-01-01T01:00:00Z")]).await);
+    let key_id = created["id"].as_str().unwrap().to_string();
+    assert_eq!(timestamp(&created["created_at"]), start_time());
+
+    // 到期前一秒不会被标记
+    clock.advance(chrono::Duration::minutes(59) + chrono::Duration::seconds(59));
+    assert_eq!(plugin.sweep_expirations().await, 0);
+
+    // 到达过期时间后被标记为 Expired，更新时间取自时钟
+    clock.advance(chrono::Duration::seconds(1));
+    assert_eq!(plugin.sweep_expirations().await, 1);
+    let expired = json(&run(&plugin, "get_key", &[("key_id", &key_id)]).await);
+    assert_eq!(expired["status"], "Expired");
+    assert_eq!(timestamp(&expired["updated_at"]), start_time() + chrono::Duration::hours(1));
+    assert_eq!(plugin.sweep_expirations().await, 0);
+}
+
+#[tokio::test]
+async fn key_validity_reports_remaining_time() {
+    let clock = MockClock::new(start_time());
+    let plugin = initialized(KeyManagementPlugin::new().with_clock(Arc::new(clock.clone()))).await;
+    let expiring = json(&run(&plugin, "create_key", &[("name", "expiring"), ("expiration_date", "2030-01-01T01:00:00Z")]).await)["id"]
+        .as_str().unwrap().to_string();
+    let permanent = json(&run(&plugin, "create_key", &[("name", "permanent")]).await)["id"].as_str().unwrap().to_string();
+
+    let validity = json(&run(&plugin, "key_validity", &[("key_id", &expiring)]).await);
+    assert_eq!(timestamp(&validity["expires_at"]), start_time() + chrono::Duration::hours(1));
+    assert_eq!(validity["seconds_remaining"], 3600);
+    assert_eq!(validity["is_expired"], false);
+
+    let validity = json(&run(&plugin, "key_validity", &[("key_id", &permanent)]).await);
+    assert!(validity["expires_at"].is_null());
+    assert!(validity["seconds_remaining"].is_null());
+    assert_eq!(validity["is_expired"], false);
+
+    // 过期后剩余时间为负数，标记为已过期后仍可查询
+    clock.advance(chrono::Duration::hours(2));
+    assert_eq!(plugin.sweep_expirations().await, 1);
+    let validity = json(&run(&plugin, "key_validity", &[("key_id", &expiring)]).await);
+    assert_eq!(validity["seconds_remaining"], -3600);
+    assert_eq!(validity["is_expired"], true);
+
+    assert!(!run(&plugin, "key_validity", &[("key_id", "missing")]).await.is_success());
+}
+
+#[tokio::test]
+async fn list_unused_keys_returns_keys_not_used_within_window() {
+    let clock = MockClock::new(start_time());
+    let persistence = Arc::new(MemoryPersistence::new());
+    let plugin = initialized(KeyManagementPlugin::new().with_clock(Arc::new(clock.clone())).with_persistence(persistence.clone())).await;
+    let used = json(&run(&plugin, "create_key", &[("name", "used")]).await)["id"].as_str().unwrap().to_string();
+    let unused = json(&run(&plugin, "create_key", &[("name", "unused")]).await)["id"].as_str().unwrap().to_string();
+
+    clock.advance(chrono::Duration::days(3));
+    let data = BASE64.encode(b"touch");
+    assert!(run(&plugin, "encrypt", &[("key_id", &used), ("data", &data)]).await.is_success());
+    settle().await;
+    assert_eq!(persistence.load_key_metadata(&used).await.unwrap().last_used_at, Some(start_time() + chrono::Duration::days(3)));
+
+    let listed = json(&run(&plugin, "list_unused_keys", &[("since_days", "1")]).await);
+    let ids: Vec<&str> = listed.as_array().unwrap().iter().map(|key| key["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec![unused.as_str()]);
+
+    // 超出时间窗口后使用过的密钥也算未使用
+    clock.advance(chrono::Duration::days(2));
+    assert_eq!(plugin.list_unused_keys(1).await.unwrap().len(), 2);
+    assert_eq!(run(&plugin, "list_unused_keys", &[("since_days", "-1")]).await.get_error_code(), Some(ErrorCode::InvalidParams));
+}
+
+#[tokio::test]
+async fn signing_advances_last_used_at_and_debounces_persistence() {
+    let clock = MockClock::new(start_time());
+    let persistence = Arc::new(MemoryPersistence::new());
+    let plugin = KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))
+        .with_clock(Arc::new(clock.clone()))
+        .with_persistence(persistence.clone());
+    let mut plugin = initialized(plugin).await;
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "signer"), ("algorithm", "ED25519")]).await);
+    let key_id = pair["private_key"]["id"].as_str().unwrap().to_string();
+    let data = BASE64.encode(b"payload");
+    let last_used_at = |metadata: KeyMetadata| metadata.last_used_at;
+
+    assert!(run(&plugin, "sign", &[("key_id", &key_id), ("data", &data)]).await.is_success());
+    settle().await;
+    assert_eq!(persistence.load_key_metadata(&key_id).await.map(last_used_at).unwrap(), Some(start_time()));
+
+    // 间隔内的再次使用只更新内存，停止时补写
+    clock.advance(chrono::Duration::seconds(10));
+    assert!(run(&plugin, "sign", &[("key_id", &key_id), ("data", &data)]).await.is_success());
+    settle().await;
+    let loaded = json(&run(&plugin, "get_key", &[("key_id", &key_id)]).await);
+    assert_eq!(timestamp(&loaded["last_used_at"]), start_time() + chrono::Duration::seconds(10));
+    assert_eq!(persistence.load_key_metadata(&key_id).await.map(last_used_at).unwrap(), Some(start_time()));
+
+    plugin.stop().await;
+    assert_eq!(persistence.load_key_metadata(&key_id).await.map(last_used_at).unwrap(), Some(start_time() + chrono::Duration::seconds(10)));
+}
+
+#[tokio::test]
+async fn apply_tags_updates_every_key_matching_filter() {
+    let persistence = Arc::new(MemoryPersistence::new());
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(persistence.clone())).await;
+    let mut alice_keys = Vec::new();
+    for name in ["a1", "a2"] {
+        alice_keys.push(json(&run(&plugin, "create_key", &[("name", name), ("user", "alice")]).await)["id"].as_str().unwrap().to_string());
+    }
+    let bob_key = json(&run(&plugin, "create_key", &[("name", "b1"), ("user", "bob")]).await)["id"].as_str().unwrap().to_string();
+
+    let result = json(&run(&plugin, "apply_tags", &[("owner", "alice"), ("tag.cost_center", "42"), ("tag.env", "prod"), ("user", "alice")]).await);
+    assert_eq!(result["updated"], 2);
+
+    for key_id in &alice_keys {
+        let stored = persistence.load_key_metadata(key_id).await.unwrap();
+        assert_eq!(stored.tags.get("cost_center").map(String::as_str), Some("42"));
+        assert_eq!(stored.tags.get("env").map(String::as_str), Some("prod"));
+        assert_eq!(stored.version, 2);
+    }
+    let listed = json(&run(&plugin, "list_keys", &[("tag.env", "prod")]).await);
+    assert_eq!(listed.as_array().unwrap().len(), 2);
+    settle().await;
+    assert!(persistence.load_key_metadata(&bob_key).await.unwrap().tags.is_empty());
+
+    assert!(!run(&plugin, "apply_tags", &[("owner", "alice")]).await.is_success());
+}
+
+#[tokio::test]
+async fn expiry_hook_fires_when_mock_clock_crosses_threshold() {
+    let clock = MockClock::new(start_time());
+    let plugin = initialized(KeyManagementPlugin::new().with_clock(Arc::new(clock.clone()))).await;
+    let notified = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&notified);
+    plugin.on_key_expiring(7, move |metadata| sink.lock().unwrap().push(metadata.id.clone()));
+
+    let created = json(&run(&plugin, "create_key", &[("name", "monthly"), ("expiration_date", "2030-01-31T00:00:00Z")]).await);
+    plugin.sweep_expirations().await;
+    assert!(notified.lock().unwrap().is_empty());
+
+    clock.advance(chrono::Duration::days(24));
+    plugin.sweep_expirations().await;
+    plugin.sweep_expirations().await;
+    assert_eq!(*notified.lock().unwrap(), vec![created["id"].as_str().unwrap().to_string()]);
+}
+
+// 使用固定种子的随机数和时钟的插件，同时返回其安全模块以便读取密钥材料
+async fn seeded_plugin(seed: u64) -> (KeyManagementPlugin, Arc<SoftwareSecurityModule>) {
+    let security_module = Arc::new(SoftwareSecurityModule::new().with_random_source(RandomSource::seeded(seed)));
+    let plugin = initialized(
+        KeyManagementPlugin::with_security_module(security_module.clone())
+            .with_random_source(RandomSource::seeded(seed))
+            .with_clock(Arc::new(MockClock::new(start_time()))),
+    ).await;
+    (plugin, security_module)
+}
+
+#[tokio::test]
+async fn identically_seeded_plugins_generate_identical_keys() {
+    let (first, first_module) = seeded_plugin(42).await;
+    let (second, second_module) = seeded_plugin(42).await;
+
+    for algorithm in ["AES-256", "AES-256", "ECDSA", "ED25519"] {
+        let key_type = if algorithm.starts_with("AES") { "SYMMETRIC" } else { "ASYMMETRIC_PRIVATE" };
+        let pairs = [("name", "seeded"), ("key_type", key_type), ("algorithm", algorithm)];
+        let first_id = json(&run(&first, "create_key", &pairs).await)["id"].as_str().unwrap().to_string();
+        let second_id = json(&run(&second, "create_key", &pairs).await)["id"].as_str().unwrap().to_string();
+        assert_eq!(first_id, second_id, "{}", algorithm);
+
+        let first_material = first_module.retrieve_key(&first_id).await.unwrap();
+        let second_material = second_module.retrieve_key(&second_id).await.unwrap();
+        assert_eq!(first_material, second_material, "{}", algorithm);
+    }
+}
+
+#[tokio::test]
+async fn differently_seeded_plugins_generate_different_keys() {
+    let (first, first_module) = seeded_plugin(1).await;
+    let (second, second_module) = seeded_plugin(2).await;
+
+    let first_id = json(&run(&first, "create_key", &[("name", "seeded")]).await)["id"].as_str().unwrap().to_string();
+    let second_id = json(&run(&second, "create_key", &[("name", "seeded")]).await)["id"].as_str().unwrap().to_string();
+    assert_ne!(first_id, second_id);
+    assert_ne!(
+        first_module.retrieve_key(&first_id).await.unwrap(),
+        second_module.retrieve_key(&second_id).await.unwrap(),
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn rewrap_all_moves_keys_from_old_to_new_master_key() {
+    let store = SharedKeyStore::with_master_key(b"master A");
+    let security_module = Arc::new(SoftwareSecurityModule::with_shared_store(store.clone()));
+    let db_path = temp_db_path();
+    let db = open_db(&db_path).await;
+    let plugin = initialized(KeyManagementPlugin::with_security_module(security_module.clone()).with_persistence(db.clone())).await;
+
+    let mut ciphertexts = Vec::new();
+    for name in ["a", "b", "c"] {
+        let created = json(&run(&plugin, "create_key", &[("name", name)]).await);
+        let key_id = created["id"].as_str().unwrap().to_string();
+        let ciphertext = security_module.encrypt_data(&key_id, name.as_bytes(), &[]).await.unwrap();
+        ciphertexts.push((key_id, name, ciphertext));
+    }
+
+    store.rotate_master_key(b"master B");
+    assert_eq!(store.keys_needing_rewrap().len(), 3);
+
+    let summary = json(&run(&plugin, "rewrap_all", &[("user", "admin")]).await);
+    assert_eq!(summary["rewrapped"], 3);
+    assert_eq!(summary["unchanged"], 0);
+    assert!(store.keys_needing_rewrap().is_empty());
+
+    // 旧主密钥删除后所有密钥仍可解密，重复执行不再做任何事
+    store.retire_master_key(1).unwrap();
+    for (key_id, name, ciphertext) in &ciphertexts {
+        assert_eq!(security_module.decrypt_data(key_id, ciphertext, &[]).await.unwrap(), name.as_bytes());
+    }
+    let summary = json(&run(&plugin, "rewrap_all", &[("user", "admin")]).await);
+    assert_eq!(summary["rewrapped"], 0);
+    assert_eq!(summary["unchanged"], 3);
+
+    settle().await;
+    let logs = db.load_audit_logs(None, None).await.unwrap();
+    assert!(logs.iter().any(|log| log.action == "REWRAP_KEYS" && log.details.starts_with("Finished rewrapping keys: 3 rewrapped")));
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn diff_keystore_compares_against_another_source() {
+    let db_path = temp_db_path();
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(open_db(&db_path).await)).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "primary-only")]).await);
+    settle().await;
+
+    let replica = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&replica).unwrap();
+    let diff = json(&run(&plugin, "diff_keystore", &[("source", replica.to_str().unwrap())]).await);
+    assert_eq!(diff["only_local"], serde_json::json!([created["id"]]));
+    assert_eq!(diff["only_other"], serde_json::json!([]));
+    assert_eq!(diff["changed"], serde_json::json!([]));
+
+    // 拼错的目录不会被当作空密钥库
+    let missing = replica.join("missing");
+    assert!(!run(&plugin, "diff_keystore", &[("source", missing.to_str().unwrap())]).await.is_success());
+    assert!(!run(&plugin, "diff_keystore", &[]).await.is_success());
+
+    let _ = std::fs::remove_dir_all(&replica);
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[tokio::test]
+async fn streamed_rewrap_reports_monotonic_progress() {
+    let store = SharedKeyStore::with_master_key(b"master A");
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::with_shared_store(store.clone())))).await;
+    for index in 0..4 {
+        json(&run(&plugin, "create_key", &[("name", &format!("k{}", index))]).await);
+    }
+    store.rotate_master_key(b"master B");
+
+    let results: Vec<CommandResult> = plugin.execute_command_stream("rewrap_all", &params(&[("user", "admin")])).collect().await;
+    let progress: Vec<f32> = results.iter().map(|result| result.get_progress().unwrap()).collect();
+    assert!(progress.len() > 2, "{:?}", progress);
+    assert!(progress.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", progress);
+    assert_eq!(progress.last(), Some(&1.0));
+    assert!(results[..results.len() - 1].iter().all(|result| result.get_stage() == Some("rewrapping")));
+
+    let last = results.last().unwrap();
+    assert_eq!(last.get_stage(), Some("completed"));
+    assert_eq!(json(last)["rewrapped"], 4);
+
+    // 非批量命令只返回最终结果，普通执行不带进度
+    let results: Vec<CommandResult> = plugin.execute_command_stream("create_key", &params(&[("name", "single")])).collect().await;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].get_progress(), Some(1.0));
+    assert_eq!(run(&plugin, "create_key", &[("name", "plain")]).await.get_progress(), None);
+}
+
+#[tokio::test]
+async fn aliases_resolve_for_key_operations_and_are_removed_with_the_key() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FilePersistence::new(dir.to_str().unwrap()));
+    let plugin = initialized(
+        KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new())).with_persistence(persistence.clone()),
+    ).await;
+
+    let created = json(&run(&plugin, "create_key", &[("name", "payments")]).await);
+    let key_id = created["id"].as_str().unwrap();
+    let result = run(&plugin, "set_alias", &[("alias", "payments-current"), ("key_id", key_id)]).await;
+    assert_eq!(result.get_result(), key_id);
+
+    let loaded = json(&run(&plugin, "get_key", &[("key_id", "payments-current")]).await);
+    assert_eq!(loaded["id"], key_id);
+    let data = BASE64.encode(b"card number");
+    let ciphertext = run(&plugin, "encrypt", &[("key_id", "payments-current"), ("data", &data)]).await;
+    assert!(ciphertext.is_success(), "{}", ciphertext.get_error_message());
+    let plaintext = run(&plugin, "decrypt", &[("key_id", key_id), ("data", ciphertext.get_result())]).await;
+    assert_eq!(plaintext.get_result(), data);
+    assert!(run(&plugin, "sign", &[("key_id", "payments-current"), ("data", &data)]).await.is_success());
+
+    // 别名不能与已有密钥ID相同，也不能指向不存在的密钥
+    let other = json(&run(&plugin, "create_key", &[("name", "other")]).await);
+    let other_id = other["id"].as_str().unwrap();
+    assert!(!run(&plugin, "set_alias", &[("alias", other_id), ("key_id", key_id)]).await.is_success());
+    assert!(!run(&plugin, "set_alias", &[("alias", "dangling"), ("key_id", "missing")]).await.is_success());
+
+    // 删除密钥时一并删除指向它的别名
+    assert!(run(&plugin, "set_alias", &[("alias", "other-current"), ("key_id", other_id)]).await.is_success());
+    assert_eq!(run(&plugin, "delete_key", &[("key_id", "payments-current")]).await.get_result(), key_id);
+    assert!(!run(&plugin, "get_key", &[("key_id", "payments-current")]).await.is_success());
+    let aliases = persistence.load_aliases().await.unwrap();
+    assert_eq!(aliases, HashMap::from([("other-current".to_string(), other_id.to_string())]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+async fn soft_delete_plugin(clock: &MockClock) -> KeyManagementPlugin {
+    let mut config = PluginConfig::new();
+    config.add_config("soft_delete_window".to_string(), "3600".to_string());
+    let mut plugin = KeyManagementPlugin::new().with_clock(Arc::new(clock.clone()));
+    assert!(plugin.initialize(config).await);
+    plugin
+}
+
+fn listed_ids(result: &CommandResult) -> Vec<String> {
+    json(result).as_array().unwrap().iter().map(|metadata| metadata["id"].as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn soft_deleted_key_can_be_recovered_within_window() {
+    let clock = MockClock::new(start_time());
+    let plugin = soft_delete_plugin(&clock).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "recoverable")]).await);
+    let key_id = created["id"].as_str().unwrap();
+
+    assert!(run(&plugin, "delete_key", &[("key_id", key_id)]).await.is_success());
+    assert!(!run(&plugin, "get_key", &[("key_id", key_id)]).await.is_success());
+    assert!(listed_ids(&run(&plugin, "list_keys", &[]).await).is_empty());
+    assert_eq!(listed_ids(&run(&plugin, "list_keys", &[("include_deleted", "true")]).await), vec![key_id.to_string()]);
+
+    clock.advance(chrono::Duration::minutes(59));
+    assert_eq!(plugin.sweep_deleted_keys().await, 0);
+    assert_eq!(run(&plugin, "recover_key", &[("key_id", key_id)]).await.get_result(), key_id);
+
+    let recovered = json(&run(&plugin, "get_key", &[("key_id", key_id)]).await);
+    assert!(recovered["deleted_at"].is_null());
+    assert_eq!(listed_ids(&run(&plugin, "list_keys", &[]).await), vec![key_id.to_string()]);
+    assert!(!run(&plugin, "recover_key", &[("key_id", key_id)]).await.is_success());
+}
+
+#[tokio::test]
+async fn soft_deleted_key_is_purged_after_window() {
+    let clock = MockClock::new(start_time());
+    let plugin = soft_delete_plugin(&clock).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "doomed")]).await);
+    let key_id = created["id"].as_str().unwrap();
+    assert!(run(&plugin, "delete_key", &[("key_id", key_id)]).await.is_success());
+
+    // 恢复期结束后不能恢复，过期检查将其彻底删除
+    clock.advance(chrono::Duration::hours(1));
+    let result = run(&plugin, "recover_key", &[("key_id", key_id)]).await;
+    assert_eq!(result.get_error_message(), "Recovery window has expired");
+    assert_eq!(plugin.sweep_deleted_keys().await, 1);
+    assert_eq!(plugin.sweep_deleted_keys().await, 0);
+    assert!(listed_ids(&run(&plugin, "list_keys", &[("include_deleted", "true")]).await).is_empty());
+    assert_eq!(run(&plugin, "recover_key", &[("key_id", key_id)]).await.get_error_message(), "Deleted key not found");
+
+    // 恢复期为 0 时直接删除
+    assert!(run(&plugin, "reconfigure", &[("soft_delete_window", "0")]).await.is_success());
+    let created = json(&run(&plugin, "create_key", &[("name", "immediate")]).await);
+    let key_id = created["id"].as_str().unwrap();
+    assert!(run(&plugin, "delete_key", &[("key_id", key_id)]).await.is_success());
+    assert!(listed_ids(&run(&plugin, "list_keys", &[("include_deleted", "true")]).await).is_empty());
+}
+
+#[tokio::test]
+async fn key_stats_counts_keys_in_memory() {
+    let clock = MockClock::new(start_time());
+    let plugin = soft_delete_plugin(&clock).await;
+    json(&run(&plugin, "create_key", &[("name", "a"), ("expiration_date", "2030-01-15T00:00:00Z")]).await);
+    json(&run(&plugin, "create_key", &[("name", "b"), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "ECDSA")]).await);
+    let deleted = json(&run(&plugin, "create_key", &[("name", "c")]).await);
+    assert!(run(&plugin, "delete_key", &[("key_id", deleted["id"].as_str().unwrap())]).await.is_success());
+
+    let stats = json(&run(&plugin, "key_stats", &[]).await);
+    assert_eq!(stats, serde_json::json!({
+        "total": 2,
+        "by_status": {"ACTIVE": 2},
+        "by_key_type": {"ASYMMETRIC_PRIVATE": 1, "SYMMETRIC": 1},
+        "by_algorithm": {"AES-256": 1, "ECDSA": 1},
+        "expiring_within_30_days": 1,
+    }));
+}
+
+#[tokio::test]
+async fn tenant_cannot_access_another_tenants_key() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "k"), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "ED25519"), ("tenant", "tenant-b")]).await);
+    let key_id = created["id"].as_str().unwrap();
+
+    let owned = json(&run(&plugin, "get_key", &[("key_id", key_id), ("tenant", "tenant-b")]).await);
+    assert_eq!(owned["tenant"], "tenant-b");
+
+    // 其他租户（包括默认租户）即使知道密钥ID也按不存在处理
+    for tenant in ["tenant-a", ""] {
+        let data = BASE64.encode(b"payload");
+        for (command, extra) in [("get_key", None), ("sign", Some(data.as_str())), ("delete_key", None)] {
+            let mut pairs = vec![("key_id", key_id), ("tenant", tenant)];
+            if let Some(data) = extra {
+                pairs.push(("data", data));
+            }
+            let result = run(&plugin, command, &pairs).await;
+            assert!(!result.is_success(), "{} in tenant {:?}", command, tenant);
+            assert_eq!(result.get_error_code(), Some(ErrorCode::KeyNotFound), "{}: {}", command, result.get_error_message());
+        }
+
+        let listed = json(&run(&plugin, "list_keys", &[("tenant", tenant)]).await);
+        assert_eq!(listed.as_array().unwrap().len(), 0);
+        let stats = json(&run(&plugin, "key_stats", &[("tenant", tenant)]).await);
+        assert_eq!(stats["total"], 0);
+    }
+
+    // 密钥在所属租户中未受影响
+    let listed = json(&run(&plugin, "list_keys", &[("tenant", "tenant-b")]).await);
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    let stats = json(&run(&plugin, "key_stats", &[("tenant", "tenant-b")]).await);
+    assert_eq!(stats["total"], 1);
+}
+
+#[tokio::test]
+async fn admin_commands_are_limited_to_the_default_tenant() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+
+    for command in ["backup", "restore", "compact_audit_log", "diff_keystore", "rewrap_all", "reconfigure", "rotate_audit_key", "selftest_security_module", "reconcile", "escrow_key", "recover_from_escrow", "replay_dead_letters"] {
+        let result = run(&plugin, command, &[("tenant", "tenant-a")]).await;
+        assert!(!result.is_success(), "{}", command);
+        assert_eq!(result.get_error_code(), Some(ErrorCode::Unauthorized), "{}", command);
+    }
+}
+
+#[tokio::test]
+async fn migrate_algorithm_replaces_rsa_2048_keys_with_rsa_4096() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    let rsa_key = |name: &'static str| [("name", name), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "RSA-2048"), ("tag.team", "release")];
+    let first = json(&run(&plugin, "create_key", &rsa_key("first")).await);
+    let second = json(&run(&plugin, "create_key", &rsa_key("second")).await);
+    let guarded = json(&run(&plugin, "create_key", &[("name", "guarded"), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "RSA-2048"), ("requires_approval", "true")]).await);
+    let other = json(&run(&plugin, "create_key", &[("name", "other")]).await);
+
+    let summary = json(&run(&plugin, "migrate_algorithm", &[("source_algorithm", "RSA-2048"), ("target_algorithm", "RSA-4096")]).await);
+    assert_eq!(summary["migrated"], 2);
+    assert_eq!(summary["pending_approval"], 1);
+    assert_eq!(summary["failed"], 0);
+
+    for old in [&first, &second] {
+        let entry = summary["keys"].as_array().unwrap().iter().find(|entry| entry["key_id"] == old["id"]).unwrap();
+        assert_eq!(entry["status"], "migrated");
+        let new_id = entry["new_key_id"].as_str().unwrap();
+
+        let old_key = json(&run(&plugin, "get_key", &[("key_id", old["id"].as_str().unwrap())]).await);
+        assert_eq!(old_key["status"], "PendingDestruction");
+        assert_eq!(old_key["tags"]["migrated_to"], new_id);
+
+        let new_key = json(&run(&plugin, "get_key", &[("key_id", new_id)]).await);
+        assert_eq!(new_key["status"], "Active");
+        assert_eq!(new_key["algorithm"], "RSA4096");
+        assert_eq!(new_key["name"], old["name"]);
+        assert_eq!(new_key["tags"]["team"], "release");
+        assert_eq!(new_key["tags"]["migrated_from"], old["id"]);
+    }
+
+    // 需要审批的密钥和其他算法的密钥保持不变
+    for untouched in [&guarded, &other] {
+        let key = json(&run(&plugin, "get_key", &[("key_id", untouched["id"].as_str().unwrap())]).await);
+        assert_eq!(key["status"], "Active");
+    }
+
+    let result = run(&plugin, "migrate_algorithm", &[("source_algorithm", "RSA-2048"), ("target_algorithm", "RSA-2048")]).await;
+    assert_eq!(result.get_error_code(), Some(ErrorCode::InvalidParams));
+}
+
+#[tokio::test]
+async fn migrate_algorithm_skips_keys_user_may_not_rotate() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    let ed25519_key = |name: &'static str, user: &'static str| {
+        [("name", name), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "ED25519"), ("user", user)]
+    };
+    let alice_key = json(&run(&plugin, "create_key", &ed25519_key("alice", "alice")).await)["id"].as_str().unwrap().to_string();
+    let bob_key = json(&run(&plugin, "create_key", &ed25519_key("bob", "bob")).await)["id"].as_str().unwrap().to_string();
+    // bob 只能用 alice 的密钥加密，不能轮换
+    assert!(run(&plugin, "grant_access", &[("key_id", &alice_key), ("grantee", "bob"), ("operations", "encrypt"), ("user", "alice")]).await.is_success());
+
+    let summary = json(&run(&plugin, "migrate_algorithm", &[("source_algorithm", "ED25519"), ("target_algorithm", "ECDSA"), ("user", "bob")]).await);
+    assert_eq!(summary["migrated"], 1);
+    assert_eq!(summary["keys"][0]["key_id"], bob_key.as_str());
+
+    let untouched = json(&run(&plugin, "get_key", &[("key_id", &alice_key), ("user", "alice")]).await);
+    assert_eq!(untouched["status"], "Active");
+    assert!(untouched["tags"].get("migrated_to").is_none());
+}
+
+async fn encrypt(plugin: &KeyManagementPlugin, key_id: &str, data: &str) -> String {
+    let result = run(plugin, "encrypt", &[("key_id", key_id), ("data", data)]).await;
+    assert!(result.is_success(), "{}", result.get_error_message());
+    result.get_result().to_string()
+}
+
+#[tokio::test]
+async fn deterministic_mode_repeats_ciphertext_and_randomized_mode_does_not() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let deterministic = json(&run(&plugin, "create_key", &[("name", "index"), ("tag.encryption_mode", "deterministic")]).await);
+    let randomized = json(&run(&plugin, "create_key", &[("name", "vault")]).await);
+    let data = BASE64.encode(b"alice@example.com");
+
+    for (key, repeats) in [(&deterministic, true), (&randomized, false)] {
+        let key_id = key["id"].as_str().unwrap();
+        let first = encrypt(&plugin, key_id, &data).await;
+        let second = encrypt(&plugin, key_id, &data).await;
+        assert_eq!(first == second, repeats, "{}", key["name"]);
+
+        // 两种模式的密文都用 decrypt 解密
+        let plaintext = run(&plugin, "decrypt", &[("key_id", key_id), ("data", &first)]).await;
+        assert_eq!(plaintext.get_result(), data);
+    }
+
+    let key_id = deterministic["id"].as_str().unwrap();
+    assert_ne!(encrypt(&plugin, key_id, &BASE64.encode(b"bob@example.com")).await, encrypt(&plugin, key_id, &data).await);
+
+    let invalid = json(&run(&plugin, "create_key", &[("name", "typo"), ("tag.encryption_mode", "determinstic")]).await);
+    let result = run(&plugin, "encrypt", &[("key_id", invalid["id"].as_str().unwrap()), ("data", &data)]).await;
+    assert!(result.get_error_message().contains("Invalid encryption_mode"), "{}", result.get_error_message());
+}
+
+#[tokio::test]
+async fn decryption_requires_the_same_associated_data() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let randomized = json(&run(&plugin, "create_key", &[("name", "vault")]).await);
+    let deterministic = json(&run(&plugin, "create_key", &[("name", "index"), ("tag.encryption_mode", "deterministic")]).await);
+    let data = BASE64.encode(b"card number");
+    let aad = BASE64.encode(b"tenant-a/record-1");
+
+    for key in [&randomized, &deterministic] {
+        let key_id = key["id"].as_str().unwrap();
+        let result = run(&plugin, "encrypt", &[("key_id", key_id), ("data", &data), ("aad", &aad)]).await;
+        assert!(result.is_success(), "{}", result.get_error_message());
+        let ciphertext = result.get_result().to_string();
+
+        let plaintext = run(&plugin, "decrypt", &[("key_id", key_id), ("data", &ciphertext), ("aad", &aad)]).await;
+        assert_eq!(plaintext.get_result(), data);
+
+        // 换到其他上下文（或不带 aad）时解密失败
+        let other = BASE64.encode(b"tenant-b/record-1");
+        for wrong_aad in [Some(other.as_str()), None] {
+            let mut pairs = vec![("key_id", key_id), ("data", ciphertext.as_str())];
+            pairs.extend(wrong_aad.map(|aad| ("aad", aad)));
+            assert!(!run(&plugin, "decrypt", &pairs).await.is_success(), "{} {:?}", key["name"], wrong_aad);
+        }
+    }
+
+    let result = run(&plugin, "encrypt", &[("key_id", randomized["id"].as_str().unwrap()), ("data", &data), ("aad", "not base64!")]).await;
+    assert!(result.get_error_message().contains("Invalid aad"), "{}", result.get_error_message());
+}
+
+#[tokio::test]
+async fn verify_batch_reports_each_signature_in_order() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let signer = json(&run(&plugin, "create_key", &[("name", "events"), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "ED25519")]).await);
+    let key_id = signer["id"].as_str().unwrap();
+
+    let mut items = Vec::new();
+    for event in ["login", "logout", "purchase"] {
+        let data = BASE64.encode(event);
+        let signature = run(&plugin, "sign", &[("key_id", key_id), ("data", &data)]).await.get_result().to_string();
+        items.push(serde_json::json!({"key_id": key_id, "data": data, "signature": signature}));
+    }
+    // 篡改的数据、别的事件的签名和不存在的密钥
+    items[1]["data"] = BASE64.encode("logout-tampered").into();
+    items.push(serde_json::json!({"key_id": key_id, "data": BASE64.encode("refund"), "signature": items[0]["signature"]}));
+    items.push(serde_json::json!({"key_id": "missing", "data": items[0]["data"], "signature": items[0]["signature"]}));
+
+    let results = json(&run(&plugin, "verify_batch", &[("items", &Value::from(items).to_string())]).await);
+    let valid: Vec<bool> = results.as_array().unwrap().iter().map(|result| result["valid"].as_bool().unwrap()).collect();
+    assert_eq!(valid, [true, false, true, false, false]);
+    assert!(results[2]["error"].is_null());
+    assert_eq!(results[4]["error"], "Key not found");
+
+    let result = run(&plugin, "verify_batch", &[("items", "{not json")]).await;
+    assert_eq!(result.get_error_code(), Some(ErrorCode::InvalidParams));
+}
+
+#[tokio::test]
+async fn every_non_active_status_is_rejected() {
+    let now = start_time();
+    let statuses = [
+        KeyStatus::Active,
+        KeyStatus::Suspended,
+        KeyStatus::Expired,
+        KeyStatus::Compromised,
+        KeyStatus::Destroyed,
+        KeyStatus::PendingDestruction,
+    ];
+
+    // 各状态的密钥预先写入持久化存储，插件启动时加载
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FilePersistence::new(dir.to_str().unwrap()));
+    let mut key_ids = Vec::new();
+    for status in &statuses {
+        let mut metadata = KeyMetadata::new(
+            format!("{:?}", status),
+            String::new(),
+            KeyType::Symmetric,
+            KeyAlgorithm::AES256,
+            "alice".to_string(),
+            false,
+        ).with_timestamp(now);
+        metadata.status = status.clone();
+        assert_eq!(metadata.is_usable_at(now), *status == KeyStatus::Active);
+        persistence.save_key_metadata(&metadata).await.unwrap();
+        key_ids.push(metadata.id);
+    }
+
+    let mut plugin = initialized(
+        KeyManagementPlugin::new()
+            .with_persistence(persistence)
+            .with_clock(Arc::new(MockClock::new(now))),
+    ).await;
+    assert!(plugin.start().await);
+
+    let data = BASE64.encode(b"payload");
+    for (status, key_id) in statuses.iter().zip(&key_ids) {
+        for command in ["sign", "encrypt", "decrypt"] {
+            let result = run(&plugin, command, &[("key_id", key_id), ("data", &data)]).await;
+            if *status == KeyStatus::Active {
+                assert!(result.is_success(), "{} with {:?}: {}", command, status, result.get_error_message());
+                continue;
+            }
+            assert!(!result.is_success(), "{} with {:?}", command, status);
+            assert_eq!(result.get_error_code(), Some(ErrorCode::InvalidStatus), "{} with {:?}", command, status);
+            assert!(result.get_error_message().starts_with("Key is not active"), "{}", result.get_error_message());
+        }
+    }
+    assert!(plugin.stop().await);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn active_key_past_expiration_is_rejected_before_sweep() {
+    let clock = MockClock::new(start_time());
+    let plugin = initialized(KeyManagementPlugin::new().with_clock(Arc::new(clock.clone()))).await;
+    let created = json(&run(&plugin, "create_key", &[("name", "k"), ("expiration_date", "2030-01-02T00:00:00Z")]).await);
+    let key_id = created["id"].as_str().unwrap();
+
+    clock.advance(chrono::Duration::days(2));
+    let data = BASE64.encode(b"payload");
+    for command in ["sign", "encrypt", "decrypt"] {
+        let result = run(&plugin, command, &[("key_id", key_id), ("data", &data)]).await;
+        assert_eq!(result.get_error_code(), Some(ErrorCode::InvalidStatus), "{}", command);
+        assert!(result.get_error_message().contains("expired at 2030-01-02T00:00:00.000Z"), "{}", result.get_error_message());
+    }
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", key_id)]).await)["status"], "Active");
+}
+
+fn selftest_statuses(report: &Value, algorithm: &str) -> Vec<(String, String)> {
+    let entry = report["algorithms"].as_array().unwrap().iter().find(|entry| entry["algorithm"] == algorithm).unwrap();
+    entry["operations"].as_array().unwrap().iter()
+        .map(|step| (step["operation"].as_str().unwrap().to_string(), step["status"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn security_module_selftest_reports_each_operation() {
+    let steps = |statuses: &[(&str, &str)]| -> Vec<(String, String)> {
+        statuses.iter().map(|(operation, status)| (operation.to_string(), status.to_string())).collect()
+    };
+    let symmetric = steps(&[
+        ("generate", "pass"), ("store", "pass"), ("retrieve", "pass"), ("sign", "pass"),
+        ("verify", "pass"), ("encrypt", "pass"), ("decrypt", "pass"), ("delete", "pass"),
+    ]);
+    let asymmetric = steps(&[
+        ("generate", "pass"), ("store", "pass"), ("retrieve", "pass"), ("sign", "pass"),
+        ("verify", "pass"), ("encrypt", "skipped"), ("decrypt", "skipped"), ("delete", "pass"),
+    ]);
+
+    // MockHSM 对所有算法都能走完支持的步骤
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    let report = json(&run(&plugin, "selftest_security_module", &[]).await);
+    assert_eq!(report["passed"], true);
+    assert_eq!(report["algorithms"].as_array().unwrap().len(), 5);
+    assert_eq!(selftest_statuses(&report, "AES-256"), symmetric);
+    assert_eq!(selftest_statuses(&report, "ED25519"), asymmetric);
+
+    // 软件安全模块做真实的往返
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let report = json(&run(&plugin, "selftest_security_module", &[("algorithms", "AES-256, ECDSA, ED25519, RSA-2048")]).await);
+    assert_eq!(report["passed"], true, "{}", report);
+    assert_eq!(selftest_statuses(&report, "AES-256"), symmetric);
+    for algorithm in ["ECDSA", "ED25519", "RSA-2048"] {
+        assert_eq!(selftest_statuses(&report, algorithm), asymmetric, "{}", algorithm);
+    }
+
+    let result = run(&plugin, "selftest_security_module", &[("algorithms", "AES-128")]).await;
+    assert!(!result.is_success());
+}
+
+#[tokio::test]
+async fn key_history_lists_lifecycle_events_in_order() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    let key = json(&run(&plugin, "create_key", &[("name", "ledger")]).await);
+    let key_id = key["id"].as_str().unwrap();
+
+    for command in ["rotate_key", "suspend_key", "resume_key"] {
+        let result = run(&plugin, command, &[("key_id", key_id)]).await;
+        assert!(result.is_success(), "{}: {}", command, result.get_error_message());
+    }
+    // 未暂停的密钥不能恢复
+    let result = run(&plugin, "resume_key", &[("key_id", key_id)]).await;
+    assert_eq!(result.get_error_code(), Some(ErrorCode::InvalidStatus));
+
+    let history = json(&run(&plugin, "key_history", &[("key_id", key_id)]).await);
+    assert_eq!(history["status"], "ACTIVE");
+    assert_eq!(history["version"], 2);
+
+    let events: Vec<(&str, &str, u64)> = history["events"].as_array().unwrap().iter()
+        .filter(|event| event["success"] == true)
+        .map(|event| (event["action"].as_str().unwrap(), event["status"].as_str().unwrap(), event["version"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(events, vec![
+        ("CREATE_KEY", "ACTIVE", 1),
+        ("ROTATE_KEY", "ACTIVE", 2),
+        ("SUSPEND_KEY", "SUSPENDED", 2),
+        ("RESUME_KEY", "ACTIVE", 2),
+    ]);
+
+    assert!(!run(&plugin, "key_history", &[("key_id", "missing")]).await.is_success());
+}
+
+/// 前若干次保存密钥元数据失败的持久化存储，模拟暂时不可用的后端
+struct FlakyKeyStore {
+    inner: FilePersistence,
+    failures_left: AtomicUsize,
+}
+
+#[async_trait]
+impl PersistenceInterface for FlakyKeyStore {
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        if self.failures_left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1)).is_ok() {
+            return Err("持久化存储不可用".to_string());
+        }
+        self.inner.save_key_metadata(metadata).await
+    }
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        self.inner.load_key_metadata(key_id).await
+    }
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        self.inner.delete_key_metadata(key_id).await
+    }
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        self.inner.query_keys(query).await
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        self.inner.save_audit_log(log).await
+    }
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        self.inner.load_audit_logs(filters, limit).await
+    }
+}
+
+#[tokio::test]
+async fn key_metadata_writes_are_retried_and_reconciled() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FlakyKeyStore {
+        inner: FilePersistence::new(dir.to_str().unwrap()),
+        failures_left: AtomicUsize::new(2),
+    });
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(persistence.clone())).await;
+
+    // 失败两次后重试成功
+    let retried = json(&run(&plugin, "create_key", &[("name", "retried")]).await);
+    let retried_id = retried["id"].as_str().unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(persistence.load_key_metadata(retried_id).await.unwrap().name, "retried");
+    assert!(plugin.dirty_keys().is_empty());
+
+    // 重试次数用尽后标记为待补写，补写时保存
+    persistence.failures_left.store(5, Ordering::SeqCst);
+    let dirty = json(&run(&plugin, "create_key", &[("name", "dirty")]).await);
+    let dirty_id = dirty["id"].as_str().unwrap();
+    tokio::time::sleep(Duration::from_millis(2000)).await;
+    assert!(persistence.load_key_metadata(dirty_id).await.is_err());
+    assert_eq!(plugin.dirty_keys(), vec![dirty_id.to_string()]);
+
+    assert_eq!(plugin.reconcile_dirty_keys().await, 1);
+    assert_eq!(persistence.load_key_metadata(dirty_id).await.unwrap().name, "dirty");
+    assert!(plugin.dirty_keys().is_empty());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn key_pair_metadata_writes_are_retried_and_marked_dirty() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FlakyKeyStore {
+        inner: FilePersistence::new(dir.to_str().unwrap()),
+        failures_left: AtomicUsize::new(10),
+    });
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(persistence.clone())).await;
+
+    // 两条元数据各重试 5 次后都标记为待补写
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "signer"), ("algorithm", "ED25519")]).await);
+    let mut key_ids = vec![
+        pair["private_key"]["id"].as_str().unwrap().to_string(),
+        pair["public_key"]["id"].as_str().unwrap().to_string(),
+    ];
+    key_ids.sort();
+    tokio::time::sleep(Duration::from_millis(2000)).await;
+    assert_eq!(plugin.dirty_keys(), key_ids);
+
+    assert_eq!(plugin.reconcile_dirty_keys().await, 2);
+    for key_id in &key_ids {
+        assert!(persistence.load_key_metadata(key_id).await.unwrap().name.starts_with("signer"));
+    }
+    assert!(plugin.dirty_keys().is_empty());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn dead_lettered_writes_land_after_replay() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FlakyKeyStore {
+        inner: FilePersistence::new(dir.to_str().unwrap()),
+        failures_left: AtomicUsize::new(5),
+    });
+    std::fs::create_dir_all(&dir).unwrap();
+    let dead_letter_path = dir.join("dead_letters.jsonl");
+    let dead_letters = Arc::new(DeadLetterStore::open(&dead_letter_path).unwrap());
+    let plugin = initialized(
+        KeyManagementPlugin::new().with_persistence(persistence.clone()).with_dead_letter_store(Arc::clone(&dead_letters)),
+    ).await;
+
+    // 重试次数用尽的元数据写入进入死信存储
+    let key_id = json(&run(&plugin, "create_key", &[("name", "dead-lettered")]).await)["id"].as_str().unwrap().to_string();
+    tokio::time::sleep(Duration::from_millis(2000)).await;
+    assert!(persistence.load_key_metadata(&key_id).await.is_err());
+    let audit_entry = AuditLogEntry::new("TEST".to_string(), "tester".to_string(), None, "dead letter".to_string(), true);
+    dead_letters.push(DeadLetterWrite::AuditLog(audit_entry.clone()), "持久化存储不可用");
+    assert_eq!(plugin.dead_letters().len(), 2);
+    // 死信写入文件，重新打开后仍然保留
+    assert_eq!(DeadLetterStore::open(&dead_letter_path).unwrap().len(), 2);
+
+    // 存储仍不可用时失败的死信保留，成功的移除
+    persistence.failures_left.store(1, Ordering::SeqCst);
+    let report = json(&run(&plugin, "replay_dead_letters", &[]).await);
+    assert_eq!(report["replayed"].as_array().unwrap().len(), 1);
+    assert_eq!(report["failed"][0]["target_id"], key_id.as_str());
+    assert_eq!(report["remaining"], 1);
+
+    // 存储恢复后重放全部写入
+    let report = json(&run(&plugin, "replay_dead_letters", &[]).await);
+    assert_eq!(report["replayed"].as_array().unwrap().len(), 1);
+    assert_eq!(report["remaining"], 0);
+    assert_eq!(persistence.load_key_metadata(&key_id).await.unwrap().name, "dead-lettered");
+    let logs = persistence.load_audit_logs(None, None).await.unwrap();
+    assert!(logs.iter().any(|log| log.id == audit_entry.id));
+    assert!(plugin.dirty_keys().is_empty());
+    assert!(DeadLetterStore::open(&dead_letter_path).unwrap().is_empty());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+// 包装内存存储，统计元数据的加载次数
+#[derive(Default)]
+struct CountingStore {
+    inner: MemoryPersistence,
+    loads: AtomicUsize,
+}
+
+impl CountingStore {
+    fn loads(&self) -> usize {
+        self.loads.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl PersistenceInterface for CountingStore {
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        self.inner.save_key_metadata(metadata).await
+    }
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        self.loads.fetch_add(1, Ordering::SeqCst);
+        self.inner.load_key_metadata(key_id).await
+    }
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        self.inner.delete_key_metadata(key_id).await
+    }
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        self.inner.query_keys(query).await
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        self.inner.save_audit_log(log).await
+    }
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        self.inner.load_audit_logs(filters, limit).await
+    }
+}
+
+// 内存中最多保留 max 个密钥的插件
+async fn capped_plugin(persistence: Arc<CountingStore>, max: usize) -> KeyManagementPlugin {
+    let mut plugin = KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))
+        .with_persistence(persistence);
+    let mut config = PluginConfig::new();
+    config.add_config("max_in_memory_keys".to_string(), max.to_string());
+    assert!(plugin.initialize(config).await);
+    plugin
+}
+
+#[tokio::test]
+async fn lru_cap_evicts_least_recently_used_and_reloads_on_demand() {
+    let persistence = Arc::new(CountingStore::default());
+    let plugin = capped_plugin(Arc::clone(&persistence), 2).await;
+
+    // 创建后立即访问一次，确定使用顺序 k1 < k2 < k3
+    let mut key_ids = Vec::new();
+    for name in ["k1", "k2", "k3"] {
+        let key_id = json(&run(&plugin, "create_key", &[("name", name)]).await)["id"].as_str().unwrap().to_string();
+        settle().await;
+        assert!(run(&plugin, "get_key", &[("key_id", &key_id)]).await.is_success());
+        key_ids.push(key_id);
+    }
+    let (k1, k2, k3) = (&key_ids[0], &key_ids[1], &key_ids[2]);
+    assert_eq!(persistence.loads(), 0);
+
+    // k1 已被淘汰，访问时从存储重新加载，随后淘汰最久未使用的 k2
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", k1)]).await)["name"], "k1");
+    assert_eq!(persistence.loads(), 1);
+    assert!(run(&plugin, "get_key", &[("key_id", k3)]).await.is_success());
+    assert_eq!(persistence.loads(), 1);
+    assert!(run(&plugin, "get_key", &[("key_id", k2)]).await.is_success());
+    assert_eq!(persistence.loads(), 2);
+
+    // 被淘汰的密钥仍可用于加密，也仍出现在列表中
+    let data = BASE64.encode(b"evicted");
+    assert!(run(&plugin, "encrypt", &[("key_id", k1), ("data", &data)]).await.is_success());
+    assert_eq!(persistence.loads(), 3);
+    assert_eq!(json(&run(&plugin, "list_keys", &[]).await).as_array().unwrap().len(), 3);
+
+    let mut config = PluginConfig::new();
+    config.add_config("max_in_memory_keys".to_string(), "0".to_string());
+    let mut plugin = KeyManagementPlugin::new().with_persistence(persistence);
+    assert!(plugin.try_initialize(config).await.unwrap_err().contains("Invalid max_in_memory_keys"));
+}
+
+#[tokio::test]
+async fn batch_and_bulk_commands_reload_evicted_keys() {
+    let persistence = Arc::new(CountingStore::default());
+    let plugin = capped_plugin(Arc::clone(&persistence), 1).await;
+
+    let data = BASE64.encode(b"event");
+    let mut items = Vec::new();
+    for name in ["first", "second"] {
+        let key_id = json(&run(&plugin, "create_key", &[("name", name), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "ED25519")]).await)["id"]
+            .as_str().unwrap().to_string();
+        let signature = run(&plugin, "sign", &[("key_id", &key_id), ("data", &data)]).await.get_result().to_string();
+        items.push(serde_json::json!({"key_id": key_id, "data": data, "signature": signature}));
+        settle().await;
+    }
+    // 第二个密钥使用后第一个已被淘汰
+    assert!(run(&plugin, "get_key", &[("key_id", items[1]["key_id"].as_str().unwrap())]).await.is_success());
+
+    let results = json(&run(&plugin, "verify_batch", &[("items", &Value::from(items.clone()).to_string())]).await);
+    let valid: Vec<bool> = results.as_array().unwrap().iter().map(|result| result["valid"].as_bool().unwrap()).collect();
+    assert_eq!(valid, [true, true], "{}", results);
+    settle().await;
+
+    let summary = json(&run(&plugin, "migrate_algorithm", &[("source_algorithm", "ED25519"), ("target_algorithm", "ECDSA")]).await);
+    assert_eq!(summary["migrated"], 2, "{}", summary);
+    for item in &items {
+        let old_key = json(&run(&plugin, "get_key", &[("key_id", item["key_id"].as_str().unwrap())]).await);
+        assert_eq!(old_key["status"], "PendingDestruction");
+    }
+}
+
+#[tokio::test]
+async fn evicted_keys_are_swept_and_paired() {
+    let clock = MockClock::new(start_time());
+    let mut plugin = KeyManagementPlugin::new()
+        .with_clock(Arc::new(clock.clone()))
+        .with_persistence(Arc::new(MemoryPersistence::new()));
+    let mut config = PluginConfig::new();
+    config.add_config("max_in_memory_keys".to_string(), "1".to_string());
+    assert!(plugin.initialize(config).await);
+
+    let expiring = json(&run(&plugin, "create_key", &[("name", "expiring"), ("expiration_date", "2030-01-01T01:00:00Z")]).await)["id"]
+        .as_str().unwrap().to_string();
+    let pair = json(&run(&plugin, "create_key_pair", &[("name", "signer"), ("algorithm", "ED25519")]).await);
+    let (private_id, public_id) = (pair["private_key"]["id"].as_str().unwrap(), pair["public_key"]["id"].as_str().unwrap());
+    settle().await;
+    let recent = json(&run(&plugin, "create_key", &[("name", "recent")]).await)["id"].as_str().unwrap().to_string();
+    settle().await;
+    assert!(run(&plugin, "get_key", &[("key_id", &recent)]).await.is_success());
+
+    // 密钥对的另一半已被淘汰时重新加载
+    assert_eq!(plugin.get_paired_key(private_id).await.unwrap().unwrap().id, public_id);
+
+    // 已被淘汰的密钥到期后同样标记为过期
+    clock.advance(chrono::Duration::hours(2));
+    assert_eq!(plugin.sweep_expirations().await, 1);
+    settle().await;
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", &expiring)]).await)["status"], "Expired");
+}
+
+#[tokio::test]
+async fn apply_tags_writes_evicted_keys_without_reloading_them() {
+    let persistence = Arc::new(CountingStore::default());
+    let plugin = capped_plugin(Arc::clone(&persistence), 1).await;
+    let mut key_ids = Vec::new();
+    for name in ["first", "second", "third"] {
+        key_ids.push(json(&run(&plugin, "create_key", &[("name", name)]).await)["id"].as_str().unwrap().to_string());
+        settle().await;
+    }
+
+    // 直接调用，不经过命令结束后的淘汰
+    let params = HashMap::from([("tag.env".to_string(), "prod".to_string())]);
+    assert_eq!(plugin.apply_tags(&params, "admin").await.unwrap(), 3);
+    assert_eq!(plugin.evict_keys(), 0);
+
+    // 已淘汰的密钥也已写入存储
+    for key_id in &key_ids {
+        let stored = persistence.load_key_metadata(key_id).await.unwrap();
+        assert_eq!(stored.tags.get("env").map(String::as_str), Some("prod"));
+    }
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", &key_ids[2])]).await)["tags"]["env"], "prod");
+}
+
+#[tokio::test]
+async fn reconcile_resolves_drift_between_memory_and_persistence() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FilePersistence::new(dir.to_str().unwrap()));
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(persistence.clone())).await;
+    let memory_only = json(&run(&plugin, "create_key", &[("name", "memory-only")]).await)["id"].as_str().unwrap().to_string();
+    let stale = json(&run(&plugin, "create_key", &[("name", "stale")]).await)["id"].as_str().unwrap().to_string();
+    settle().await;
+
+    // 制造差异：一个密钥只在内存中，一个只在存储中，一个存储中的版本更高
+    persistence.delete_key_metadata(&memory_only).await.unwrap();
+    let mut stored_only = persistence.load_key_metadata(&stale).await.unwrap();
+    stored_only.id = uuid::Uuid::new_v4().to_string();
+    stored_only.name = "stored-only".to_string();
+    persistence.save_key_metadata(&stored_only).await.unwrap();
+    let mut newer = persistence.load_key_metadata(&stale).await.unwrap();
+    newer.version = 3;
+    persistence.save_key_metadata(&newer).await.unwrap();
+
+    let report = json(&run(&plugin, "reconcile", &[]).await);
+    assert_eq!(report["only_memory"], serde_json::json!([memory_only]));
+    assert_eq!(report["only_persistence"], serde_json::json!([stored_only.id]));
+    assert_eq!(report["changed"][0]["id"], stale.as_str());
+    assert_eq!(report["written"], serde_json::json!([memory_only]));
+    assert_eq!(report["loaded"].as_array().unwrap().len(), 2);
+
+    assert_eq!(persistence.load_key_metadata(&memory_only).await.unwrap().name, "memory-only");
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", &stored_only.id)]).await)["name"], "stored-only");
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", &stale)]).await)["version"], 3);
+
+    // 差异消除后再次对比没有差异
+    let report = json(&run(&plugin, "reconcile", &[]).await);
+    assert_eq!(report["only_memory"], serde_json::json!([]));
+    assert_eq!(report["only_persistence"], serde_json::json!([]));
+    assert_eq!(report["changed"], serde_json::json!([]));
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn granted_user_can_only_perform_granted_operations() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let key_id = json(&run(&plugin, "create_key", &[("name", "shared"), ("user", "alice")]).await)["id"].as_str().unwrap().to_string();
+    let data = BASE64.encode(b"payload");
+
+    let acl = json(&run(&plugin, "grant_access", &[("key_id", &key_id), ("grantee", "bob"), ("operations", "encrypt"), ("user", "alice")]).await);
+    assert_eq!(acl, serde_json::json!({ "bob": ["encrypt"] }));
+
+    assert!(run(&plugin, "encrypt", &[("key_id", &key_id), ("data", &data), ("user", "bob")]).await.is_success());
+    for (command, user) in [("delete_key", "bob"), ("decrypt", "bob"), ("encrypt", "carol"), ("grant_access", "bob")] {
+        let result = run(&plugin, command, &[("key_id", &key_id), ("data", &data), ("grantee", "bob"), ("operations", "*"), ("user", user)]).await;
+        assert_eq!(result.get_error_code(), Some(ErrorCode::Unauthorized), "{} {}", command, user);
+    }
+    let result = run(&plugin, "grant_access", &[("key_id", &key_id), ("grantee", "bob"), ("operations", "export"), ("user", "alice")]).await;
+    assert!(result.get_error_message().contains("Invalid operation"), "{}", result.get_error_message());
+
+    // 撤销后只有所有者可以操作
+    json(&run(&plugin, "revoke_access", &[("key_id", &key_id), ("grantee", "bob"), ("user", "alice")]).await);
+    let result = run(&plugin, "encrypt", &[("key_id", &key_id), ("data", &data), ("user", "bob")]).await;
+    assert_eq!(result.get_error_code(), Some(ErrorCode::Unauthorized));
+    assert!(run(&plugin, "delete_key", &[("key_id", &key_id), ("user", "alice")]).await.is_success());
+}
+
+#[tokio::test]
+async fn try_initialize_reports_why_initialization_failed() {
+    let mut config = PluginConfig::new();
+    config.add_config("audit_queue_size".to_string(), "0".to_string());
+    let error = KeyManagementPlugin::new().try_initialize(config.clone()).await.unwrap_err();
+    assert!(error.contains("Invalid audit_queue_size: 0"), "{}", error);
+    assert!(!KeyManagementPlugin::new().initialize(config).await);
+
+    let db_url = "sqlite:/nonexistent/password_manager/keys.db";
+    let mut config = PluginConfig::new();
+    config.add_config("db_url".to_string(), db_url.to_string());
+    let error = KeyManagementPlugin::new().try_initialize(config).await.unwrap_err();
+    assert!(error.contains(db_url), "{}", error);
+}
+
+#[tokio::test]
+async fn update_key_sets_and_clears_notes() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FilePersistence::new(dir.to_str().unwrap()));
+    let plugin = initialized(KeyManagementPlugin::new().with_persistence(persistence.clone())).await;
+    let key = json(&run(&plugin, "create_key", &[("name", "billing"), ("description", "Billing exports")]).await);
+    let key_id = key["id"].as_str().unwrap();
+    assert!(key.get("notes").is_none());
+    settle().await;
+
+    json(&run(&plugin, "update_key", &[("key_id", key_id), ("notes", "Owned by payments on-call")]).await);
+    let key = json(&run(&plugin, "get_key", &[("key_id", key_id)]).await);
+    assert_eq!(key["notes"], "Owned by payments on-call");
+    assert_eq!(key["description"], "Billing exports");
+    assert_eq!(persistence.load_key_metadata(key_id).await.unwrap().notes.as_deref(), Some("Owned by payments on-call"));
+
+    // 只修改描述时备注不变，备注为空时清除
+    json(&run(&plugin, "update_key", &[("key_id", key_id), ("description", "Billing exports (EU)")]).await);
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", key_id)]).await)["notes"], "Owned by payments on-call");
+    json(&run(&plugin, "update_key", &[("key_id", key_id), ("notes", "")]).await);
+    assert!(json(&run(&plugin, "get_key", &[("key_id", key_id)]).await).get("notes").is_none());
+
+    assert!(!run(&plugin, "update_key", &[("key_id", key_id)]).await.is_success());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn escrowed_key_is_recovered_after_material_is_lost() {
+    let recovery_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, 2048).unwrap();
+    let recovery_private_key = BASE64.encode(recovery_key.to_pkcs8_der().unwrap().as_bytes());
+    let recovery_public_key = BASE64.encode(recovery_key.to_public_key().to_public_key_der().unwrap().as_bytes());
+
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let mut plugin = KeyManagementPlugin::with_security_module(security_module.clone())
+        .with_persistence(Arc::new(FilePersistence::new(dir.to_str().unwrap())));
+    let mut config = PluginConfig::new();
+    config.add_config("escrow_public_key".to_string(), recovery_public_key);
+    assert!(plugin.initialize(config).await);
+
+    let key_id = json(&run(&plugin, "create_key", &[("name", "escrowed")]).await)["id"].as_str().unwrap().to_string();
+    let other_id = json(&run(&plugin, "create_key", &[("name", "other")]).await)["id"].as_str().unwrap().to_string();
+    let data = BASE64.encode(b"ledger entry");
+    let ciphertext = encrypt(&plugin, &key_id, &data).await;
+
+    let escrow = json(&run(&plugin, "escrow_key", &[("key_id", &key_id)]).await);
+    assert_eq!(json(&run(&plugin, "get_key", &[("key_id", &key_id)]).await)["tags"]["escrow_key_fingerprint"], escrow["recovery_key_fingerprint"]);
+    let result = run(&plugin, "put_attachment", &[("key_id", &key_id), ("name", "escrow.bin"), ("data", &data)]).await;
+    assert!(result.get_error_message().contains("reserved"), "{}", result.get_error_message());
+
+    // 丢失原密钥材料后无法解密，从托管数据恢复后可以解密之前的密文
+    security_module.delete_key(&key_id).await.unwrap();
+    assert!(!run(&plugin, "decrypt", &[("key_id", &key_id), ("data", &ciphertext)]).await.is_success());
+    let result = run(&plugin, "recover_from_escrow", &[("key_id", &key_id), ("recovery_private_key", &recovery_private_key)]).await;
+    assert!(result.is_success(), "{}", result.get_error_message());
+    assert_eq!(run(&plugin, "decrypt", &[("key_id", &key_id), ("data", &ciphertext)]).await.get_result(), data);
+
+    // 托管数据绑定密钥ID，不能恢复到其他密钥
+    let blob = escrow["blob"].as_str().unwrap();
+    let result = run(&plugin, "recover_from_escrow", &[("key_id", &other_id), ("recovery_private_key", &recovery_private_key), ("blob", blob)]).await;
+    assert!(result.get_error_message().contains("belongs to another key"), "{}", result.get_error_message());
+
+    let history = json(&run(&plugin, "key_history", &[("key_id", &key_id)]).await);
+    let actions: Vec<&str> = history["events"].as_array().unwrap().iter().map(|event| event["action"].as_str().unwrap()).collect();
+    assert!(actions.contains(&"ESCROW_KEY") && actions.contains(&"RECOVER_FROM_ESCROW"), "{:?}", actions);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn list_owners_counts_keys_per_owner() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    for (name, owner) in [("a1", "alice"), ("a2", "alice"), ("b1", "bob"), ("c1", "carol"), ("c2", "carol"), ("c3", "carol")] {
+        json(&run(&plugin, "create_key", &[("name", name), ("user", owner)]).await);
+    }
+
+    let owners = json(&run(&plugin, "list_owners", &[]).await);
+    assert_eq!(owners, serde_json::json!([
+        {"owner": "alice", "key_count": 2},
+        {"owner": "bob", "key_count": 1},
+        {"owner": "carol", "key_count": 3},
+    ]));
+}
+
+#[tokio::test]
+async fn create_key_reports_every_invalid_field() {
+    let plugin = initialized(KeyManagementPlugin::new()).await;
+    let result = run(&plugin, "create_key", &[("name", ""), ("algorithm", "AES-128"), ("expiration_date", "tomorrow")]).await;
+    assert_eq!(result.get_error_code(), Some(ErrorCode::InvalidParams));
+
+    let errors: Vec<ValidationError> = serde_json::from_str(result.get_error_message()).unwrap();
+    let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+    assert_eq!(fields, ["name", "algorithm", "expiration_date"]);
+
+    // 类型和算法各自有效但不匹配时报告在 algorithm 上
+    let result = run(&plugin, "create_key", &[("name", "mismatch"), ("key_type", "SYMMETRIC"), ("algorithm", "ED25519")]).await;
+    let errors: Vec<ValidationError> = serde_json::from_str(result.get_error_message()).unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "algorithm");
+    assert!(errors[0].message.contains("not compatible"), "{}", errors[0]);
+}
+
+#[tokio::test]
+async fn keys_are_routed_to_the_security_module_they_were_created_in() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let persistence = Arc::new(FilePersistence::new(dir.to_str().unwrap()));
+    let software = Arc::new(SoftwareSecurityModule::new());
+    let hsm = Arc::new(SoftwareSecurityModule::new());
+    let plugin = initialized(
+        KeyManagementPlugin::with_security_module(software.clone())
+            .with_named_security_module("hsm", hsm.clone())
+            .with_persistence(persistence.clone()),
+    ).await;
+
+    let local = json(&run(&plugin, "create_key", &[("name", "local")]).await);
+    let remote = json(&run(&plugin, "create_key", &[("name", "remote"), ("security_module", "hsm")]).await);
+    assert_eq!(local["security_module_id"], "default");
+    assert_eq!(remote["security_module_id"], "hsm");
+    let (local_id, remote_id) = (local["id"].as_str().unwrap(), remote["id"].as_str().unwrap());
+
+    assert!(software.retrieve_key(local_id).await.is_ok() && software.retrieve_key(remote_id).await.is_err());
+    assert!(hsm.retrieve_key(remote_id).await.is_ok() && hsm.retrieve_key(local_id).await.is_err());
+
+    let data = BASE64.encode(b"routed");
+    for key_id in [local_id, remote_id] {
+        let ciphertext = encrypt(&plugin, key_id, &data).await;
+        assert_eq!(run(&plugin, "decrypt", &[("key_id", key_id), ("data", &ciphertext)]).await.get_result(), data);
+    }
+
+    let result = run(&plugin, "create_key", &[("name", "nowhere"), ("security_module", "tpm")]).await;
+    assert_eq!(result.get_error_code(), Some(ErrorCode::InvalidParams));
+
+    // 重启后没有注册原安全模块时，该密钥的操作明确失败
+    settle().await;
+    let mut restarted = KeyManagementPlugin::with_security_module(software.clone()).with_persistence(persistence);
+    assert!(restarted.initialize(PluginConfig::new()).await);
+    let result = run(&restarted, "encrypt", &[("key_id", remote_id), ("data", &data)]).await;
+    assert!(result.get_error_message().contains("Security module hsm is not registered"), "{}", result.get_error_message());
+    encrypt(&restarted, local_id, &data).await;
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn evicted_key_is_reloaded_into_its_own_security_module() {
+    let hsm = Arc::new(SoftwareSecurityModule::new());
+    let mut plugin = KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))
+        .with_named_security_module("hsm", hsm.clone())
+        .with_persistence(Arc::new(MemoryPersistence::new()));
+    let mut config = PluginConfig::new();
+    config.add_config("max_in_memory_keys".to_string(), "1".to_string());
+    assert!(plugin.initialize(config).await);
+
+    let remote = json(&run(&plugin, "create_key", &[("name", "remote"), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "ED25519"), ("security_module", "hsm")]).await)["id"]
+        .as_str().unwrap().to_string();
+    let data = BASE64.encode(b"pinned");
+    let signature = run(&plugin, "sign", &[("key_id", &remote), ("data", &data)]).await.get_result().to_string();
+    settle().await;
+    let local = json(&run(&plugin, "create_key", &[("name", "local")]).await)["id"].as_str().unwrap().to_string();
+    settle().await;
+    assert!(run(&plugin, "get_key", &[("key_id", &local)]).await.is_success());
+
+    // remote 已被淘汰，重新加载后仍由 hsm 验证，而不是默认安全模块
+    let items = serde_json::json!([{"key_id": remote, "data": data, "signature": signature}]);
+    let results = json(&run(&plugin, "verify_batch", &[("items", &items.to_string())]).await);
+    assert_eq!(results[0]["valid"], true, "{}", results);
+}
+
+#[tokio::test]
+async fn generated_data_key_is_recovered_by_decrypting_the_wrapped_copy() {
+    use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    let master_id = json(&run(&plugin, "create_key", &[("name", "master")]).await)["id"].as_str().unwrap().to_string();
+    let aad = BASE64.encode(b"orders");
+
+    let data_key = json(&run(&plugin, "generate_data_key", &[("key_id", &master_id), ("aad", &aad)]).await);
+    let plaintext_key = BASE64.decode(data_key["plaintext"].as_str().unwrap()).unwrap();
+    assert_eq!(plaintext_key.len(), 32);
+
+    // 用明文数据密钥加密后丢弃，之后由主密钥解开密文副本再解密
+    let mut sealed = b"order #1001".to_vec();
+    let key = || LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &plaintext_key).unwrap());
+    key().seal_in_place_append_tag(Nonce::assume_unique_for_key([7; 12]), Aad::empty(), &mut sealed).unwrap();
+
+    let unwrapped = run(&plugin, "decrypt", &[("key_id", &master_id), ("data", data_key["ciphertext"].as_str().unwrap()), ("aad", &aad)]).await;
+    let recovered = BASE64.decode(unwrapped.get_result()).unwrap();
+    assert_eq!(recovered, plaintext_key);
+    let recovered_key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &recovered).unwrap());
+    let opened = recovered_key.open_in_place(Nonce::assume_unique_for_key([7; 12]), Aad::empty(), &mut sealed).unwrap();
+    assert_eq!(opened, b"order #1001");
+
+    assert_eq!(BASE64.decode(json(&run(&plugin, "generate_data_key", &[("key_id", &master_id), ("length", "16")]).await)["plaintext"].as_str().unwrap()).unwrap().len(), 16);
+    assert!(!run(&plugin, "generate_data_key", &[("key_id", &master_id), ("length", "20")]).await.is_success());
+    let signer = json(&run(&plugin, "create_key", &[("name", "signer"), ("key_type", "ASYMMETRIC_PRIVATE"), ("algorithm", "ED25519")]).await);
+    assert!(!run(&plugin, "generate_data_key", &[("key_id", signer["id"].as_str().unwrap())]).await.is_success());
+}
+
+#[tokio::test]
+async fn binary_attachments_round_trip_within_size_limit() {
+    let dir = std::env::temp_dir().join(format!("password_manager_it_{}", uuid::Uuid::new_v4()));
+    let mut config = PluginConfig::new();
+    config.add_config("max_attachment_size".to_string(), "1024".to_string());
+    let mut plugin = KeyManagementPlugin::new().with_persistence(Arc::new(FilePersistence::new(dir.to_str().unwrap())));
+    assert!(plugin.initialize(config).await);
+
+    let created = json(&run(&plugin, "create_key", &[("name", "tls")]).await);
+    let key_id = created["id"].as_str().unwrap();
+    let blob: Vec<u8> = (0..1024).map(|i| (i % 251) as u8).collect();
+    let encoded = BASE64.encode(&blob);
+
+    let info = json(&run(&plugin, "put_attachment", &[("key_id", key_id), ("name", "chain.der"), ("data", &encoded)]).await);
+    assert_eq!(info, serde_json::json!({"name": "chain.der", "size": 1024}));
+    let fetched = run(&plugin, "get_attachment", &[("key_id", key_id), ("name", "chain.der")]).await;
+    assert_eq!(BASE64.decode(fetched.get_result()).unwrap(), blob);
+    let list = json(&run(&plugin, "list_attachments", &[("key_id", key_id)]).await);
+    assert_eq!(list, serde_json::json!([{"name": "chain.der", "size": 1024}]));
+
+    // 超过大小限制、非法附件名和不存在的密钥都被拒绝
+    let too_large = BASE64.encode(vec![0u8; 1025]);
+    let result = run(&plugin, "put_attachment", &[("key_id", key_id), ("name", "big"), ("data", &too_large)]).await;
+    assert!(result.get_error_message().starts_with("Attachment too large"), "{}", result.get_error_message());
+    for name in ["../escape", ".hidden", ""] {
+        assert!(!run(&plugin, "put_attachment", &[("key_id", key_id), ("name", name), ("data", "AA==")]).await.is_success(), "{}", name);
+    }
+    assert!(!run(&plugin, "put_attachment", &[("key_id", "missing"), ("name", "a"), ("data", "AA==")]).await.is_success());
+
+    assert!(run(&plugin, "delete_key", &[("key_id", key_id)]).await.is_success());
+    assert!(!run(&plugin, "list_attachments", &[("key_id", key_id)]).await.is_success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn failed_results_carry_stable_error_codes() {
+    let clock = MockClock::new(start_time());
+    let plugin = initialized(KeyManagementPlugin::new().with_clock(Arc::new(clock.clone()))).await;
+
+    let missing = run(&plugin, "get_key", &[("key_id", "missing")]).await;
+    assert_eq!(missing.get_error_code(), Some(ErrorCode::KeyNotFound));
+    assert_eq!(missing.get_error_code().unwrap().to_string(), "KEY_NOT_FOUND");
+    assert_eq!(missing.get_error_message(), "Key not found");
+
+    let created = json(&run(&plugin, "create_key", &[("name", "short-lived"), ("expiration_date", "2030-01-01T00:00:01Z")]).await);
+    let key_id = created["id"].as_str().unwrap();
+    let data = BASE64.encode(b"payload");
+    let signed = run(&plugin, "sign", &[("key_id", key_id), ("data", &data)]).await;
+    assert!(signed.is_success());
+    assert_eq!(signed.get_error_code(), None);
+
+    clock.advance(chrono::Duration::seconds(1));
+    plugin.sweep_expirations().await;
+    let expired = run(&plugin, "sign", &[("key_id", key_id), ("data", &data)]).await;
+    assert_eq!(expired.get_error_code(), Some(ErrorCode::InvalidStatus));
+
+    let cases = [
+        (run(&plugin, "get_key", &[]).await, ErrorCode::InvalidParams),
+        (run(&plugin, "create_key", &[("name", "k"), ("expiration_date", "soon")]).await, ErrorCode::InvalidParams),
+        (run(&plugin, "no_such_command", &[]).await, ErrorCode::UnknownCommand),
+    ];
+    for (result, code) in cases {
+        assert_eq!(result.get_error_code(), Some(code), "{}", result.get_error_message());
+    }
+}
+
+// 最简单的 HTTP 接收端：记录每个请求的 JSON 请求体，按 statuses 依次返回状态码，用完后返回 200
+struct MockWebhook {
+    url: String,
+    received: Arc<Mutex<Vec<Value>>>,
+}
+
+async fn mock_webhook(statuses: &[u16]) -> MockWebhook {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/audit", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let statuses = Arc::new(Mutex::new(statuses.iter().copied().collect::<VecDeque<u16>>()));
+
+    let requests = Arc::clone(&received);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let requests = Arc::clone(&requests);
+            let statuses = Arc::clone(&statuses);
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                loop {
+                    let mut content_length = 0;
+                    let mut line = String::new();
+                    // 读取请求行和请求头，连接关闭时结束
+                    loop {
+                        line.clear();
+                        if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':')
+                            && name.eq_ignore_ascii_case("content-length")
+                        {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+
+                    let mut body = vec![0; content_length];
+                    stream.read_exact(&mut body).await.unwrap();
+                    requests.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+
+                    let status = statuses.lock().unwrap().pop_front().unwrap_or(200);
+                    let response = format!("HTTP/1.1 {} Mock\r\ncontent-length: 0\r\n\r\n", status);
+                    stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+        }
+    });
+
+    MockWebhook { url, received }
+}
+
+async fn wait_for_requests(webhook: &MockWebhook, count: usize) -> Vec<Value> {
+    for _ in 0..100 {
+        let received = webhook.received.lock().unwrap().clone();
+        if received.len() >= count {
+            return received;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("webhook 只收到 {} 个请求", webhook.received.lock().unwrap().len());
+}
+
+fn webhook_config(url: &str, settings: &[(&str, &str)]) -> PluginConfig {
+    let mut config = PluginConfig::new();
+    config.add_config("audit_webhook_url".to_string(), url.to_string());
+    for (key, value) in settings {
+        config.add_config(key.to_string(), value.to_string());
+    }
+    config
+}
+
+#[tokio::test]
+async fn audit_entries_are_posted_to_webhook() {
+    let webhook = mock_webhook(&[]).await;
+    let mut plugin = KeyManagementPlugin::new();
+    assert!(plugin.initialize(webhook_config(&webhook.url, &[])).await);
+
+    let created = json(&run(&plugin, "create_key", &[("name", "audited"), ("user", "alice")]).await);
+
+    let received = wait_for_requests(&webhook, 1).await;
+    assert_eq!(received[0]["action"], "CREATE_KEY");
+    assert_eq!(received[0]["user"], "alice");
+    assert_eq!(received[0]["key_id"], created["id"]);
+    assert_eq!(received[0]["success"], true);
+}
+
+#[tokio::test]
+async fn webhook_retries_failed_deliveries_and_replays_buffered_entries() {
+    // 第一个条目重试一次后成功；第二个条目两次都失败被缓存，第三个成功后补发
+    let webhook = mock_webhook(&[500, 200, 503, 503]).await;
+    let mut plugin = KeyManagementPlugin::new();
+    let config = webhook_config(&webhook.url, &[("audit_webhook_max_retries", "1"), ("audit_webhook_buffer_failed", "true")]);
+    assert!(plugin.initialize(config).await);
+
+    for name in ["first", "second", "third"] {
+        json(&run(&plugin, "create_key", &[("name", name)]).await);
+        tokio::time::sleep(Duration::from_millis(400)).await;
+    }
+
+    let received = wait_for_requests(&webhook, 6).await;
+    let details: Vec<&str> = received.iter().map(|entry| entry["details"].as_str().unwrap()).collect();
+    assert!(details[0].contains("first") && details[1].contains("first"));
+    assert!(details[2].contains("second") && details[3].contains("second"));
+    assert!(details[4].contains("third"));
+    assert!(details[5].contains("second"));
+    assert_eq!(details.len(), 6);
+}
+
+#[tokio::test]
+async fn invalid_webhook_configuration_fails_initialization() {
+    for settings in [
+        webhook_config("ftp://example.com", &[]),
+        webhook_config("http://127.0.0.1:9", &[("audit_webhook_queue_size", "0")]),
+        webhook_config("http://127.0.0.1:9", &[("audit_webhook_max_retries", "many")]),
+        webhook_config("http://127.0.0.1:9", &[("audit_webhook_batch_size", "0")]),
+        webhook_config("http://127.0.0.1:9", &[("audit_webhook_batch_ms", "soon")]),
+    ] {
+        let mut plugin = KeyManagementPlugin::new();
+        assert!(!plugin.initialize(settings).await);
+    }
+}
+
+fn batch_details(batch: &Value) -> Vec<&str> {
+    batch.as_array().unwrap().iter().map(|entry| entry["details"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn rapid_audit_entries_arrive_in_one_batch() {
+    let webhook = mock_webhook(&[]).await;
+    let mut plugin = KeyManagementPlugin::new();
+    assert!(plugin.initialize(webhook_config(&webhook.url, &[("audit_webhook_batch_ms", "300")])).await);
+
+    for name in ["one", "two", "three"] {
+        json(&run(&plugin, "create_key", &[("name", name)]).await);
+    }
+
+    let received = wait_for_requests(&webhook, 1).await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(webhook.received.lock().unwrap().len(), 1);
+    let details = batch_details(&received[0]);
+    assert_eq!(details.len(), 3);
+    assert!(details[0].contains("one") && details[2].contains("three"));
+}
+
+#[tokio::test]
+async fn full_batch_is_sent_before_the_deadline() {
+    let webhook = mock_webhook(&[]).await;
+    let mut plugin = KeyManagementPlugin::new();
+    let config = webhook_config(&webhook.url, &[("audit_webhook_batch_size", "2"), ("audit_webhook_batch_ms", "60000")]);
+    assert!(plugin.initialize(config).await);
+
+    for name in ["a", "b", "c", "d"] {
+        json(&run(&plugin, "create_key", &[("name", name)]).await);
+    }
+
+    let received = wait_for_requests(&webhook, 2).await;
+    assert_eq!(batch_details(&received[0]).len(), 2);
+    assert_eq!(batch_details(&received[1]).len(), 2);
+}
+
+#[tokio::test]
+async fn stop_flushes_pending_batch() {
+    let webhook = mock_webhook(&[]).await;
+    let mut plugin = KeyManagementPlugin::new();
+    assert!(plugin.initialize(webhook_config(&webhook.url, &[("audit_webhook_batch_ms", "60000")])).await);
+
+    json(&run(&plugin, "create_key", &[("name", "pending")]).await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(webhook.received.lock().unwrap().is_empty());
+
+    plugin.stop().await;
+    let received = webhook.received.lock().unwrap().clone();
+    assert_eq!(received.len(), 1);
+    assert!(batch_details(&received[0])[0].contains("pending"));
+}
+
+#[tokio::test]
+async fn signed_responses_verify_and_detect_tampering() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let material = security_module.generate_key(KeyAlgorithm::ED25519).await.unwrap();
+    security_module.store_key("response-signer", &material).await.unwrap();
+
+    let mut plugin = KeyManagementPlugin::with_security_module(security_module.clone());
+    let mut config = PluginConfig::new();
+    config.add_config("response_signing_key_id".to_string(), "response-signer".to_string());
+    assert!(plugin.initialize(config).await);
+
+    let spki = security_module.get_public_key("response-signer", PublicKeyFormat::Der).await.unwrap();
+    let spki = SubjectPublicKeyInfoRef::from_der(&spki).unwrap();
+    let public_key = UnparsedPublicKey::new(&signature::ED25519, spki.subject_public_key.raw_bytes().to_vec());
+
+    // 成功和失败的结果都带签名
+    for result in [
+        run(&plugin, "create_key", &[("name", "signed")]).await,
+        run(&plugin, "get_key", &[("key_id", "missing")]).await,
+    ] {
+        let signature_bytes = result.get_signature().expect("结果缺少签名").to_vec();
+        public_key.verify(&result.signing_payload(), &signature_bytes).unwrap();
+
+        let mut tampered = CommandResult::new(result.is_success(), format!("{} ", result.get_result()), result.get_error_message().to_string());
+        tampered.set_signature(Some(signature_bytes.clone()));
+        assert!(public_key.verify(&tampered.signing_payload(), &signature_bytes).is_err());
+
+        let flipped = CommandResult::new(!result.is_success(), result.get_result().to_string(), result.get_error_message().to_string());
+        assert!(public_key.verify(&flipped.signing_payload(), &signature_bytes).is_err());
+    }
+}
+
+#[tokio::test]
+async fn responses_are_unsigned_by_default_and_fail_closed_without_signing_key() {
+    let plugin = initialized(KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()))).await;
+    assert!(run(&plugin, "create_key", &[("name", "plain")]).await.get_signature().is_none());
+
+    let mut plugin = KeyManagementPlugin::with_security_module(Arc::new(SoftwareSecurityModule::new()));
+    let mut config = PluginConfig::new();
+    config.add_config("response_signing_key_id".to_string(), "missing".to_string());
+    assert!(plugin.initialize(config).await);
+
+    let result = run(&plugin, "create_key", &[("name", "unsigned")]).await;
+    assert!(!result.is_success());
+    assert!(result.get_result().is_empty());
+    assert!(result.get_error_message().starts_with("Failed to sign response"));
+}
+
+async fn ed25519_verifier(security_module: &SoftwareSecurityModule, key_id: &str) -> UnparsedPublicKey<Vec<u8>> {
+    let spki = security_module.get_public_key(key_id, PublicKeyFormat::Der).await.unwrap();
+    let spki = SubjectPublicKeyInfoRef::from_der(&spki).unwrap();
+    UnparsedPublicKey::new(&signature::ED25519, spki.subject_public_key.raw_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn results_signed_before_and_after_audit_key_rotation_both_verify() {
+    let security_module = Arc::new(SoftwareSecurityModule::new());
+    let material = security_module.generate_key(KeyAlgorithm::ED25519).await.unwrap();
+    security_module.store_key("response-signer", &material).await.unwrap();
+
+    let mut plugin = KeyManagementPlugin::with_security_module(security_module.clone());
+    let mut config = PluginConfig::new();
+    config.add_config("response_signing_key_id".to_string(), "response-signer".to_string());
+    assert!(plugin.initialize(config).await);
+
+    let before = run(&plugin, "create_key", &[("name", "before")]).await;
+    assert_eq!(before.get_signing_key_id(), Some("response-signer"));
+
+    let rotated = run(&plugin, "rotate_audit_key", &[("algorithm", "ED25519")]).await;
+    let checkpoint = json(&rotated);
+    let new_key_id = checkpoint["key_id"].as_str().unwrap();
+    assert_eq!(checkpoint["previous_key_id"], "response-signer");
+    assert_eq!(rotated.get_signing_key_id(), Some(new_key_id));
+
+    let after = run(&plugin, "create_key", &[("name", "after")]).await;
+    assert_eq!(after.get_signing_key_id(), Some(new_key_id));
+
+    // 每个结果用 signing_key_id 指明的密钥验证，换用另一个密钥验证失败
+    let old_key = ed25519_verifier(&security_module, "response-signer").await;
+    let new_key = ed25519_verifier(&security_module, new_key_id).await;
+    old_key.verify(&before.signing_payload(), before.get_signature().unwrap()).unwrap();
+    new_key.verify(&after.signing_payload(), after.get_signature().unwrap()).unwrap();
+    assert!(new_key.verify(&before.signing_payload(), before.get_signature().unwrap()).is_err());
+    assert!(old_key.verify(&after.signing_payload(), after.get_signature().unwrap()).is_err());
+
+    // 再次轮换时原密钥由插件管理且处于启用状态，检查点由新旧两个密钥分别签名
+    let checkpoint = json(&run(&plugin, "rotate_audit_key", &[]).await);
+    assert_eq!(checkpoint["previous_key_id"], new_key_id);
+    let payload = serde_json::json!([
+        checkpoint["previous_key_id"], checkpoint["key_id"], checkpoint["public_key"], checkpoint["rotated_at"],
+    ]).to_string();
+    let signature_of = |field: &str| BASE64.decode(checkpoint[field].as_str().unwrap()).unwrap();
+    let newest_key = ed25519_verifier(&security_module, checkpoint["key_id"].as_str().unwrap()).await;
+    newest_key.verify(payload.as_bytes(), &signature_of("signature")).unwrap();
+    new_key.verify(payload.as_bytes(), &signature_of("previous_signature")).unwrap();
+
+    let retired = json(&run(&plugin, "get_key", &[("key_id", new_key_id)]).await);
+    assert_eq!(retired["tags"]["audit_signing"], "retired");
+    assert_eq!(retired["tags"]["rotated_to"], checkpoint["key_id"]);
+}
+
+#[tokio::test]
+async fn canonical_metadata_signature_ignores_tag_insertion_order() {
+    let security_module = SoftwareSecurityModule::new();
+    let material = security_module.generate_key(KeyAlgorithm::ED25519).await.unwrap();
+    security_module.store_key("metadata-signer", &material).await.unwrap();
+
+    let mut first = KeyMetadata::new("k".to_string(), String::new(), KeyType::Symmetric, KeyAlgorithm::AES256, "alice".to_string(), false);
+    let mut second = first.clone();
+    let tags: Vec<(String, String)> = (0..16).map(|i| (format!("tag{}", i), i.to_string())).collect();
+    first.tags = tags.iter().cloned().collect();
+    second.tags = HashMap::new();
+    for (key, value) in tags.iter().rev() {
+        second.tags.insert(key.clone(), value.clone());
+    }
+
+    let first_bytes = canonical_json::to_vec(&first).unwrap();
+    assert_eq!(first_bytes, canonical_json::to_vec(&second).unwrap());
+    assert_eq!(
+        security_module.sign_data("metadata-signer", &first_bytes).await.unwrap(),
+        security_module.sign_data("metadata-signer", &canonical_json::to_vec(&second).unwrap()).await.unwrap(),
+    );
+}
@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+use password_manager::{BasePlugin, PluginConfig, PluginSDK, PluginState, ShutdownSignals};
+use std::process::Command;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+
+fn send_sigterm() {
+    let status = Command::new("kill")
+        .args(["-TERM", &std::process::id().to_string()])
+        .status()
+        .expect("执行 kill 失败");
+    assert!(status.success());
+}
+
+#[tokio::test]
+async fn sigterm_stops_plugin_gracefully() {
+    // 先安装一个处理器，避免运行循环开始监听前到达的 SIGTERM 按默认行为结束测试进程
+    let _guard = signal(SignalKind::terminate()).unwrap();
+
+    let mut plugin = BasePlugin::new();
+    assert!(plugin.initialize(PluginConfig::new()).await);
+    let health = plugin.health();
+
+    let run = tokio::spawn(async move {
+        let result = plugin
+            .run_until_signal(&ShutdownSignals::default().with_ctrl_c(false))
+            .await;
+        (result, plugin.state())
+    });
+
+    // 运行循环何时开始监听不确定，重复发送直到它返回
+    let (result, state) = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if run.is_finished() {
+                break run.await.unwrap();
+            }
+            if health.state() != PluginState::Stopped {
+                send_sigterm();
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("收到 SIGTERM 后插件应停止");
+
+    result.unwrap();
+    assert_eq!(state, PluginState::Stopped);
+}
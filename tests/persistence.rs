@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+
+use futures::StreamExt;
+
+use password_manager::key_management::{AuditLogEntry, AuditQueue, KeyAlgorithm, KeyMetadata, KeyStatus, KeyType};
+#[cfg(feature = "sqlite")]
+use password_manager::persistence::DbPersistence;
+use password_manager::persistence::{build_persistence, diff_keystores, CachedPersistence, FilePersistence, KeyQuery, MemoryPersistence, PersistenceInterface, ReplicatingPersistence};
+use password_manager::timestamp;
+use password_manager::PluginConfig;
+
+fn temp_path(suffix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("password_manager_test_{}{}", uuid::Uuid::new_v4(), suffix))
+}
+
+// 每种持久化后端各创建一个实例，返回后端名称、实例和需要清理的路径
+//
+// 未启用 sqlite 特性时只测试文件存储和内存存储，CI 中分别以默认特性和 `--no-default-features` 运行
+async fn backends() -> Vec<(&'static str, Box<dyn PersistenceInterface>, PathBuf)> {
+    let mut backends: Vec<(&'static str, Box<dyn PersistenceInterface>, PathBuf)> = Vec::new();
+
+    let dir = temp_path("");
+    backends.push(("file", Box::new(FilePersistence::new(dir.to_str().unwrap())), dir));
+
+    #[cfg(feature = "sqlite")]
+    {
+        let db_path = temp_path(".db");
+        let db = DbPersistence::new(&format!("sqlite:{}?mode=rwc", db_path.display())).await.unwrap();
+        backends.push(("db", Box::new(db), db_path));
+    }
+
+    backends.push(("memory", Box::new(MemoryPersistence::new()), PathBuf::new()));
+
+    backends
+}
+
+fn cleanup(path: &Path) {
+    if path.is_dir() {
+        let _ = std::fs::remove_dir_all(path);
+    } else {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+async fn save(
+    persistence: &dyn PersistenceInterface,
+    name: &str,
+    owner: &str,
+    customize: impl FnOnce(&mut KeyMetadata),
+) -> String {
+    let mut metadata = KeyMetadata::new(
+        name.to_string(),
+        String::new(),
+        KeyType::Symmetric,
+        KeyAlgorithm::AES256,
+        owner.to_string(),
+        false,
+    );
+    customize(&mut metadata);
+    persistence.save_key_metadata(&metadata).await.unwrap();
+    metadata.id
+}
+
+fn ids(list: &[KeyMetadata]) -> Vec<String> {
+    let mut ids: Vec<String> = list.iter().map(|metadata| metadata.id.clone()).collect();
+    ids.sort();
+    ids
+}
+
+fn sorted(expected: &[&String]) -> Vec<String> {
+    let mut expected: Vec<String> = expected.iter().map(|id| id.to_string()).collect();
+    expected.sort();
+    expected
+}
+
+#[tokio::test]
+async fn typed_query_filters_on_every_backend() {
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        let vault = save(persistence, "prod-vault", "alice", |m| {
+            m.tags.insert("env".to_string(), "prod".to_string());
+        }).await;
+        let signer = save(persistence, "release-signer", "bob", |m| {
+            m.key_type = KeyType::AsymmetricPrivate;
+            m.algorithm = KeyAlgorithm::ED25519;
+            m.tags.insert("env".to_string(), "prod".to_string());
+            m.tenant = "acme".to_string();
+        }).await;
+        let old = save(persistence, "old-vault", "alice", |m| {
+            m.status = KeyStatus::Suspended;
+            m.tags.insert("env".to_string(), "dev".to_string());
+        }).await;
+
+        let cases = [
+            (KeyQuery::new(), sorted(&[&vault, &signer, &old])),
+            (KeyQuery::new().with_status(KeyStatus::Suspended), sorted(&[&old])),
+            (KeyQuery::new().with_key_type(KeyType::AsymmetricPrivate), sorted(&[&signer])),
+            (KeyQuery::new().with_algorithm(KeyAlgorithm::AES256), sorted(&[&vault, &old])),
+            (KeyQuery::new().with_owner("alice".to_string()), sorted(&[&vault, &old])),
+            (KeyQuery::new().with_name_contains("vault".to_string()), sorted(&[&vault, &old])),
+            (KeyQuery::new().with_name_contains("Vault".to_string()), vec![]),
+            (KeyQuery::new().with_tag("env".to_string(), "prod".to_string()).with_owner("bob".to_string()), sorted(&[&signer])),
+            (KeyQuery::new().with_tenant("acme".to_string()), sorted(&[&signer])),
+            (KeyQuery::new().with_tenant("default".to_string()), sorted(&[&vault, &old])),
+        ];
+        for (query, expected) in cases {
+            let list = persistence.query_keys(&query).await.unwrap();
+            assert_eq!(ids(&list), expected, "{} {:?}", backend, query);
+
+            let streamed: Vec<KeyMetadata> = persistence.query_keys_stream(query.clone())
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            assert_eq!(ids(&streamed), expected, "{} {:?}", backend, query);
+        }
+
+        cleanup(&path);
+    }
+}
+
+#[tokio::test]
+async fn string_filters_are_adapted_to_typed_query() {
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        let vault = save(persistence, "vault", "alice", |_| {}).await;
+        save(persistence, "other", "bob", |_| {}).await;
+
+        let filters = HashMap::from([
+            ("owner".to_string(), "alice".to_string()),
+            ("name_contains".to_string(), "vau".to_string()),
+        ]);
+        let list = persistence.list_key_metadata(Some(filters)).await.unwrap();
+        assert_eq!(ids(&list), vec![vault.clone()], "{}", backend);
+
+        // 拼写错误的枚举值返回错误，而不是静默地匹配不到任何密钥
+        let filters = HashMap::from([("status".to_string(), "ACTIV".to_string())]);
+        assert!(persistence.list_key_metadata(Some(filters.clone())).await.is_err(), "{}", backend);
+        let results: Vec<_> = persistence.list_key_metadata_stream(Some(filters)).collect().await;
+        assert!(results.len() == 1 && results[0].is_err(), "{}", backend);
+
+        cleanup(&path);
+    }
+}
+
+#[tokio::test]
+async fn key_stats_match_on_every_backend() {
+    let now = chrono::Utc::now();
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        save(persistence, "active", "alice", |m| m.expiration_date = Some(now + chrono::Duration::days(10))).await;
+        save(persistence, "later", "alice", |m| m.expiration_date = Some(now + chrono::Duration::days(31))).await;
+        save(persistence, "past", "alice", |m| {
+            m.status = KeyStatus::Expired;
+            m.expiration_date = Some(now - chrono::Duration::days(1));
+        }).await;
+        save(persistence, "signer", "bob", |m| {
+            m.status = KeyStatus::Suspended;
+            m.key_type = KeyType::AsymmetricPrivate;
+            m.algorithm = KeyAlgorithm::ED25519;
+            m.tenant = "acme".to_string();
+        }).await;
+        save(persistence, "deleted", "bob", |m| m.deleted_at = Some(now)).await;
+
+        let stats = persistence.key_stats(None, now).await.unwrap();
+        assert_eq!(stats.total, 4, "{}", backend);
+        let counts = |pairs: &[(&str, usize)]| pairs.iter().map(|(key, count)| (key.to_string(), *count)).collect();
+        assert_eq!(stats.by_status, counts(&[("ACTIVE", 2), ("EXPIRED", 1), ("SUSPENDED", 1)]), "{}", backend);
+        assert_eq!(stats.by_key_type, counts(&[("ASYMMETRIC_PRIVATE", 1), ("SYMMETRIC", 3)]), "{}", backend);
+        assert_eq!(stats.by_algorithm, counts(&[("AES-256", 3), ("ED25519", 1)]), "{}", backend);
+        assert_eq!(stats.expiring_within_30_days, 1, "{}", backend);
+
+        let stats = persistence.key_stats(Some("acme"), now).await.unwrap();
+        assert_eq!(stats.total, 1, "{}", backend);
+        assert_eq!(stats.by_status, counts(&[("SUSPENDED", 1)]), "{}", backend);
+
+        cleanup(&path);
+    }
+}
+
+#[tokio::test]
+async fn timestamps_round_trip_exactly_on_every_backend() {
+    // 纳秒精度的时间按毫秒截断后保存
+    let precise = chrono::DateTime::parse_from_rfc3339("2030-05-06T07:08:09.123456789+08:00").unwrap().with_timezone(&chrono::Utc);
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        let mut metadata = KeyMetadata::new("k".to_string(), String::new(), KeyType::Symmetric, KeyAlgorithm::AES256, "alice".to_string(), false);
+        metadata.expiration_date = Some(timestamp::truncate(precise));
+        metadata.deleted_at = Some(timestamp::now());
+        persistence.save_key_metadata(&metadata).await.unwrap();
+
+        let loaded = persistence.load_key_metadata(&metadata.id).await.unwrap();
+        assert_eq!(loaded.created_at, metadata.created_at, "{}", backend);
+        assert_eq!(loaded.updated_at, metadata.updated_at, "{}", backend);
+        assert_eq!(loaded.expiration_date, metadata.expiration_date, "{}", backend);
+        assert_eq!(loaded.deleted_at, metadata.deleted_at, "{}", backend);
+
+        let entry = AuditLogEntry::new("CREATE_KEY".to_string(), "alice".to_string(), Some(metadata.id.clone()), String::new(), true);
+        persistence.save_audit_log(&entry).await.unwrap();
+        let logs = persistence.load_audit_logs(None, None).await.unwrap();
+        assert_eq!(logs[0].timestamp, entry.timestamp, "{}", backend);
+
+        cleanup(&path);
+    }
+
+    assert_eq!(timestamp::format(&timestamp::truncate(precise)), "2030-05-05T23:08:09.123Z");
+    assert_eq!(timestamp::parse("2030-05-05T23:08:09.123456Z").unwrap(), timestamp::truncate(precise));
+}
+
+#[tokio::test]
+async fn owner_counts_match_on_every_backend() {
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        for (name, owner) in [("a1", "alice"), ("a2", "alice"), ("a3", "alice"), ("b1", "bob"), ("c1", "carol")] {
+            save(persistence, name, owner, |_| {}).await;
+        }
+        save(persistence, "b2", "bob", |m| m.tenant = "acme".to_string()).await;
+        save(persistence, "deleted", "dave", |m| m.deleted_at = Some(chrono::Utc::now())).await;
+
+        let counts = |pairs: &[(&str, usize)]| pairs.iter().map(|(owner, count)| (owner.to_string(), *count)).collect();
+        assert_eq!(persistence.count_keys_by_owner(None).await.unwrap(), counts(&[("alice", 3), ("bob", 2), ("carol", 1)]), "{}", backend);
+        assert_eq!(persistence.count_keys_by_owner(Some("default")).await.unwrap(), counts(&[("alice", 3), ("bob", 1), ("carol", 1)]), "{}", backend);
+        assert_eq!(persistence.count_keys_by_owner(Some("acme")).await.unwrap(), counts(&[("bob", 1)]), "{}", backend);
+
+        cleanup(&path);
+    }
+}
+
+#[tokio::test]
+async fn access_control_lists_round_trip_on_every_backend() {
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        let restricted = save(persistence, "restricted", "alice", |metadata| {
+            metadata.acl = Some(HashMap::from([("bob".to_string(), ["encrypt".to_string(), "decrypt".to_string()].into())]));
+        }).await;
+        let open = save(persistence, "open", "alice", |_| {}).await;
+
+        let acl = persistence.load_key_metadata(&restricted).await.unwrap().acl.unwrap();
+        assert_eq!(acl["bob"].iter().collect::<Vec<_>>(), ["decrypt", "encrypt"], "{}", backend);
+        assert!(persistence.load_key_metadata(&open).await.unwrap().acl.is_none(), "{}", backend);
+
+        cleanup(&path);
+    }
+}
+
+fn persistence_config(settings: &[(&str, &str)]) -> PluginConfig {
+    let mut config = PluginConfig::new();
+    for (key, value) in settings {
+        config.add_config(key.to_string(), value.to_string());
+    }
+    config
+}
+
+#[tokio::test]
+async fn every_backend_can_be_built_from_config() {
+    let dir = temp_path("");
+    let mut configs = vec![
+        ("file", persistence_config(&[("persistence_backend", "file"), ("persistence_path", dir.to_str().unwrap())]), dir.clone()),
+    ];
+    #[cfg(feature = "sqlite")]
+    {
+        // 未指定后端时按 db_url 使用 sqlite
+        let db_path = temp_path(".db");
+        let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
+        configs.push(("sqlite", persistence_config(&[("db_url", &db_url)]), db_path));
+    }
+    configs.push(("memory", persistence_config(&[("persistence_backend", "memory")]), PathBuf::new()));
+
+    for (backend, config, path) in configs {
+        let persistence = build_persistence(&config).await.unwrap();
+        let id = save(persistence.as_ref(), "built", "alice", |_| {}).await;
+        assert_eq!(persistence.load_key_metadata(&id).await.unwrap().name, "built", "{}", backend);
+        cleanup(&path);
+    }
+
+    for (settings, error) in [
+        (vec![("persistence_backend", "redis")], "Invalid persistence_backend: redis"),
+        (vec![("persistence_backend", "file")], "Missing config: persistence_path"),
+        (vec![], "Missing config: persistence_backend"),
+    ] {
+        let result = build_persistence(&persistence_config(&settings)).await;
+        assert!(result.as_ref().err().is_some_and(|e| e.contains(error)), "{}", error);
+    }
+}
+
+#[tokio::test]
+async fn aliases_round_trip_on_every_backend() {
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        assert!(persistence.load_aliases().await.unwrap().is_empty(), "{}", backend);
+
+        persistence.save_alias("primary", "k1").await.unwrap();
+        persistence.save_alias("legacy", "k1").await.unwrap();
+        persistence.save_alias("primary", "k2").await.unwrap();
+        persistence.delete_alias("legacy").await.unwrap();
+        persistence.delete_alias("missing").await.unwrap();
+
+        let aliases = persistence.load_aliases().await.unwrap();
+        assert_eq!(aliases, HashMap::from([("primary".to_string(), "k2".to_string())]), "{}", backend);
+
+        cleanup(&path);
+    }
+}
+
+#[tokio::test]
+async fn attachments_round_trip_and_are_deleted_with_the_key() {
+    for (backend, persistence, path) in backends().await {
+        let persistence = persistence.as_ref();
+        let key_id = save(persistence, "with-attachments", "alice", |_| {}).await;
+        let blob: Vec<u8> = (0..=255).collect();
+
+        persistence.put_attachment(&key_id, "chain.der", &blob).await.unwrap();
+        persistence.put_attachment(&key_id, "note", b"first").await.unwrap();
+        persistence.put_attachment(&key_id, "note", b"second").await.unwrap();
+        assert_eq!(persistence.get_attachment(&key_id, "chain.der").await.unwrap(), blob, "{}", backend);
+        assert_eq!(persistence.get_attachment(&key_id, "note").await.unwrap(), b"second", "{}", backend);
+        assert!(persistence.get_attachment(&key_id, "missing").await.is_err(), "{}", backend);
+
+        let mut list = persistence.list_attachments(&key_id).await.unwrap();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        let summary: Vec<(String, usize)> = list.into_iter().map(|info| (info.name, info.size)).collect();
+        assert_eq!(summary, vec![("chain.der".to_string(), 256), ("note".to_string(), 6)], "{}", backend);
+
+        persistence.delete_key_metadata(&key_id).await.unwrap();
+        assert!(persistence.list_attachments(&key_id).await.unwrap().is_empty(), "{}", backend);
+        assert!(persistence.get_attachment(&key_id, "note").await.is_err(), "{}", backend);
+
+        cleanup(&path);
+    }
+}
+
+#[tokio::test]
+async fn keystore_diff_reports_added_and_modified_keys() {
+    let local_path = temp_path("");
+    let other_path = temp_path("");
+    let local = FilePersistence::new(local_path.to_str().unwrap());
+    let other = FilePersistence::new(other_path.to_str().unwrap());
+
+    let mut shared = KeyMetadata::new(
+        "shared".to_string(),
+        String::new(),
+        KeyType::Symmetric,
+        KeyAlgorithm::AES256,
+        "alice".to_string(),
+        false,
+    );
+    local.save_key_metadata(&shared).await.unwrap();
+    other.save_key_metadata(&shared).await.unwrap();
+    let unchanged = save(&local, "unchanged", "alice", |_| {}).await;
+    let metadata = local.load_key_metadata(&unchanged).await.unwrap();
+    other.save_key_metadata(&metadata).await.unwrap();
+    assert!(diff_keystores(&local, &other).await.unwrap().is_empty());
+
+    // 本地新增一个密钥，另一侧的共享密钥被轮换
+    let added = save(&local, "added", "alice", |_| {}).await;
+    shared.version += 1;
+    shared.updated_at += chrono::Duration::seconds(1);
+    other.save_key_metadata(&shared).await.unwrap();
+
+    let diff = diff_keystores(&local, &other).await.unwrap();
+    assert_eq!(diff.only_local, vec![added.clone()]);
+    assert!(diff.only_other.is_empty());
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].id, shared.id);
+    assert_eq!((diff.changed[0].local_version, diff.changed[0].other_version), (1, 2));
+
+    let reversed = diff_keystores(&other, &local).await.unwrap();
+    assert_eq!(reversed.only_other, vec![added]);
+    assert!(reversed.only_local.is_empty());
+
+    cleanup(&local_path);
+    cleanup(&other_path);
+}
+
+#[tokio::test]
+async fn replicated_writes_reach_every_secondary_after_flush() {
+    let mut stores: Vec<(Arc<dyn PersistenceInterface + Send + Sync>, PathBuf)> = Vec::new();
+    for _ in 0..3 {
+        let dir = temp_path("");
+        stores.push((Arc::new(FilePersistence::new(dir.to_str().unwrap())), dir));
+    }
+    let primary = Arc::clone(&stores[0].0);
+    let secondaries = vec![Arc::clone(&stores[1].0), Arc::clone(&stores[2].0)];
+    let replicating = ReplicatingPersistence::new(Arc::clone(&primary), secondaries.clone());
+
+    let kept = save(&replicating, "kept", "alice", |_| {}).await;
+    let removed = save(&replicating, "removed", "alice", |_| {}).await;
+    replicating.delete_key_metadata(&removed).await.unwrap();
+    let entry = AuditLogEntry::new("CREATE_KEY".to_string(), "alice".to_string(), Some(kept.clone()), "created".to_string(), true);
+    replicating.save_audit_log(&entry).await.unwrap();
+
+    // 读取直接来自主存储，不需要等待同步
+    assert_eq!(ids(&replicating.query_keys(&KeyQuery::new()).await.unwrap()), vec![kept.clone()]);
+
+    replicating.flush().await;
+    for secondary in &secondaries {
+        assert_eq!(ids(&secondary.query_keys(&KeyQuery::new()).await.unwrap()), vec![kept.clone()]);
+        let logs = secondary.load_audit_logs(None, None).await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].id, entry.id);
+        assert!(diff_keystores(primary.as_ref(), secondary.as_ref()).await.unwrap().is_empty());
+    }
+
+    for (_, path) in &stores {
+        cleanup(path);
+    }
+}
+
+/// 记录读写次数的持久化存储，其余行为交给文件存储
+struct CountingPersistence {
+    inner: FilePersistence,
+    reads: AtomicUsize,
+    writes: AtomicUsize,
+}
+
+impl CountingPersistence {
+    fn new(path: &Path) -> Self {
+        Self {
+            inner: FilePersistence::new(path.to_str().unwrap()),
+            reads: AtomicUsize::new(0),
+            writes: AtomicUsize::new(0),
+        }
+    }
+
+    fn counts(&self) -> (usize, usize) {
+        (self.reads.load(Ordering::SeqCst), self.writes.load(Ordering::SeqCst))
+    }
+}
+
+#[async_trait]
+impl PersistenceInterface for CountingPersistence {
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        self.inner.save_key_metadata(metadata).await
+    }
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.load_key_metadata(key_id).await
+    }
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        self.inner.delete_key_metadata(key_id).await
+    }
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.query_keys(query).await
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        self.inner.save_audit_log(log).await
+    }
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        self.inner.load_audit_logs(filters, limit).await
+    }
+}
+
+/// 前若干次保存审计日志失败的持久化存储，模拟暂时不可用的后端
+struct FlakyPersistence {
+    inner: FilePersistence,
+    failures_left: AtomicUsize,
+}
+
+impl FlakyPersistence {
+    fn new(path: &Path, failures: usize) -> Self {
+        Self {
+            inner: FilePersistence::new(path.to_str().unwrap()),
+            failures_left: AtomicUsize::new(failures),
+        }
+    }
+
+    async fn audit_ids(&self) -> Vec<String> {
+        let mut logs = self.inner.load_audit_logs(None, None).await.unwrap();
+        logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.id.cmp(&b.id)));
+        logs.into_iter().map(|log| log.id).collect()
+    }
+}
+
+#[async_trait]
+impl PersistenceInterface for FlakyPersistence {
+    async fn save_key_metadata(&self, metadata: &KeyMetadata) -> Result<(), String> {
+        self.inner.save_key_metadata(metadata).await
+    }
+
+    async fn load_key_metadata(&self, key_id: &str) -> Result<KeyMetadata, String> {
+        self.inner.load_key_metadata(key_id).await
+    }
+
+    async fn delete_key_metadata(&self, key_id: &str) -> Result<(), String> {
+        self.inner.delete_key_metadata(key_id).await
+    }
+
+    async fn query_keys(&self, query: &KeyQuery) -> Result<Vec<KeyMetadata>, String> {
+        self.inner.query_keys(query).await
+    }
+
+    async fn save_audit_log(&self, log: &AuditLogEntry) -> Result<(), String> {
+        if self.failures_left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1)).is_ok() {
+            return Err("持久化存储不可用".to_string());
+        }
+        self.inner.save_audit_log(log).await
+    }
+
+    async fn load_audit_logs(&self, filters: Option<HashMap<String, String>>, limit: Option<usize>) -> Result<Vec<AuditLogEntry>, String> {
+        self.inner.load_audit_logs(filters, limit).await
+    }
+}
+
+fn audit_entries(count: usize) -> Vec<AuditLogEntry> {
+    let start = timestamp::now();
+    (0..count)
+        .map(|i| {
+            AuditLogEntry::new("SIGN_DATA".to_string(), "alice".to_string(), None, format!("entry {}", i), true)
+                .with_timestamp(start + chrono::Duration::milliseconds(i as i64))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn audit_queue_retries_until_persistence_recovers() {
+    let dir = temp_path("");
+    let backend = Arc::new(FlakyPersistence::new(&dir, 3));
+    let queue = AuditQueue::spawn(backend.clone(), 16);
+    let entries = audit_entries(5);
+    for entry in &entries {
+        queue.send(entry);
+    }
+
+    // 三次失败后按退避重试，恢复后按原顺序补写
+    let expected: Vec<String> = entries.iter().map(|entry| entry.id.clone()).collect();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(10);
+    while backend.audit_ids().await.len() < expected.len() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(backend.audit_ids().await, expected);
+
+    queue.close().await;
+    cleanup(&dir);
+}
+
+#[tokio::test]
+async fn closing_audit_queue_flushes_failed_entries() {
+    let dir = temp_path("");
+    let backend = Arc::new(FlakyPersistence::new(&dir, 1));
+    let queue = AuditQueue::spawn(backend.clone(), 16);
+    let entries = audit_entries(3);
+    for entry in &entries {
+        queue.send(entry);
+    }
+
+    // 不等待退避结束，关闭时最后写入一次
+    queue.close().await;
+    let expected: Vec<String> = entries.iter().map(|entry| entry.id.clone()).collect();
+    assert_eq!(backend.audit_ids().await, expected);
+    cleanup(&dir);
+}
+
+#[tokio::test]
+async fn cached_reads_do_not_reach_the_backend() {
+    let dir = temp_path("");
+    let backend = Arc::new(CountingPersistence::new(&dir));
+    let vault = save(backend.as_ref(), "vault", "alice", |_| {}).await;
+    let cached = CachedPersistence::new(backend.clone());
+
+    assert_eq!(cached.load_key_metadata(&vault).await.unwrap().name, "vault");
+    assert_eq!(cached.load_key_metadata(&vault).await.unwrap().name, "vault");
+    assert_eq!(backend.counts(), (1, 1));
+
+    // 写穿：底层存储和缓存同时更新
+    let mut updated = cached.load_key_metadata(&vault).await.unwrap();
+    updated.name = "renamed".to_string();
+    cached.save_key_metadata(&updated).await.unwrap();
+    assert_eq!(backend.counts(), (1, 2));
+    assert_eq!(backend.inner.load_key_metadata(&vault).await.unwrap().name, "renamed");
+    assert_eq!(cached.load_key_metadata(&vault).await.unwrap().name, "renamed");
+
+    // 首次列表查询加载全部密钥，之后的查询和未知ID都在内存中处理
+    let other = save(&cached, "other", "bob", |_| {}).await;
+    let query = KeyQuery::new().with_owner("bob".to_string());
+    assert_eq!(ids(&cached.query_keys(&query).await.unwrap()), vec![other.clone()]);
+    assert_eq!(ids(&cached.query_keys(&query).await.unwrap()), vec![other.clone()]);
+    assert!(cached.load_key_metadata("missing").await.is_err());
+    assert_eq!(backend.counts(), (2, 3));
+
+    cached.delete_key_metadata(&other).await.unwrap();
+    assert!(cached.load_key_metadata(&other).await.is_err());
+    assert!(backend.inner.load_key_metadata(&other).await.is_err());
+
+    cleanup(&dir);
+}
+
+#[tokio::test]
+async fn write_back_cache_defers_writes_until_flush() {
+    let dir = temp_path("");
+    let backend = Arc::new(CountingPersistence::new(&dir));
+    let removed = save(backend.as_ref(), "removed", "alice", |_| {}).await;
+    let cached = CachedPersistence::new(backend.clone()).with_write_back();
+
+    let added = save(&cached, "added", "alice", |_| {}).await;
+    cached.delete_key_metadata(&removed).await.unwrap();
+    assert_eq!(backend.counts(), (0, 1));
+    assert_eq!(cached.load_key_metadata(&added).await.unwrap().name, "added");
+    assert!(cached.load_key_metadata(&removed).await.is_err());
+    assert_eq!(ids(&cached.query_keys(&KeyQuery::new()).await.unwrap()), vec![added.clone()]);
+
+    assert_eq!(cached.flush().await.unwrap(), 2);
+    assert_eq!(cached.flush().await.unwrap(), 0);
+    assert_eq!(ids(&backend.inner.query_keys(&KeyQuery::new()).await.unwrap()), vec![added]);
+
+    cleanup(&dir);
+}
+
+#[tokio::test]
+async fn write_back_cache_flushes_on_interval_and_on_stop() {
+    let dir = temp_path("");
+    let backend = Arc::new(CountingPersistence::new(&dir));
+    let cached = CachedPersistence::new(backend.clone()).with_flush_interval(std::time::Duration::from_millis(100));
+
+    let first = save(&cached, "first", "alice", |_| {}).await;
+    assert!(backend.inner.load_key_metadata(&first).await.is_err());
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert_eq!(backend.inner.load_key_metadata(&first).await.unwrap().name, "first");
+
+    let stats = cached.flush_stats();
+    assert!(stats.flushes >= 1, "{:?}", stats);
+    assert_eq!(stats.flushed_entries, 1);
+    assert_eq!(stats.failed_flushes, 0);
+
+    // 停止时写入剩余的修改
+    let second = save(&cached, "second", "alice", |_| {}).await;
+    cached.stop().await;
+    assert_eq!(backend.inner.load_key_metadata(&second).await.unwrap().name, "second");
+    assert_eq!(cached.flush_stats().flushed_entries, 2);
+
+    cleanup(&dir);
+}